@@ -0,0 +1,265 @@
+//! `gitgenius`: headless companion to the desktop app. Runs the same commit
+//! pipeline and reads the same config file, without the Tauri UI.
+
+use gitgenius_core::config::{self, AppConfig};
+use gitgenius_core::events::EventSink;
+use gitgenius_core::proxy::ProxySettings;
+use gitgenius_core::{cleanup, health, logging, mcp, pipeline, remote, schedule, trial};
+use std::collections::HashMap;
+use std::env;
+use std::process::ExitCode;
+use tokio_util::sync::CancellationToken;
+use tokio::time::{interval, Duration};
+
+const USAGE: &str = "Usage:
+  gitgenius commit [PATH] [--confirm]     Commit and push pending changes once; --confirm skips the size guard
+  gitgenius watch [PATH] [--interval MIN] Commit and push on a fixed interval
+  gitgenius doctor [PATH]                 Check that the remote is reachable and accepts credentials
+  gitgenius trial [PATH] [--keep]         Commit and push a test change to a temporary local remote
+  gitgenius cleanup [PATH] [--delete]     List (or delete) stale autocommit/ and autosave/ branches
+  gitgenius mcp                           Serve the commit tools to AI assistants over stdio (MCP)
+  gitgenius config show                   Print the current configuration
+  gitgenius config path                   Print the config file location
+  gitgenius config set KEY VALUE          Update a configuration value";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Held until exit so buffered log lines are flushed; the CLI still works without logs
+    let _log_guard = logging::init().ok();
+
+    let result = match args.first().map(String::as_str) {
+        Some("commit") => commit(&args[1..]).await,
+        Some("watch") => watch(&args[1..]).await,
+        Some("doctor") => doctor(&args[1..]),
+        Some("trial") => run_trial(&args[1..]).await,
+        Some("cleanup") => cleanup_branches(&args[1..]),
+        // Stdout carries the protocol, so events still go to stderr
+        Some("mcp") => mcp::serve_stdio(&ConsoleEvents).await,
+        Some("config") => config_command(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Prints pipeline events to stderr
+struct ConsoleEvents;
+
+impl EventSink for ConsoleEvents {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        eprintln!("[{}] {}", event, payload);
+    }
+}
+
+// Uses the path given on the command line, or the configured repository
+fn repo_path(args: &[String], config: &AppConfig) -> Result<String, String> {
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .cloned()
+        .unwrap_or_else(|| config.repo_path.clone());
+
+    if path.is_empty() {
+        return Err("No repository given and none configured".into());
+    }
+
+    Ok(path)
+}
+
+async fn commit(args: &[String]) -> Result<(), String> {
+    let config = config::load_config()?;
+    let path = repo_path(args, &config)?;
+
+    let cancel = CancellationToken::new();
+    let message = if args.iter().any(|arg| arg == "--confirm") {
+        pipeline::run_confirmed_commit(&path, &config, &ConsoleEvents, &cancel).await?
+    } else {
+        pipeline::run_commit(&path, &config, &ConsoleEvents, &cancel).await?
+    };
+    println!("{}", message);
+    Ok(())
+}
+
+async fn watch(args: &[String]) -> Result<(), String> {
+    let config = config::load_config()?;
+
+    let mut interval_minutes = config.interval_minutes;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--interval" {
+            interval_minutes = iter
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or("--interval expects a number of minutes")?;
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+
+    let path = repo_path(&positional, &config)?;
+    println!("Watching {} every {} minute(s)", path, interval_minutes);
+    tracing::info!(repo = %path, interval_minutes, "watch started");
+
+    // Background fetches follow each repo's own interval, independent of commits
+    tokio::spawn(async {
+        let mut last_fetch = HashMap::new();
+        let mut fetch_timer = interval(Duration::from_secs(60));
+        loop {
+            fetch_timer.tick().await;
+            if let Ok(config) = config::load_config() {
+                remote::fetch_due_repos(&config, &mut last_fetch, &ConsoleEvents).await;
+            }
+        }
+    });
+
+    // Ctrl+C ends the process, so cycles never need cancelling
+    let cancel = CancellationToken::new();
+    loop {
+        // Re-read the config every cycle so changes made in the app are picked up
+        let config = config::load_config()?;
+
+        if let Some(wait) = schedule::delay_until_open(&config.schedule) {
+            tracing::debug!(repo = %path, wait_secs = wait.as_secs(), "outside scheduled hours");
+            tokio::time::sleep(wait.min(schedule::RECHECK)).await;
+            continue;
+        }
+
+        match pipeline::run_scheduled_commit(&path, &config, interval_minutes, &ConsoleEvents, &cancel).await {
+            Ok(msg) => {
+                if !pipeline::is_skip(&msg) {
+                    println!("Committed: {}", msg);
+                }
+            }
+            Err(e) => eprintln!("Auto-commit error: {}", e),
+        }
+
+        tokio::time::sleep(health::cycle_delay(&path, interval_minutes, &config)).await;
+    }
+}
+
+fn doctor(args: &[String]) -> Result<(), String> {
+    let config = config::load_config()?;
+    let path = repo_path(args, &config)?;
+
+    let push_remote = config.repo_settings(&path).push_remote;
+    let probe = remote::probe(&path, &push_remote, &ProxySettings::from_config(&config))?;
+    let url = probe.url.as_deref().unwrap_or("no URL");
+    match probe.failure {
+        None => {
+            println!("Remote {} ({}) is reachable ({} ms)", probe.remote, url, probe.elapsed_ms);
+            Ok(())
+        }
+        Some(failure) => Err(format!(
+            "Remote {} ({}): {}\n{}",
+            probe.remote,
+            url,
+            failure.describe(),
+            probe.detail
+        )),
+    }
+}
+
+async fn run_trial(args: &[String]) -> Result<(), String> {
+    let config = config::load_config()?;
+    // Without a repository the trial starts from an empty one
+    let source = args.iter().find(|arg| !arg.starts_with("--")).cloned();
+    let keep = args.iter().any(|arg| arg == "--keep");
+
+    let result = trial::run(source.as_deref(), &config, &ConsoleEvents, keep).await?;
+    println!("Committed: {}", result.message);
+    if let Some(workdir) = &result.workdir {
+        println!("Trial repository kept at {}", workdir);
+    }
+    if result.pushed {
+        println!("Push to the local remote succeeded ({})", result.branch);
+        Ok(())
+    } else {
+        Err(format!("The local remote's {} branch did not receive the commit", result.branch))
+    }
+}
+
+fn cleanup_branches(args: &[String]) -> Result<(), String> {
+    let config = config::load_config()?;
+    let path = repo_path(args, &config)?;
+
+    if !args.iter().any(|arg| arg == "--delete") {
+        let stale = cleanup::stale_branches(&path, &config)?;
+        if stale.is_empty() {
+            println!("No stale branches");
+        }
+        for branch in &stale {
+            let remote = branch.remote.as_deref().map(|remote| format!("{}/", remote)).unwrap_or_default();
+            println!(
+                "{}{}  {:.7}  {} day(s) old, {:?}",
+                remote, branch.name, branch.sha, branch.age_days, branch.reason
+            );
+        }
+        if !stale.is_empty() {
+            println!("Run again with --delete to remove them");
+        }
+        return Ok(());
+    }
+
+    let (deleted, errors) = cleanup::delete_stale_branches(&path, &config)?;
+    for name in &deleted {
+        println!("Deleted {}", name);
+    }
+    if !errors.is_empty() {
+        return Err(format!("Failed to delete:\n{}", errors.join("\n")));
+    }
+    Ok(())
+}
+
+fn config_command(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("show") | None => {
+            let mut config = config::load_config()?;
+            if !config.gemini_api_key.is_empty() {
+                config.gemini_api_key = "********".into();
+            }
+            let config_json = serde_json::to_string_pretty(&config)
+                .map_err(|e| format!("Failed to serialize config: {}", e))?;
+            println!("{}", config_json);
+            Ok(())
+        }
+        Some("path") => {
+            println!("{}", config::get_config_path()?.display());
+            Ok(())
+        }
+        Some("set") => {
+            let (key, value) = match (args.get(1), args.get(2)) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Err(USAGE.to_string()),
+            };
+            set_config_value(key, value)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// Edits the config as JSON so every field can be set without a dedicated flag
+fn set_config_value(key: &str, value: &str) -> Result<(), String> {
+    let config = config::load_config()?;
+    let mut json = serde_json::to_value(&config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+
+    let field = json
+        .get_mut(key)
+        .ok_or_else(|| format!("Unknown config key: {}", key))?;
+
+    // Accept typed values (numbers, booleans) and fall back to a plain string
+    *field = serde_json::from_str(value)
+        .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+
+    let config: AppConfig = serde_json::from_value(json)
+        .map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+    config::save_config(&config)
+}
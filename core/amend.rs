@@ -0,0 +1,59 @@
+//! Amend window: a cycle that follows shortly after one of the app's own
+//! commits folds its changes into that commit instead of adding another,
+//! as long as the commit hasn't left the machine yet.
+
+use crate::history::{EntryOrigin, HistoryStore};
+use git2::{BranchType, Oid, Repository};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The HEAD commit, if the next commit should amend it: made by the app
+/// less than `window_minutes` ago, not a merge, and not on any remote
+/// branch. A window of 0 turns amending off.
+pub fn amend_candidate(repo: &Repository, repo_id: &str, window_minutes: u64) -> Option<Oid> {
+    if window_minutes == 0 {
+        return None;
+    }
+
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    let commit = head.peel_to_commit().ok()?;
+    if commit.parent_count() != 1 {
+        return None;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    // Amending keeps the author date, so the window doesn't slide along
+    // with every amend
+    if now - commit.author().when().seconds() > (window_minutes * 60) as i64 {
+        return None;
+    }
+
+    let sha = commit.id().to_string();
+    let made_by_app = HistoryStore::load().ok()?.entries.iter().any(|entry| {
+        entry.repo_id == repo_id && entry.sha == sha && entry.origin == EntryOrigin::App
+    });
+    if !made_by_app || is_pushed(repo, commit.id()) {
+        return None;
+    }
+    Some(commit.id())
+}
+
+/// Pushed commits are the tip or an ancestor of some remote-tracking branch.
+pub fn is_pushed(repo: &Repository, oid: Oid) -> bool {
+    let branches = match repo.branches(Some(BranchType::Remote)) {
+        Ok(branches) => branches,
+        // When in doubt, don't rewrite
+        Err(_) => return true,
+    };
+    branches.flatten().any(|(branch, _)| {
+        branch
+            .get()
+            .target()
+            .is_some_and(|tip| tip == oid || repo.graph_descendant_of(tip, oid).unwrap_or(true))
+    })
+}
@@ -0,0 +1,237 @@
+//! Anonymized diff mode: identifiers, string literals and file names are
+//! replaced with stable placeholders before the diff leaves the machine, so
+//! the model sees the structure of a change but not its literal code. The
+//! generated message is mapped back to the real names afterwards.
+
+use std::collections::HashMap;
+
+// Words shared by most languages carry structure, not secrets, and are kept
+const KEYWORDS: [&str; 74] = [
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def",
+    "default", "delete", "do", "elif", "else", "enum", "export", "extends", "false", "final",
+    "finally", "fn", "for", "from", "func", "function", "go", "if", "impl", "implements",
+    "import", "in", "interface", "is", "lambda", "let", "loop", "match", "mod", "module", "mut",
+    "new", "nil", "None", "not", "null", "or", "and", "package", "private", "protected", "pub",
+    "public", "raise", "return", "self", "Self", "static", "struct", "super", "switch", "this",
+    "throw", "trait", "true", "True", "False", "try", "type", "use", "var", "void", "where",
+    "while",
+];
+
+/// Placeholder mapping for one diff. Use the same instance to anonymize
+/// everything sent in one request and to restore the answer.
+#[derive(Default)]
+pub struct Anonymizer {
+    words: HashMap<String, String>,
+    strings: HashMap<String, String>,
+    // Placeholder -> original
+    originals: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Anonymizer::default()
+    }
+
+    /// Anonymizes a `git diff --stat` + `git diff` text line by line, keeping
+    /// diff syntax, numbers, punctuation and file extensions.
+    pub fn anonymize_diff(&mut self, diff_text: &str) -> String {
+        diff_text
+            .lines()
+            .map(|line| self.anonymize_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Anonymizes free text (e.g. an earlier message) with the same mapping.
+    pub fn anonymize_text(&mut self, text: &str) -> String {
+        self.anonymize_code(text)
+    }
+
+    /// Replaces every placeholder in `message` with the original it stands for.
+    pub fn restore(&self, message: &str) -> String {
+        let mut restored = String::with_capacity(message.len());
+        let mut word = String::new();
+
+        for c in message.chars().chain(std::iter::once('\0')) {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                restored.push_str(self.originals.get(&word).unwrap_or(&word));
+                word.clear();
+            }
+            if c != '\0' {
+                restored.push(c);
+            }
+        }
+        restored
+    }
+
+    fn anonymize_line(&mut self, line: &str) -> String {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let anonymized: Vec<String> = paths.split(' ').map(|path| self.anonymize_path(path)).collect();
+            return format!("diff --git {}", anonymized.join(" "));
+        }
+        for prefix in ["--- ", "+++ ", "rename from ", "rename to ", "copy from ", "copy to "] {
+            if let Some(path) = line.strip_prefix(prefix) {
+                return format!("{}{}", prefix, self.anonymize_path(path));
+            }
+        }
+        if line.starts_with("index ")
+            || line.starts_with("new file mode")
+            || line.starts_with("deleted file mode")
+            || line.starts_with("old mode")
+            || line.starts_with("new mode")
+            || line.starts_with("similarity index")
+            || line.starts_with("Binary files")
+        {
+            return line.to_string();
+        }
+
+        // `@@ -1,4 +1,6 @@ fn context`: keep the ranges, anonymize the context
+        if line.starts_with("@@") {
+            if let Some(end) = line[2..].find("@@") {
+                let (ranges, context) = line.split_at(end + 4);
+                return format!("{}{}", ranges, self.anonymize_code(context));
+            }
+        }
+
+        // Stat lines: ` src/auth.rs | 12 +++---`
+        if let Some((path, counts)) = line.split_once(" | ") {
+            if counts.trim_start().starts_with(|c: char| c.is_ascii_digit()) || counts.trim_start().starts_with("Bin") {
+                let indent = &path[..path.len() - path.trim_start().len()];
+                return format!("{}{} | {}", indent, self.anonymize_path(path.trim()), counts);
+            }
+        }
+        if line.trim_start().starts_with(|c: char| c.is_ascii_digit()) && line.contains("changed") {
+            return line.to_string();
+        }
+
+        match line.chars().next() {
+            Some(marker @ ('+' | '-' | ' ')) => format!("{}{}", marker, self.anonymize_code(&line[1..])),
+            _ => self.anonymize_code(line),
+        }
+    }
+
+    // `a/src/auth.rs` -> `a/id_1/id_2.rs`; `/dev/null` is kept
+    fn anonymize_path(&mut self, path: &str) -> String {
+        if path == "/dev/null" {
+            return path.to_string();
+        }
+
+        let (stem, extension) = match path.rsplit_once('.') {
+            Some((stem, extension))
+                if !extension.contains('/') && !stem.ends_with('/') && !stem.is_empty() =>
+            {
+                (stem, Some(extension))
+            }
+            _ => (path, None),
+        };
+
+        let mut anonymized = self.anonymize_words(stem);
+        if let Some(extension) = extension {
+            anonymized.push('.');
+            anonymized.push_str(extension);
+        }
+        anonymized
+    }
+
+    fn anonymize_code(&mut self, code: &str) -> String {
+        let mut output = String::with_capacity(code.len());
+        let mut chars = code.char_indices().peekable();
+        let mut plain_start = 0;
+
+        while let Some((index, c)) = chars.next() {
+            if !matches!(c, '"' | '\'' | '`') {
+                continue;
+            }
+
+            // `'a` followed by anything but a quote is a lifetime or label; any
+            // words in it are still covered by the word pass
+            if c == '\'' {
+                let rest = &code[index + 1..];
+                let word_len = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').count();
+                if word_len > 0 && rest.chars().nth(word_len) != Some('\'') {
+                    continue;
+                }
+            }
+
+            // A quote without a closing partner on this line is left to the word pass
+            let closing = code[index + 1..]
+                .char_indices()
+                .scan(false, |escaped, (offset, next)| {
+                    let is_end = next == c && !*escaped;
+                    *escaped = next == '\\' && !*escaped;
+                    Some((offset, is_end))
+                })
+                .find(|(_, is_end)| *is_end)
+                .map(|(offset, _)| index + 1 + offset);
+
+            if let Some(closing) = closing {
+                output.push_str(&self.anonymize_words(&code[plain_start..index]));
+                let literal = &code[index + 1..closing];
+                output.push(c);
+                output.push_str(&self.placeholder_for_string(literal));
+                output.push(c);
+                plain_start = closing + c.len_utf8();
+                while chars.peek().is_some_and(|(next, _)| *next < plain_start) {
+                    chars.next();
+                }
+            }
+        }
+
+        output.push_str(&self.anonymize_words(&code[plain_start..]));
+        output
+    }
+
+    fn anonymize_words(&mut self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut word = String::new();
+
+        for c in text.chars().chain(std::iter::once('\0')) {
+            let continues_word = c.is_alphanumeric() || c == '_';
+            if continues_word && (!word.is_empty() || !c.is_ascii_digit()) {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                output.push_str(&self.placeholder_for_word(&word));
+                word.clear();
+            }
+            if c != '\0' {
+                output.push(c);
+            }
+        }
+        output
+    }
+
+    fn placeholder_for_word(&mut self, word: &str) -> String {
+        // Single letters and keywords reveal nothing
+        if word.chars().count() <= 1 || KEYWORDS.contains(&word) {
+            return word.to_string();
+        }
+        if let Some(placeholder) = self.words.get(word) {
+            return placeholder.clone();
+        }
+
+        let placeholder = format!("id_{}", self.words.len() + 1);
+        self.words.insert(word.to_string(), placeholder.clone());
+        self.originals.insert(placeholder.clone(), word.to_string());
+        placeholder
+    }
+
+    fn placeholder_for_string(&mut self, literal: &str) -> String {
+        if literal.is_empty() {
+            return String::new();
+        }
+        if let Some(placeholder) = self.strings.get(literal) {
+            return placeholder.clone();
+        }
+
+        let placeholder = format!("str_{}", self.strings.len() + 1);
+        self.strings.insert(literal.to_string(), placeholder.clone());
+        self.originals.insert(placeholder.clone(), literal.to_string());
+        placeholder
+    }
+}
@@ -0,0 +1,202 @@
+//! Commit approval queue: with `commit_approval` set, a timed cycle stops
+//! once its message is generated and the commit waits here until the user
+//! approves or rejects it. Entries are keyed by a hash of the staged diff,
+//! so an approval only commits the changes it was given for; a repository
+//! whose changes moved on gets a new request instead.
+
+use crate::message_cache;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalState {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingCommit {
+    pub id: String,
+    pub repo: String,
+    pub message: String,
+    // `message_cache::diff_hash` of the diff the message was written for
+    pub fingerprint: String,
+    pub files: Vec<String>,
+    pub state: ApprovalState,
+    // Unix seconds
+    pub requested_at: u64,
+}
+
+// One entry per repository: a newer request replaces the older one
+#[derive(Serialize, Deserialize, Default)]
+struct ApprovalStore {
+    repos: HashMap<String, PendingCommit>,
+}
+
+impl ApprovalStore {
+    fn load() -> Result<Self, String> {
+        let path = approvals_path()?;
+        if !path.exists() {
+            return Ok(ApprovalStore::default());
+        }
+
+        let approvals_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read approvals: {}", e))?;
+        serde_json::from_str(&approvals_str)
+            .map_err(|e| format!("Failed to parse approvals: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let approvals_json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize approvals: {}", e))?;
+        crate::config::write_atomic(&approvals_path()?, approvals_json.as_bytes())
+            .map_err(|e| format!("Failed to save approvals: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn approvals_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("approvals.json");
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The entry of `repo_id` for the diff hashed as `fingerprint`, in any state.
+pub fn decision(repo_id: &str, fingerprint: &str) -> Option<PendingCommit> {
+    ApprovalStore::load()
+        .ok()?
+        .repos
+        .remove(repo_id)
+        .filter(|entry| entry.fingerprint == fingerprint)
+}
+
+/// Queues `message` for the user's approval, replacing what `repo_id` had
+/// waiting.
+pub fn request(repo_id: &str, message: &str, diff_text: &str, files: Vec<String>) -> Result<PendingCommit, String> {
+    let fingerprint = message_cache::diff_hash(diff_text);
+    let requested_at = unix_now();
+    let entry = PendingCommit {
+        id: format!("{}-{}", requested_at, fingerprint.get(..12).unwrap_or(&fingerprint)),
+        repo: repo_id.to_string(),
+        message: message.to_string(),
+        fingerprint,
+        files,
+        state: ApprovalState::Pending,
+        requested_at,
+    };
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = ApprovalStore::load()?;
+    store.repos.insert(repo_id.to_string(), entry.clone());
+    store.save()?;
+    Ok(entry)
+}
+
+/// Commits waiting for approval, oldest first.
+pub fn pending() -> Result<Vec<PendingCommit>, String> {
+    let mut pending: Vec<PendingCommit> = ApprovalStore::load()?
+        .repos
+        .into_values()
+        .filter(|entry| entry.state == ApprovalState::Pending)
+        .collect();
+    pending.sort_by_key(|entry| entry.requested_at);
+    Ok(pending)
+}
+
+/// Approves the commit `id`, with `message` instead of the generated one
+/// when given. The next cycle of its repository commits it.
+pub fn approve(id: &str, message: Option<String>) -> Result<PendingCommit, String> {
+    decide(id, ApprovalState::Approved, message.filter(|message| !message.trim().is_empty()))
+}
+
+/// Rejects the commit `id`; its changes wait until they change again.
+pub fn reject(id: &str) -> Result<PendingCommit, String> {
+    decide(id, ApprovalState::Rejected, None)
+}
+
+/// Drops the entry of `repo_id` once its changes are committed.
+pub fn finish(repo_id: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = ApprovalStore::load()?;
+    if store.repos.remove(repo_id).is_some() {
+        store.save()?;
+    }
+    Ok(())
+}
+
+fn decide(id: &str, state: ApprovalState, message: Option<String>) -> Result<PendingCommit, String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = ApprovalStore::load()?;
+    let entry = store
+        .repos
+        .values_mut()
+        .find(|entry| entry.id == id && entry.state == ApprovalState::Pending)
+        .ok_or_else(|| format!("No commit waiting for approval with id {}", id))?;
+    entry.state = state;
+    if let Some(message) = message {
+        entry.message = message.trim().to_string();
+    }
+    let decided = entry.clone();
+    store.save()?;
+    Ok(decided)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approval_applies_to_the_diff_it_was_given_for() {
+        let repo_id = "/tmp/approvals-approve";
+        let requested = request(repo_id, "feat: add x", "+x\n", vec!["x.rs".into()]).unwrap();
+        assert!(pending().unwrap().iter().any(|entry| entry.id == requested.id));
+
+        let approved = approve(&requested.id, Some("  feat: add the x module \n".into())).unwrap();
+        assert_eq!(approved.state, ApprovalState::Approved);
+        assert_eq!(approved.message, "feat: add the x module");
+        assert!(!pending().unwrap().iter().any(|entry| entry.id == requested.id));
+        assert!(approve(&requested.id, None).is_err());
+
+        let fingerprint = message_cache::diff_hash("+x\n");
+        assert_eq!(decision(repo_id, &fingerprint).unwrap().state, ApprovalState::Approved);
+        assert!(decision(repo_id, &message_cache::diff_hash("+y\n")).is_none());
+
+        finish(repo_id).unwrap();
+        assert!(decision(repo_id, &fingerprint).is_none());
+    }
+
+    #[test]
+    fn a_newer_request_replaces_the_waiting_one() {
+        let repo_id = "/tmp/approvals-replace";
+        let first = request(repo_id, "fix: one", "+1\n", Vec::new()).unwrap();
+        let second = request(repo_id, "fix: two", "+2\n", Vec::new()).unwrap();
+        assert!(reject(&first.id).is_err());
+
+        let rejected = reject(&second.id).unwrap();
+        assert_eq!(rejected.state, ApprovalState::Rejected);
+        assert_eq!(rejected.message, "fix: two");
+        finish(repo_id).unwrap();
+    }
+
+    #[test]
+    fn blank_edits_keep_the_generated_message() {
+        let repo_id = "/tmp/approvals-blank";
+        let requested = request(repo_id, "docs: explain y", "+y\n", Vec::new()).unwrap();
+        assert_eq!(approve(&requested.id, Some("   ".into())).unwrap().message, "docs: explain y");
+        finish(repo_id).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+//! Keeps leftovers of merges and editors out of commits: staged files
+//! matching a repository's `staging_blocklist` (`*.orig`, `*.rej`,
+//! `.DS_Store`, swap files, ...) are unstaged again right after staging,
+//! whatever the repo's .gitignore says. Deleting such a file is still
+//! committed.
+
+use crate::split;
+use git2::Repository;
+use serde::Serialize;
+use std::path::Path;
+
+/// The default `staging_blocklist`.
+pub fn default_blocklist() -> Vec<String> {
+    [
+        // Merge and patch leftovers
+        "*.orig",
+        "*.rej",
+        "*_BACKUP_*",
+        "*_BASE_*",
+        "*_LOCAL_*",
+        "*_REMOTE_*",
+        // Editor swap, backup and lock files
+        "*.swp",
+        "*.swo",
+        "*~",
+        ".#*",
+        "#*#",
+        // OS metadata
+        ".DS_Store",
+        "Thumbs.db",
+    ]
+    .iter()
+    .map(|pattern| pattern.to_string())
+    .collect()
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ArtifactsExcluded {
+    pub repo: String,
+    pub files: Vec<String>,
+}
+
+/// True when `path` matches one of `patterns`. A pattern without `/`
+/// matches the file name in any directory, one with `/` the whole path
+/// from the repository root; `*` and `?` don't cross `/`.
+pub fn is_blocked(path: &str, patterns: &[String]) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    patterns.iter().map(|pattern| pattern.trim()).any(|pattern| match pattern {
+        "" => false,
+        _ if pattern.contains('/') => glob_match(pattern.trim_start_matches('/').as_bytes(), path.as_bytes()),
+        _ => glob_match(pattern.as_bytes(), name.as_bytes()),
+    })
+}
+
+/// Staged files matching `patterns`, deletions left out.
+pub fn staged_blocked(repo: &Repository, patterns: &[String]) -> Result<Vec<String>, String> {
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+    let index = repo.index().map_err(|e| e.to_string())?;
+    Ok(split::staged_paths(repo)?
+        .into_iter()
+        .filter(|path| is_blocked(path, patterns))
+        // A deleted file is no longer in the index
+        .filter(|path| index.get_path(Path::new(path), 0).is_some())
+        .collect())
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], text)
+                || (text.first().is_some_and(|&c| c != b'/') && glob_match(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(&c)) if c != b'/' => glob_match(&pattern[1..], &text[1..]),
+        (Some(p), Some(c)) if p == c => glob_match(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
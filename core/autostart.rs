@@ -0,0 +1,162 @@
+//! Launch at login: the app registers itself with the OS to start with
+//! `--minimized`, through the `Run` registry key on Windows, a launch agent
+//! on macOS and an XDG autostart entry elsewhere.
+
+use std::path::Path;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use std::{fs, path::PathBuf};
+
+pub const MINIMIZED_FLAG: &str = "--minimized";
+
+/// Registers `exe` to start at login with `--minimized`, replacing any
+/// earlier registration.
+pub fn enable(exe: &Path) -> Result<(), String> {
+    register(exe)
+}
+
+pub fn disable() -> Result<(), String> {
+    unregister()
+}
+
+/// Whether the app is currently registered to start at login.
+pub fn is_enabled() -> bool {
+    registered()
+}
+
+/// Brings the registration in line with `enabled`.
+pub fn sync(enabled: bool, exe: &Path) -> Result<(), String> {
+    match (enabled, is_enabled()) {
+        (true, _) => enable(exe),
+        (false, true) => disable(),
+        (false, false) => Ok(()),
+    }
+}
+
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+#[cfg(target_os = "windows")]
+const VALUE_NAME: &str = "GitGenius";
+
+#[cfg(target_os = "windows")]
+fn reg(args: &[&str]) -> Result<(), String> {
+    let output = std::process::Command::new("reg")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run reg: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn register(exe: &Path) -> Result<(), String> {
+    let command = format!("\"{}\" {}", exe.display(), MINIMIZED_FLAG);
+    reg(&["add", RUN_KEY, "/v", VALUE_NAME, "/t", "REG_SZ", "/d", &command, "/f"])
+        .map_err(|e| format!("Failed to register launch at login: {}", e))
+}
+
+#[cfg(target_os = "windows")]
+fn unregister() -> Result<(), String> {
+    reg(&["delete", RUN_KEY, "/v", VALUE_NAME, "/f"])
+        .map_err(|e| format!("Failed to remove launch at login: {}", e))
+}
+
+#[cfg(target_os = "windows")]
+fn registered() -> bool {
+    reg(&["query", RUN_KEY, "/v", VALUE_NAME]).is_ok()
+}
+
+#[cfg(target_os = "macos")]
+const AGENT_LABEL: &str = "com.gitgenius.autocommit";
+
+#[cfg(target_os = "macos")]
+fn agent_path() -> Result<std::path::PathBuf, String> {
+    let mut path = dirs::home_dir().ok_or("Failed to get home directory")?;
+    path.push("Library/LaunchAgents");
+    path.push(format!("{}.plist", AGENT_LABEL));
+    Ok(path)
+}
+
+#[cfg(target_os = "macos")]
+fn register(exe: &Path) -> Result<(), String> {
+    let escape = |text: &str| text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        AGENT_LABEL,
+        escape(&exe.display().to_string()),
+        MINIMIZED_FLAG
+    );
+
+    let path = agent_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create LaunchAgents: {}", e))?;
+    }
+    std::fs::write(&path, plist).map_err(|e| format!("Failed to write launch agent: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn unregister() -> Result<(), String> {
+    std::fs::remove_file(agent_path()?).map_err(|e| format!("Failed to remove launch agent: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn registered() -> bool {
+    agent_path().is_ok_and(|path| path.exists())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn desktop_entry_path() -> Result<PathBuf, String> {
+    let mut path = dirs::config_dir().ok_or("Failed to get config directory")?;
+    path.push("autostart");
+    path.push("gitgenius.desktop");
+    Ok(path)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn register(exe: &Path) -> Result<(), String> {
+    // Quoting rules of the Exec key in the desktop entry spec
+    let mut exec = String::new();
+    for c in exe.display().to_string().chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            exec.push('\\');
+        }
+        exec.push(c);
+    }
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=GitGenius\nComment=Automatic commits with AI-generated messages\nExec=\"{}\" {}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+        exec, MINIMIZED_FLAG
+    );
+
+    let path = desktop_entry_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create autostart directory: {}", e))?;
+    }
+    fs::write(&path, entry).map_err(|e| format!("Failed to write autostart entry: {}", e))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn unregister() -> Result<(), String> {
+    fs::remove_file(desktop_entry_path()?).map_err(|e| format!("Failed to remove autostart entry: {}", e))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn registered() -> bool {
+    desktop_entry_path().is_ok_and(|path| path.exists())
+}
@@ -0,0 +1,61 @@
+//! Overflow batching: a cycle that would commit more than
+//! `max_files_per_commit` files is split into several commits, each holding
+//! files from the same or neighbouring directories.
+
+use std::collections::BTreeMap;
+
+/// Splits `paths` into batches of at most `max` files. Files are grouped by
+/// directory; small directories share a batch, large ones are cut into
+/// several. With `max` of 0 or few enough files, everything is one batch.
+pub fn plan(paths: Vec<String>, max: usize) -> Vec<Vec<String>> {
+    if max == 0 || paths.len() <= max {
+        return vec![paths];
+    }
+
+    // Sorted by directory, so consecutive groups are neighbours in the tree
+    let mut by_directory: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        let directory = path.rsplit_once('/').map(|(directory, _)| directory).unwrap_or_default();
+        by_directory.entry(directory.to_string()).or_default().push(path);
+    }
+
+    let mut batches: Vec<Vec<String>> = Vec::new();
+    for files in by_directory.into_values() {
+        for chunk in files.chunks(max) {
+            match batches.last_mut() {
+                Some(batch) if batch.len() + chunk.len() <= max => batch.extend_from_slice(chunk),
+                _ => batches.push(chunk.to_vec()),
+            }
+        }
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn one_batch_when_under_the_limit_or_unlimited() {
+        let files = paths(&["a.rs", "src/b.rs", "src/c.rs"]);
+        assert_eq!(plan(files.clone(), 3), vec![files.clone()]);
+        assert_eq!(plan(files.clone(), 0), vec![files]);
+    }
+
+    #[test]
+    fn groups_neighbouring_directories() {
+        let batches = plan(paths(&["web/a.ts", "api/x.rs", "web/b.ts", "api/y.rs", "docs/r.md"]), 2);
+        assert_eq!(batches, vec![paths(&["api/x.rs", "api/y.rs"]), paths(&["docs/r.md"]), paths(&["web/a.ts", "web/b.ts"])]);
+    }
+
+    #[test]
+    fn cuts_large_directories_and_never_exceeds_the_limit() {
+        let files: Vec<String> = (0..7).map(|n| format!("src/{}.rs", n)).collect();
+        let batches = plan(files, 3);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![3, 3, 1]);
+    }
+}
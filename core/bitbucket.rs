@@ -0,0 +1,128 @@
+//! Bitbucket Cloud: pull requests and commit build statuses.
+
+use crate::ci::{BuildStatus, CiState};
+use crate::forge::{self, Forge, ForgeFuture, PullRequest, PullRequestDraft};
+use crate::secrets;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+const API_BASE: &str = "https://api.bitbucket.org/2.0";
+
+/// A Bitbucket repository. The token stored with `set_bitbucket_token` is
+/// either an access token, or `username:app-password`.
+pub struct Bitbucket {
+    workspace: String,
+    name: String,
+    token: String,
+}
+
+impl Bitbucket {
+    pub fn new(workspace: &str, name: &str) -> Result<Self, String> {
+        let token = secrets::get_secret(secrets::BITBUCKET_TOKEN)?
+            .ok_or("Bitbucket token not configured")?;
+        Ok(Bitbucket { workspace: workspace.to_string(), name: name.to_string(), token })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/repositories/{}/{}/{}", API_BASE, self.workspace, self.name, path)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.token.split_once(':') {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request.bearer_auth(&self.token),
+        }
+    }
+}
+
+impl Forge for Bitbucket {
+    fn name(&self) -> &'static str {
+        "Bitbucket"
+    }
+
+    fn open_or_update_pull_request<'a>(&'a self, client: &'a Client, draft: &'a PullRequestDraft) -> ForgeFuture<'a, PullRequest> {
+        Box::pin(async move {
+            let query = format!(
+                "source.branch.name=\"{}\" AND destination.branch.name=\"{}\" AND state=\"OPEN\"",
+                draft.head, draft.base
+            );
+            let existing = forge::send(
+                self.authorized(client.get(self.url("pullrequests")).query(&[("q", query)])),
+                "Bitbucket",
+            )
+            .await?;
+
+            let open = existing["values"][0]["id"].as_u64();
+            let (request, created) = match open {
+                Some(id) => (
+                    client
+                        .put(self.url(&format!("pullrequests/{}", id)))
+                        .json(&json!({ "title": draft.title, "description": draft.body })),
+                    false,
+                ),
+                None => (
+                    client.post(self.url("pullrequests")).json(&json!({
+                        "title": draft.title,
+                        "description": draft.body,
+                        "source": { "branch": { "name": draft.head } },
+                        "destination": { "branch": { "name": draft.base } },
+                    })),
+                    true,
+                ),
+            };
+            let pull = forge::send(self.authorized(request), "Bitbucket").await?;
+
+            Ok(PullRequest {
+                number: pull["id"].as_u64().unwrap_or_default(),
+                url: pull["links"]["html"]["href"].as_str().unwrap_or_default().to_string(),
+                title: pull["title"].as_str().unwrap_or_default().to_string(),
+                created,
+            })
+        })
+    }
+
+    fn build_status<'a>(&'a self, client: &'a Client, branch: &'a str) -> ForgeFuture<'a, BuildStatus> {
+        Box::pin(async move {
+            // Statuses belong to commits, so the branch is resolved first
+            let branch_info = forge::send(
+                self.authorized(client.get(self.url(&format!("refs/branches/{}", branch)))),
+                "Bitbucket",
+            )
+            .await?;
+            let hash = branch_info["target"]["hash"]
+                .as_str()
+                .ok_or_else(|| format!("Branch {} not found on Bitbucket", branch))?;
+            let statuses = forge::send(
+                self.authorized(client.get(self.url(&format!("commit/{}/statuses", hash)))),
+                "Bitbucket",
+            )
+            .await?;
+            Ok(statuses_state(&statuses))
+        })
+    }
+}
+
+fn statuses_state(statuses: &Value) -> BuildStatus {
+    let statuses = statuses["values"].as_array().cloned().unwrap_or_default();
+    let failed: Vec<&Value> = statuses
+        .iter()
+        .filter(|status| matches!(status["state"].as_str(), Some("FAILED" | "STOPPED")))
+        .collect();
+    let state = if !failed.is_empty() {
+        CiState::Failing
+    } else if statuses.iter().any(|status| status["state"].as_str() == Some("INPROGRESS")) {
+        CiState::Pending
+    } else if statuses.is_empty() {
+        CiState::Unknown
+    } else {
+        CiState::Passing
+    };
+    BuildStatus {
+        state,
+        failing: failed
+            .iter()
+            .filter_map(|status| status["name"].as_str().or_else(|| status["key"].as_str()).map(str::to_string))
+            .collect(),
+        url: failed.first().and_then(|status| status["url"].as_str()).map(str::to_string),
+    }
+}
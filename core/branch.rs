@@ -0,0 +1,128 @@
+use crate::budget;
+use crate::config::AppConfig;
+use crate::conventional;
+use crate::gemini;
+use crate::rules;
+use git2::{Branch, Repository};
+
+const BRANCH_SYSTEM_CONTEXT: &str = r#"You name Git branches for a piece of work, described either by a git diff or by a short task description.
+
+RULES:
+1. Format: <type>/<kebab-case-summary>
+2. Types: feat, fix, docs, style, refactor, test, chore, perf
+3. Summary: 2 to 5 lowercase words joined by hyphens, no articles, max 40 characters
+4. Only lowercase ASCII letters, digits and hyphens after the slash
+
+EXAMPLES:
+- feat/jwt-token-validation
+- fix/null-user-endpoint
+- docs/installation-guide
+
+Always respond with ONLY the branch name, no explanations or additional text."#;
+
+const MAX_SUMMARY_LENGTH: usize = 40;
+
+/// Suggests a branch name such as `feat/jwt-token-validation` for a diff or
+/// a free-text task description. Falls back to a name derived from the
+/// rule-based commit message when the model's answer isn't usable.
+pub async fn suggest_branch_name(repo_id: &str, config: &AppConfig, work: &str) -> Result<String, String> {
+    if config.api_key().is_empty() {
+        return Ok(fallback_name(work));
+    }
+
+    let options = gemini::ModelOptions::from_config(config).for_repo(repo_id);
+    let prefix = "Suggest a branch name for this work:\n\n";
+    let work_for_model = budget::fit_diff(config, &options, BRANCH_SYSTEM_CONTEXT, prefix, work).await;
+    let prompt = format!("{}{}", prefix, work_for_model);
+    let text = gemini::generate(&config.api_key(), &options, BRANCH_SYSTEM_CONTEXT, &prompt).await?;
+
+    Ok(normalize(&text).unwrap_or_else(|| fallback_name(work)))
+}
+
+/// Turns free text into a `type/kebab-case` branch name, or None when
+/// nothing usable is left.
+pub fn normalize(text: &str) -> Option<String> {
+    let line = text
+        .lines()
+        .map(|line| line.trim().trim_matches(['`', '"', '\'']))
+        .find(|line| !line.is_empty())?;
+    let (commit_type, summary) = match line.split_once('/') {
+        Some((commit_type, summary)) => (kebab(commit_type), kebab(summary)),
+        None => ("chore".to_string(), kebab(line)),
+    };
+    if summary.is_empty() {
+        return None;
+    }
+    let commit_type = if commit_type.is_empty() { "chore".to_string() } else { commit_type };
+
+    let summary = truncate_words(&summary, MAX_SUMMARY_LENGTH);
+    let name = format!("{}/{}", commit_type, summary);
+    Branch::name_is_valid(&name).unwrap_or(false).then_some(name)
+}
+
+// `feat(auth): add JWT token validation` -> `feat/add-jwt-token-validation`
+fn fallback_name(work: &str) -> String {
+    let message = if work.contains("diff --git") {
+        rules::generate_message(work)
+    } else {
+        format!("chore: {}", work)
+    };
+    let name = conventional::parse(&message)
+        .and_then(|parsed| normalize(&format!("{}/{}", parsed.commit_type, parsed.subject)));
+    name.unwrap_or_else(|| "chore/work-in-progress".into())
+}
+
+fn kebab(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Drops whole words from the end until it fits
+fn truncate_words(summary: &str, max: usize) -> String {
+    let mut truncated = String::new();
+    for word in summary.split('-') {
+        let extra = if truncated.is_empty() { word.len() } else { word.len() + 1 };
+        if truncated.len() + extra > max {
+            break;
+        }
+        if !truncated.is_empty() {
+            truncated.push('-');
+        }
+        truncated.push_str(word);
+    }
+    if truncated.is_empty() {
+        summary[..max.min(summary.len())].to_string()
+    } else {
+        truncated
+    }
+}
+
+/// Creates `name` at HEAD and switches to it. The working tree and index
+/// are kept as they are, so uncommitted changes move to the new branch.
+pub fn create_and_checkout_branch(repo_id: &str, name: &str) -> Result<String, String> {
+    if !Branch::name_is_valid(name).unwrap_or(false) {
+        return Err(format!("Invalid branch name: {}", name));
+    }
+
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|_| "Cannot create a branch before the first commit".to_string())?;
+
+    let branch = repo
+        .branch(name, &head, false)
+        .map_err(|e| format!("Failed to create branch: {}", e))?;
+    let refname = branch
+        .get()
+        .name()
+        .ok_or("Branch name is not valid UTF-8")?
+        .to_string();
+    repo.set_head(&refname)
+        .map_err(|e| format!("Failed to switch to {}: {}", name, e))?;
+
+    Ok(name.to_string())
+}
@@ -0,0 +1,235 @@
+//! Keeps the prompt within a token budget. The diff is reordered so the
+//! stat comes first, then source files by how much they changed, then
+//! documentation and condensed files, and is cut where the budget runs out.
+//! Tokens are estimated locally and calibrated against the model's own
+//! count, which is only asked for when the prompt is anywhere near the limit.
+
+use crate::condense;
+use crate::config::AppConfig;
+use crate::gemini::{self, ModelOptions};
+use std::time::Duration;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+pub const DEFAULT_PROMPT_TOKEN_LIMIT: u32 = 8_000;
+
+// A file section gets cut rather than left out only if this much is left
+const MIN_PARTIAL_TOKENS: u32 = 200;
+
+// Counting must never hold up the cycle for long
+const COUNT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Files read after the source files
+const DOC_EXTENSIONS: [&str; 5] = [".md", ".markdown", ".txt", ".rst", ".adoc"];
+
+/// The prompt token limit for `model`: its entry in
+/// `prompt_token_limits`, or `prompt_token_limit`.
+pub fn limit_for(config: &AppConfig, model: &str) -> u32 {
+    config
+        .prompt_token_limits
+        .get(model)
+        .copied()
+        .unwrap_or(config.prompt_token_limit)
+        .max(MIN_PARTIAL_TOKENS * 2)
+}
+
+/// Local token estimate, close to what SentencePiece-style tokenizers
+/// produce for code: words split into pieces of about four characters,
+/// each punctuation mark a token, and a leading space folded into the
+/// following word.
+pub fn estimate_tokens(text: &str) -> u32 {
+    let mut tokens = 0;
+    let mut word = 0;
+    let mut spaces = 0;
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word += 1;
+            continue;
+        }
+        tokens += word_tokens(word);
+        word = 0;
+
+        if c == ' ' || c == '\t' {
+            spaces += 1;
+            continue;
+        }
+        // Indentation is a token per run; a single space joins the next word
+        if spaces > 1 {
+            tokens += 1;
+        }
+        spaces = 0;
+        // Newlines, punctuation and non-ASCII characters count one each
+        tokens += 1;
+    }
+    tokens + word_tokens(word) + u32::from(spaces > 1)
+}
+
+fn word_tokens(length: u32) -> u32 {
+    length.div_ceil(4)
+}
+
+/// Fits `diff_text` into what `options.model` has left for it after the
+/// system instruction and `prefix`. Small diffs are returned unchanged
+/// without asking the API; without a token count from it, the local
+/// estimate is used with a safety margin.
+pub async fn fit_diff(
+    config: &AppConfig,
+    options: &ModelOptions,
+    system_context: &str,
+    prefix: &str,
+    diff_text: &str,
+) -> String {
+    let limit = limit_for(config, &options.model);
+    let overhead = estimate_tokens(system_context) + estimate_tokens(prefix);
+    let estimate = overhead + estimate_tokens(diff_text);
+    if estimate < limit / 2 {
+        return diff_text.to_string();
+    }
+
+    let count = |diff: String| {
+        let prompt = format!("{}{}", prefix, diff);
+        async move {
+            let api_key = config.api_key();
+            let request = gemini::count_tokens(&api_key, options, system_context, &prompt);
+            match timeout(COUNT_TIMEOUT, request).await {
+                Ok(counted) => counted,
+                Err(_) => Err("token count timed out".to_string()),
+            }
+        }
+    };
+
+    // How far off the local estimate is for this model and this diff
+    let scale = match count(diff_text.to_string()).await {
+        Ok(actual) if actual <= limit => return diff_text.to_string(),
+        Ok(actual) => actual as f64 / estimate.max(1) as f64,
+        Err(error) => {
+            warn!(model = %options.model, error = %error, "token count failed, using the estimate");
+            1.25
+        }
+    };
+
+    let available = limit.saturating_sub((overhead as f64 * scale).ceil() as u32);
+    let fitted = fit(diff_text, available, scale);
+
+    // One correction if the calibrated estimate still came out too low
+    match count(fitted.clone()).await {
+        Ok(actual) if actual > limit => {
+            debug!(model = %options.model, actual, limit, "prompt still over budget, trimming further");
+            let overshoot = ((actual - limit) as f64 * 1.1).ceil() as u32;
+            fit(diff_text, available.saturating_sub(overshoot), scale)
+        }
+        _ => fitted,
+    }
+}
+
+/// Reorders the stat and file sections of `diff_text` by priority and keeps
+/// what fits in `max_tokens`, counting `scale` tokens per estimated token.
+/// The first file that doesn't fit is cut at a line; files after it that
+/// don't fit either are only named.
+pub fn fit(diff_text: &str, max_tokens: u32, scale: f64) -> String {
+    let count = |text: &str| (estimate_tokens(text) as f64 * scale).ceil() as u32;
+
+    let mut sections = condense::file_sections(diff_text);
+    let stat = match sections.first() {
+        Some(first) if !first.starts_with("diff --git ") => sections.remove(0),
+        _ => "",
+    };
+    sections.sort_by_key(|section| priority(section));
+
+    // A huge stat can't take the whole budget
+    let (mut output, mut used) = cut(stat, max_tokens / 2, &count);
+    let mut cut_one = false;
+    let mut left_out = Vec::new();
+    for section in sections {
+        let cost = count(section);
+        let remaining = max_tokens.saturating_sub(used);
+        if cost <= remaining {
+            output.push_str(section);
+            used += cost;
+        } else if !cut_one && remaining >= MIN_PARTIAL_TOKENS {
+            let (partial, cost) = cut(section, remaining - MIN_PARTIAL_TOKENS / 2, &count);
+            output.push_str(&partial);
+            output.push_str("[... rest of this file's diff left out]\n");
+            used += cost;
+            cut_one = true;
+        } else {
+            left_out.push(condense::section_path(section));
+        }
+    }
+
+    if !left_out.is_empty() {
+        output.push_str(&format!(
+            "\n[Diff of {} more file(s) left out: {}]\n",
+            left_out.len(),
+            left_out.join(", ")
+        ));
+    }
+    output
+}
+
+// Source files first, the most changed ahead; docs and condensed files last
+fn priority(section: &str) -> (bool, std::cmp::Reverse<usize>) {
+    let path = condense::section_path(section).to_lowercase();
+    let is_doc = DOC_EXTENSIONS.iter().any(|extension| path.ends_with(extension));
+    let changed = section
+        .lines()
+        .filter(|line| {
+            (line.starts_with('+') && !line.starts_with("+++"))
+                || (line.starts_with('-') && !line.starts_with("---"))
+        })
+        .count();
+    // Condensed sections have no +/- lines left
+    (is_doc || changed == 0, std::cmp::Reverse(changed))
+}
+
+// Whole lines of `text` up to `max_tokens`, with their cost
+fn cut(text: &str, max_tokens: u32, count: &impl Fn(&str) -> u32) -> (String, u32) {
+    let mut kept = String::new();
+    let mut used = 0;
+    for line in text.split_inclusive('\n') {
+        let cost = count(line);
+        if used + cost > max_tokens {
+            break;
+        }
+        kept.push_str(line);
+        used += cost;
+    }
+    (kept, used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(path: &str, lines: usize) -> String {
+        let mut section = format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1 +1 @@\n", path);
+        for number in 0..lines {
+            section.push_str(&format!("+let value_{} = compute_something({});\n", number, number));
+        }
+        section
+    }
+
+    #[test]
+    fn source_files_come_before_docs() {
+        let diff = format!(" 2 files changed\n{}{}", section("README.md", 5), section("src/lib.rs", 2));
+        let fitted = fit(&diff, 10_000, 1.0);
+        assert!(fitted.starts_with(" 2 files changed\n"));
+        assert!(fitted.find("src/lib.rs").unwrap() < fitted.find("README.md").unwrap());
+        assert_eq!(fitted.len(), diff.len());
+    }
+
+    #[test]
+    fn files_past_the_budget_are_cut_then_named() {
+        let diff = format!("{}{}{}", section("src/a.rs", 200), section("src/b.rs", 100), section("src/c.rs", 100));
+        let fitted = fit(&diff, 1_000, 1.0);
+        assert!(estimate_tokens(&fitted) <= 1_100);
+        assert!(fitted.contains("[... rest of this file's diff left out]"));
+        assert!(fitted.contains("[Diff of 2 more file(s) left out: src/b.rs, src/c.rs]"));
+    }
+
+    #[test]
+    fn the_scale_shrinks_what_fits() {
+        let diff = section("src/a.rs", 100);
+        assert!(fit(&diff, 2_000, 2.0).len() < fit(&diff, 2_000, 1.0).len());
+    }
+}
@@ -0,0 +1,41 @@
+//! Editor busy markers: swap, lock and auto-save files that editors keep
+//! next to a file with unsaved changes (`.main.rs.swp`, `.#main.rs`,
+//! `~$report.docx`, ...). While any is in the working tree, timed cycles
+//! are deferred, so a half-saved edit isn't committed.
+
+use crate::artifacts;
+use git2::{Repository, StatusOptions};
+use serde::Serialize;
+
+// Vim, Emacs lock and auto-save files, LibreOffice and Microsoft Office
+// locks, Kate
+pub const DEFAULT_BUSY_MARKERS: [&str; 7] = [".*.swp", ".*.swo", ".#*", "#*#", ".~lock.*#", "~$*", "*.kate-swp"];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BusyMarkers {
+    pub repo: String,
+    pub files: Vec<String>,
+}
+
+/// Files in the working tree of `repo` matching `patterns`, ignored ones
+/// included; the contents of ignored folders aren't searched.
+pub fn find(repo: &Repository, patterns: &[String]) -> Result<Vec<String>, String> {
+    if patterns.iter().all(|pattern| pattern.trim().is_empty()) {
+        return Ok(Vec::new());
+    }
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(true)
+        .exclude_submodules(true);
+    let statuses = repo.statuses(Some(&mut options)).map_err(|e| e.to_string())?;
+
+    Ok(statuses
+        .iter()
+        .filter(|entry| !entry.status().is_wt_deleted() && !entry.status().is_index_deleted())
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .filter(|path| artifacts::is_blocked(path, patterns))
+        .collect())
+}
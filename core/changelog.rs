@@ -0,0 +1,212 @@
+use crate::config::AppConfig;
+use crate::conventional::{self, ConventionalSubject};
+use crate::gemini;
+use chrono::Local;
+use git2::{DescribeFormatOptions, DescribeOptions, Oid, Repository};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+const CHANGELOG_SYSTEM_CONTEXT: &str = r#"You write release notes for a changelog from a list of commits grouped by Conventional Commits type.
+
+RULES:
+1. Start with the given level-2 heading, unchanged
+2. Then level-3 sections, in this order when present: Breaking Changes, Features, Bug Fixes, Performance, Documentation, Other Changes
+3. One bullet per user-visible change, in plain language and imperative mood: "Add ...", "Fix ..."
+4. Merge commits that describe the same change, and leave out purely internal chores
+5. Keep scopes as a bold prefix, e.g. "**auth:** Add JWT validation"
+6. Do not invent changes that are not in the commits
+
+Always respond with ONLY the Markdown section, no explanations or additional text."#;
+
+// Changelog sections and the commit types that go in them, in display order
+const SECTIONS: [(&str, &[&str]); 5] = [
+    ("Features", &["feat"]),
+    ("Bug Fixes", &["fix"]),
+    ("Performance", &["perf"]),
+    ("Documentation", &["docs"]),
+    ("Other Changes", &[]),
+];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChangelogCommit {
+    pub sha: String,
+    // Conventional Commits type, `other` when the subject doesn't follow it
+    pub commit_type: String,
+    pub scope: Option<String>,
+    pub subject: String,
+    pub breaking: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Changelog {
+    // Start of the range, None when it covers the whole history
+    pub from: Option<String>,
+    pub to: String,
+    pub commits: Vec<ChangelogCommit>,
+    pub markdown: String,
+    // Set when the section was added to CHANGELOG.md
+    pub written_to: Option<String>,
+}
+
+/// The most recent tag reachable from HEAD, with the commit it points at.
+pub fn latest_tag(repo: &Repository) -> Option<(String, Oid)> {
+    let describe = repo
+        .describe(DescribeOptions::new().describe_tags())
+        .ok()?;
+    let name = describe
+        .format(Some(DescribeFormatOptions::new().abbreviated_size(0)))
+        .ok()?;
+    let oid = repo.revparse_single(&name).ok()?.peel_to_commit().ok()?.id();
+    Some((name, oid))
+}
+
+/// Commits reachable from `to` but not from `from`, newest first. Merge
+/// commits are left out.
+pub fn commits_between(
+    repo: &Repository,
+    from: Option<&str>,
+    to: &str,
+) -> Result<Vec<ChangelogCommit>, String> {
+    let resolve = |spec: &str| {
+        repo.revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map(|commit| commit.id())
+            .map_err(|e| format!("Unknown revision {}: {}", spec, e))
+    };
+
+    let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+    revwalk.push(resolve(to)?).map_err(|e| e.to_string())?;
+    if let Some(from) = from {
+        revwalk.hide(resolve(from)?).map_err(|e| e.to_string())?;
+    }
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo
+            .find_commit(oid.map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+
+        let message = commit.message().unwrap_or_default();
+        let breaking_footer = conventional::body(message).contains("BREAKING CHANGE");
+        let parsed = conventional::parse(message).unwrap_or_else(|| ConventionalSubject {
+            commit_type: "other".into(),
+            scope: None,
+            breaking: false,
+            subject: message.lines().next().unwrap_or_default().trim().to_string(),
+        });
+        commits.push(ChangelogCommit {
+            sha: commit.id().to_string(),
+            commit_type: parsed.commit_type,
+            scope: parsed.scope,
+            subject: parsed.subject,
+            breaking: parsed.breaking || breaking_footer,
+        });
+    }
+    Ok(commits)
+}
+
+/// Builds a changelog section for the commits between `from` (the latest
+/// tag by default) and `to` (HEAD by default). The model writes it when an
+/// API key is configured; otherwise, or if it fails, the commits are listed
+/// by type. With `write` the section is added to the top of the
+/// repository's `CHANGELOG.md`.
+pub async fn generate_changelog(
+    repo_id: &str,
+    config: &AppConfig,
+    from: Option<String>,
+    to: Option<String>,
+    title: Option<String>,
+    write: bool,
+) -> Result<Changelog, String> {
+    let (from, to, commits) = {
+        let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+        let to = to.unwrap_or_else(|| "HEAD".into());
+        let from = from.or_else(|| latest_tag(&repo).map(|(name, _)| name));
+        let commits = commits_between(&repo, from.as_deref(), &to)?;
+        (from, to, commits)
+    };
+    if commits.is_empty() {
+        return Err("No commits in the selected range".into());
+    }
+
+    let title = title.unwrap_or_else(|| if to == "HEAD" { "Unreleased".into() } else { to.clone() });
+    let heading = format!("## {} ({})", title, Local::now().format("%Y-%m-%d"));
+    let fallback = render(&heading, &commits);
+
+    let markdown = if config.api_key().is_empty() {
+        fallback
+    } else {
+        let prompt = format!(
+            "Write the changelog section with the heading \"{}\" for these commits:\n\n{}",
+            heading, fallback
+        );
+        let options = gemini::ModelOptions::from_config(config).for_repo(repo_id);
+        match gemini::generate(&config.api_key(), &options, CHANGELOG_SYSTEM_CONTEXT, &prompt).await {
+            Ok(text) if text.trim_start().starts_with("## ") => text.trim().to_string(),
+            Ok(_) => fallback,
+            Err(error) => {
+                tracing::warn!(repo = repo_id, error = %error, "changelog generation failed, listing commits");
+                fallback
+            }
+        }
+    };
+
+    let written_to = if write { Some(prepend_to_changelog(repo_id, &markdown)?) } else { None };
+    Ok(Changelog { from, to, commits, markdown, written_to })
+}
+
+/// Deterministic changelog section: breaking changes first, then one
+/// section per group of commit types.
+pub fn render(heading: &str, commits: &[ChangelogCommit]) -> String {
+    let bullet = |commit: &ChangelogCommit| {
+        let scope = commit
+            .scope
+            .as_ref()
+            .map(|scope| format!("**{}:** ", scope))
+            .unwrap_or_default();
+        format!("- {}{} ({:.7})", scope, commit.subject, commit.sha)
+    };
+
+    let mut sections: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for commit in commits {
+        if commit.breaking {
+            sections.entry(0).or_default().push(bullet(commit));
+        }
+        let section = SECTIONS
+            .iter()
+            .position(|(_, types)| types.contains(&commit.commit_type.as_str()))
+            .unwrap_or(SECTIONS.len() - 1);
+        sections.entry(section + 1).or_default().push(bullet(commit));
+    }
+
+    let mut markdown = heading.to_string();
+    for (index, bullets) in sections {
+        let name = if index == 0 { "Breaking Changes" } else { SECTIONS[index - 1].0 };
+        markdown.push_str(&format!("\n\n### {}\n\n{}", name, bullets.join("\n")));
+    }
+    markdown
+}
+
+// Keeps a leading `# Changelog` title above the new section
+fn prepend_to_changelog(repo_id: &str, section: &str) -> Result<String, String> {
+    let path = std::path::Path::new(repo_id).join("CHANGELOG.md");
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+
+    let (title, rest) = match existing.strip_prefix("# ") {
+        Some(_) => existing.split_once('\n').unwrap_or((existing.as_str(), "")),
+        None => ("# Changelog", existing.as_str()),
+    };
+    let rest = rest.trim_start();
+    let content = if rest.is_empty() {
+        format!("{}\n\n{}\n", title, section)
+    } else {
+        format!("{}\n\n{}\n\n{}", title, section, rest)
+    };
+
+    fs::write(&path, content).map_err(|e| format!("Failed to write CHANGELOG.md: {}", e))?;
+    Ok(path.display().to_string())
+}
@@ -0,0 +1,60 @@
+//! CI gate: before pushing, the latest build of the branch being pushed to
+//! is looked up on the repository's forge, so commits aren't pushed on top
+//! of a failing one.
+
+use crate::config::{AppConfig, RepoSettings};
+use crate::forge;
+use crate::github;
+use crate::proxy::{self, ProxySettings};
+use git2::Repository;
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CiState {
+    Passing,
+    Pending,
+    Failing,
+    // No checks or pipelines ran for the branch
+    Unknown,
+}
+
+/// A forge's answer for one branch.
+#[derive(Serialize, Clone, Debug)]
+pub struct BuildStatus {
+    pub state: CiState,
+    // Names of the failed checks or jobs
+    pub failing: Vec<String>,
+    // Page with the build, when the forge gives one
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CiStatus {
+    pub repo: String,
+    pub forge: &'static str,
+    pub branch: String,
+    #[serde(flatten)]
+    pub build: BuildStatus,
+}
+
+/// The latest CI result of the branch the repository pushes to: the
+/// destination of `push_refspec`, or else the current branch.
+pub async fn branch_status(path: &str, config: &AppConfig, settings: &RepoSettings) -> Result<CiStatus, String> {
+    let (forge, branch) = {
+        let repo = Repository::open(path).map_err(|e| e.to_string())?;
+        (forge::for_repo(&repo, settings)?, target_branch(&repo, settings)?)
+    };
+    let client = proxy::client(&ProxySettings::from_config(config))?;
+    let build = forge.build_status(&client, &branch).await?;
+    Ok(CiStatus { repo: path.to_string(), forge: forge.name(), branch, build })
+}
+
+fn target_branch(repo: &Repository, settings: &RepoSettings) -> Result<String, String> {
+    match settings.push_refspec.trim().split_once(':') {
+        Some((_, destination)) if !destination.is_empty() => {
+            Ok(destination.trim_start_matches("refs/heads/").to_string())
+        }
+        _ => github::current_branch(repo),
+    }
+}
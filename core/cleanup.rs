@@ -0,0 +1,179 @@
+//! Maintenance of backup branch namespaces (`autocommit/*`, `autosave/*`
+//! by default): old branches whose work is already merged, or carried on by
+//! a newer branch in the same namespace, are listed and can be deleted
+//! locally and on their remote.
+
+use crate::config::AppConfig;
+use crate::proxy::{self, ProxySettings};
+use git2::{BranchType, Oid, Repository};
+use serde::Serialize;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleReason {
+    // Reachable from HEAD
+    Merged,
+    // A newer branch in the namespace builds on it
+    Superseded,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StaleBranch {
+    // Without the remote prefix, e.g. `autosave/2024-05-01`
+    pub name: String,
+    // Set for remote-tracking branches
+    pub remote: Option<String>,
+    pub sha: String,
+    pub age_days: u64,
+    pub reason: StaleReason,
+}
+
+struct Candidate {
+    name: String,
+    remote: Option<String>,
+    tip: Oid,
+    time: i64,
+}
+
+fn candidates(repo: &Repository, prefixes: &[String]) -> Result<Vec<Candidate>, String> {
+    let remotes: Vec<String> = repo
+        .remotes()
+        .map(|names| names.iter().flatten().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let mut found = Vec::new();
+    for entry in repo.branches(None).map_err(|e| e.to_string())? {
+        let (branch, branch_type) = entry.map_err(|e| e.to_string())?;
+        // The checked-out branch is never stale, however old its tip
+        if branch.is_head() {
+            continue;
+        }
+        let full_name = match branch.name() {
+            Ok(Some(name)) => name.to_string(),
+            _ => continue,
+        };
+
+        // `origin/autosave/x` -> (`origin`, `autosave/x`)
+        let (remote, name) = match branch_type {
+            BranchType::Local => (None, full_name),
+            BranchType::Remote => match remotes
+                .iter()
+                .find_map(|remote| full_name.strip_prefix(&format!("{}/", remote)).map(|name| (remote, name)))
+            {
+                Some((remote, name)) => (Some(remote.clone()), name.to_string()),
+                None => continue,
+            },
+        };
+        if !prefixes.iter().any(|prefix| !prefix.is_empty() && name.starts_with(prefix.as_str())) {
+            continue;
+        }
+
+        let commit = match branch.get().peel_to_commit() {
+            Ok(commit) => commit,
+            Err(_) => continue,
+        };
+        found.push(Candidate {
+            name,
+            remote,
+            tip: commit.id(),
+            time: commit.time().seconds(),
+        });
+    }
+    Ok(found)
+}
+
+/// Backup branches older than `branch_cleanup_age_days` that are merged
+/// into HEAD or superseded. Nothing is deleted.
+pub fn stale_branches(repo_id: &str, config: &AppConfig) -> Result<Vec<StaleBranch>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let head = repo.head().ok().and_then(|head| head.target());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    let candidates = candidates(&repo, &config.branch_cleanup_prefixes)?;
+    let mut stale = Vec::new();
+    for candidate in &candidates {
+        let age_days = (now - candidate.time).max(0) as u64 / 86_400;
+        if age_days < config.branch_cleanup_age_days {
+            continue;
+        }
+
+        let merged = head.is_some_and(|head| {
+            head == candidate.tip || repo.graph_descendant_of(head, candidate.tip).unwrap_or(false)
+        });
+        let superseded = || {
+            candidates.iter().any(|other| {
+                other.tip != candidate.tip
+                    && other.time > candidate.time
+                    && repo.graph_descendant_of(other.tip, candidate.tip).unwrap_or(false)
+            })
+        };
+        let reason = if merged {
+            StaleReason::Merged
+        } else if superseded() {
+            StaleReason::Superseded
+        } else {
+            continue;
+        };
+
+        stale.push(StaleBranch {
+            name: candidate.name.clone(),
+            remote: candidate.remote.clone(),
+            sha: candidate.tip.to_string(),
+            age_days,
+            reason,
+        });
+    }
+    Ok(stale)
+}
+
+/// Deletes the branches `stale_branches` currently reports: local ones with
+/// git2, remote ones with `git push --delete`. Returns the deleted names;
+/// a branch that fails to delete is reported without stopping the rest.
+pub fn delete_stale_branches(repo_id: &str, config: &AppConfig) -> Result<(Vec<String>, Vec<String>), String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let proxy = ProxySettings::from_config(config);
+    let mut deleted = Vec::new();
+    let mut errors = Vec::new();
+
+    for branch in stale_branches(repo_id, config)? {
+        let result = match &branch.remote {
+            None => repo
+                .find_branch(&branch.name, BranchType::Local)
+                .and_then(|mut local| local.delete())
+                .map_err(|e| e.to_string()),
+            Some(remote) => delete_remote_branch(repo_id, remote, &branch.name, &proxy),
+        };
+
+        let label = match &branch.remote {
+            Some(remote) => format!("{}/{}", remote, branch.name),
+            None => branch.name.clone(),
+        };
+        match result {
+            Ok(()) => deleted.push(label),
+            Err(error) => errors.push(format!("{}: {}", label, error)),
+        }
+    }
+    Ok((deleted, errors))
+}
+
+fn delete_remote_branch(repo_id: &str, remote: &str, name: &str, proxy: &ProxySettings) -> Result<(), String> {
+    let mut command = Command::new("git");
+    command
+        .arg("push")
+        .arg(remote)
+        .arg("--delete")
+        .arg(name)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(repo_id);
+    proxy::configure_git(&mut command, proxy);
+    let output = command.output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
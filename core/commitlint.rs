@@ -0,0 +1,203 @@
+//! Conventional Commits types and scopes a repository allows: the
+//! repository's `allowed_commit_types` / `allowed_commit_scopes`, or else
+//! the `type-enum` / `scope-enum` rules of its commitlint config or the
+//! types and scopes of its `.czrc`. They go into the prompt, and a message
+//! using anything else is asked for again and never committed.
+
+use crate::config::RepoSettings;
+use crate::conventional;
+use regex::Regex;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// Searched in this order; JavaScript and TypeScript configs aren't run,
+// their rules are read from the source
+const COMMITLINT_FILES: [&str; 11] = [
+    ".commitlintrc",
+    ".commitlintrc.json",
+    ".commitlintrc.js",
+    ".commitlintrc.cjs",
+    ".commitlintrc.mjs",
+    ".commitlintrc.ts",
+    "commitlint.config.js",
+    "commitlint.config.cjs",
+    "commitlint.config.mjs",
+    "commitlint.config.ts",
+    "package.json",
+];
+
+const COMMITIZEN_FILES: [&str; 2] = [".czrc", ".cz.json"];
+
+/// Allowed types and scopes, lowercased; an empty list allows anything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AllowList {
+    pub types: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+impl AllowList {
+    pub fn for_repo(repo_path: &str, settings: &RepoSettings) -> Self {
+        let configured = AllowList {
+            types: normalize(&settings.allowed_commit_types),
+            scopes: normalize(&settings.allowed_commit_scopes),
+        };
+        if !configured.types.is_empty() && !configured.scopes.is_empty() {
+            return configured;
+        }
+
+        let found = from_repo_files(Path::new(repo_path));
+        AllowList {
+            types: if configured.types.is_empty() { found.types } else { configured.types },
+            scopes: if configured.scopes.is_empty() { found.scopes } else { configured.scopes },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.scopes.is_empty()
+    }
+
+    /// What in `message` isn't allowed, worded so it can be sent back to the
+    /// model. A message without a scope is fine.
+    pub fn problems(&self, message: &str) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let parsed = match conventional::parse(message) {
+            Some(parsed) => parsed,
+            None => return vec!["The subject line must follow `type(scope): subject`".into()],
+        };
+
+        let mut problems = Vec::new();
+        if !self.types.is_empty() && !self.types.contains(&parsed.commit_type) {
+            problems.push(format!(
+                "The type `{}` is not allowed; use one of: {}",
+                parsed.commit_type,
+                self.types.join(", ")
+            ));
+        }
+        if let Some(scope) = &parsed.scope {
+            if !self.scopes.is_empty() && !self.scopes.contains(&scope.to_lowercase()) {
+                problems.push(format!("The scope `{}` is not allowed; use one of: {}", scope, self.scopes.join(", ")));
+            }
+        }
+        problems
+    }
+
+    /// Fails with the problems when `message` isn't allowed.
+    pub fn check(&self, message: &str) -> Result<(), String> {
+        let problems = self.problems(message);
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(format!("The commit message breaks the repository's commit rules: {}", problems.join("; ")))
+    }
+
+    /// A line for the system instruction naming the allowed types and scopes.
+    pub fn prompt_hint(&self) -> Option<String> {
+        let mut hint = Vec::new();
+        if !self.types.is_empty() {
+            hint.push(format!("The type must be one of: {}.", self.types.join(", ")));
+        }
+        if !self.scopes.is_empty() {
+            hint.push(format!("The scope, if any, must be one of: {}.", self.scopes.join(", ")));
+        }
+        (!hint.is_empty()).then(|| hint.join(" "))
+    }
+}
+
+fn normalize(values: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for value in values.iter().map(|value| value.trim().to_lowercase()) {
+        if !value.is_empty() && !normalized.contains(&value) {
+            normalized.push(value);
+        }
+    }
+    normalized
+}
+
+// The first commitlint config with an enum rule, then commitizen's config
+fn from_repo_files(repo: &Path) -> AllowList {
+    let mut found = AllowList::default();
+    for name in COMMITLINT_FILES {
+        let content = match fs::read_to_string(repo.join(name)) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        if name == "package.json" && !content.contains("\"commitlint\"") {
+            continue;
+        }
+        let types = enum_rule(&content, "type-enum");
+        let scopes = enum_rule(&content, "scope-enum");
+        if !types.is_empty() || !scopes.is_empty() {
+            found = AllowList { types, scopes };
+            break;
+        }
+    }
+    if !found.types.is_empty() && !found.scopes.is_empty() {
+        return found;
+    }
+
+    for name in COMMITIZEN_FILES {
+        let czrc = match fs::read_to_string(repo.join(name)).ok().and_then(|content| serde_json::from_str(&content).ok()) {
+            Some(czrc) => czrc,
+            None => continue,
+        };
+        let from_czrc = commitizen_lists(&czrc);
+        if found.types.is_empty() {
+            found.types = from_czrc.types;
+        }
+        if found.scopes.is_empty() {
+            found.scopes = from_czrc.scopes;
+        }
+        break;
+    }
+    found
+}
+
+// `'type-enum': [2, 'always', ['feat', 'fix']]`, as JSON or JavaScript. A
+// disabled rule or a `never` list allows anything.
+fn enum_rule(content: &str, rule: &str) -> Vec<String> {
+    let pattern = format!(
+        r#"["']?{}["']?\s*:\s*\[\s*(\d)\s*,\s*["'](always|never)["']\s*,\s*\[([^\]]*)\]"#,
+        regex::escape(rule)
+    );
+    let captures = match Regex::new(&pattern).ok().and_then(|regex| regex.captures(content)) {
+        Some(captures) => captures,
+        None => return Vec::new(),
+    };
+    if &captures[1] == "0" || &captures[2] == "never" {
+        return Vec::new();
+    }
+    let item = Regex::new(r#"["']([^"']+)["']"#).expect("valid regex");
+    let items: Vec<String> = item.captures_iter(&captures[3]).map(|item| item[1].to_string()).collect();
+    normalize(&items)
+}
+
+// `types` as an object keyed by type (cz-conventional-changelog) or a list
+// of strings or `{value}` objects (cz-customizable, cz-git); `scopes` as a
+// list of strings or `{name}` / `{value}` objects
+fn commitizen_lists(czrc: &Value) -> AllowList {
+    let names = |value: Option<&Value>, keys: &[&str]| -> Vec<String> {
+        let items: Vec<String> = match value {
+            Some(Value::Object(map)) => map.keys().cloned().collect(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Value::String(name) => Some(name.clone()),
+                    Value::Object(object) => keys
+                        .iter()
+                        .find_map(|key| object.get(*key).and_then(Value::as_str))
+                        .map(str::to_string),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        normalize(&items)
+    };
+    AllowList {
+        types: names(czrc.get("types"), &["value"]),
+        scopes: names(czrc.get("scopes"), &["name", "value"]),
+    }
+}
@@ -0,0 +1,121 @@
+//! Shrinks a diff before it is sent to the model. Lockfile and generated
+//! file hunks are replaced with a line count, so one `Cargo.lock` update
+//! can't fill the whole prompt, and binary content is never sent.
+
+use crate::gitattributes::{BINARY_EXTENSIONS, GENERATED_SUFFIXES, LOCKFILES};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Condensed {
+    Lockfile,
+    Generated,
+    Binary,
+}
+
+/// Rewrites the `git diff` output in `diff_content` file by file. Files
+/// worth reading are kept as they are; the rest keep their `diff --git`
+/// header followed by a one-line summary such as
+/// `Cargo.lock: 312 lines changed (lockfile)`.
+pub fn condense(diff_content: &str) -> String {
+    let mut output = String::with_capacity(diff_content.len());
+    for section in file_sections(diff_content) {
+        let path = section_path(section);
+        match classify(&path, section) {
+            Some(kind) => {
+                let header = section.lines().next().unwrap_or_default();
+                output.push_str(header);
+                output.push('\n');
+                output.push_str(&summary(&path, kind, section));
+                output.push('\n');
+            }
+            None => output.push_str(section),
+        }
+    }
+    output
+}
+
+/// Splits at each `diff --git` line, keeping the lines with their
+/// section. Text before the first file, such as a stat, is a section too.
+pub fn file_sections(diff_content: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = diff_content
+        .match_indices("diff --git ")
+        .map(|(index, _)| index)
+        .filter(|index| *index == 0 || diff_content.as_bytes()[index - 1] == b'\n')
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts.push(diff_content.len());
+
+    starts
+        .windows(2)
+        .map(|bounds| &diff_content[bounds[0]..bounds[1]])
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// `diff --git a/src/x.rs b/src/x.rs` -> `src/x.rs`; empty for text that
+/// isn't a file section.
+pub fn section_path(section: &str) -> String {
+    let header = section.lines().next().unwrap_or_default();
+    header
+        .strip_prefix("diff --git ")
+        .and_then(|paths| paths.rsplit_once(" b/").map(|(_, path)| path))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn classify(path: &str, section: &str) -> Option<Condensed> {
+    if path.is_empty() {
+        return None;
+    }
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+
+    // Binary first: a binary lockfile is still binary
+    let binary_marker = section
+        .lines()
+        .any(|line| line.starts_with("Binary files ") || line.starts_with("GIT binary patch"));
+    // Content git didn't recognise as binary but that isn't valid text
+    // either shows up as NULs or replacement characters after lossy decoding
+    if binary_marker
+        || BINARY_EXTENSIONS.contains(&extension.as_str())
+        || section.contains('\0')
+        || section.contains('\u{FFFD}')
+    {
+        return Some(Condensed::Binary);
+    }
+    if LOCKFILES.contains(&file_name) {
+        return Some(Condensed::Lockfile);
+    }
+    if GENERATED_SUFFIXES.iter().any(|suffix| file_name.ends_with(suffix)) {
+        return Some(Condensed::Generated);
+    }
+    None
+}
+
+fn summary(path: &str, kind: Condensed, section: &str) -> String {
+    if kind == Condensed::Binary {
+        return format!("{}: binary file changed", path);
+    }
+
+    let (added, removed) = section
+        .lines()
+        .filter(|line| !line.starts_with("+++") && !line.starts_with("---"))
+        .fold((0, 0), |(added, removed), line| match line.chars().next() {
+            Some('+') => (added + 1, removed),
+            Some('-') => (added, removed + 1),
+            _ => (added, removed),
+        });
+    let label = if kind == Condensed::Lockfile { "lockfile" } else { "generated" };
+    format!(
+        "{}: {} lines changed (+{} -{}, {})",
+        path,
+        added + removed,
+        added,
+        removed,
+        label
+    )
+}
@@ -0,0 +1,824 @@
+use crate::artifacts;
+use crate::budget;
+use crate::busy;
+use crate::gemini;
+use crate::issues::IssuePlacement;
+use crate::pipeline::FallbackStep;
+use crate::power::PowerAction;
+use crate::repo_path;
+use crate::schedule::ScheduleWindow;
+use crate::secrets;
+use crate::style::{self, MessageStyle};
+use crate::submodules::SubmodulePolicy;
+use crate::timer_state::CatchUpPolicy;
+use crate::tracker::IssueTracker;
+use crate::trailers::Trailer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use git2::Repository;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// Environment variable that overrides every other source of the API key.
+pub const API_KEY_ENV: &str = "GEMINI_API_KEY";
+
+/// Where the Gemini API key in use comes from, in the order they are tried.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeySource {
+    Environment,
+    // A `.env` file in the working directory or the data dir
+    DotEnv,
+    Keychain,
+    ConfigFile,
+}
+
+impl ApiKeySource {
+    pub fn describe(self) -> &'static str {
+        match self {
+            ApiKeySource::Environment => "the GEMINI_API_KEY environment variable",
+            ApiKeySource::DotEnv => "a .env file",
+            ApiKeySource::Keychain => "the OS keychain",
+            ApiKeySource::ConfigFile => "config.json",
+        }
+    }
+}
+
+/// Schema version written to `config.json`. Files with an older one are
+/// upgraded by `migrate` when loaded. Still 1: every field added since the
+/// first release is optional, so the files it wrote load as they are.
+pub const CONFIG_VERSION: u32 = 1;
+
+// Missing fields fall back to their defaults so older config files keep loading
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AppConfig {
+    pub repo_path: String,
+    pub auto_commit_enabled: bool,
+    pub interval_minutes: u64,
+    pub auto_start: bool,
+    pub gemini_api_key: String,
+    // Gemini model used for generation, e.g. `gemini-2.0-flash-exp`
+    pub model: String,
+    // Sampling settings; None keeps the model's defaults
+    pub temperature: Option<f32>,
+    pub max_output_tokens: Option<u32>,
+    // Tokens the prompt may take, diff included; larger diffs are trimmed
+    pub prompt_token_limit: u32,
+    // Per-model overrides of `prompt_token_limit`, keyed by model name
+    pub prompt_token_limits: HashMap<String, u32>,
+    // Tokens a calendar month may use before timed cycles pause, 0 for no limit
+    pub monthly_token_budget: u64,
+    // Size guard: cycles with more changed files, a bigger diff or a bigger
+    // single file than this stop until confirmed; 0 turns a limit off
+    pub guard_max_files: usize,
+    pub guard_max_total_bytes: u64,
+    pub guard_max_file_bytes: u64,
+    // Where the issue found by `issue_patterns` is looked up, to tell the
+    // model what the work is for; the API token is kept in the keychain
+    pub issue_tracker: IssueTracker,
+    // Jira site, e.g. `https://example.atlassian.net`
+    pub jira_base_url: String,
+    // Account email for Jira Cloud API tokens; empty sends the token as a
+    // Jira Server personal access token
+    pub jira_email: String,
+    // Gemini API root, overridable to go through a corporate proxy
+    pub api_base_url: String,
+    // Proxy for Gemini, GitHub, webhooks and git remotes, e.g.
+    // `http://proxy.corp:8080` or `socks5://127.0.0.1:1080`. Empty means
+    // none, or the one from the environment
+    pub proxy_url: String,
+    // The password is kept in the keychain
+    pub proxy_username: String,
+    // Honour `HTTPS_PROXY` and friends when `proxy_url` is empty
+    pub use_environment_proxy: bool,
+    // Run the repo's pre-commit and commit-msg hooks before committing, in
+    // place of git's own run of them; off commits with `--no-verify`
+    pub run_git_hooks: bool,
+    // Per-repository settings, keyed by repository path
+    pub repos: Vec<RepoSettings>,
+    // Publish events as NDJSON on a local socket / named pipe
+    pub event_stream_enabled: bool,
+    // Serve the editor integration protocol on a local socket / named pipe
+    pub editor_api_enabled: bool,
+    // Serve JSON status on 127.0.0.1 at this port, 0 turns it off
+    pub status_server_port: u16,
+    // Serve the token-protected control API on 127.0.0.1 at this port, 0
+    // turns it off
+    pub control_api_port: u16,
+    // Rewrite `heartbeat.json` in the data dir this often, 0 turns it off
+    pub heartbeat_interval_secs: u64,
+    // How long quitting waits for a running cycle to wind down
+    pub shutdown_timeout_secs: u64,
+    // Consecutive failed cycles before a repo enters safe mode, 0 disables it
+    pub safe_mode_failure_threshold: u32,
+    // How much slower the timer runs for a repo in safe mode
+    pub safe_mode_interval_multiplier: u64,
+    pub message_style: MessageStyle,
+    // Used by the custom-template style, e.g. `[{type}] {subject}`
+    pub custom_message_template: String,
+    // Latency budget per model before falling back to the next tier
+    pub generation_timeout_secs: u64,
+    // Faster model tried when the primary one exceeds the budget, empty skips it
+    pub fallback_model: String,
+    // What to try, in order, when the primary model gives no message
+    pub fallback_chain: Vec<FallbackStep>,
+    // OpenAI-compatible server for the `local_model` step, e.g.
+    // `http://localhost:11434` for Ollama; empty skips the step
+    pub local_model_url: String,
+    pub local_model: String,
+    // Message of the `template` step; `{files}` is the number of changed files
+    pub fallback_template: String,
+    // Model for cycles that only touch Markdown or text files, or
+    // `rule-based` to skip the API for them; empty uses `model`
+    pub text_only_model: String,
+    // Derive the Conventional Commits scope from the changed paths when the
+    // model leaves it out or makes one up
+    pub infer_scope: bool,
+    // Directory whose first segment becomes the scope, e.g. `src/auth/x.rs` -> `auth`
+    pub scope_root: String,
+    // Path prefix -> scope, taking precedence over `scope_root`
+    pub scope_overrides: HashMap<String, String>,
+    // Package directories whose folder name is the scope, e.g. `packages/*`
+    pub scope_patterns: Vec<String>,
+    // Use the Cargo and npm workspace package names as scopes
+    pub workspace_scopes: bool,
+    // Commit the changes of each package separately, dependencies first;
+    // packages come from `scope_patterns` and `workspace_scopes`
+    pub monorepo_commits: bool,
+    // Desktop notifications, per category
+    pub notify_commits: bool,
+    pub notify_push_failures: bool,
+    pub notify_api_errors: bool,
+    pub notify_approvals: bool,
+    pub notify_safe_mode: bool,
+    // Timed cycles queue their commit for the user to approve or reject
+    // instead of committing it, see `approvals`
+    pub commit_approval: bool,
+    // Scheduled cycles wait until no file has changed for this long, 0 disables it
+    pub idle_seconds: u64,
+    // Scheduled cycles are deferred while a file matching one of these
+    // globs, such as an editor's swap or lock file, is in the working tree
+    pub busy_markers: Vec<String>,
+    // Replace identifiers, string literals and file names with placeholders
+    // before the diff is sent to Gemini
+    pub anonymize_diff: bool,
+    // Scheduled cycles only run inside these windows; empty means any time
+    pub schedule: Vec<ScheduleWindow>,
+    // Start the timer when a schedule window opens and stop it, after a
+    // final commit, when the window closes
+    pub working_hours_auto_toggle: bool,
+    // Cycles with more changed files are committed in directory-grouped
+    // batches of at most this many files, 0 disables batching
+    pub max_files_per_commit: usize,
+    // Each gets a JSON POST after a cycle commits, e.g. Slack or Discord
+    // incoming webhook URLs
+    pub webhook_urls: Vec<String>,
+    // Generated messages containing any of these (case-insensitive) are rejected
+    pub forbidden_message_patterns: Vec<String>,
+    // Ask the model once more, listing the problems, when its message is
+    // malformed; otherwise it is only fixed up deterministically
+    pub reask_invalid_messages: bool,
+    // Candidate messages Gemini writes per generation (up to 8); the best
+    // scoring one is used
+    pub message_candidates: u32,
+    // Minutes a generated message is reused for an identical diff; 0 turns
+    // the cache off
+    pub message_cache_ttl_minutes: u64,
+    // Language of the description and body, e.g. `Spanish`; the Conventional
+    // Commits type stays in English. Empty means English.
+    pub commit_language: String,
+    // Move files with conflict markers to a stash entry instead of only
+    // leaving them out of the commit
+    pub stash_conflicted_files: bool,
+    // Backup branch namespaces the cleanup command looks at
+    pub branch_cleanup_prefixes: Vec<String>,
+    // Branches in them younger than this are never reported as stale
+    pub branch_cleanup_age_days: u64,
+    // A commit within this many minutes of the app's previous commit amends
+    // it while it is unpushed; 0 always adds a new commit
+    pub amend_window_minutes: u64,
+    // Restart the timer on launch when it was running at the last exit
+    pub resume_timer_on_startup: bool,
+    // What to do about intervals missed while the app was closed or the
+    // machine asleep
+    pub catch_up_policy: CatchUpPolicy,
+    // Wait before the catch-up cycle with the `delayed` policy
+    pub catch_up_delay_secs: u64,
+    // Timed runs are spread over this many seconds so repositories on the
+    // same interval don't all fire at once; 0 turns the spreading off
+    pub schedule_jitter_secs: u64,
+    // Limits across all repositories; 0 means no limit
+    pub max_concurrent_cycles: usize,
+    pub max_concurrent_api_calls: usize,
+    pub max_concurrent_pushes: usize,
+    // What timed cycles do on battery below `min_battery_percent`, on a
+    // metered network and offline: `ignore`, `local` (commit, don't push)
+    // or `defer` (skip the cycle)
+    pub low_battery_action: PowerAction,
+    pub min_battery_percent: u8,
+    pub metered_network_action: PowerAction,
+    pub offline_action: PowerAction,
+    // Suggest .gitignore rules for build output and tool folders that turn
+    // up in this many cycles in a row; 0 turns the suggestions off
+    pub gitignore_suggest_after_cycles: u32,
+    // Let the model refine the suggested rules from the file list
+    pub gitignore_ai_suggestions: bool,
+    // Named repository settings to start repositories from; their `path`
+    // is ignored
+    pub repo_templates: HashMap<String, RepoSettings>,
+    // Template for repositories added without naming one, and for those
+    // without settings of their own; empty means none
+    pub default_repo_template: String,
+    // Schema of the file, see `CONFIG_VERSION`
+    pub config_version: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RepoSettings {
+    pub path: String,
+    // Minutes between background fetches, 0 disables them
+    pub fetch_interval_minutes: u64,
+    // Shell command that must succeed before staging, e.g. `cargo check`
+    pub pre_commit_command: String,
+    pub pre_commit_timeout_secs: u64,
+    // Open or update a GitHub pull request into `pull_request_base` after
+    // pushing from any other branch
+    pub auto_pull_request: bool,
+    pub pull_request_base: String,
+    pub generate_pull_request_description: bool,
+    // Staged changes under `docs_paths` are committed to this branch instead
+    // of the current one, without checking it out. Empty disables the split.
+    pub docs_branch: String,
+    pub docs_paths: Vec<String>,
+    // Appended to every commit message, e.g. `Generated-by: GitGenius`
+    pub trailers: Vec<Trailer>,
+    // Add `Signed-off-by` with the name and email from git config
+    pub sign_off: bool,
+    // Timed cycles commit the working tree to `refs/gitgenius/snapshots/<branch>`
+    // instead of the current branch, and don't push
+    pub snapshot_mode: bool,
+    // Replaces the global `fallback_chain` for this repo
+    pub fallback_chain: Option<Vec<FallbackStep>>,
+    // Remote to push to; empty uses the branch's upstream
+    pub push_remote: String,
+    // What to push, e.g. `HEAD:refs/heads/wip`; empty pushes the current
+    // branch to the branch of the same name
+    pub push_refspec: String,
+    // What a cycle does with submodules: leave them, commit moved pointers,
+    // or also commit inside them first
+    pub submodule_policy: SubmodulePolicy,
+    // Regexes matched against the branch name to find the issue ID, e.g.
+    // `[A-Z]+-\d+`; a capture group narrows the match
+    pub issue_patterns: Vec<String>,
+    // Issue used when the branch name has none
+    pub issue_ticket: String,
+    pub issue_placement: IssuePlacement,
+    // How the reference is written, `{id}` is replaced by the issue ID
+    pub issue_format: String,
+    // Refuse to commit without an issue reference in the message
+    pub require_issue_reference: bool,
+    // Hold pushes back while the latest GitHub or GitLab build of the
+    // target branch is failing
+    pub ci_gate: bool,
+    // Timed cycles skip changes that are only whitespace
+    pub skip_trivial_changes: bool,
+    // ...or touch at most this many lines once whitespace is ignored
+    pub trivial_change_max_lines: usize,
+    // Commit on `autocommit/<date>` branches, squashed into the real branch
+    // with `squash_day_into_branch`
+    pub daily_branches: bool,
+    // Commit identity for this repository instead of git's user.name and
+    // user.email; empty keeps git's
+    pub author_name: String,
+    pub author_email: String,
+    // Files never staged, e.g. `*.orig`; a pattern without `/` matches the
+    // file name anywhere. Empty turns the blocklist off.
+    pub staging_blocklist: Vec<String>,
+    // Conventional Commits types and scopes messages may use; empty reads
+    // them from the repo's commitlint config or .czrc, if any
+    pub allowed_commit_types: Vec<String>,
+    pub allowed_commit_scopes: Vec<String>,
+    // Run the timer for this repository too, not only for `repo_path`
+    pub auto_commit: bool,
+    // Minutes between timed cycles; None uses the global `interval_minutes`
+    pub interval_minutes: Option<u64>,
+    // Among runs due at the same time, higher goes first
+    pub schedule_priority: i32,
+}
+
+impl Default for RepoSettings {
+    fn default() -> Self {
+        RepoSettings {
+            path: String::new(),
+            fetch_interval_minutes: 0,
+            pre_commit_command: String::new(),
+            pre_commit_timeout_secs: 300,
+            auto_pull_request: false,
+            pull_request_base: "main".into(),
+            generate_pull_request_description: true,
+            docs_branch: String::new(),
+            docs_paths: vec!["docs".into()],
+            trailers: Vec::new(),
+            sign_off: false,
+            snapshot_mode: false,
+            fallback_chain: None,
+            push_remote: String::new(),
+            push_refspec: String::new(),
+            submodule_policy: SubmodulePolicy::Ignore,
+            issue_patterns: Vec::new(),
+            issue_ticket: String::new(),
+            issue_placement: IssuePlacement::Subject,
+            issue_format: "[{id}]".into(),
+            require_issue_reference: false,
+            ci_gate: false,
+            skip_trivial_changes: true,
+            trivial_change_max_lines: 0,
+            daily_branches: false,
+            author_name: String::new(),
+            author_email: String::new(),
+            staging_blocklist: artifacts::default_blocklist(),
+            allowed_commit_types: Vec::new(),
+            allowed_commit_scopes: Vec::new(),
+            auto_commit: false,
+            interval_minutes: None,
+            schedule_priority: 0,
+        }
+    }
+}
+
+impl RepoSettings {
+    pub fn for_path(path: &str) -> Self {
+        RepoSettings {
+            path: path.to_string(),
+            ..RepoSettings::default()
+        }
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            repo_path: String::new(),
+            auto_commit_enabled: false,
+            interval_minutes: 30,
+            auto_start: false,
+            gemini_api_key: String::new(),
+            model: gemini::DEFAULT_MODEL.into(),
+            temperature: None,
+            max_output_tokens: None,
+            prompt_token_limit: budget::DEFAULT_PROMPT_TOKEN_LIMIT,
+            prompt_token_limits: HashMap::new(),
+            monthly_token_budget: 0,
+            guard_max_files: 1_000,
+            guard_max_total_bytes: 20_000_000,
+            guard_max_file_bytes: 10_000_000,
+            issue_tracker: IssueTracker::None,
+            jira_base_url: String::new(),
+            jira_email: String::new(),
+            api_base_url: gemini::DEFAULT_API_BASE_URL.into(),
+            proxy_url: String::new(),
+            proxy_username: String::new(),
+            use_environment_proxy: true,
+            run_git_hooks: true,
+            repos: Vec::new(),
+            event_stream_enabled: false,
+            editor_api_enabled: false,
+            status_server_port: 0,
+            control_api_port: 0,
+            heartbeat_interval_secs: 0,
+            shutdown_timeout_secs: 30,
+            safe_mode_failure_threshold: 5,
+            safe_mode_interval_multiplier: 4,
+            message_style: MessageStyle::Conventional,
+            custom_message_template: style::DEFAULT_TEMPLATE.into(),
+            generation_timeout_secs: 10,
+            fallback_model: "gemini-2.0-flash-lite".into(),
+            fallback_chain: vec![FallbackStep::FallbackModel, FallbackStep::RuleBased],
+            local_model_url: String::new(),
+            local_model: "llama3.2".into(),
+            fallback_template: "chore: periodic auto-commit ({files} files)".into(),
+            text_only_model: String::new(),
+            infer_scope: false,
+            scope_root: "src".into(),
+            scope_overrides: HashMap::new(),
+            scope_patterns: Vec::new(),
+            workspace_scopes: false,
+            monorepo_commits: false,
+            notify_commits: true,
+            notify_push_failures: true,
+            notify_api_errors: true,
+            notify_approvals: true,
+            notify_safe_mode: true,
+            commit_approval: false,
+            idle_seconds: 0,
+            busy_markers: busy::DEFAULT_BUSY_MARKERS.iter().map(|marker| marker.to_string()).collect(),
+            anonymize_diff: false,
+            schedule: Vec::new(),
+            working_hours_auto_toggle: false,
+            max_files_per_commit: 0,
+            webhook_urls: Vec::new(),
+            forbidden_message_patterns: vec!["as an AI".into(), "I'm sorry".into(), "I cannot".into()],
+            reask_invalid_messages: true,
+            message_candidates: 1,
+            message_cache_ttl_minutes: 60,
+            commit_language: String::new(),
+            stash_conflicted_files: false,
+            branch_cleanup_prefixes: vec!["autocommit/".into(), "autosave/".into()],
+            branch_cleanup_age_days: 30,
+            amend_window_minutes: 0,
+            resume_timer_on_startup: true,
+            catch_up_policy: CatchUpPolicy::Immediate,
+            catch_up_delay_secs: 120,
+            schedule_jitter_secs: 30,
+            max_concurrent_cycles: 2,
+            max_concurrent_api_calls: 2,
+            max_concurrent_pushes: 1,
+            low_battery_action: PowerAction::Ignore,
+            min_battery_percent: 20,
+            metered_network_action: PowerAction::Ignore,
+            offline_action: PowerAction::Ignore,
+            gitignore_suggest_after_cycles: 3,
+            gitignore_ai_suggestions: false,
+            repo_templates: HashMap::new(),
+            default_repo_template: String::new(),
+            config_version: CONFIG_VERSION,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Settings for the repository at `path`, or defaults when it has none.
+    pub fn repo_settings(&self, path: &str) -> RepoSettings {
+        self.repos
+            .iter()
+            .find(|repo| repo.path == path)
+            .cloned()
+            .unwrap_or_else(|| RepoSettings::for_path(path))
+    }
+
+    /// Settings for a new repository at `path` from the template `name`, or
+    /// from the default template; plain defaults when there is none. Fails
+    /// when the template doesn't exist.
+    pub fn settings_from_template(&self, path: &str, name: Option<&str>) -> Result<RepoSettings, String> {
+        let name = match name.map(str::trim).filter(|name| !name.is_empty()) {
+            Some(name) => name,
+            None if !self.default_repo_template.trim().is_empty() => self.default_repo_template.trim(),
+            None => return Ok(RepoSettings::for_path(path)),
+        };
+        let template = self
+            .repo_templates
+            .get(name)
+            .ok_or_else(|| format!("No repository template named {:?}", name))?;
+        Ok(RepoSettings { path: path.to_string(), ..template.clone() })
+    }
+
+    /// The fallback chain for `path`: its own, or the global one.
+    pub fn fallback_chain_for(&self, path: &str) -> Vec<FallbackStep> {
+        self.repo_settings(path)
+            .fallback_chain
+            .unwrap_or_else(|| self.fallback_chain.clone())
+    }
+
+    /// The Gemini API key and where it was found: `GEMINI_API_KEY` in the
+    /// environment, then in a `.env` file, then the keychain, then
+    /// `gemini_api_key`. The keychain is read once, until
+    /// `forget_keychain_api_key`; the others on every call.
+    pub fn resolve_api_key(&self) -> Option<(String, ApiKeySource)> {
+        let present = |key: Option<String>| key.map(|key| key.trim().to_string()).filter(|key| !key.is_empty());
+        if let Some(key) = present(std::env::var(API_KEY_ENV).ok()) {
+            return Some((key, ApiKeySource::Environment));
+        }
+        if let Some(key) = present(dotenv().get(API_KEY_ENV).cloned()) {
+            return Some((key, ApiKeySource::DotEnv));
+        }
+        // No keychain, as on a headless server, is the same as no key in it
+        if let Some(key) = present(keychain_api_key()) {
+            return Some((key, ApiKeySource::Keychain));
+        }
+        present(Some(self.gemini_api_key.clone())).map(|key| (key, ApiKeySource::ConfigFile))
+    }
+
+    /// The API key from `resolve_api_key`, empty when there is none.
+    pub fn api_key(&self) -> String {
+        self.resolve_api_key().map(|(key, _)| key).unwrap_or_default()
+    }
+
+    /// Minutes between timed cycles for `path`: its own interval, or the global one.
+    pub fn interval_for(&self, path: &str) -> u64 {
+        self.repo_settings(path)
+            .interval_minutes
+            .filter(|&minutes| minutes > 0)
+            .unwrap_or(self.interval_minutes)
+    }
+
+    /// Repositories the timer runs for: the main one plus those with `auto_commit`.
+    pub fn scheduled_repos(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        if !self.repo_path.is_empty() {
+            paths.push(self.repo_path.clone());
+        }
+        for repo in self.repos.iter().filter(|repo| repo.auto_commit) {
+            if !paths.contains(&repo.path) {
+                paths.push(repo.path.clone());
+            }
+        }
+        paths
+    }
+
+    /// Every repository the app knows about: the main one plus any with settings.
+    pub fn repo_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        if !self.repo_path.is_empty() {
+            paths.push(self.repo_path.clone());
+        }
+        for repo in &self.repos {
+            if !paths.contains(&repo.path) {
+                paths.push(repo.path.clone());
+            }
+        }
+        paths
+    }
+
+    /// Resolves repository paths to the root of their working tree (so a
+    /// subfolder or a linked worktree's folder works), normalizes them and
+    /// drops `repos` entries that point at a
+    /// repository already listed under another path (symlinks, mapped drives,
+    /// trailing separators), so two timers never race over one repository.
+    /// The first entry wins; settings recorded under another path for the
+    /// main repository move to `repo_path`. Returns the dropped paths.
+    pub fn dedupe_repos(&mut self) -> Vec<String> {
+        self.repo_path = normalize_path(&self.repo_path);
+
+        let mut known: Vec<(PathBuf, String)> = Vec::new();
+        if !self.repo_path.is_empty() {
+            known.push((repo_identity(&self.repo_path), self.repo_path.clone()));
+        }
+
+        let mut kept: Vec<RepoSettings> = Vec::new();
+        let mut removed = Vec::new();
+        for mut repo in self.repos.drain(..) {
+            repo.path = normalize_path(&repo.path);
+            let identity = repo_identity(&repo.path);
+
+            let listed_as = known
+                .iter()
+                .find(|(known_identity, _)| *known_identity == identity)
+                .map(|(_, path)| path.clone());
+
+            match listed_as {
+                Some(path) if !kept.iter().any(|existing| existing.path == path) => {
+                    if repo.path != path {
+                        removed.push(repo.path.clone());
+                        repo.path = path;
+                    }
+                    kept.push(repo);
+                }
+                Some(_) => removed.push(repo.path),
+                None => {
+                    known.push((identity, repo.path.clone()));
+                    kept.push(repo);
+                }
+            }
+        }
+
+        self.repos = kept;
+        removed
+    }
+}
+
+// The working tree root for paths inside a repository, otherwise the path
+// without trailing separators
+fn normalize_path(path: &str) -> String {
+    let path = path.trim();
+    if !path.is_empty() {
+        if let Ok(found) = repo_path::resolve(path) {
+            return found.root;
+        }
+    }
+    let trimmed = path.trim_end_matches(['/', '\\']);
+    // Keep roots such as `/` and `C:\` intact
+    if trimmed.is_empty() || trimmed.ends_with(':') {
+        path.to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+// Resolved git common dir plus working tree: the same for every path that
+// reaches one checkout, but different for separate worktrees of one repo.
+// Paths that aren't repositories fall back to the path itself.
+fn repo_identity(path: &str) -> PathBuf {
+    let repo = match Repository::open(path) {
+        Ok(repo) => repo,
+        Err(_) => return PathBuf::from(path),
+    };
+
+    let resolve = |dir: &std::path::Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let mut identity = resolve(repo.commondir());
+    if let Some(workdir) = repo.workdir() {
+        identity.push(resolve(workdir));
+    }
+    identity
+}
+
+/// Reads the persisted config, falling back to defaults when none was saved yet.
+/// A file written by an older version is upgraded and saved again, after a
+/// copy of it is kept as `config.v<version>.bak.json`.
+pub fn load_config() -> Result<AppConfig, String> {
+    let config_path = get_config_path()?;
+
+    if !config_path.exists() {
+        return Ok(AppConfig::default());
+    }
+
+    let config_str = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read config: {}", e))?;
+
+    let mut value: serde_json::Value = serde_json::from_str(&config_str)
+        .map_err(|e| format!("Failed to parse config: {}", e))?;
+    let version = file_version(&value);
+    if version > CONFIG_VERSION {
+        tracing::warn!(version, supported = CONFIG_VERSION, "config was written by a newer version");
+    }
+    let migrated = version < CONFIG_VERSION;
+    if migrated {
+        migrate(&mut value, version);
+    }
+
+    let mut config: AppConfig = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse config: {}", e))?;
+    for path in config.dedupe_repos() {
+        tracing::warn!(repo = %path, "ignoring duplicate repository entry");
+    }
+
+    if migrated {
+        let backup = config_path.with_file_name(format!("config.v{}.bak.json", version));
+        fs::copy(&config_path, &backup).map_err(|e| format!("Failed to back up config: {}", e))?;
+        save_config(&config)?;
+        tracing::info!(from = version, to = CONFIG_VERSION, backup = %backup.display(), "config upgraded");
+    }
+    Ok(config)
+}
+
+// The keychain's API key, cached because a cycle asks for the key many times
+// and every keychain read is a call into the OS, which may block or prompt
+static KEYCHAIN_API_KEY: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+fn keychain_api_key() -> Option<String> {
+    let mut cached = KEYCHAIN_API_KEY.lock().unwrap_or_else(PoisonError::into_inner);
+    cached
+        .get_or_insert_with(|| secrets::get_secret(secrets::GEMINI_API_KEY).ok().flatten())
+        .clone()
+}
+
+/// Drops the cached keychain API key, so a key just stored or removed is
+/// read again.
+pub fn forget_keychain_api_key() {
+    *KEYCHAIN_API_KEY.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+// `KEY=VALUE` lines of the `.env` in the working directory, then of the one
+// in the data dir; read once, and never written to the process environment
+fn dotenv() -> &'static HashMap<String, String> {
+    static DOTENV: OnceLock<HashMap<String, String>> = OnceLock::new();
+    DOTENV.get_or_init(|| {
+        let mut values = HashMap::new();
+        let files = [Some(PathBuf::from(".env")), get_data_dir().ok().map(|dir| dir.join(".env"))];
+        for contents in files.into_iter().flatten().filter_map(|path| fs::read_to_string(path).ok()) {
+            for line in contents.lines().map(str::trim).filter(|line| !line.starts_with('#')) {
+                let line = line.strip_prefix("export ").unwrap_or(line);
+                if let Some((key, value)) = line.split_once('=') {
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .or_else(|| value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')))
+                        .unwrap_or(value);
+                    values.entry(key.trim().to_string()).or_insert_with(|| value.to_string());
+                }
+            }
+        }
+        values
+    })
+}
+
+// Files from before versioning have no `config_version`
+fn file_version(value: &serde_json::Value) -> u32 {
+    value
+        .get("config_version")
+        .and_then(serde_json::Value::as_u64)
+        .map_or(1, |version| version as u32)
+}
+
+/// Upgrades a config file's JSON from `version` to `CONFIG_VERSION`. A
+/// change older files can't be read with, such as a renamed or retyped
+/// field, bumps `CONFIG_VERSION` and adds its step here, e.g.
+/// `if version < 2 { ... }`. New fields need none: `#[serde(default)]`
+/// fills them in.
+pub fn migrate(value: &mut serde_json::Value, version: u32) {
+    tracing::debug!(from = version, to = CONFIG_VERSION, "upgrading config");
+    if let Some(object) = value.as_object_mut() {
+        object.insert("config_version".into(), CONFIG_VERSION.into());
+    }
+}
+
+pub fn save_config(config: &AppConfig) -> Result<(), String> {
+    let config_path = get_config_path()?;
+    let mut config = config.clone();
+    config.config_version = CONFIG_VERSION;
+    let config_json = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+
+    write_atomic(&config_path, config_json.as_bytes())
+        .map_err(|e| format!("Failed to save config: {}", e))
+}
+
+/// Writes `contents` to a temporary file next to `path`, flushes it to disk
+/// and renames it over `path`, so a crash leaves either the old file or the
+/// new one, never half of it.
+pub fn write_atomic(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp_path, path)
+}
+
+/// Holds `lock` while one of the JSON stores in the data dir is loaded,
+/// changed and saved, so cycles running side by side don't lose each
+/// other's updates. A panic while it was held doesn't leave it locked.
+pub fn lock_store(lock: &Mutex<()>) -> MutexGuard<'_, ()> {
+    lock.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn get_config_path() -> Result<PathBuf, String> {
+    let mut path = get_data_dir()?;
+    path.push("config.json");
+    Ok(path)
+}
+
+pub fn get_data_dir() -> Result<PathBuf, String> {
+    let mut path = config_dir()?;
+    path.push("auto-commit-app");
+    fs::create_dir_all(&path)
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    Ok(path)
+}
+
+#[cfg(not(test))]
+fn config_dir() -> Result<PathBuf, String> {
+    dirs::config_dir().ok_or_else(|| "Failed to get config directory".into())
+}
+
+// Tests keep their stores away from the user's config
+#[cfg(test)]
+fn config_dir() -> Result<PathBuf, String> {
+    Ok(std::env::temp_dir().join(format!("gitgenius-test-{}", std::process::id())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_release_config_loads_unchanged() {
+        // What the first release wrote, before `config_version` existed
+        let written = r#"{
+            "repo_path": "/work/app",
+            "auto_commit_enabled": true,
+            "interval_minutes": 15,
+            "auto_start": true,
+            "gemini_api_key": "AIza-test"
+        }"#;
+        let config_path = get_config_path().unwrap();
+        fs::write(&config_path, written).unwrap();
+
+        let config = load_config().unwrap();
+        assert_eq!(config.repo_path, "/work/app");
+        assert!(config.auto_commit_enabled);
+        assert_eq!(config.interval_minutes, 15);
+        assert!(config.auto_start);
+        assert_eq!(config.gemini_api_key, "AIza-test");
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        // Nothing to upgrade, so no backup and the file is left alone
+        assert!(!config_path.with_file_name("config.v1.bak.json").exists());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), written);
+        fs::remove_file(&config_path).ok();
+    }
+
+    #[test]
+    fn hooks_run_unless_turned_off() {
+        // Git ran the hooks before the setting existed
+        let config: AppConfig = serde_json::from_str("{}").unwrap();
+        assert!(config.run_git_hooks);
+        assert!(AppConfig::default().run_git_hooks);
+    }
+}
@@ -0,0 +1,210 @@
+//! Help with the conflicts of a merge or rebase the app found in progress:
+//! the three versions of every conflicted file, resolving one by taking a
+//! side or writing new content, and a merged version proposed by the model
+//! for the user to review. Finishing the operation (`git rebase --continue`,
+//! `git commit`) is left to the user.
+//!
+//! During a rebase "ours" is the branch being rebased onto and "theirs" the
+//! commit being replayed, as in git itself.
+
+use crate::config::AppConfig;
+use crate::gemini;
+use crate::status::{self, RepoOperation};
+use git2::{IndexConflict, Repository};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+const MERGE_SYSTEM_CONTEXT: &str = r#"You resolve git merge conflicts. Given the common ancestor of a file and the two versions that conflict, answer with the complete merged file.
+
+RULES:
+1. Keep the intent of both sides; where they can't both hold, prefer "ours" and say nothing about it
+2. Answer with the file content only, no explanations and no code fences
+3. Never leave conflict markers (<<<<<<<, =======, >>>>>>>) in the answer"#;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConflictedFile {
+    pub path: String,
+    // None where that version has no such file, or isn't text
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    pub binary: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Conflicts {
+    pub repo: String,
+    pub operation: Option<RepoOperation>,
+    pub files: Vec<ConflictedFile>,
+}
+
+/// How to resolve a conflicted file. A side that doesn't have the file
+/// resolves it as deleted.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "side", content = "content", rename_all = "snake_case")]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Base,
+    Custom(String),
+}
+
+#[derive(Clone, Copy)]
+enum Stage {
+    Base,
+    Ours,
+    Theirs,
+}
+
+/// The conflicted files in the index of `repo_id`, with their three versions.
+pub fn get_conflicts(repo_id: &str) -> Result<Conflicts, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+    for conflict in index_conflicts(&repo)? {
+        let blob = |stage| {
+            entry(&conflict, stage).and_then(|entry| repo.find_blob(entry.id).ok())
+        };
+        let versions = [blob(Stage::Base), blob(Stage::Ours), blob(Stage::Theirs)];
+        let binary = versions.iter().flatten().any(|blob| blob.is_binary());
+        let [base, ours, theirs] = versions.map(|blob| {
+            blob.filter(|blob| !blob.is_binary())
+                .map(|blob| String::from_utf8_lossy(blob.content()).into_owned())
+        });
+        files.push(ConflictedFile { path: conflict_path(&conflict), base, ours, theirs, binary });
+    }
+    Ok(Conflicts {
+        repo: repo_id.to_string(),
+        operation: status::operation_in_progress(&repo),
+        files,
+    })
+}
+
+/// Paths of the conflicted files in `repo`.
+pub fn conflicted_paths(repo: &Repository) -> Vec<String> {
+    index_conflicts(repo)
+        .map(|conflicts| conflicts.iter().map(conflict_path).collect())
+        .unwrap_or_default()
+}
+
+/// Writes the chosen version of `file` to the working tree and stages it,
+/// which marks it resolved. Returns the files still conflicted.
+pub fn apply_resolution(repo_id: &str, file: &str, resolution: Resolution) -> Result<Vec<String>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let workdir = repo.workdir().ok_or("Repository has no working directory")?;
+    let conflict = index_conflicts(&repo)?
+        .into_iter()
+        .find(|conflict| conflict_path(conflict) == file)
+        .ok_or_else(|| format!("{} has no conflict to resolve", file))?;
+
+    let content = match resolution {
+        Resolution::Custom(content) => Some(content.into_bytes()),
+        side => {
+            let stage = match side {
+                Resolution::Ours => Stage::Ours,
+                Resolution::Theirs => Stage::Theirs,
+                _ => Stage::Base,
+            };
+            match entry(&conflict, stage) {
+                Some(entry) => Some(
+                    repo.find_blob(entry.id)
+                        .map_err(|e| format!("Failed to read {}: {}", file, e))?
+                        .content()
+                        .to_vec(),
+                ),
+                None => None,
+            }
+        }
+    };
+
+    let target = workdir.join(file);
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    match content {
+        Some(content) => {
+            fs::write(&target, content).map_err(|e| format!("Failed to write {}: {}", file, e))?;
+            index
+                .add_path(Path::new(file))
+                .map_err(|e| format!("Failed to stage {}: {}", file, e))?;
+        }
+        None => {
+            if target.exists() {
+                fs::remove_file(&target).map_err(|e| format!("Failed to delete {}: {}", file, e))?;
+            }
+            index
+                .conflict_remove(Path::new(file))
+                .map_err(|e| format!("Failed to resolve {}: {}", file, e))?;
+        }
+    }
+    index.write().map_err(|e| e.to_string())?;
+
+    Ok(conflicted_paths(&repo))
+}
+
+/// Asks the model for a merged version of `file`, for the user to review
+/// and apply as a custom resolution. Nothing is written.
+pub async fn suggest_resolution(config: &AppConfig, repo_id: &str, file: &str) -> Result<String, String> {
+    if config.api_key().is_empty() {
+        return Err("A Gemini API key is needed to suggest a merge".into());
+    }
+    let conflicted = get_conflicts(repo_id)?
+        .files
+        .into_iter()
+        .find(|conflicted| conflicted.path == file)
+        .ok_or_else(|| format!("{} has no conflict to resolve", file))?;
+    if conflicted.binary {
+        return Err(format!("{} is binary and can't be merged as text", file));
+    }
+
+    let version = |content: &Option<String>| content.clone().unwrap_or_else(|| "(no such file)".into());
+    let prompt = format!(
+        "File: {}\n\nCommon ancestor:\n{}\n\nOurs:\n{}\n\nTheirs:\n{}",
+        file,
+        version(&conflicted.base),
+        version(&conflicted.ours),
+        version(&conflicted.theirs)
+    );
+    let options = gemini::ModelOptions::from_config(config).for_repo(repo_id);
+    let merged = gemini::generate(&config.api_key(), &options, MERGE_SYSTEM_CONTEXT, &prompt).await?;
+
+    // Models wrap files in fences despite being told not to
+    let merged = merged.trim();
+    let merged = match merged.strip_prefix("```") {
+        Some(fenced) => fenced
+            .split_once('\n')
+            .map_or("", |(_, body)| body)
+            .trim_end()
+            .trim_end_matches("```")
+            .trim_end(),
+        None => merged,
+    };
+    Ok(format!("{}\n", merged))
+}
+
+fn index_conflicts(repo: &Repository) -> Result<Vec<IndexConflict>, String> {
+    let index = repo.index().map_err(|e| e.to_string())?;
+    if !index.has_conflicts() {
+        return Ok(Vec::new());
+    }
+    index
+        .conflicts()
+        .map_err(|e| format!("Failed to read conflicts: {}", e))?
+        .map(|conflict| conflict.map_err(|e| format!("Failed to read conflicts: {}", e)))
+        .collect()
+}
+
+fn entry(conflict: &IndexConflict, stage: Stage) -> Option<&git2::IndexEntry> {
+    match stage {
+        Stage::Base => conflict.ancestor.as_ref(),
+        Stage::Ours => conflict.our.as_ref(),
+        Stage::Theirs => conflict.their.as_ref(),
+    }
+}
+
+fn conflict_path(conflict: &IndexConflict) -> String {
+    [&conflict.our, &conflict.their, &conflict.ancestor]
+        .into_iter()
+        .flatten()
+        .next()
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,183 @@
+//! Local control API for scripts and editor tasks: with `control_api_port`
+//! set, an HTTP server on 127.0.0.1 runs the same pipeline as the app's own
+//! commands. Every request needs `Authorization: Bearer <token>`, the token
+//! stored in the keychain with `generate_control_api_token`.
+//!
+//! - `GET /status` -> the monitoring status, see `monitor::AppStatus`
+//! - `POST /commit {repo?}` -> `{repo, message}` once the cycle is done; 403
+//!   for a repository the app isn't configured for
+//! - `POST /pause` -> `{paused: true}`, stops the auto-commit timer
+
+use crate::config::AppConfig;
+use crate::events::{self, EventSink};
+use crate::monitor::{self, HttpRequest, StatusServer};
+use crate::pipeline;
+use crate::secrets;
+use crate::timer_state;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Deserialize, Default)]
+struct CommitRequest {
+    // Defaults to the configured repository
+    repo: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TimerToggled {
+    running: bool,
+    reason: &'static str,
+}
+
+/// Everything the API needs from its host application.
+#[derive(Clone)]
+pub struct ControlApi {
+    // Shared with the host, which may replace it while the API is running
+    pub config: Arc<RwLock<AppConfig>>,
+    pub events: Arc<dyn EventSink>,
+    pub timer_running: Arc<Mutex<bool>>,
+    // Stop token of the host's running timer
+    pub timer_stop: Arc<Mutex<CancellationToken>>,
+    pub next_run: Arc<Mutex<Option<u64>>>,
+    // Current cancellation token of the host, replaced after each cancel
+    pub cancel: Arc<Mutex<CancellationToken>>,
+}
+
+/// Creates a new random API token, stores it in the keychain and returns it
+/// so it can be handed to the scripts using it. The previous token stops
+/// working.
+pub fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    secrets::set_secret(secrets::CONTROL_API_TOKEN, &token)?;
+    Ok(token)
+}
+
+impl ControlApi {
+    /// Serves the API on `127.0.0.1:port` until the listener fails. Refuses
+    /// to start without a token.
+    pub async fn serve(self, port: u16) -> Result<(), String> {
+        if secrets::get_secret(secrets::CONTROL_API_TOKEN)?.is_none() {
+            return Err("No control API token; generate one first".into());
+        }
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| format!("Failed to bind control API on port {}: {}", port, e))?;
+        loop {
+            let (socket, _) = listener
+                .accept()
+                .await
+                .map_err(|e| format!("Control API error: {}", e))?;
+            tokio::spawn(self.clone().handle_connection(socket));
+        }
+    }
+
+    async fn handle_connection(self, mut socket: TcpStream) {
+        let request = match tokio::time::timeout(monitor::READ_TIMEOUT, monitor::read_request(&mut socket)).await {
+            Ok(Some(request)) => request,
+            _ => return,
+        };
+        let (code, body) = self.handle_request(&request).await;
+        monitor::respond(&mut socket, code, &body.to_string()).await;
+    }
+
+    async fn handle_request(&self, request: &HttpRequest) -> (u16, serde_json::Value) {
+        // Read per request, so a regenerated token takes effect right away
+        let authorized = match (secrets::get_secret(secrets::CONTROL_API_TOKEN), bearer(request)) {
+            (Ok(Some(token)), Some(given)) => constant_time_eq(token.as_bytes(), given.as_bytes()),
+            _ => false,
+        };
+        if !authorized {
+            return (401, json!({ "error": "missing or wrong token" }));
+        }
+
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => {
+                let status = self.status_server().status().await;
+                (200, serde_json::to_value(status).unwrap_or_default())
+            }
+            ("POST", "/commit") => {
+                let params: CommitRequest = if request.body.iter().all(u8::is_ascii_whitespace) {
+                    CommitRequest::default()
+                } else {
+                    match serde_json::from_slice(&request.body) {
+                        Ok(params) => params,
+                        Err(e) => return (400, json!({ "error": e.to_string() })),
+                    }
+                };
+                let config = self.config.read().await.clone();
+                let repo = match params.repo {
+                    Some(requested) => match configured_repo(&config, &requested) {
+                        Ok(repo) => repo,
+                        Err(response) => return response,
+                    },
+                    None => config.repo_path.clone(),
+                };
+                let cancel = self.cancel.lock().await.clone();
+                match pipeline::run_commit(&repo, &config, self.events.as_ref(), &cancel).await {
+                    Ok(message) => (200, json!({ "repo": repo, "message": message })),
+                    Err(e) => (500, json!({ "repo": repo, "error": e })),
+                }
+            }
+            ("POST", "/pause") => {
+                self.pause().await;
+                (200, json!({ "paused": true }))
+            }
+            ("GET", "/commit" | "/pause") | ("POST", "/status") => (405, json!({ "error": "method not allowed" })),
+            _ => (404, json!({ "error": "not found" })),
+        }
+    }
+
+    // Same as stopping the timer from the window
+    async fn pause(&self) {
+        let was_running = std::mem::replace(&mut *self.timer_running.lock().await, false);
+        self.timer_stop.lock().await.cancel();
+        *self.next_run.lock().await = None;
+        if !was_running {
+            return;
+        }
+        for repo in self.config.read().await.scheduled_repos() {
+            if let Err(e) = timer_state::set_running(&repo, false) {
+                tracing::warn!(repo = %repo, error = %e, "could not save timer state");
+            }
+        }
+        events::emit(self.events.as_ref(), "timer-toggled", &TimerToggled { running: false, reason: "api" });
+    }
+
+    fn status_server(&self) -> StatusServer {
+        StatusServer {
+            config: self.config.clone(),
+            timer_running: self.timer_running.clone(),
+            next_run: self.next_run.clone(),
+        }
+    }
+}
+
+// The configured repository at `requested`, so a token can't be used to
+// commit and push in any folder on the machine
+fn configured_repo(config: &AppConfig, requested: &str) -> Result<String, (u16, serde_json::Value)> {
+    let requested = fs::canonicalize(requested)
+        .map_err(|_| (404, json!({ "repo": requested, "error": "no such repository" })))?;
+    config
+        .repo_paths()
+        .into_iter()
+        .find(|path| fs::canonicalize(path).is_ok_and(|path| path == requested))
+        .ok_or_else(|| (403, json!({ "repo": requested, "error": "repository is not configured" })))
+}
+
+fn bearer(request: &HttpRequest) -> Option<&str> {
+    request.header("Authorization")?.strip_prefix("Bearer ").map(str::trim)
+}
+
+// Doesn't stop at the first difference, so timing says nothing about the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
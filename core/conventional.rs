@@ -0,0 +1,99 @@
+/// The parts of a Conventional Commits subject line: `type(scope)!: subject`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConventionalSubject {
+    pub commit_type: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub subject: String,
+}
+
+impl ConventionalSubject {
+    pub fn header(&self) -> String {
+        format!(
+            "{}{}{}: {}",
+            self.commit_type,
+            self.scope
+                .as_ref()
+                .map(|scope| format!("({})", scope))
+                .unwrap_or_default(),
+            if self.breaking { "!" } else { "" },
+            self.subject
+        )
+    }
+}
+
+/// Parses the first line of `message`. Returns `None` when it does not follow
+/// the `type(scope)!: subject` shape.
+pub fn parse(message: &str) -> Option<ConventionalSubject> {
+    let line = message.lines().next()?.trim();
+    let (prefix, subject) = line.split_once(':')?;
+    let subject = subject.trim();
+
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+
+    let (commit_type, scope) = match prefix.split_once('(') {
+        Some((commit_type, rest)) => {
+            let scope = rest.strip_suffix(')')?.trim();
+            (commit_type, (!scope.is_empty()).then(|| scope.to_string()))
+        }
+        None => (prefix, None),
+    };
+
+    let valid_type = !commit_type.is_empty()
+        && commit_type.chars().all(|c| c.is_ascii_alphabetic());
+    if !valid_type || subject.is_empty() {
+        return None;
+    }
+
+    Some(ConventionalSubject {
+        commit_type: commit_type.to_lowercase(),
+        scope,
+        breaking,
+        subject: subject.to_string(),
+    })
+}
+
+/// Everything after the subject line, without the separating blank line.
+pub fn body(message: &str) -> &str {
+    message
+        .split_once('\n')
+        .map(|(_, body)| body.trim_start_matches(['\r', '\n']))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_type_scope_and_breaking_marker() {
+        let parsed = parse("Feat(api)!: drop the v1 endpoints\n\nBody").unwrap();
+        assert_eq!(parsed.commit_type, "feat");
+        assert_eq!(parsed.scope.as_deref(), Some("api"));
+        assert!(parsed.breaking);
+        assert_eq!(parsed.subject, "drop the v1 endpoints");
+        assert_eq!(parsed.header(), "feat(api)!: drop the v1 endpoints");
+    }
+
+    #[test]
+    fn an_empty_scope_is_no_scope() {
+        assert_eq!(parse("fix(): typo").unwrap().scope, None);
+    }
+
+    #[test]
+    fn rejects_subjects_without_the_shape() {
+        assert_eq!(parse("Update README"), None);
+        assert_eq!(parse("fix:"), None);
+        assert_eq!(parse("fix(api: typo"), None);
+        assert_eq!(parse("bug fix: typo"), None);
+    }
+
+    #[test]
+    fn body_skips_the_blank_line() {
+        assert_eq!(body("fix: typo\n\nFirst line\nSecond"), "First line\nSecond");
+        assert_eq!(body("fix: typo"), "");
+    }
+}
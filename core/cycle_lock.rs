@@ -0,0 +1,132 @@
+//! One commit cycle per repository at a time. A cycle holds an in-process
+//! lock, keyed by the repository's git directory so two entries for the
+//! same working tree share it, and an advisory `gitgenius.lock` file in
+//! that directory, which keeps the app and the CLI apart. A second cycle
+//! doesn't wait: it is told the repository is busy.
+
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOCK_FILE: &str = "gitgenius.lock";
+
+// A lock file older than this was left behind, whoever wrote it
+const STALE_AFTER_SECS: u64 = 6 * 60 * 60;
+
+// Git directories with a cycle running in this process
+static HELD: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LockOwner {
+    pub pid: u32,
+    // Unix seconds
+    pub acquired_at: u64,
+}
+
+/// Held for the length of a cycle; dropping it releases both locks.
+pub struct CycleLock {
+    git_dir: PathBuf,
+    // The lock file was created by this lock
+    owns_file: bool,
+}
+
+impl Drop for CycleLock {
+    fn drop(&mut self) {
+        if self.owns_file {
+            fs::remove_file(self.git_dir.join(LOCK_FILE)).ok();
+        }
+        if let Ok(mut held) = held().lock() {
+            held.remove(&self.git_dir);
+        }
+    }
+}
+
+/// Takes the locks for `path`. Returns None when another cycle, in this
+/// process or another, holds them; a lock file left by a process that is
+/// gone is taken over.
+pub fn try_acquire(path: &str) -> Result<Option<CycleLock>, String> {
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let git_dir = repo.path().canonicalize().unwrap_or_else(|_| repo.path().to_path_buf());
+
+    {
+        let mut held = held().lock().map_err(|_| "Cycle lock poisoned".to_string())?;
+        if !held.insert(git_dir.clone()) {
+            return Ok(None);
+        }
+    }
+    // From here on the guard releases the in-process lock on every return
+    let mut lock = CycleLock { git_dir, owns_file: false };
+
+    let lock_path = lock.git_dir.join(LOCK_FILE);
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(mut file) => {
+                lock.owns_file = true;
+                let owner = LockOwner { pid: std::process::id(), acquired_at: unix_now() };
+                let json = serde_json::to_string(&owner).map_err(|e| e.to_string())?;
+                file.write_all(json.as_bytes())
+                    .map_err(|e| format!("Failed to write {}: {}", lock_path.display(), e))?;
+                return Ok(Some(lock));
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                if !is_stale(&lock_path) {
+                    return Ok(None);
+                }
+                tracing::warn!(repo = path, "removing a stale cycle lock");
+                fs::remove_file(&lock_path).ok();
+            }
+            Err(error) => return Err(format!("Failed to create {}: {}", lock_path.display(), error)),
+        }
+    }
+    Ok(None)
+}
+
+/// Who holds the lock file of `path`, if anyone.
+pub fn owner(path: &str) -> Option<LockOwner> {
+    let repo = Repository::open(path).ok()?;
+    read_owner(&repo.path().join(LOCK_FILE))
+}
+
+fn held() -> &'static Mutex<HashSet<PathBuf>> {
+    HELD.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn read_owner(lock_path: &Path) -> Option<LockOwner> {
+    serde_json::from_str(&fs::read_to_string(lock_path).ok()?).ok()
+}
+
+// Too old, unreadable for longer than it takes to write, or written by a
+// process that no longer runs. Only Linux can tell the last cheaply;
+// elsewhere the age decides.
+fn is_stale(lock_path: &Path) -> bool {
+    let owner = match read_owner(lock_path) {
+        Some(owner) => owner,
+        None => {
+            let age = fs::metadata(lock_path)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            return age.is_none_or(|age| age.as_secs() > 10);
+        }
+    };
+    if unix_now().saturating_sub(owner.acquired_at) > STALE_AFTER_SECS {
+        return true;
+    }
+    // This process only holds locks it has in `HELD`
+    if owner.pid == std::process::id() {
+        return true;
+    }
+    cfg!(target_os = "linux") && !Path::new(&format!("/proc/{}", owner.pid)).exists()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
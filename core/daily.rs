@@ -0,0 +1,156 @@
+//! Daily work branches: with `daily_branches`, auto-commits land on
+//! `autocommit/<date>`, branched off the real branch at the day's first
+//! commit, and `squash_day` folds them into one generated commit on the
+//! real branch at the end of the day.
+
+use crate::config::AppConfig;
+use crate::github;
+use crate::history;
+use crate::identity;
+use crate::pipeline;
+use chrono::Local;
+use git2::{BranchType, Repository, Sort};
+use serde::Serialize;
+
+pub const BRANCH_PREFIX: &str = "autocommit/";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DayBranchStarted {
+    pub repo: String,
+    pub branch: String,
+    pub base: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SquashedDay {
+    pub branch: String,
+    pub base: String,
+    // Commits of the day branch folded into the new one
+    pub commits: usize,
+    pub sha: String,
+    pub message: String,
+}
+
+/// Today's day branch, e.g. `autocommit/2024-06-12`.
+pub fn today_branch() -> String {
+    format!("{}{}", BRANCH_PREFIX, Local::now().format("%Y-%m-%d"))
+}
+
+// The real branch a day branch was started from, kept in the repo's config
+fn base_key(branch: &str) -> String {
+    format!("branch.{}.gitgeniusbase", branch)
+}
+
+fn base_of(repo: &Repository, branch: &str) -> Option<String> {
+    repo.config().ok()?.get_string(&base_key(branch)).ok()
+}
+
+/// Puts `repo` on today's day branch before a cycle stages anything, and
+/// returns it when it was just created. The branch starts at HEAD, so the
+/// working tree and index carry over untouched; coming from an earlier day
+/// branch that wasn't squashed, it continues from that one and keeps its
+/// base. Before the first commit there is nothing to branch from, so that
+/// one goes on the current branch.
+pub fn switch_to_today(repo: &Repository, repo_id: &str) -> Result<Option<DayBranchStarted>, String> {
+    let head_commit = match repo.head().ok().and_then(|head| head.peel_to_commit().ok()) {
+        Some(commit) => commit,
+        None => return Ok(None),
+    };
+    let current = github::current_branch(repo)?;
+    let today = today_branch();
+    if current == today {
+        return Ok(None);
+    }
+
+    let base = match current.strip_prefix(BRANCH_PREFIX) {
+        Some(_) => base_of(repo, &current).ok_or_else(|| format!("{} has no recorded base branch", current))?,
+        None => current.clone(),
+    };
+
+    // Leaving today's branch means the user chose to work elsewhere; going
+    // back to it would need a checkout, which a cycle doesn't do
+    if let Ok(existing) = repo.find_branch(&today, BranchType::Local) {
+        if existing.get().target() != Some(head_commit.id()) {
+            return Err(format!("{} already exists; switch back to it or squash it first", today));
+        }
+    } else {
+        repo.branch(&today, &head_commit, false)
+            .map_err(|e| format!("Failed to create {}: {}", today, e))?;
+        repo.config()
+            .and_then(|mut config| config.set_str(&base_key(&today), &base))
+            .map_err(|e| format!("Failed to record the base of {}: {}", today, e))?;
+    }
+    repo.set_head(&format!("refs/heads/{}", today))
+        .map_err(|e| format!("Failed to switch to {}: {}", today, e))?;
+
+    Ok(Some(DayBranchStarted { repo: repo_id.to_string(), branch: today, base }))
+}
+
+/// Squashes the day branch of `date` (`YYYY-MM-DD`; default: the current
+/// day branch, or today's) into one commit on its base branch, with a
+/// message generated from the combined diff, then deletes it. The base
+/// must not have moved since the day began. Only refs change: when HEAD is
+/// on the day branch it moves to the base, whose new tip has the same tree,
+/// so uncommitted work stays where it is.
+pub async fn squash_day(path: &str, config: &AppConfig, date: Option<&str>) -> Result<SquashedDay, String> {
+    let (branch, base, base_tip, day_tip, commits) = {
+        let repo = Repository::open(path).map_err(|e| e.to_string())?;
+        let branch = match date {
+            Some(date) => format!("{}{}", BRANCH_PREFIX, date.trim()),
+            None => github::current_branch(&repo)
+                .ok()
+                .filter(|current| current.starts_with(BRANCH_PREFIX))
+                .unwrap_or_else(today_branch),
+        };
+        let base = base_of(&repo, &branch).ok_or_else(|| format!("{} is not a day branch", branch))?;
+        let tip = |name: &str| {
+            repo.find_branch(name, BranchType::Local)
+                .ok()
+                .and_then(|found| found.get().target())
+                .ok_or_else(|| format!("Branch {} not found", name))
+        };
+        let (day_tip, base_tip) = (tip(&branch)?, tip(&base)?);
+
+        if day_tip == base_tip {
+            return Err(format!("{} has no commits to squash", branch));
+        }
+        if !repo.graph_descendant_of(day_tip, base_tip).unwrap_or(false) {
+            return Err(format!("{} moved since {} started; merge it into the day branch first", base, branch));
+        }
+        let mut walk = repo.revwalk().map_err(|e| e.to_string())?;
+        walk.set_sorting(Sort::TOPOLOGICAL).map_err(|e| e.to_string())?;
+        walk.push(day_tip).map_err(|e| e.to_string())?;
+        walk.hide(base_tip).map_err(|e| e.to_string())?;
+        (branch, base, base_tip, day_tip, walk.count())
+    };
+
+    let diff_text = pipeline::range_diff(path, &base_tip.to_string(), &day_tip.to_string())?;
+    let generated = pipeline::generate_message(path, config, &diff_text).await?;
+
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let on_day_branch = github::current_branch(&repo).is_ok_and(|current| current == branch);
+    let signature = identity::resolve(&repo, &config.repo_settings(path))?;
+    let tree = repo
+        .find_commit(day_tip)
+        .and_then(|commit| commit.tree())
+        .map_err(|e| e.to_string())?;
+    let parent = repo.find_commit(base_tip).map_err(|e| e.to_string())?;
+    // Fails if the base moved while the message was being generated
+    let sha = repo
+        .commit(Some(&format!("refs/heads/{}", base)), &signature, &signature, &generated.message, &tree, &[&parent])
+        .map_err(|e| format!("Failed to commit to {}: {}", base, e))?;
+
+    if on_day_branch {
+        repo.set_head(&format!("refs/heads/{}", base))
+            .map_err(|e| format!("Failed to switch to {}: {}", base, e))?;
+        history::record_head_commit(path, Some(generated.tier)).ok();
+    }
+    repo.find_branch(&branch, BranchType::Local)
+        .and_then(|mut day| day.delete())
+        .map_err(|e| format!("Failed to delete {}: {}", branch, e))?;
+    if let Ok(mut git_config) = repo.config() {
+        git_config.remove(&base_key(&branch)).ok();
+    }
+
+    Ok(SquashedDay { branch, base, commits, sha: sha.to_string(), message: generated.message })
+}
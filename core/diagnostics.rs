@@ -0,0 +1,279 @@
+//! Environment checks for first-run setup: everything the app needs from the
+//! machine, the repository, the remote and the API, each with a hint on how
+//! to fix it.
+
+use crate::config::{self, AppConfig};
+use crate::gemini::{self, ModelOptions};
+use crate::proxy::ProxySettings;
+use crate::remote::{self, RemoteFailure};
+use crate::repo_path;
+use crate::secrets;
+use serde::Serialize;
+use std::process::Command;
+
+// `GIT_CONFIG_COUNT`, which passes the proxy to git, arrived in 2.31
+const MIN_GIT_VERSION: (u32, u32) = (2, 31);
+
+// Written and removed again to prove the keychain works
+const KEYCHAIN_PROBE: &str = "diagnostics-probe";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+    // Not run because an earlier check failed or nothing was configured
+    Skipped,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DiagnosticCheck {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    // What to do about a warning or failure
+    pub fix: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn new(id: &'static str, label: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        DiagnosticCheck { id, label, status, detail: detail.into(), fix: None }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DiagnosticsReport {
+    pub repo: Option<String>,
+    pub checks: Vec<DiagnosticCheck>,
+    // No check failed; warnings don't count
+    pub ok: bool,
+}
+
+/// Runs every check against `path`, or the configured repository. The
+/// webview is the only place that knows whether notifications are allowed,
+/// so the UI passes that in as `notifications_granted`.
+pub async fn run_diagnostics(
+    config: &AppConfig,
+    path: Option<&str>,
+    notifications_granted: Option<bool>,
+) -> DiagnosticsReport {
+    let repo = path
+        .map(str::to_string)
+        .or_else(|| (!config.repo_path.is_empty()).then(|| config.repo_path.clone()));
+
+    let blocking_config = config.clone();
+    let blocking_repo = repo.clone();
+    let mut checks = tokio::task::spawn_blocking(move || {
+        let mut checks = vec![git_check(), libgit2_check()];
+        checks.extend(repository_checks(&blocking_config, blocking_repo.as_deref()));
+        checks.push(keychain_check());
+        checks
+    })
+    .await
+    .unwrap_or_default();
+
+    checks.push(api_key_check(config).await);
+    checks.push(notifications_check(config, notifications_granted));
+
+    let ok = !checks.iter().any(|check| check.status == CheckStatus::Failed);
+    DiagnosticsReport { repo, checks, ok }
+}
+
+fn git_check() -> DiagnosticCheck {
+    const ID: &str = "git";
+    const LABEL: &str = "Git command line";
+    let output = match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            return DiagnosticCheck::new(ID, LABEL, CheckStatus::Failed, "git was not found on the PATH")
+                .fix("Install Git from https://git-scm.com and restart the app");
+        }
+    };
+
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let mut numbers = version
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse::<u32>().unwrap_or(0));
+    let found = (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0));
+    if found < MIN_GIT_VERSION {
+        return DiagnosticCheck::new(ID, LABEL, CheckStatus::Warning, version).fix(format!(
+            "Update Git to {}.{} or later; older versions ignore the proxy settings",
+            MIN_GIT_VERSION.0, MIN_GIT_VERSION.1
+        ));
+    }
+    DiagnosticCheck::new(ID, LABEL, CheckStatus::Ok, version)
+}
+
+fn libgit2_check() -> DiagnosticCheck {
+    const ID: &str = "libgit2";
+    const LABEL: &str = "Built-in Git library";
+    let version = git2::Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    let detail = format!(
+        "libgit2 {}.{}.{} (HTTPS: {}, SSH: {}, threads: {})",
+        major,
+        minor,
+        patch,
+        yes_no(version.https()),
+        yes_no(version.ssh()),
+        yes_no(version.threads())
+    );
+    if !version.threads() {
+        return DiagnosticCheck::new(ID, LABEL, CheckStatus::Failed, detail)
+            .fix("Reinstall the app; this build can't use Git from several threads");
+    }
+    DiagnosticCheck::new(ID, LABEL, CheckStatus::Ok, detail)
+}
+
+fn repository_checks(config: &AppConfig, path: Option<&str>) -> Vec<DiagnosticCheck> {
+    const LABEL: &str = "Repository";
+    const REMOTE_LABEL: &str = "Remote";
+    let path = match path {
+        Some(path) => path,
+        None => {
+            return vec![
+                DiagnosticCheck::new("repository", LABEL, CheckStatus::Failed, "No repository is configured")
+                    .fix("Choose the repository folder with Browse"),
+                DiagnosticCheck::new("remote", REMOTE_LABEL, CheckStatus::Skipped, "No repository to check"),
+            ];
+        }
+    };
+
+    let validation = repo_path::validate_repo_path(path);
+    let detail = if validation.messages.is_empty() {
+        format!("{} is a usable repository", validation.repo_root.as_deref().unwrap_or(path))
+    } else {
+        validation.messages.join("; ")
+    };
+    let repository = match (validation.usable, validation.problems.is_empty()) {
+        (true, true) => DiagnosticCheck::new("repository", LABEL, CheckStatus::Ok, detail),
+        (true, false) => DiagnosticCheck::new("repository", LABEL, CheckStatus::Warning, detail)
+            .fix("Pick the repository's root folder, and add a remote if commits should be pushed"),
+        (false, _) => DiagnosticCheck::new("repository", LABEL, CheckStatus::Failed, detail)
+            .fix("Choose a folder inside a Git working tree that you can write to"),
+    };
+    if !validation.usable || validation.remotes.is_empty() {
+        return vec![
+            repository,
+            DiagnosticCheck::new("remote", REMOTE_LABEL, CheckStatus::Skipped, "No remote to check"),
+        ];
+    }
+
+    let root = validation.repo_root.as_deref().unwrap_or(path);
+    let settings = config.repo_settings(root);
+    let remote = match remote::probe(root, &settings.push_remote, &ProxySettings::from_config(config)) {
+        Ok(probe) => match probe.failure {
+            None => DiagnosticCheck::new(
+                "remote",
+                REMOTE_LABEL,
+                CheckStatus::Ok,
+                format!("{} is reachable ({} ms)", probe.remote, probe.elapsed_ms),
+            ),
+            Some(failure) => DiagnosticCheck::new(
+                "remote",
+                REMOTE_LABEL,
+                CheckStatus::Failed,
+                format!("{}: {}", probe.remote, failure.describe()),
+            )
+            .fix(remote_fix(failure)),
+        },
+        Err(error) => DiagnosticCheck::new("remote", REMOTE_LABEL, CheckStatus::Failed, error)
+            .fix("Check that the repository can be opened"),
+    };
+    vec![repository, remote]
+}
+
+fn remote_fix(failure: RemoteFailure) -> &'static str {
+    match failure {
+        RemoteFailure::Dns | RemoteFailure::Connection => {
+            "Check the network connection, or set `proxy_url` if you are behind a proxy"
+        }
+        RemoteFailure::Tls => "Check the system clock and any TLS-intercepting proxy's certificate",
+        RemoteFailure::Auth => "Set up credentials for the remote: an SSH key or a Git credential helper",
+        RemoteFailure::NotFound => "Check the remote's URL with `git remote -v`",
+        RemoteFailure::HookRejected | RemoteFailure::NonFastForward | RemoteFailure::Other => {
+            "Try `git push` in a terminal to see the full error"
+        }
+    }
+}
+
+fn keychain_check() -> DiagnosticCheck {
+    const ID: &str = "keychain";
+    const LABEL: &str = "System keychain";
+    let round_trip = secrets::set_secret(KEYCHAIN_PROBE, "ok")
+        .and_then(|_| secrets::get_secret(KEYCHAIN_PROBE))
+        .and_then(|read| {
+            secrets::delete_secret(KEYCHAIN_PROBE)?;
+            Ok(read)
+        });
+    match round_trip {
+        Ok(Some(value)) if value == "ok" => {
+            DiagnosticCheck::new(ID, LABEL, CheckStatus::Ok, "Secrets can be stored and read back")
+        }
+        Ok(_) => DiagnosticCheck::new(ID, LABEL, CheckStatus::Failed, "A stored secret could not be read back")
+            .fix("Unlock the keychain, or on Linux start a Secret Service provider such as GNOME Keyring"),
+        Err(error) => DiagnosticCheck::new(ID, LABEL, CheckStatus::Failed, error)
+            .fix("Unlock the keychain, or on Linux start a Secret Service provider such as GNOME Keyring"),
+    }
+}
+
+async fn api_key_check(config: &AppConfig) -> DiagnosticCheck {
+    const ID: &str = "api_key";
+    const LABEL: &str = "Gemini API key";
+    let (api_key, source) = match config.resolve_api_key() {
+        Some(resolved) => resolved,
+        None => {
+            return DiagnosticCheck::new(ID, LABEL, CheckStatus::Failed, "No API key is configured").fix(format!(
+                "Create a key at https://aistudio.google.com/app/apikey and paste it in the settings, or set {}",
+                config::API_KEY_ENV
+            ))
+        }
+    };
+    match gemini::test_api_key(&api_key, &ModelOptions::from_config(config)).await {
+        Ok(_) => DiagnosticCheck::new(
+            ID,
+            LABEL,
+            CheckStatus::Ok,
+            format!("Accepted by {}, read from {}", config.model, source.describe()),
+        ),
+        Err(error) if gemini::is_unavailable(&error) => DiagnosticCheck::new(ID, LABEL, CheckStatus::Warning, error)
+            .fix("Gemini could not be reached or is over quota; try again later"),
+        Err(error) => DiagnosticCheck::new(ID, LABEL, CheckStatus::Failed, error)
+            .fix("Check the key and the model name in the settings"),
+    }
+}
+
+fn notifications_check(config: &AppConfig, granted: Option<bool>) -> DiagnosticCheck {
+    const ID: &str = "notifications";
+    const LABEL: &str = "Desktop notifications";
+    let wanted = config.notify_commits
+        || config.notify_push_failures
+        || config.notify_api_errors
+        || config.notify_approvals
+        || config.notify_safe_mode;
+    match granted {
+        _ if !wanted => DiagnosticCheck::new(ID, LABEL, CheckStatus::Skipped, "All notification categories are off"),
+        None => DiagnosticCheck::new(ID, LABEL, CheckStatus::Skipped, "Permission was not checked"),
+        Some(true) => DiagnosticCheck::new(ID, LABEL, CheckStatus::Ok, "Notifications are allowed"),
+        Some(false) => DiagnosticCheck::new(ID, LABEL, CheckStatus::Warning, "Notifications are not allowed")
+            .fix("Allow notifications for the app in the system settings"),
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
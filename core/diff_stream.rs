@@ -0,0 +1,248 @@
+//! The staged diff sent to the frontend in chunks, so the approval view can
+//! render a diff viewer for multi-megabyte changes as they arrive instead of
+//! parsing one huge string.
+
+use crate::events::{self, EventSink};
+use git2::{Delta, DiffFormat, Repository};
+use serde::Serialize;
+
+// Lines are sent once a chunk holds this much text
+const CHUNK_BYTES: usize = 64 * 1024;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DiffLine {
+    // `+`, `-`, ` ` for context, or `@` for a hunk header
+    pub origin: char,
+    pub content: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+}
+
+/// Payload of `staged-diff-chunk` events. `stream` is the ID the frontend
+/// passed to `get_staged_diff`, so chunks of an older request can be told
+/// apart.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffChunk {
+    // A file starts; its lines follow in `lines` chunks with the same index
+    File {
+        stream: String,
+        index: usize,
+        path: String,
+        // Set for renames and copies
+        old_path: Option<String>,
+        status: &'static str,
+        binary: bool,
+        // Highlighting hint from the file name, e.g. `rust`
+        language: Option<&'static str>,
+    },
+    Lines {
+        stream: String,
+        file: usize,
+        lines: Vec<DiffLine>,
+    },
+    Done {
+        stream: String,
+        summary: DiffSummary,
+    },
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DiffSummary {
+    pub files: usize,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+// Lines waiting to be sent, and what has been sent so far
+struct Streamer<'a> {
+    sink: &'a dyn EventSink,
+    stream: &'a str,
+    pending: Vec<DiffLine>,
+    pending_bytes: usize,
+    summary: DiffSummary,
+}
+
+impl Streamer<'_> {
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let chunk = DiffChunk::Lines {
+            stream: self.stream.to_string(),
+            file: self.summary.files - 1,
+            lines: std::mem::take(&mut self.pending),
+        };
+        events::emit(self.sink, "staged-diff-chunk", &chunk);
+        self.pending_bytes = 0;
+    }
+
+    fn push(&mut self, line: DiffLine) {
+        self.pending_bytes += line.content.len();
+        self.summary.lines += 1;
+        self.summary.bytes += line.content.len();
+        self.pending.push(line);
+        if self.pending_bytes >= CHUNK_BYTES {
+            self.flush();
+        }
+    }
+}
+
+/// Sends the staged changes of `repo_id` to `sink` as `staged-diff-chunk`
+/// events: each file's header, then its lines in chunks of about 64 KiB,
+/// then a `done` chunk with the totals, which are also returned.
+pub fn stream_staged_diff(repo_id: &str, stream: &str, sink: &dyn EventSink) -> Result<DiffSummary, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    // Before the first commit everything staged is new
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let mut diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, None)
+        .map_err(|e| format!("Failed to diff the index: {}", e))?;
+    diff.find_similar(None)
+        .map_err(|e| format!("Failed to detect renames: {}", e))?;
+
+    let mut streamer = Streamer {
+        sink,
+        stream,
+        pending: Vec::new(),
+        pending_bytes: 0,
+        summary: DiffSummary::default(),
+    };
+    let mut current: Option<(String, String)> = None;
+
+    diff.print(DiffFormat::Patch, |delta, hunk, line| {
+        let old_path = delta.old_file().path().map(|path| path.to_string_lossy().replace('\\', "/"));
+        let path = delta
+            .new_file()
+            .path()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .or_else(|| old_path.clone())
+            .unwrap_or_default();
+
+        // A file starts whenever the delta changes
+        let key = (old_path.clone().unwrap_or_default(), path.clone());
+        if current.as_ref() != Some(&key) {
+            streamer.flush();
+            let chunk = DiffChunk::File {
+                stream: stream.to_string(),
+                index: streamer.summary.files,
+                language: language(&path),
+                old_path: old_path.filter(|old| matches!(delta.status(), Delta::Renamed | Delta::Copied) && *old != path),
+                path,
+                status: status(delta.status()),
+                binary: delta.flags().is_binary(),
+            };
+            events::emit(sink, "staged-diff-chunk", &chunk);
+            streamer.summary.files += 1;
+            current = Some(key);
+        }
+
+        match line.origin() {
+            'H' => {
+                if let Some(hunk) = hunk {
+                    streamer.push(DiffLine {
+                        origin: '@',
+                        content: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+                        old_lineno: None,
+                        new_lineno: None,
+                    });
+                }
+            }
+            origin @ ('+' | '-' | ' ') => streamer.push(DiffLine {
+                origin,
+                content: String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string(),
+                old_lineno: line.old_lineno(),
+                new_lineno: line.new_lineno(),
+            }),
+            _ => {}
+        }
+        true
+    })
+    .map_err(|e| format!("Failed to read the diff: {}", e))?;
+
+    streamer.flush();
+    let summary = streamer.summary;
+    events::emit(sink, "staged-diff-chunk", &DiffChunk::Done { stream: stream.to_string(), summary: summary.clone() });
+    Ok(summary)
+}
+
+fn status(delta: Delta) -> &'static str {
+    match delta {
+        Delta::Added => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "typechange",
+        _ => "modified",
+    }
+}
+
+/// Language name for highlighting, from the file name; None when unknown.
+pub fn language(path: &str) -> Option<&'static str> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match file_name {
+        "Dockerfile" => return Some("dockerfile"),
+        "Makefile" | "GNUmakefile" => return Some("makefile"),
+        "CMakeLists.txt" => return Some("cmake"),
+        _ => {}
+    }
+    let extension = file_name.rsplit_once('.')?.1.to_lowercase();
+    let language = match extension.as_str() {
+        "rs" => "rust",
+        "ts" | "tsx" => "typescript",
+        "js" | "jsx" | "mjs" | "cjs" => "javascript",
+        "py" => "python",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "sh" | "bash" | "zsh" => "shell",
+        "ps1" => "powershell",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" | "sass" => "scss",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "sql" => "sql",
+        "lua" => "lua",
+        "dart" => "dart",
+        _ => return None,
+    };
+    Some(language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_from_extension_in_any_case() {
+        assert_eq!(language("core/pipeline.rs"), Some("rust"));
+        assert_eq!(language("web/App.TSX"), Some("typescript"));
+        assert_eq!(language("scripts/build.sh"), Some("shell"));
+    }
+
+    #[test]
+    fn language_from_well_known_file_names() {
+        assert_eq!(language("docker/Dockerfile"), Some("dockerfile"));
+        assert_eq!(language("Makefile"), Some("makefile"));
+        assert_eq!(language("CMakeLists.txt"), Some("cmake"));
+    }
+
+    #[test]
+    fn no_language_for_unknown_files() {
+        assert_eq!(language("LICENSE"), None);
+        assert_eq!(language("notes.txt"), None);
+        assert_eq!(language("archive.tar.gz"), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! Two-phase pushes: the target branch is fetched first and compared with
+//! what is about to be pushed. A fast-forward is pushed as usual. When the
+//! remote went elsewhere, the push is only forced when the remote tip is
+//! part of history the app itself rewrote (a squash of pushed commits), and
+//! then with a lease on exactly that tip, so a colleague's commit pushed in
+//! between is never overwritten. Any other divergence is reported instead.
+
+use crate::proxy::{self, ProxySettings};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a push goes.
+#[derive(Clone, Debug)]
+pub struct PushTarget {
+    pub remote: String,
+    // What is pushed, e.g. `HEAD` or `refs/heads/gh-pages`
+    pub local: String,
+    // The ref it updates on the remote, e.g. `refs/heads/main`
+    pub remote_ref: String,
+}
+
+pub enum PushPlan {
+    // The remote already has it
+    UpToDate,
+    FastForward,
+    // Replaces history the app rewrote; forced with a lease on this tip
+    ForceWithLease(String),
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Diverged {
+    pub repo: String,
+    pub remote: String,
+    pub remote_ref: String,
+    pub local_tip: String,
+    pub remote_tip: String,
+    // Commits only on the local side, and only on the remote
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Rewrite {
+    // Local branch, e.g. `refs/heads/main`
+    branch: String,
+    // The tip the rewrite replaced
+    replaced: String,
+    // Unix seconds
+    at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RewriteStore {
+    repos: HashMap<String, Vec<Rewrite>>,
+}
+
+impl RewriteStore {
+    fn load() -> Result<Self, String> {
+        let path = store_path()?;
+        if !path.exists() {
+            return Ok(RewriteStore::default());
+        }
+
+        let store_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read rewrite records: {}", e))?;
+        serde_json::from_str(&store_str)
+            .map_err(|e| format!("Failed to parse rewrite records: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let store_json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize rewrite records: {}", e))?;
+        crate::config::write_atomic(&store_path()?, store_json.as_bytes())
+            .map_err(|e| format!("Failed to save rewrite records: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn store_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("rewrites.json");
+    Ok(path)
+}
+
+/// Remembers that the app replaced `replaced`, the old tip of `branch`
+/// (e.g. `refs/heads/main`), with rewritten history, so the next push may
+/// replace it on the remote too.
+pub fn record_rewrite(repo_id: &str, branch: &str, replaced: Oid) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = RewriteStore::load()?;
+    store.repos.entry(repo_id.to_string()).or_default().push(Rewrite {
+        branch: branch.to_string(),
+        replaced: replaced.to_string(),
+        at: unix_now(),
+    });
+    store.save()
+}
+
+/// Forgets the rewrites of `branch` once they are pushed.
+pub fn forget_rewrites(repo_id: &str, branch: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = RewriteStore::load()?;
+    if let Some(rewrites) = store.repos.get_mut(repo_id) {
+        rewrites.retain(|rewrite| rewrite.branch != branch);
+        if rewrites.is_empty() {
+            store.repos.remove(repo_id);
+        }
+        store.save()?;
+    }
+    Ok(())
+}
+
+/// The local branch a push target pushes from, e.g. `refs/heads/main`.
+pub fn local_branch(repo: &Repository, target: &PushTarget) -> Option<String> {
+    let reference = repo.find_reference(&target.local).ok()?.resolve().ok()?;
+    reference.name().map(str::to_string)
+}
+
+/// Fetches `target.remote_ref` and decides how to push. Err(Diverged) when
+/// the remote has commits that aren't local and the app didn't rewrite
+/// them away. Fails when the fetch does.
+pub fn plan(path: &str, target: &PushTarget, proxy: &ProxySettings) -> Result<Result<PushPlan, Diverged>, String> {
+    let mut command = Command::new("git");
+    command
+        .args(["fetch", "--no-tags"])
+        .arg(&target.remote)
+        .arg(format!("+{}:{}", target.remote_ref, lease_ref(target)))
+        .current_dir(path);
+    proxy::configure_git(&mut command, proxy);
+    let output = command.output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        // A branch pushed for the first time
+        if error.contains("couldn't find remote ref") {
+            return Ok(Ok(PushPlan::FastForward));
+        }
+        return Err(format!("git fetch before pushing failed: {}", error));
+    }
+
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let tip = |spec: &str| {
+        repo.revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map(|commit| commit.id())
+            .map_err(|e| format!("Failed to resolve {}: {}", spec, e))
+    };
+    let local = tip(&target.local)?;
+    let remote = tip(&lease_ref(target))?;
+    repo.find_reference(&lease_ref(target)).and_then(|mut lease| lease.delete()).ok();
+
+    if local == remote {
+        return Ok(Ok(PushPlan::UpToDate));
+    }
+    if repo.graph_descendant_of(local, remote).unwrap_or(false) {
+        return Ok(Ok(PushPlan::FastForward));
+    }
+
+    let rewritten = local_branch(&repo, target).is_some_and(|branch| {
+        RewriteStore::load().ok().and_then(|store| store.repos.get(path).cloned()).is_some_and(|rewrites| {
+            rewrites.iter().filter(|rewrite| rewrite.branch == branch).any(|rewrite| {
+                Oid::from_str(&rewrite.replaced).is_ok_and(|replaced| {
+                    replaced == remote || repo.graph_descendant_of(replaced, remote).unwrap_or(false)
+                })
+            })
+        })
+    });
+    if rewritten {
+        return Ok(Ok(PushPlan::ForceWithLease(remote.to_string())));
+    }
+
+    let (ahead, behind) = repo.graph_ahead_behind(local, remote).unwrap_or_default();
+    Ok(Err(Diverged {
+        repo: path.to_string(),
+        remote: target.remote.clone(),
+        remote_ref: target.remote_ref.clone(),
+        local_tip: local.to_string(),
+        remote_tip: remote.to_string(),
+        ahead,
+        behind,
+    }))
+}
+
+// Where the fetched tip is kept while it is compared
+fn lease_ref(target: &PushTarget) -> String {
+    format!(
+        "refs/gitgenius/lease/{}/{}",
+        target.remote,
+        target.remote_ref.trim_start_matches("refs/")
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
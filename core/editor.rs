@@ -0,0 +1,342 @@
+//! Editor integration protocol: JSON-RPC 2.0 messages, one per line, over a
+//! local socket (`editor.sock` in the app data dir) or the `PIPE_NAME` named
+//! pipe on Windows. Clients start with `handshake` and must speak the same
+//! major `PROTOCOL_VERSION`; until it succeeds, every other method fails.
+//!
+//! Methods (`repo` defaults to the configured repository and must be one of
+//! the repositories the app knows about):
+//! - `handshake {protocol_version}` -> `{protocol_version, app_version, methods}`
+//! - `getPendingDiff {repo?}` -> `{repo, diff}`
+//! - `generateMessage {repo?}` -> `{repo, message, tier, candidates}`, the
+//!   candidates ranked best first when `message_candidates` is above 1
+//! - `commit {repo?}` -> `{repo, message}`
+//! - `subscribe` -> `{subscribed: true}`, followed by `event` notifications
+//!   whose params are the event stream lines.
+
+use crate::config::AppConfig;
+use crate::events::EventSink;
+use crate::pipeline;
+use crate::stream::EventStream;
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
+
+pub const PROTOCOL_VERSION: u64 = 1;
+
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\gitgenius-editor";
+
+const METHODS: [&str; 5] = ["handshake", "getPendingDiff", "generateMessage", "commit", "subscribe"];
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const APP_ERROR: i64 = -32000;
+const VERSION_MISMATCH: i64 = -32001;
+const REPO_NOT_CONFIGURED: i64 = -32002;
+const HANDSHAKE_REQUIRED: i64 = -32003;
+
+/// Everything the protocol needs from its host application.
+#[derive(Clone)]
+pub struct EditorApi {
+    // Shared with the host, which may replace it while the API is running
+    pub config: Arc<RwLock<AppConfig>>,
+    pub events: Arc<dyn EventSink>,
+    pub stream: EventStream,
+    // Current cancellation token of the host, replaced after each cancel
+    pub cancel: Arc<Mutex<CancellationToken>>,
+}
+
+impl EditorApi {
+    #[cfg(unix)]
+    pub async fn serve(self) -> Result<(), String> {
+        use tokio::net::UnixListener;
+
+        let mut socket_path = crate::config::get_data_dir()?;
+        socket_path.push("editor.sock");
+
+        // A stale socket from a previous run would make bind fail
+        std::fs::remove_file(&socket_path).ok();
+
+        let listener = UnixListener::bind(&socket_path)
+            .map_err(|e| format!("Failed to bind editor socket: {}", e))?;
+
+        loop {
+            let (socket, _) = listener
+                .accept()
+                .await
+                .map_err(|e| format!("Editor socket error: {}", e))?;
+            tokio::spawn(self.clone().handle_connection(socket));
+        }
+    }
+
+    #[cfg(windows)]
+    pub async fn serve(self) -> Result<(), String> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(PIPE_NAME)
+            .map_err(|e| format!("Failed to create editor pipe: {}", e))?;
+
+        loop {
+            server
+                .connect()
+                .await
+                .map_err(|e| format!("Editor pipe error: {}", e))?;
+
+            let client = server;
+            server = ServerOptions::new()
+                .create(PIPE_NAME)
+                .map_err(|e| format!("Failed to create editor pipe: {}", e))?;
+            tokio::spawn(self.clone().handle_connection(client));
+        }
+    }
+
+    async fn handle_connection<S: AsyncRead + AsyncWrite + Send + 'static>(self, socket: S) {
+        let (reader, mut writer) = tokio::io::split(socket);
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+
+        // Responses and event notifications share one writer
+        tokio::spawn(async move {
+            while let Some(line) = outgoing_rx.recv().await {
+                if writer.write_all(format!("{}\n", line).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut handshaken = false;
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(request) => self.handle_request(request, &mut handshaken, &outgoing).await,
+                Err(e) => error_response(Value::Null, PARSE_ERROR, &e.to_string()),
+            };
+
+            if outgoing.send(response.to_string()).is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn handle_request(
+        &self,
+        request: Value,
+        handshaken: &mut bool,
+        outgoing: &mpsc::UnboundedSender<String>,
+    ) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "handshake" => {
+                let result = handshake(&params);
+                if result.is_ok() {
+                    *handshaken = true;
+                }
+                result
+            }
+            _ if !*handshaken => Err((HANDSHAKE_REQUIRED, "Call handshake first".to_string())),
+            "getPendingDiff" => {
+                let repo = match self.repo_param(&params).await {
+                    Ok(repo) => repo,
+                    Err(e) => return error_response(id, e.0, &e.1),
+                };
+                pipeline::pending_diff(&repo)
+                    .map(|diff| json!({ "repo": repo, "diff": diff }))
+                    .map_err(|e| (APP_ERROR, e))
+            }
+            "generateMessage" => {
+                let repo = match self.repo_param(&params).await {
+                    Ok(repo) => repo,
+                    Err(e) => return error_response(id, e.0, &e.1),
+                };
+                let config = self.config.read().await.clone();
+                match pipeline::pending_diff(&repo) {
+                    Ok(diff) => pipeline::generate_message(&repo, &config, &diff)
+                        .await
+                        .map(|generated| {
+                            json!({
+                                "repo": repo,
+                                "message": generated.message,
+                                "tier": generated.tier,
+                                "candidates": generated.candidates,
+                            })
+                        })
+                        .map_err(|e| (APP_ERROR, e)),
+                    Err(e) => Err((APP_ERROR, e)),
+                }
+            }
+            "commit" => {
+                let repo = match self.repo_param(&params).await {
+                    Ok(repo) => repo,
+                    Err(e) => return error_response(id, e.0, &e.1),
+                };
+                let config = self.config.read().await.clone();
+                let cancel = self.cancel.lock().await.clone();
+                pipeline::run_commit(&repo, &config, self.events.as_ref(), &cancel)
+                    .await
+                    .map(|message| json!({ "repo": repo, "message": message }))
+                    .map_err(|e| (APP_ERROR, e))
+            }
+            "subscribe" => {
+                let mut receiver = self.stream.subscribe();
+                let outgoing = outgoing.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let line = match receiver.recv().await {
+                            Ok(line) => line,
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+                        let notification = format!(
+                            "{{\"jsonrpc\":\"2.0\",\"method\":\"event\",\"params\":{}}}",
+                            line
+                        );
+                        if outgoing.send(notification).is_err() {
+                            break;
+                        }
+                    }
+                });
+                Ok(json!({ "subscribed": true }))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        }
+    }
+
+    async fn repo_param(&self, params: &Value) -> Result<String, (i64, String)> {
+        let config = self.config.read().await;
+        match params.get("repo").and_then(Value::as_str) {
+            Some(repo) => configured_repo(&config, repo).map_err(|e| (REPO_NOT_CONFIGURED, e)),
+            None => Ok(config.repo_path.clone()),
+        }
+    }
+}
+
+// The configured repository at `requested`, so a local client can't have
+// the app commit and push in any folder on the machine
+fn configured_repo(config: &AppConfig, requested: &str) -> Result<String, String> {
+    let canonical = fs::canonicalize(requested)
+        .map_err(|_| format!("No such repository: {}", requested))?;
+    config
+        .repo_paths()
+        .into_iter()
+        .find(|path| fs::canonicalize(path).is_ok_and(|path| path == canonical))
+        .ok_or_else(|| format!("Repository is not configured: {}", requested))
+}
+
+fn handshake(params: &Value) -> Result<Value, (i64, String)> {
+    let client_version = params
+        .get("protocol_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(PROTOCOL_VERSION);
+
+    if client_version != PROTOCOL_VERSION {
+        return Err((
+            VERSION_MISMATCH,
+            format!(
+                "Protocol version {} is not supported, server speaks {}",
+                client_version, PROTOCOL_VERSION
+            ),
+        ));
+    }
+
+    Ok(json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "methods": METHODS,
+    }))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NoopSink;
+
+    fn api(config: AppConfig) -> EditorApi {
+        EditorApi {
+            config: Arc::new(RwLock::new(config)),
+            events: Arc::new(NoopSink),
+            stream: EventStream::default(),
+            cancel: Arc::new(Mutex::new(CancellationToken::new())),
+        }
+    }
+
+    fn request(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+    }
+
+    #[test]
+    fn only_configured_repos_are_accepted() {
+        let base = std::env::temp_dir().join(format!("gitgenius-editor-{}", std::process::id()));
+        let configured = base.join("configured");
+        let other = base.join("other");
+        fs::create_dir_all(&configured).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        let config = AppConfig {
+            repo_path: configured.to_string_lossy().to_string(),
+            ..AppConfig::default()
+        };
+
+        // A different spelling of the same folder resolves to the configured path
+        let dotted = configured.join("..").join("configured");
+        assert_eq!(configured_repo(&config, &dotted.to_string_lossy()), Ok(config.repo_path.clone()));
+        assert!(configured_repo(&config, &other.to_string_lossy()).is_err());
+        assert!(configured_repo(&config, &base.join("missing").to_string_lossy()).is_err());
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[tokio::test]
+    async fn methods_wait_for_the_handshake() {
+        let api = api(AppConfig::default());
+        let (outgoing, _outgoing_rx) = mpsc::unbounded_channel();
+        let mut handshaken = false;
+
+        let response = api.handle_request(request("subscribe", Value::Null), &mut handshaken, &outgoing).await;
+        assert_eq!(response["error"]["code"], HANDSHAKE_REQUIRED);
+
+        let mismatch = json!({ "protocol_version": PROTOCOL_VERSION + 1 });
+        let response = api.handle_request(request("handshake", mismatch), &mut handshaken, &outgoing).await;
+        assert_eq!(response["error"]["code"], VERSION_MISMATCH);
+        assert!(!handshaken);
+
+        let version = json!({ "protocol_version": PROTOCOL_VERSION });
+        let response = api.handle_request(request("handshake", version), &mut handshaken, &outgoing).await;
+        assert_eq!(response["result"]["protocol_version"], PROTOCOL_VERSION);
+        let response = api.handle_request(request("subscribe", Value::Null), &mut handshaken, &outgoing).await;
+        assert_eq!(response["result"]["subscribed"], true);
+    }
+
+    #[tokio::test]
+    async fn unconfigured_repos_are_rejected() {
+        let api = api(AppConfig::default());
+        let (outgoing, _outgoing_rx) = mpsc::unbounded_channel();
+        let mut handshaken = true;
+
+        let params = json!({ "repo": std::env::temp_dir().to_string_lossy() });
+        let response = api.handle_request(request("commit", params), &mut handshaken, &outgoing).await;
+        assert_eq!(response["error"]["code"], REPO_NOT_CONFIGURED);
+    }
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// How many events `recent_events` keeps
+const HISTORY_SIZE: usize = 500;
+
+// Events `recent_events` keeps: the small ones a late window needs to catch
+// up, never bulky payloads like `staged-diff-chunk`
+const RECORDED_EVENTS: [&str; 4] = ["cycle-event", "cycle-summary", "session-summary", "timer-toggled"];
+
+static HISTORY: Mutex<VecDeque<RecordedEvent>> = Mutex::new(VecDeque::new());
+
+/// Receives named events raised by the pipeline. The Tauri app forwards them
+/// to the window, the CLI prints them.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: Value);
+}
+
+/// Sink that drops every event, for callers that only care about the result.
+pub struct NoopSink;
+
+impl EventSink for NoopSink {
+    fn emit(&self, _event: &str, _payload: Value) {}
+}
+
+/// A step of a commit cycle. Sent as a `cycle-event` whose payload has the
+/// repository, a timestamp and `kind`, e.g.
+/// `{"repo": ..., "timestamp": ..., "kind": "push_failed", "reason": ...}`,
+/// next to the older events named after each outcome.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CycleEvent {
+    CycleStarted { scheduled: bool },
+    DiffCollected { files: usize, bytes: usize },
+    MessageGenerated { message: String },
+    CommitCreated { sha: String, message: String },
+    PushStarted { remote: String },
+    PushCompleted,
+    PushFailed { reason: String },
+    CycleSkipped { reason: String },
+    CycleFailed { error: String },
+    CycleFinished,
+}
+
+#[derive(Serialize)]
+struct CycleEventPayload<'a> {
+    repo: &'a str,
+    timestamp: u64,
+    #[serde(flatten)]
+    event: CycleEvent,
+}
+
+/// An event as it was emitted, for `recent_events`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RecordedEvent {
+    pub event: String,
+    pub payload: Value,
+    // Unix seconds
+    pub timestamp: u64,
+}
+
+pub fn emit<T: Serialize>(sink: &dyn EventSink, event: &str, payload: &T) {
+    if let Ok(value) = serde_json::to_value(payload) {
+        if RECORDED_EVENTS.contains(&event) {
+            record(event, &value);
+        }
+        sink.emit(event, value);
+    }
+}
+
+/// Emits a step of the cycle in `repo` as a `cycle-event`.
+pub fn cycle(sink: &dyn EventSink, repo: &str, event: CycleEvent) {
+    emit(sink, "cycle-event", &CycleEventPayload { repo, timestamp: unix_now(), event });
+}
+
+/// The last `limit` recorded events emitted in this process (all kept ones
+/// by default), oldest first, so a window opened late can catch up.
+pub fn recent_events(limit: Option<usize>) -> Vec<RecordedEvent> {
+    let history = HISTORY.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let skip = limit.map_or(0, |limit| history.len().saturating_sub(limit));
+    history.iter().skip(skip).cloned().collect()
+}
+
+fn record(event: &str, payload: &Value) {
+    let mut history = HISTORY.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if history.len() == HISTORY_SIZE {
+        history.pop_front();
+    }
+    history.push_back(RecordedEvent {
+        event: event.to_string(),
+        payload: payload.clone(),
+        timestamp: unix_now(),
+    });
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
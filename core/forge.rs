@@ -0,0 +1,162 @@
+//! Code hosting providers: pull requests and CI status behind one `Forge`
+//! trait, with the provider picked from the push remote's URL.
+
+use crate::bitbucket::Bitbucket;
+use crate::ci::BuildStatus;
+use crate::config::{AppConfig, RepoSettings};
+use crate::github::{self, GitHub};
+use crate::gitlab::GitLab;
+use crate::proxy::{self, ProxySettings};
+use git2::Repository;
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+// A slow provider must not hold a cycle up for long
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What the `Forge` methods return; boxed so the trait can be used as
+/// `dyn Forge`.
+pub type ForgeFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send + 'a>>;
+
+#[derive(Serialize, Clone)]
+pub struct PullRequest {
+    pub number: u64,
+    pub url: String,
+    pub title: String,
+    // false when an already open pull request was updated
+    pub created: bool,
+}
+
+/// Title and description of a pull request from `head` into `base`.
+pub struct PullRequestDraft {
+    pub head: String,
+    pub base: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// A code hosting provider for one repository.
+pub trait Forge: Send + Sync {
+    /// Name for messages, e.g. `GitHub`.
+    fn name(&self) -> &'static str;
+
+    /// Opens a pull request (a merge request on GitLab) for `draft`, or
+    /// updates the open one from the same branch.
+    fn open_or_update_pull_request<'a>(&'a self, client: &'a Client, draft: &'a PullRequestDraft) -> ForgeFuture<'a, PullRequest>;
+
+    /// The latest CI result of `branch`.
+    fn build_status<'a>(&'a self, client: &'a Client, branch: &'a str) -> ForgeFuture<'a, BuildStatus>;
+}
+
+/// The forge hosting the repository's push remote (`push_remote`, or
+/// `origin`), with its API token from the keychain.
+pub fn for_repo(repo: &Repository, settings: &RepoSettings) -> Result<Box<dyn Forge>, String> {
+    let remote_name = match settings.push_remote.trim() {
+        "" => "origin",
+        configured => configured,
+    };
+    let remote = repo
+        .find_remote(remote_name)
+        .map_err(|e| format!("No {} remote: {}", remote_name, e))?;
+    let url = remote.url().unwrap_or_default();
+    detect(url)?.ok_or_else(|| format!("{} is not a GitHub, GitLab or Bitbucket remote", url))
+}
+
+/// Picks the forge from a remote URL (HTTPS, SSH or scp-style). Self-hosted
+/// GitLab is recognized by `gitlab` in the host name.
+pub fn detect(url: &str) -> Result<Option<Box<dyn Forge>>, String> {
+    if let Some((owner, name)) = github::parse_remote(url) {
+        return Ok(Some(Box::new(GitHub::new(owner, name)?)));
+    }
+    let (host, path) = match host_and_path(url) {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    if host == "bitbucket.org" {
+        let (workspace, name) = path.split_once('/').unwrap_or_default();
+        return Ok(Some(Box::new(Bitbucket::new(workspace, name)?)));
+    }
+    if host.contains("gitlab") {
+        return Ok(Some(Box::new(GitLab::new(&host, path)?)));
+    }
+    Ok(None)
+}
+
+/// Opens a pull request from the current branch of `path` into `base`, or
+/// updates the open one, on the repository's forge. Without an explicit
+/// title, the title and body are generated from the branch's commits when
+/// `generate` is set, otherwise the latest commit subject is used.
+pub async fn open_or_update_pull_request(
+    path: &str,
+    config: &AppConfig,
+    base: &str,
+    title: Option<String>,
+    body: Option<String>,
+    generate: bool,
+) -> Result<PullRequest, String> {
+    let (forge, head, commits) = {
+        let repo = Repository::open(path).map_err(|e| e.to_string())?;
+        let forge = for_repo(&repo, &config.repo_settings(path))?;
+        let head = github::current_branch(&repo)?;
+        if head == base {
+            return Err(format!("Already on the base branch {}", base));
+        }
+        let commits = github::commits_since_base(&repo, base)?;
+        (forge, head, commits)
+    };
+
+    if commits.is_empty() {
+        return Err(format!("{} has no commits ahead of {}", head, base));
+    }
+
+    let (title, body) = match title {
+        Some(title) => (title, body.unwrap_or_default()),
+        None if generate => github::generate_description(path, config, &commits).await?,
+        None => {
+            let subject = commits[0].lines().next().unwrap_or_default().to_string();
+            (subject, body.unwrap_or_default())
+        }
+    };
+
+    let client = proxy::client(&ProxySettings::from_config(config))?;
+    let draft = PullRequestDraft { head, base: base.to_string(), title, body };
+    forge.open_or_update_pull_request(&client, &draft).await
+}
+
+/// `host` and `owner/name` of a remote URL.
+pub fn host_and_path(url: &str) -> Option<(String, String)> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .or_else(|| url.strip_prefix("ssh://"));
+    let (host, path) = match rest {
+        Some(rest) => rest.split_once('/')?,
+        None => url.split_once(':')?,
+    };
+    let host = host.rsplit('@').next()?.split(':').next()?;
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    (!host.is_empty() && path.contains('/')).then(|| (host.to_string(), path.to_string()))
+}
+
+/// Sends `request` and parses the JSON answer; `forge` names the provider
+/// in errors.
+pub async fn send(request: RequestBuilder, forge: &str) -> Result<Value, String> {
+    let response = request
+        .header("User-Agent", "auto-commit-app")
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("{} API error: {}", forge, error_text));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
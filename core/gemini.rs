@@ -0,0 +1,521 @@
+use crate::config::AppConfig;
+use crate::proxy::{self, ProxySettings};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+struct GeminiRequest {
+    contents: Vec<Content>,
+    #[serde(rename = "systemInstruction")]
+    system_instruction: SystemInstruction,
+    #[serde(rename = "generationConfig")]
+    generation_config: GenerationConfig,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidate_count: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SystemInstruction {
+    parts: Vec<Part>,
+}
+
+#[derive(Serialize)]
+struct Content {
+    role: &'static str,
+    parts: Vec<Part>,
+}
+
+/// Speaker of a conversation turn sent to Gemini.
+#[derive(Clone, Copy)]
+pub enum Role {
+    User,
+    Model,
+}
+
+impl Role {
+    fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Model => "model",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Part {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct GeminiResponse {
+    candidates: Vec<Candidate>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CountTokensRequest {
+    generate_content_request: CountedRequest,
+}
+
+#[derive(Serialize)]
+struct CountedRequest {
+    model: String,
+    #[serde(flatten)]
+    request: GeminiRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CountTokensResponse {
+    #[serde(default)]
+    total_tokens: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    total_token_count: u32,
+}
+
+/// Tokens billed for one request, as reported by Gemini.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelList {
+    #[serde(default)]
+    models: Vec<ModelEntry>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelEntry {
+    name: String,
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+    #[serde(default)]
+    input_token_limit: u32,
+    #[serde(default)]
+    output_token_limit: u32,
+}
+
+/// A model that can generate commit messages.
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelInfo {
+    // Without the `models/` prefix, as used in `AppConfig::model`
+    pub name: String,
+    pub display_name: String,
+    pub input_token_limit: u32,
+    pub output_token_limit: u32,
+}
+
+/// Text of the first candidate, with the token usage of the request.
+pub struct Generation {
+    pub text: String,
+    // Texts of the other candidates, when more than one was asked for
+    pub alternatives: Vec<String>,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct Candidate {
+    content: ContentResponse,
+}
+
+#[derive(Deserialize)]
+struct ContentResponse {
+    parts: Vec<PartResponse>,
+}
+
+#[derive(Deserialize)]
+struct PartResponse {
+    text: String,
+}
+
+// RAG: System context for commit message generation
+pub const SYSTEM_CONTEXT: &str = r#"You are an expert Git commit message generator specialized in creating professional, concise, and meaningful commit messages following industry best practices.
+
+CONTEXT AND PURPOSE:
+- You analyze git diffs to understand code changes
+- You generate commit messages following the Conventional Commits specification
+- Your primary function is to create clear, actionable commit messages that help developers understand changes at a glance
+
+COMMIT MESSAGE RULES:
+1. Format: <type>(<scope>): <subject>
+2. Types: feat, fix, docs, style, refactor, test, chore, perf
+3. Subject: Imperative mood, lowercase, no period, max 50 characters
+4. Be specific and descriptive
+5. Focus on WHAT and WHY, not HOW
+
+EXAMPLES:
+- feat(auth): add JWT token validation
+- fix(api): resolve null pointer in user endpoint
+- refactor(database): optimize query performance
+- docs(readme): update installation instructions
+- style(components): format code with prettier
+
+ANALYSIS APPROACH:
+1. Identify modified files and their purpose
+2. Determine the type of change (feature, bug fix, etc.)
+3. Extract the main impact or goal
+4. Formulate a clear, concise message
+
+Always respond with ONLY the commit message, no explanations or additional text."#;
+
+pub const DEFAULT_MODEL: &str = "gemini-2.0-flash-exp";
+
+pub const DEFAULT_API_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+const NETWORK_ERROR: &str = "Network error";
+
+// Rate limits, exhausted quota and server errors
+const UNAVAILABLE_ERROR: &str = "Gemini API unavailable";
+
+/// Which model to call, where, and with which sampling settings.
+#[derive(Clone, Debug)]
+pub struct ModelOptions {
+    pub model: String,
+    // None leaves the model's own default
+    pub temperature: Option<f32>,
+    pub max_output_tokens: Option<u32>,
+    // None asks for a single candidate
+    pub candidate_count: Option<u32>,
+    pub api_base_url: String,
+    pub proxy: ProxySettings,
+    // Repository the call is made for, to attribute its token usage
+    pub repo: Option<String>,
+}
+
+impl Default for ModelOptions {
+    fn default() -> Self {
+        ModelOptions {
+            model: DEFAULT_MODEL.into(),
+            temperature: None,
+            max_output_tokens: None,
+            candidate_count: None,
+            api_base_url: DEFAULT_API_BASE_URL.into(),
+            proxy: ProxySettings::default(),
+            repo: None,
+        }
+    }
+}
+
+impl ModelOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let model = config.model.trim();
+        ModelOptions {
+            model: if model.is_empty() { DEFAULT_MODEL.into() } else { model.to_string() },
+            temperature: config.temperature,
+            max_output_tokens: config.max_output_tokens,
+            candidate_count: None,
+            api_base_url: base_url(&config.api_base_url),
+            proxy: ProxySettings::from_config(config),
+            repo: None,
+        }
+    }
+
+    /// Same settings, with the usage recorded against `repo_id`.
+    pub fn for_repo(self, repo_id: &str) -> Self {
+        ModelOptions {
+            repo: Some(repo_id.to_string()),
+            ..self
+        }
+    }
+
+    /// Same settings, asking for `count` candidates (Gemini allows 1 to 8).
+    pub fn with_candidates(&self, count: u32) -> Self {
+        ModelOptions {
+            candidate_count: Some(count.clamp(1, 8)).filter(|&count| count > 1),
+            ..self.clone()
+        }
+    }
+
+    /// Same settings, different model.
+    pub fn with_model(&self, model: &str) -> Self {
+        ModelOptions {
+            model: model.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Whether a generation error means Gemini couldn't be reached or is out of
+/// quota, as opposed to rejecting the request itself.
+pub fn is_unavailable(error: &str) -> bool {
+    error.starts_with(NETWORK_ERROR) || error.starts_with(UNAVAILABLE_ERROR)
+}
+
+fn base_url(configured: &str) -> String {
+    let configured = configured.trim().trim_end_matches('/');
+    if configured.is_empty() {
+        DEFAULT_API_BASE_URL.into()
+    } else {
+        configured.to_string()
+    }
+}
+
+fn build_request(
+    options: &ModelOptions,
+    system_context: &str,
+    turns: &[(Role, &str)],
+) -> GeminiRequest {
+    GeminiRequest {
+        generation_config: GenerationConfig {
+            temperature: options.temperature,
+            max_output_tokens: options.max_output_tokens,
+            candidate_count: options.candidate_count,
+        },
+        system_instruction: SystemInstruction {
+            parts: vec![Part {
+                text: system_context.to_string(),
+            }],
+        },
+        contents: turns
+            .iter()
+            .map(|(role, text)| Content {
+                role: role.as_str(),
+                parts: vec![Part {
+                    text: text.to_string(),
+                }],
+            })
+            .collect(),
+    }
+}
+
+fn endpoint_url(options: &ModelOptions, api_key: &str) -> String {
+    format!(
+        "{}/models/{}:generateContent?key={}",
+        options.api_base_url, options.model, api_key
+    )
+}
+
+/// Sends `prompt` to Gemini with the given system instruction and returns the
+/// trimmed text of the first candidate.
+pub async fn generate(
+    api_key: &str,
+    options: &ModelOptions,
+    system_context: &str,
+    prompt: &str,
+) -> Result<String, String> {
+    generate_with_usage(api_key, options, system_context, prompt)
+        .await
+        .map(|generation| generation.text)
+}
+
+/// Like `generate`, but also returns the tokens the request used.
+pub async fn generate_with_usage(
+    api_key: &str,
+    options: &ModelOptions,
+    system_context: &str,
+    prompt: &str,
+) -> Result<Generation, String> {
+    generate_chat_with_usage(api_key, options, system_context, &[(Role::User, prompt)]).await
+}
+
+/// Like `generate`, but sends a whole conversation so the model can refine
+/// its own earlier answers.
+pub async fn generate_chat(
+    api_key: &str,
+    options: &ModelOptions,
+    system_context: &str,
+    turns: &[(Role, &str)],
+) -> Result<String, String> {
+    generate_chat_with_usage(api_key, options, system_context, turns)
+        .await
+        .map(|generation| generation.text)
+}
+
+async fn generate_chat_with_usage(
+    api_key: &str,
+    options: &ModelOptions,
+    system_context: &str,
+    turns: &[(Role, &str)],
+) -> Result<Generation, String> {
+    let client = proxy::client(&options.proxy)?;
+
+    let response = client
+        .post(endpoint_url(options, api_key))
+        .json(&build_request(options, system_context, turns))
+        .send()
+        .await
+        .map_err(|e| format!("{}: {}", NETWORK_ERROR, e))?;
+
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let error_text = response.text().await.unwrap_or_default();
+        if status == 429 || status >= 500 {
+            return Err(format!("{} ({}): {}", UNAVAILABLE_ERROR, status, error_text));
+        }
+        return Err(format!("Gemini API error: {}", error_text));
+    }
+
+    let gemini_response: GeminiResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    let mut texts = gemini_response
+        .candidates
+        .iter()
+        .filter_map(|c| c.content.parts.first())
+        .map(|p| p.text.trim().to_string());
+    let text = texts.next().ok_or_else(|| "No commit message generated".to_string())?;
+    let alternatives = texts.collect();
+
+    let usage = gemini_response.usage_metadata.map(|usage| TokenUsage {
+        prompt_tokens: usage.prompt_token_count,
+        output_tokens: usage.candidates_token_count,
+        total_tokens: usage.total_token_count,
+    });
+    if let Some(tokens) = usage {
+        // Usage tracking must never fail a generation
+        if let Err(error) = crate::usage::record_usage(options.repo.as_deref(), &options.model, tokens) {
+            tracing::warn!(error = %error, "could not record token usage");
+        }
+    }
+
+    Ok(Generation { text, alternatives, usage })
+}
+
+pub async fn test_api_key(api_key: &str, options: &ModelOptions) -> Result<String, String> {
+    let client = proxy::client(&options.proxy)?;
+
+    let test_request = build_request(
+        options,
+        "You are a helpful assistant.",
+        &[(Role::User, "Say 'API Key is valid' if you can read this.")],
+    );
+
+    let response = client
+        .post(endpoint_url(options, api_key))
+        .json(&test_request)
+        .send()
+        .await
+        .map_err(|e| format!("Connection error: {}", e))?;
+
+    if response.status().is_success() {
+        Ok("API Key is valid!".to_string())
+    } else {
+        let error_text = response.text().await.unwrap_or_default();
+        Err(format!("Invalid API Key: {}", error_text))
+    }
+}
+
+/// Tokens that `prompt` and the system instruction take up for
+/// `options.model`, counted by the model's own tokenizer. Nothing is
+/// generated or billed.
+pub async fn count_tokens(
+    api_key: &str,
+    options: &ModelOptions,
+    system_context: &str,
+    prompt: &str,
+) -> Result<u32, String> {
+    let client = proxy::client(&options.proxy)?;
+    let url = format!(
+        "{}/models/{}:countTokens?key={}",
+        options.api_base_url, options.model, api_key
+    );
+    let request = CountTokensRequest {
+        generate_content_request: CountedRequest {
+            model: format!("models/{}", options.model),
+            request: build_request(options, system_context, &[(Role::User, prompt)]),
+        },
+    };
+
+    let response = client
+        .post(url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| format!("{}: {}", NETWORK_ERROR, e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Gemini API error: {}", error_text));
+    }
+
+    let counted: CountTokensResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse token count: {}", e))?;
+    Ok(counted.total_tokens)
+}
+
+/// Models available to `api_key` at `options.api_base_url` that support
+/// `generateContent`, sorted by name.
+pub async fn list_models(api_key: &str, options: &ModelOptions) -> Result<Vec<ModelInfo>, String> {
+    let client = proxy::client(&options.proxy)?;
+    let url = format!("{}/models", options.api_base_url);
+
+    let mut models = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = client.get(&url).query(&[("key", api_key), ("pageSize", "100")]);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token)]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("{}: {}", NETWORK_ERROR, e))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("Gemini API error: {}", error_text));
+        }
+
+        let page: ModelList = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse model list: {}", e))?;
+
+        models.extend(
+            page.models
+                .into_iter()
+                .filter(|entry| {
+                    entry
+                        .supported_generation_methods
+                        .iter()
+                        .any(|method| method == "generateContent")
+                })
+                .map(|entry| ModelInfo {
+                    name: entry.name.trim_start_matches("models/").to_string(),
+                    display_name: entry.display_name,
+                    input_token_limit: entry.input_token_limit,
+                    output_token_limit: entry.output_token_limit,
+                }),
+        );
+
+        page_token = page.next_page_token.filter(|token| !token.is_empty());
+        if page_token.is_none() {
+            break;
+        }
+    }
+
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
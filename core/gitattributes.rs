@@ -0,0 +1,267 @@
+use git2::{AttrCheckFlags, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// A pattern must show up as noise in this many cycles before it is suggested
+const RECURRENCE_THRESHOLD: u32 = 2;
+
+pub(crate) const BINARY_EXTENSIONS: [&str; 24] = [
+    "png", "jpg", "jpeg", "gif", "ico", "webp", "pdf", "zip", "gz", "jar", "woff", "woff2",
+    "ttf", "otf", "eot", "exe", "dll", "so", "dylib", "mp3", "mp4", "psd", "sqlite", "db",
+];
+
+pub(crate) const LOCKFILES: [&str; 8] = [
+    "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "Cargo.lock",
+    "composer.lock", "Gemfile.lock", "poetry.lock", "go.sum",
+];
+
+pub(crate) const GENERATED_SUFFIXES: [&str; 4] = [".min.js", ".min.css", ".map", ".snap"];
+
+const APPEND_ONLY_NAMES: [&str; 4] = ["CHANGELOG", "CHANGES", "HISTORY", "NEWS"];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Suggestion {
+    pub pattern: String,
+    pub attributes: String,
+    pub reason: String,
+}
+
+impl Suggestion {
+    fn line(&self) -> String {
+        format!("{} {}", self.pattern, self.attributes)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Sighting {
+    suggestion: Suggestion,
+    count: u32,
+}
+
+// Sightings per repository, keyed by the `.gitattributes` line they would add
+#[derive(Serialize, Deserialize, Default)]
+struct SuggestionStore {
+    repos: HashMap<String, HashMap<String, Sighting>>,
+}
+
+impl SuggestionStore {
+    fn load() -> Result<Self, String> {
+        let path = store_path()?;
+        if !path.exists() {
+            return Ok(SuggestionStore::default());
+        }
+
+        let store_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read attribute suggestions: {}", e))?;
+        serde_json::from_str(&store_str)
+            .map_err(|e| format!("Failed to parse attribute suggestions: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let store_json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize attribute suggestions: {}", e))?;
+        crate::config::write_atomic(&store_path()?, store_json.as_bytes())
+            .map_err(|e| format!("Failed to save attribute suggestions: {}", e))
+    }
+
+    fn ready(&self, repo_id: &str) -> Vec<Suggestion> {
+        let mut ready: Vec<Suggestion> = self
+            .repos
+            .get(repo_id)
+            .map(|sightings| {
+                sightings
+                    .values()
+                    .filter(|sighting| sighting.count >= RECURRENCE_THRESHOLD)
+                    .map(|sighting| sighting.suggestion.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        ready.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+        ready
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn store_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("gitattributes.json");
+    Ok(path)
+}
+
+/// Inspects the staged changes for diff noise not yet covered by the repo's
+/// attributes and records the sightings. When a pattern crosses the
+/// recurrence threshold in this cycle, returns every pending suggestion so
+/// the caller can advise the user; otherwise returns nothing.
+pub fn record_staged_noise(repo: &Repository, repo_id: &str) -> Result<Vec<Suggestion>, String> {
+    let head_tree = repo.head().and_then(|head| head.peel_to_tree()).ok();
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, None)
+        .map_err(|e| e.to_string())?;
+
+    let mut found = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let path = match delta.new_file().path() {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let blob = repo.find_blob(delta.new_file().id()).ok();
+        let is_binary = blob.as_ref().map(|blob| blob.is_binary()).unwrap_or(false);
+        let content = blob.as_ref().map(|blob| blob.content()).unwrap_or_default();
+
+        let pure_append = git2::Patch::from_diff(&diff, index)
+            .ok()
+            .flatten()
+            .and_then(|patch| patch.line_stats().ok())
+            .map(|(_, additions, deletions)| additions > 0 && deletions == 0)
+            .unwrap_or(false);
+
+        if let Some(suggestion) = classify(path, is_binary, content, pure_append) {
+            if !already_configured(repo, path, &suggestion) {
+                found.push(suggestion);
+            }
+        }
+    }
+
+    if found.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+
+    let mut store = SuggestionStore::load()?;
+    let sightings = store.repos.entry(repo_id.to_string()).or_default();
+    // Several files of one type in a cycle count as a single sighting
+    found.sort_by_key(Suggestion::line);
+    found.dedup();
+
+    let mut newly_ready = false;
+    for suggestion in found {
+        let sighting = sightings
+            .entry(suggestion.line())
+            .or_insert(Sighting { suggestion, count: 0 });
+        sighting.count += 1;
+        newly_ready |= sighting.count == RECURRENCE_THRESHOLD;
+    }
+    store.save()?;
+
+    Ok(if newly_ready { store.ready(repo_id) } else { Vec::new() })
+}
+
+fn classify(path: &Path, is_binary: bool, content: &[u8], pure_append: bool) -> Option<Suggestion> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+
+    if LOCKFILES.contains(&file_name.as_str()) {
+        return Some(Suggestion {
+            pattern: file_name,
+            attributes: "-diff linguist-generated=true".into(),
+            reason: "Lockfile diffs are large and carry little meaning".into(),
+        });
+    }
+
+    if let Some(suffix) = GENERATED_SUFFIXES.iter().find(|suffix| file_name.ends_with(*suffix)) {
+        return Some(Suggestion {
+            pattern: format!("*{}", suffix),
+            attributes: "-diff linguist-generated=true".into(),
+            reason: "Generated file".into(),
+        });
+    }
+
+    if let Some(extension) = &extension {
+        if BINARY_EXTENSIONS.contains(&extension.as_str()) || is_binary {
+            return Some(Suggestion {
+                pattern: format!("*.{}", extension),
+                attributes: "binary".into(),
+                reason: "Binary content".into(),
+            });
+        }
+    }
+
+    // Minified or machine-written text: very long lines on average
+    if !content.is_empty() {
+        let lines = content.iter().filter(|&&byte| byte == b'\n').count().max(1);
+        if content.len() / lines > 1000 {
+            return Some(Suggestion {
+                pattern: path.to_string_lossy().replace('\\', "/"),
+                attributes: "-diff linguist-generated=true".into(),
+                reason: "Looks machine-generated (very long lines)".into(),
+            });
+        }
+    }
+
+    let stem = file_name.split('.').next().unwrap_or_default().to_uppercase();
+    if pure_append && APPEND_ONLY_NAMES.contains(&stem.as_str()) {
+        return Some(Suggestion {
+            pattern: file_name,
+            attributes: "merge=union".into(),
+            reason: "Append-only file, union merge avoids conflicts".into(),
+        });
+    }
+
+    None
+}
+
+// The user (or a previous suggestion) already decided how git treats this path
+fn already_configured(repo: &Repository, path: &Path, suggestion: &Suggestion) -> bool {
+    let attribute = if suggestion.attributes.starts_with("merge") { "merge" } else { "diff" };
+    repo.get_attr(path, attribute, AttrCheckFlags::FILE_THEN_INDEX)
+        .map(|value| value.is_some())
+        .unwrap_or(false)
+}
+
+/// Suggestions for `repo_id` that have recurred often enough to be proposed.
+pub fn pending_suggestions(repo_id: &str) -> Result<Vec<Suggestion>, String> {
+    Ok(SuggestionStore::load()?.ready(repo_id))
+}
+
+/// Appends the pending suggestions to the repository's `.gitattributes` and
+/// forgets them. Returns the lines that were added.
+pub fn apply_suggestions(repo_id: &str) -> Result<Vec<String>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let workdir = repo.workdir().ok_or("Repository has no working directory")?;
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+
+    let mut store = SuggestionStore::load()?;
+    let suggestions = store.ready(repo_id);
+    if suggestions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let attributes_path = workdir.join(".gitattributes");
+    let existing = fs::read_to_string(&attributes_path).unwrap_or_default();
+
+    let lines: Vec<String> = suggestions
+        .iter()
+        .map(Suggestion::line)
+        .filter(|line| !existing.lines().any(|existing_line| existing_line.trim() == line))
+        .collect();
+
+    if !lines.is_empty() {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&attributes_path)
+            .map_err(|e| format!("Failed to open .gitattributes: {}", e))?;
+
+        let separator = if existing.is_empty() || existing.ends_with('\n') { "" } else { "\n" };
+        let block = format!("{}\n# Suggested by GitGenius\n{}\n", separator, lines.join("\n"));
+        file.write_all(block.as_bytes())
+            .map_err(|e| format!("Failed to write .gitattributes: {}", e))?;
+    }
+
+    if let Some(sightings) = store.repos.get_mut(repo_id) {
+        sightings.retain(|_, sighting| sighting.count < RECURRENCE_THRESHOLD);
+    }
+    store.save()?;
+
+    Ok(lines)
+}
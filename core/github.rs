@@ -0,0 +1,212 @@
+use crate::ci::{BuildStatus, CiState};
+use crate::config::AppConfig;
+use crate::forge::{self, Forge, ForgeFuture, PullRequest, PullRequestDraft};
+use crate::gemini;
+use crate::secrets;
+use git2::Repository;
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const API_BASE: &str = "https://api.github.com";
+
+// Check run conclusions that mean the build is broken
+const FAILED_CONCLUSIONS: [&str; 4] = ["failure", "timed_out", "cancelled", "action_required"];
+
+const PR_SYSTEM_CONTEXT: &str = r#"You write GitHub pull request titles and descriptions from a list of commit messages.
+
+RULES:
+1. First line: the pull request title, imperative mood, max 72 characters, no trailing period
+2. Then one blank line
+3. Then a Markdown description: a short summary paragraph followed by a bullet list of the notable changes
+4. Do not invent changes that are not in the commits
+
+Always respond with ONLY the title and description, no explanations or additional text."#;
+
+#[derive(Deserialize)]
+struct PullResponse {
+    number: u64,
+    html_url: String,
+    title: String,
+}
+
+/// Owner and repository name of a GitHub remote URL (HTTPS or SSH form).
+pub fn parse_remote(url: &str) -> Option<(String, String)> {
+    let path = url
+        .strip_prefix("https://github.com/")
+        .or_else(|| url.strip_prefix("http://github.com/"))
+        .or_else(|| url.strip_prefix("git@github.com:"))
+        .or_else(|| url.strip_prefix("ssh://git@github.com/"))?;
+
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let (owner, name) = path.split_once('/')?;
+    Some((owner.to_string(), name.to_string()))
+}
+
+pub fn current_branch(repo: &Repository) -> Result<String, String> {
+    let head = repo.head().map_err(|e| e.to_string())?;
+    head.shorthand()
+        .filter(|_| head.is_branch())
+        .map(str::to_string)
+        .ok_or_else(|| "HEAD is not on a branch".to_string())
+}
+
+/// Messages of the commits on HEAD that are not on `base` (preferring the
+/// remote-tracking branch), newest first.
+pub fn commits_since_base(repo: &Repository, base: &str) -> Result<Vec<String>, String> {
+    let base_oid = repo
+        .revparse_single(&format!("origin/{}", base))
+        .or_else(|_| repo.revparse_single(base))
+        .map_err(|_| format!("Base branch {} not found", base))?
+        .id();
+
+    let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+    revwalk.push_head().map_err(|e| e.to_string())?;
+    revwalk.hide(base_oid).map_err(|e| e.to_string())?;
+
+    let mut messages = Vec::new();
+    for oid in revwalk {
+        let oid = oid.map_err(|e| e.to_string())?;
+        let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+        messages.push(commit.message().unwrap_or_default().trim().to_string());
+    }
+    Ok(messages)
+}
+
+/// Generates a pull request title and Markdown body from commit messages.
+pub async fn generate_description(
+    path: &str,
+    config: &AppConfig,
+    commits: &[String],
+) -> Result<(String, String), String> {
+    let prompt = format!(
+        "Write a pull request title and description for these commits:\n\n{}",
+        commits
+            .iter()
+            .map(|message| format!("- {}", message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    let options = gemini::ModelOptions::from_config(config).for_repo(path);
+    let text = gemini::generate(&config.api_key(), &options, PR_SYSTEM_CONTEXT, &prompt).await?;
+    let (title, body) = text.split_once('\n').unwrap_or((text.as_str(), ""));
+    Ok((title.trim().to_string(), body.trim().to_string()))
+}
+
+/// A repository on GitHub; pull requests and check runs need a token
+/// stored with `set_github_token`.
+pub struct GitHub {
+    owner: String,
+    name: String,
+    token: String,
+}
+
+impl GitHub {
+    pub fn new(owner: String, name: String) -> Result<Self, String> {
+        let token = secrets::get_secret(secrets::GITHUB_TOKEN)?
+            .ok_or("GitHub token not configured")?;
+        Ok(GitHub { owner, name, token })
+    }
+
+    fn get(&self, client: &Client, url: String) -> RequestBuilder {
+        client
+            .get(url)
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+    }
+}
+
+impl Forge for GitHub {
+    fn name(&self) -> &'static str {
+        "GitHub"
+    }
+
+    fn open_or_update_pull_request<'a>(&'a self, client: &'a Client, draft: &'a PullRequestDraft) -> ForgeFuture<'a, PullRequest> {
+        Box::pin(async move {
+            let pulls_url = format!("{}/repos/{}/{}/pulls", API_BASE, self.owner, self.name);
+            let existing = forge::send(
+                self.get(client, pulls_url.clone())
+                    .query(&[("head", format!("{}:{}", self.owner, draft.head)), ("state", "open".to_string())]),
+                "GitHub",
+            )
+            .await?;
+            let existing: Vec<PullResponse> =
+                serde_json::from_value(existing).map_err(|e| format!("Failed to parse response: {}", e))?;
+
+            let (request, created) = match existing.first() {
+                Some(pull) => (
+                    client
+                        .patch(format!("{}/{}", pulls_url, pull.number))
+                        .json(&json!({ "title": draft.title, "body": draft.body })),
+                    false,
+                ),
+                None => (
+                    client.post(&pulls_url).json(&json!({
+                        "title": draft.title,
+                        "body": draft.body,
+                        "head": draft.head,
+                        "base": draft.base,
+                    })),
+                    true,
+                ),
+            };
+            let request = request
+                .bearer_auth(&self.token)
+                .header("Accept", "application/vnd.github+json");
+            let pull: PullResponse = serde_json::from_value(forge::send(request, "GitHub").await?)
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+            Ok(PullRequest {
+                number: pull.number,
+                url: pull.html_url,
+                title: pull.title,
+                created,
+            })
+        })
+    }
+
+    fn build_status<'a>(&'a self, client: &'a Client, branch: &'a str) -> ForgeFuture<'a, BuildStatus> {
+        Box::pin(async move {
+            let commit_url = format!("{}/repos/{}/{}/commits/{}", API_BASE, self.owner, self.name, branch);
+            let runs = forge::send(self.get(client, format!("{}/check-runs", commit_url)), "GitHub").await?;
+            let combined = forge::send(self.get(client, format!("{}/status", commit_url)), "GitHub").await?;
+            Ok(github_state(&runs, &combined))
+        })
+    }
+}
+
+fn github_state(runs: &Value, combined: &Value) -> BuildStatus {
+    let runs = runs["check_runs"].as_array().cloned().unwrap_or_default();
+    let statuses = combined["statuses"].as_array().cloned().unwrap_or_default();
+
+    let mut failing: Vec<String> = runs
+        .iter()
+        .filter(|run| FAILED_CONCLUSIONS.contains(&run["conclusion"].as_str().unwrap_or_default()))
+        .filter_map(|run| run["name"].as_str().map(str::to_string))
+        .collect();
+    failing.extend(
+        statuses
+            .iter()
+            .filter(|status| matches!(status["state"].as_str(), Some("failure" | "error")))
+            .filter_map(|status| status["context"].as_str().map(str::to_string)),
+    );
+    let url = runs
+        .iter()
+        .find(|run| FAILED_CONCLUSIONS.contains(&run["conclusion"].as_str().unwrap_or_default()))
+        .and_then(|run| run["html_url"].as_str())
+        .map(str::to_string);
+
+    let pending = runs.iter().any(|run| run["status"].as_str() != Some("completed"))
+        || statuses.iter().any(|status| status["state"].as_str() == Some("pending"));
+    let state = if !failing.is_empty() {
+        CiState::Failing
+    } else if pending {
+        CiState::Pending
+    } else if runs.is_empty() && statuses.is_empty() {
+        CiState::Unknown
+    } else {
+        CiState::Passing
+    };
+    BuildStatus { state, failing, url }
+}
@@ -0,0 +1,245 @@
+//! `.gitignore` suggestions for build output and tool folders that keep
+//! turning up in cycles (`node_modules/`, `dist/`, `.idea/`, `*.log`, ...),
+//! whether they get staged or are held back by the staging blocklist. A rule
+//! seen in `gitignore_suggest_after_cycles` cycles in a row is suggested
+//! once; `append_suggestions` writes the pending ones to the repo's
+//! `.gitignore`.
+
+use crate::artifacts;
+use crate::config::AppConfig;
+use crate::gemini;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Folders that only ever hold dependencies, build output or tool state
+const NOISY_DIRS: [&str; 20] = [
+    "node_modules", "bower_components", "vendor", "dist", "build", "out", "target", "bin", "obj",
+    ".idea", ".vscode", ".vs", "__pycache__", ".pytest_cache", ".mypy_cache", ".venv", "venv",
+    "coverage", ".next", ".gradle",
+];
+
+const NOISY_FILES: [&str; 7] = ["*.log", "*.pyc", "*.class", "*.o", "*.obj", "*.tmp", "*.iml"];
+
+const GITIGNORE_SYSTEM_CONTEXT: &str = r#"You maintain .gitignore files. Given files that keep showing up as changes in a repository, answer with the .gitignore rules that would keep the build output, dependencies, caches, logs and editor or OS files among them out of git.
+
+RULES:
+1. One rule per line, in .gitignore syntax, no comments or explanations
+2. Prefer folder rules such as `dist/` and extension rules such as `*.log` over single files
+3. Never ignore source code, configuration meant to be shared or documentation
+4. Answer with nothing if none of the files should be ignored"#;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GitignoreSuggestion {
+    pub repo: String,
+    // Rules for `.gitignore`, e.g. `dist/`
+    pub rules: Vec<String>,
+    // The files that brought them up
+    pub files: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Sightings {
+    // Consecutive cycles each rule came up in, and one file it matched
+    streaks: HashMap<String, (u32, String)>,
+    // Suggested and not yet appended
+    pending: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct NoiseStore {
+    repos: HashMap<String, Sightings>,
+}
+
+impl NoiseStore {
+    fn load() -> Result<Self, String> {
+        let path = store_path()?;
+        if !path.exists() {
+            return Ok(NoiseStore::default());
+        }
+
+        let store_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read ignore suggestions: {}", e))?;
+        serde_json::from_str(&store_str)
+            .map_err(|e| format!("Failed to parse ignore suggestions: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let store_json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize ignore suggestions: {}", e))?;
+        crate::config::write_atomic(&store_path()?, store_json.as_bytes())
+            .map_err(|e| format!("Failed to save ignore suggestions: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn store_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("gitignore_suggestions.json");
+    Ok(path)
+}
+
+/// The `.gitignore` rule that would cover `path`, if it looks like noise:
+/// its outermost noisy folder, else a noisy file pattern or the blocklist
+/// pattern it matches.
+pub fn rule_for(path: &str, blocklist: &[String]) -> Option<String> {
+    let mut components = path.split('/');
+    components.next_back();
+    if let Some(dir) = components.find(|component| NOISY_DIRS.contains(component)) {
+        return Some(format!("{}/", dir));
+    }
+    NOISY_FILES
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .chain(blocklist.iter().cloned())
+        .find(|pattern| artifacts::is_blocked(path, std::slice::from_ref(pattern)))
+}
+
+/// Records the noisy rules among the files of this cycle. A rule missing
+/// from a cycle starts over. Returns a suggestion when a rule reaches
+/// `after_cycles` cycles in a row, with every rule suggested and not yet
+/// appended; nothing otherwise, or with `after_cycles` 0.
+pub fn record_cycle(
+    repo_id: &str,
+    files: &[String],
+    blocklist: &[String],
+    after_cycles: u32,
+) -> Result<Option<GitignoreSuggestion>, String> {
+    if after_cycles == 0 {
+        return Ok(None);
+    }
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for file in files {
+        if let Some(rule) = rule_for(file, blocklist) {
+            seen.entry(rule).or_insert_with(|| file.clone());
+        }
+    }
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+
+    let mut store = NoiseStore::load()?;
+    if seen.is_empty() && !store.repos.contains_key(repo_id) {
+        return Ok(None);
+    }
+    let sightings = store.repos.entry(repo_id.to_string()).or_default();
+    sightings.streaks.retain(|rule, _| seen.contains_key(rule));
+
+    let mut newly_ready = false;
+    for (rule, file) in seen {
+        let streak = sightings.streaks.entry(rule.clone()).or_insert((0, file));
+        streak.0 += 1;
+        if streak.0 >= after_cycles && !sightings.pending.contains(&rule) {
+            sightings.pending.push(rule);
+            newly_ready = true;
+        }
+    }
+    sightings.pending.sort();
+    let suggestion = newly_ready.then(|| suggestion(repo_id, sightings));
+    store.save()?;
+    Ok(suggestion)
+}
+
+fn suggestion(repo_id: &str, sightings: &Sightings) -> GitignoreSuggestion {
+    let files = sightings
+        .pending
+        .iter()
+        .filter_map(|rule| sightings.streaks.get(rule).map(|(_, file)| file.clone()))
+        .collect();
+    GitignoreSuggestion { repo: repo_id.to_string(), rules: sightings.pending.clone(), files }
+}
+
+/// The pending suggestion for `repo_id`, if any.
+pub fn pending_suggestion(repo_id: &str) -> Result<Option<GitignoreSuggestion>, String> {
+    Ok(NoiseStore::load()?
+        .repos
+        .get(repo_id)
+        .filter(|sightings| !sightings.pending.is_empty())
+        .map(|sightings| suggestion(repo_id, sightings)))
+}
+
+/// Asks the model for `.gitignore` rules covering `files`; the rules found
+/// by name are returned when there is no key or the model fails.
+pub async fn refine(config: &AppConfig, repo_id: &str, suggestion: GitignoreSuggestion) -> GitignoreSuggestion {
+    if config.api_key().is_empty() {
+        return suggestion;
+    }
+    let options = gemini::ModelOptions::from_config(config).for_repo(repo_id);
+    let prompt = format!(
+        "Files that keep showing up:\n{}\n\nRules found by name:\n{}",
+        suggestion.files.join("\n"),
+        suggestion.rules.join("\n")
+    );
+    match gemini::generate(&config.api_key(), &options, GITIGNORE_SYSTEM_CONTEXT, &prompt).await {
+        Ok(text) => {
+            let rules: Vec<String> = text
+                .lines()
+                .map(|line| line.trim().trim_matches('`').trim().to_string())
+                .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("```"))
+                .collect();
+            if rules.is_empty() {
+                suggestion
+            } else {
+                GitignoreSuggestion { rules, ..suggestion }
+            }
+        }
+        Err(error) => {
+            tracing::warn!(repo = repo_id, error = %error, "could not refine ignore suggestions");
+            suggestion
+        }
+    }
+}
+
+/// Appends `rules` (default: the pending ones) to the `.gitignore` at the
+/// root of the working tree, skipping rules it already has, and forgets
+/// them. Files already committed stay tracked. Returns the added rules.
+pub fn append_suggestions(repo_id: &str, rules: Option<Vec<String>>) -> Result<Vec<String>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let workdir = repo.workdir().ok_or("Repository has no working directory")?;
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+
+    let mut store = NoiseStore::load()?;
+    let rules = match rules {
+        Some(rules) => rules,
+        None => store.repos.get(repo_id).map(|sightings| sightings.pending.clone()).unwrap_or_default(),
+    };
+
+    let ignore_path = workdir.join(".gitignore");
+    let existing = fs::read_to_string(&ignore_path).unwrap_or_default();
+    let mut lines: Vec<String> = Vec::new();
+    for rule in rules.iter().map(|rule| rule.trim()).filter(|rule| !rule.is_empty()) {
+        if rule.contains('\n') {
+            return Err(format!("Invalid ignore rule: {:?}", rule));
+        }
+        let present = existing.lines().any(|line| line.trim() == rule) || lines.iter().any(|line| line == rule);
+        if !present {
+            lines.push(rule.to_string());
+        }
+    }
+
+    if !lines.is_empty() {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&ignore_path)
+            .map_err(|e| format!("Failed to open .gitignore: {}", e))?;
+
+        let separator = if existing.is_empty() || existing.ends_with('\n') { "" } else { "\n" };
+        let block = format!("{}\n# Suggested by GitGenius\n{}\n", separator, lines.join("\n"));
+        file.write_all(block.as_bytes())
+            .map_err(|e| format!("Failed to write .gitignore: {}", e))?;
+    }
+
+    if let Some(sightings) = store.repos.get_mut(repo_id) {
+        sightings.pending.retain(|rule| !rules.contains(rule));
+        sightings.streaks.retain(|rule, _| !rules.contains(rule));
+    }
+    store.save()?;
+
+    Ok(lines)
+}
@@ -0,0 +1,113 @@
+//! GitLab, on gitlab.com or self-hosted: merge requests and pipelines.
+
+use crate::ci::{BuildStatus, CiState};
+use crate::forge::{self, Forge, ForgeFuture, PullRequest, PullRequestDraft};
+use crate::proxy;
+use crate::secrets;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+/// A GitLab project; API calls need a token stored with `set_gitlab_token`.
+pub struct GitLab {
+    // e.g. `https://gitlab.com/api/v4`
+    api_base: String,
+    // `group/subgroup/project`, percent-encoded as the project ID
+    project: String,
+    token: String,
+}
+
+impl GitLab {
+    pub fn new(host: &str, project: String) -> Result<Self, String> {
+        let token = secrets::get_secret(secrets::GITLAB_TOKEN)?
+            .ok_or("GitLab token not configured")?;
+        Ok(GitLab {
+            api_base: format!("https://{}/api/v4", host),
+            project: proxy::encode(&project),
+            token,
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/projects/{}/{}", self.api_base, self.project, path)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header("PRIVATE-TOKEN", &self.token)
+    }
+}
+
+impl Forge for GitLab {
+    fn name(&self) -> &'static str {
+        "GitLab"
+    }
+
+    fn open_or_update_pull_request<'a>(&'a self, client: &'a Client, draft: &'a PullRequestDraft) -> ForgeFuture<'a, PullRequest> {
+        Box::pin(async move {
+            let existing = forge::send(
+                self.authorized(client.get(self.url("merge_requests")).query(&[
+                    ("source_branch", draft.head.as_str()),
+                    ("target_branch", draft.base.as_str()),
+                    ("state", "opened"),
+                ])),
+                "GitLab",
+            )
+            .await?;
+
+            let open = existing.as_array().and_then(|requests| requests.first()).and_then(|request| request["iid"].as_u64());
+            let (request, created) = match open {
+                Some(iid) => (
+                    client
+                        .put(self.url(&format!("merge_requests/{}", iid)))
+                        .json(&json!({ "title": draft.title, "description": draft.body })),
+                    false,
+                ),
+                None => (
+                    client.post(self.url("merge_requests")).json(&json!({
+                        "title": draft.title,
+                        "description": draft.body,
+                        "source_branch": draft.head,
+                        "target_branch": draft.base,
+                    })),
+                    true,
+                ),
+            };
+            let merge_request = forge::send(self.authorized(request), "GitLab").await?;
+
+            Ok(PullRequest {
+                number: merge_request["iid"].as_u64().unwrap_or_default(),
+                url: merge_request["web_url"].as_str().unwrap_or_default().to_string(),
+                title: merge_request["title"].as_str().unwrap_or_default().to_string(),
+                created,
+            })
+        })
+    }
+
+    fn build_status<'a>(&'a self, client: &'a Client, branch: &'a str) -> ForgeFuture<'a, BuildStatus> {
+        Box::pin(async move {
+            let pipelines = forge::send(
+                self.authorized(client.get(self.url("pipelines")).query(&[("ref", branch), ("per_page", "1")])),
+                "GitLab",
+            )
+            .await?;
+            Ok(pipeline_state(&pipelines))
+        })
+    }
+}
+
+fn pipeline_state(pipelines: &Value) -> BuildStatus {
+    let latest = match pipelines.as_array().and_then(|pipelines| pipelines.first()) {
+        Some(latest) => latest,
+        None => return BuildStatus { state: CiState::Unknown, failing: Vec::new(), url: None },
+    };
+    let state = match latest["status"].as_str().unwrap_or_default() {
+        "success" => CiState::Passing,
+        "failed" | "canceled" => CiState::Failing,
+        "skipped" | "manual" => CiState::Unknown,
+        _ => CiState::Pending,
+    };
+    let failing = match state {
+        CiState::Failing => vec![format!("pipeline #{}", latest["id"])],
+        _ => Vec::new(),
+    };
+    BuildStatus { state, failing, url: latest["web_url"].as_str().map(str::to_string) }
+}
@@ -0,0 +1,108 @@
+//! Size guard: a cycle that would stage an unusual amount of changes, say a
+//! `node_modules` that lost its ignore rule or a build output dumped into
+//! the tree, stops before staging and waits for the user to confirm.
+
+use crate::config::AppConfig;
+use git2::{DiffFormat, DiffOptions, Repository};
+use serde::Serialize;
+use std::fs;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Threshold {
+    Files,
+    TotalBytes,
+    FileBytes,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ThresholdExceeded {
+    pub repo: String,
+    pub exceeded: Vec<Threshold>,
+    pub files: usize,
+    // Text diff measured up to just past `max_total_bytes`
+    pub total_bytes: u64,
+    pub largest_file: Option<String>,
+    pub largest_file_bytes: u64,
+    pub max_files: usize,
+    pub max_total_bytes: u64,
+    pub max_file_bytes: u64,
+}
+
+/// Measures everything `git add .` would stage in `repo` against the
+/// `guard_*` limits; 0 turns a limit off. Returns what was exceeded, if
+/// anything.
+pub fn check(repo: &Repository, repo_id: &str, config: &AppConfig) -> Result<Option<ThresholdExceeded>, String> {
+    let (max_files, max_total, max_file) =
+        (config.guard_max_files, config.guard_max_total_bytes, config.guard_max_file_bytes);
+    if max_files == 0 && max_total == 0 && max_file == 0 {
+        return Ok(None);
+    }
+
+    let mut options = DiffOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let diff = repo
+        .diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut options))
+        .map_err(|e| format!("Failed to read changes: {}", e))?;
+    let workdir = repo.workdir().ok_or("Repository has no working tree")?;
+
+    let files = diff.deltas().len();
+    let mut largest_file = None;
+    let mut largest_file_bytes = 0;
+    for delta in diff.deltas() {
+        let path = match delta.new_file().path() {
+            Some(path) => path,
+            None => continue,
+        };
+        // Deleted files aren't there to measure
+        let size = fs::metadata(workdir.join(path)).map(|meta| meta.len()).unwrap_or(0);
+        if size > largest_file_bytes {
+            largest_file_bytes = size;
+            largest_file = Some(path.to_string_lossy().replace('\\', "/"));
+        }
+    }
+
+    // Stop reading the diff as soon as it is known to be too big
+    let mut total_bytes = 0u64;
+    if max_total > 0 {
+        let printed = diff.print(DiffFormat::Patch, |_, _, line| {
+            total_bytes += line.content().len() as u64;
+            total_bytes <= max_total
+        });
+        if let Err(error) = printed {
+            if total_bytes <= max_total {
+                return Err(format!("Failed to measure the diff: {}", error));
+            }
+        }
+    }
+
+    let mut exceeded = Vec::new();
+    if max_files > 0 && files > max_files {
+        exceeded.push(Threshold::Files);
+    }
+    if max_total > 0 && total_bytes > max_total {
+        exceeded.push(Threshold::TotalBytes);
+    }
+    if max_file > 0 && largest_file_bytes > max_file {
+        exceeded.push(Threshold::FileBytes);
+    }
+    if exceeded.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(ThresholdExceeded {
+        repo: repo_id.to_string(),
+        exceeded,
+        files,
+        total_bytes,
+        largest_file,
+        largest_file_bytes,
+        max_files,
+        max_total_bytes: max_total,
+        max_file_bytes: max_file,
+    }))
+}
@@ -0,0 +1,180 @@
+use crate::config::AppConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Failure tracking for one repository. After too many consecutive failed
+/// cycles the repo enters safe mode: it keeps committing locally but stops
+/// pushing and runs less often until the user acknowledges it.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RepoHealth {
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub safe_mode: bool,
+    // Unix seconds
+    pub safe_mode_since: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct HealthStore {
+    repos: HashMap<String, RepoHealth>,
+}
+
+impl HealthStore {
+    fn load() -> Result<Self, String> {
+        let path = health_path()?;
+        if !path.exists() {
+            return Ok(HealthStore::default());
+        }
+
+        let health_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read repo health: {}", e))?;
+        serde_json::from_str(&health_str)
+            .map_err(|e| format!("Failed to parse repo health: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let health_json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize repo health: {}", e))?;
+        crate::config::write_atomic(&health_path()?, health_json.as_bytes())
+            .map_err(|e| format!("Failed to save repo health: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn health_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("repo_health.json");
+    Ok(path)
+}
+
+pub fn repo_health(repo_id: &str) -> RepoHealth {
+    HealthStore::load()
+        .ok()
+        .and_then(|store| store.repos.get(repo_id).cloned())
+        .unwrap_or_default()
+}
+
+pub fn is_in_safe_mode(repo_id: &str) -> bool {
+    repo_health(repo_id).safe_mode
+}
+
+/// Records the outcome of a cycle. Returns true when this failure put the
+/// repository into safe mode.
+pub fn record_outcome(
+    repo_id: &str,
+    result: &Result<String, String>,
+    config: &AppConfig,
+) -> Result<bool, String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = HealthStore::load()?;
+    let health = store.repos.entry(repo_id.to_string()).or_default();
+
+    let mut entered = false;
+    match result {
+        Ok(_) => {
+            health.consecutive_failures = 0;
+            health.last_error = None;
+        }
+        Err(error) => {
+            health.consecutive_failures += 1;
+            health.last_error = Some(error.clone());
+
+            let threshold = config.safe_mode_failure_threshold;
+            if threshold > 0 && !health.safe_mode && health.consecutive_failures >= threshold {
+                health.safe_mode = true;
+                health.safe_mode_since = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .ok();
+                entered = true;
+            }
+        }
+    }
+
+    store.save()?;
+    Ok(entered)
+}
+
+/// Leaves safe mode after the user has looked at the failures. Returns
+/// true when the repository was in safe mode.
+pub fn acknowledge_safe_mode(repo_id: &str) -> Result<bool, String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = HealthStore::load()?;
+    let previous = store.repos.insert(repo_id.to_string(), RepoHealth::default());
+    store.save()?;
+    Ok(previous.is_some_and(|health| health.safe_mode))
+}
+
+/// Delay before the next auto-commit cycle, slowed down while in safe mode.
+pub fn cycle_delay(repo_id: &str, interval_minutes: u64, config: &AppConfig) -> Duration {
+    let multiplier = if is_in_safe_mode(repo_id) {
+        config.safe_mode_interval_multiplier.max(1)
+    } else {
+        1
+    };
+    Duration::from_secs(interval_minutes * 60 * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed() -> Result<String, String> {
+        Err("push failed".into())
+    }
+
+    #[test]
+    fn repeated_failures_enter_safe_mode_once() {
+        let repo_id = "/tmp/health-enter";
+        let config = AppConfig { safe_mode_failure_threshold: 2, ..AppConfig::default() };
+        acknowledge_safe_mode(repo_id).unwrap();
+
+        assert!(!record_outcome(repo_id, &failed(), &config).unwrap());
+        assert!(record_outcome(repo_id, &failed(), &config).unwrap());
+        assert!(!record_outcome(repo_id, &failed(), &config).unwrap());
+        let health = repo_health(repo_id);
+        assert!(health.safe_mode);
+        assert_eq!(health.consecutive_failures, 3);
+        assert_eq!(health.last_error.as_deref(), Some("push failed"));
+
+        // Successful cycles reset the count but don't end safe mode
+        record_outcome(repo_id, &Ok("feat: x".into()), &config).unwrap();
+        assert_eq!(repo_health(repo_id).consecutive_failures, 0);
+        assert!(is_in_safe_mode(repo_id));
+
+        assert!(acknowledge_safe_mode(repo_id).unwrap());
+        assert!(!is_in_safe_mode(repo_id));
+        assert!(!acknowledge_safe_mode(repo_id).unwrap());
+    }
+
+    #[test]
+    fn safe_mode_slows_the_timer_down() {
+        let repo_id = "/tmp/health-delay";
+        let config = AppConfig {
+            safe_mode_failure_threshold: 1,
+            safe_mode_interval_multiplier: 4,
+            ..AppConfig::default()
+        };
+        acknowledge_safe_mode(repo_id).unwrap();
+        assert_eq!(cycle_delay(repo_id, 5, &config), Duration::from_secs(300));
+        record_outcome(repo_id, &failed(), &config).unwrap();
+        assert_eq!(cycle_delay(repo_id, 5, &config), Duration::from_secs(1200));
+        acknowledge_safe_mode(repo_id).unwrap();
+    }
+
+    #[test]
+    fn a_zero_threshold_never_enters_safe_mode() {
+        let repo_id = "/tmp/health-disabled";
+        let config = AppConfig { safe_mode_failure_threshold: 0, ..AppConfig::default() };
+        acknowledge_safe_mode(repo_id).unwrap();
+        for _ in 0..5 {
+            assert!(!record_outcome(repo_id, &failed(), &config).unwrap());
+        }
+        assert!(!is_in_safe_mode(repo_id));
+    }
+}
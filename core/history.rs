@@ -0,0 +1,293 @@
+use crate::pipeline::GenerationTier;
+use crate::usage::UsageRecord;
+use git2::{Commit, DiffOptions, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Where a history entry came from: committed by the app, or backfilled
+// from commits that existed before the app was installed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryOrigin {
+    App,
+    PreApp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub repo_id: String,
+    pub sha: String,
+    pub message: String,
+    pub author: String,
+    pub timestamp: i64,
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub origin: EntryOrigin,
+    // Which generator wrote the message, for commits made by the app
+    #[serde(default)]
+    pub generation_tier: Option<GenerationTier>,
+    // Paths the commit touched; empty for entries recorded before they were kept
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct HistoryStore {
+    pub entries: Vec<HistoryEntry>,
+    // Repositories whose pre-existing history has already been imported
+    #[serde(default)]
+    pub imported_repos: Vec<String>,
+    // Token usage written by older versions, moved to the usage store the
+    // first time it is loaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usage: Vec<UsageRecord>,
+}
+
+impl HistoryStore {
+    pub fn load() -> Result<Self, String> {
+        let path = history_path()?;
+
+        if !path.exists() {
+            return Ok(HistoryStore::default());
+        }
+
+        let history_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read history: {}", e))?;
+
+        serde_json::from_str(&history_str)
+            .map_err(|e| format!("Failed to parse history: {}", e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let history_json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?;
+
+        crate::config::write_atomic(&history_path()?, history_json.as_bytes())
+            .map_err(|e| format!("Failed to save history: {}", e))
+    }
+
+    pub fn contains(&self, repo_id: &str, sha: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.repo_id == repo_id && entry.sha == sha)
+    }
+
+    pub fn record(&mut self, entry: HistoryEntry) {
+        if !self.contains(&entry.repo_id, &entry.sha) {
+            self.entries.push(entry);
+        }
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn history_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("history.json");
+    Ok(path)
+}
+
+/// Builds a history entry for `commit`, with diff stats against its first parent.
+pub fn entry_for_commit(
+    repo: &Repository,
+    repo_id: &str,
+    commit: &Commit,
+    origin: EntryOrigin,
+) -> Result<HistoryEntry, git2::Error> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+
+    let mut diff_options = DiffOptions::new();
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))?;
+    let stats = diff.stats()?;
+    let paths = diff_paths(&diff);
+
+    Ok(HistoryEntry {
+        repo_id: repo_id.to_string(),
+        sha: commit.id().to_string(),
+        message: commit.message().unwrap_or_default().trim().to_string(),
+        author: commit.author().name().unwrap_or_default().to_string(),
+        timestamp: commit.time().seconds(),
+        files_changed: stats.files_changed(),
+        insertions: stats.insertions(),
+        deletions: stats.deletions(),
+        origin,
+        generation_tier: None,
+        paths,
+    })
+}
+
+/// The paths `commit` touched, against its first parent.
+pub fn commit_paths(repo: &Repository, commit: &Commit) -> Result<Vec<String>, git2::Error> {
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    Ok(diff_paths(&diff))
+}
+
+fn diff_paths(diff: &git2::Diff) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for delta in diff.deltas() {
+        for file in [delta.old_file(), delta.new_file()] {
+            if let Some(path) = file.path().map(|path| path.to_string_lossy().replace('\\', "/")) {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// Records the current HEAD commit of `repo_id` as created by the app.
+pub fn record_head_commit(
+    repo_id: &str,
+    generation_tier: Option<GenerationTier>,
+) -> Result<(), String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+
+    let mut entry = entry_for_commit(&repo, repo_id, &head, EntryOrigin::App)
+        .map_err(|e| e.to_string())?;
+    entry.generation_tier = generation_tier;
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = HistoryStore::load()?;
+    store.record(entry);
+    store.save()
+}
+
+/// Drops the token usage older versions kept here, once the usage store
+/// has its own copy.
+pub fn clear_legacy_usage() -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = HistoryStore::load()?;
+    if store.usage.is_empty() {
+        return Ok(());
+    }
+    store.usage.clear();
+    store.save()
+}
+
+/// Drops the entry for `sha`, e.g. a commit that was amended away.
+pub fn forget_commit(repo_id: &str, sha: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = HistoryStore::load()?;
+    store
+        .entries
+        .retain(|entry| !(entry.repo_id == repo_id && entry.sha == sha));
+    store.save()
+}
+
+/// Walks the full history reachable from HEAD and backfills every commit not
+/// yet known to the store as a pre-app entry. Returns the number of imported commits.
+pub fn import_history(repo_id: &str) -> Result<usize, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let store = HistoryStore::load()?;
+    if store.imported_repos.iter().any(|id| id == repo_id) {
+        return Err("History for this repository has already been imported".into());
+    }
+
+    // The walk can take a while on a long history, so it runs without the
+    // lock and cycles recording their commits meanwhile aren't held up
+    let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+    revwalk.push_head().map_err(|e| e.to_string())?;
+    let known = known_commits(&store, repo_id);
+    let mut walked = Vec::new();
+    for oid in revwalk {
+        let oid = oid.map_err(|e| e.to_string())?;
+        if known.contains(&oid.to_string()) {
+            continue;
+        }
+
+        let commit = repo.find_commit(oid).map_err(|e| e.to_string())?;
+        let entry = entry_for_commit(&repo, repo_id, &commit, EntryOrigin::PreApp)
+            .map_err(|e| e.to_string())?;
+        walked.push(entry);
+    }
+
+    // Another import of the same repository may have finished meanwhile
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = HistoryStore::load()?;
+    if store.imported_repos.iter().any(|id| id == repo_id) {
+        return Err("History for this repository has already been imported".into());
+    }
+
+    // Cycles may have recorded some of the walked commits meanwhile
+    let known = known_commits(&store, repo_id);
+    walked.retain(|entry| !known.contains(&entry.sha));
+    let imported = walked.len();
+
+    store.entries.append(&mut walked);
+    store.entries.sort_by_key(|entry| entry.timestamp);
+    store.imported_repos.push(repo_id.to_string());
+    store.save()?;
+
+    Ok(imported)
+}
+
+// Looked up once per commit, which a scan of the store would make quadratic
+fn known_commits(store: &HistoryStore, repo_id: &str) -> HashSet<String> {
+    store
+        .entries
+        .iter()
+        .filter(|entry| entry.repo_id == repo_id)
+        .map(|entry| entry.sha.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::path::Path;
+
+    fn commit_file(repo: &Repository, name: &str, message: &str) {
+        fs::write(repo.workdir().unwrap().join(name), format!("{}\n", name)).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn import_skips_recorded_commits() {
+        let dir = std::env::temp_dir().join(format!("gitgenius-history-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let repo_id = dir.to_string_lossy().to_string();
+        commit_file(&repo, "before.txt", "chore: before the app");
+        commit_file(&repo, "app.txt", "feat: made by the app");
+        record_head_commit(&repo_id, Some(GenerationTier::Primary)).unwrap();
+
+        assert_eq!(import_history(&repo_id).unwrap(), 1);
+        assert!(import_history(&repo_id).is_err());
+
+        let store = HistoryStore::load().unwrap();
+        let origins: Vec<(&str, EntryOrigin)> = store
+            .entries
+            .iter()
+            .filter(|entry| entry.repo_id == repo_id)
+            .map(|entry| (entry.message.as_str(), entry.origin))
+            .collect();
+        assert_eq!(origins.len(), 2);
+        assert!(origins.contains(&("chore: before the app", EntryOrigin::PreApp)));
+        assert!(origins.contains(&("feat: made by the app", EntryOrigin::App)));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,116 @@
+//! Search over every commit in the history store: full text over messages
+//! and changed paths, narrowed by repository, date and Conventional Commits
+//! type, with `git show` for a result.
+
+use crate::conventional;
+use crate::history::{self, HistoryEntry, HistoryStore};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Command;
+
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct HistoryQuery {
+    // Words that must all appear in the message or a changed path,
+    // case-insensitive; empty matches everything
+    pub text: String,
+    pub repo: Option<String>,
+    // Unix seconds, inclusive
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    // Conventional Commits type, e.g. `fix`
+    pub commit_type: Option<String>,
+    // 0 uses the default of 50
+    pub limit: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HistoryHit {
+    pub entry: HistoryEntry,
+    // Changed paths that matched a search word
+    pub matched_paths: Vec<String>,
+}
+
+/// Entries matching `query`, newest first.
+pub fn search_history(query: &HistoryQuery) -> Result<Vec<HistoryHit>, String> {
+    let store = HistoryStore::load()?;
+    let words: Vec<String> = query.text.split_whitespace().map(str::to_lowercase).collect();
+    let commit_type = query.commit_type.as_deref().map(str::trim).filter(|t| !t.is_empty()).map(str::to_lowercase);
+    let limit = if query.limit == 0 { DEFAULT_LIMIT } else { query.limit };
+
+    let mut entries: Vec<&HistoryEntry> = store
+        .entries
+        .iter()
+        .filter(|entry| query.repo.as_deref().is_none_or(|repo| entry.repo_id == repo))
+        .filter(|entry| query.since.is_none_or(|since| entry.timestamp >= since))
+        .filter(|entry| query.until.is_none_or(|until| entry.timestamp <= until))
+        .filter(|entry| match &commit_type {
+            Some(wanted) => conventional::parse(&entry.message).is_some_and(|parsed| &parsed.commit_type == wanted),
+            None => true,
+        })
+        .collect();
+    entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    // Entries from before paths were kept get them from git, once per repo
+    let mut repos: HashMap<String, Option<Repository>> = HashMap::new();
+    let mut hits = Vec::new();
+    for entry in entries {
+        if hits.len() >= limit {
+            break;
+        }
+        let mut entry = entry.clone();
+        if entry.paths.is_empty() && !words.is_empty() {
+            let repo = repos
+                .entry(entry.repo_id.clone())
+                .or_insert_with(|| Repository::open(&entry.repo_id).ok());
+            if let Some(repo) = repo {
+                let commit = Oid::from_str(&entry.sha).and_then(|oid| repo.find_commit(oid));
+                if let Ok(paths) = commit.and_then(|commit| history::commit_paths(repo, &commit)) {
+                    entry.paths = paths;
+                }
+            }
+        }
+
+        let message = entry.message.to_lowercase();
+        let lower_paths: Vec<String> = entry.paths.iter().map(|path| path.to_lowercase()).collect();
+        let matches_word =
+            |word: &String| message.contains(word.as_str()) || lower_paths.iter().any(|path| path.contains(word.as_str()));
+        if !words.iter().all(matches_word) {
+            continue;
+        }
+        let matched_paths = entry
+            .paths
+            .iter()
+            .zip(&lower_paths)
+            .filter(|(_, lower)| words.iter().any(|word| lower.contains(word.as_str())))
+            .map(|(path, _)| path.clone())
+            .collect();
+        hits.push(HistoryHit { entry, matched_paths });
+    }
+    Ok(hits)
+}
+
+/// `git show --stat --patch` of `sha` in `repo_id`.
+pub fn show_commit(repo_id: &str, sha: &str) -> Result<String, String> {
+    // Only object ids, so the argument can't be taken for an option
+    if sha.is_empty() || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Not a commit id: {}", sha));
+    }
+    let output = Command::new("git")
+        .arg("show")
+        .arg("--stat")
+        .arg("--patch")
+        .arg("--no-color")
+        .arg(sha)
+        .arg("--")
+        .current_dir(repo_id)
+        .output()
+        .map_err(|e| format!("Failed to run git show: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("git show failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
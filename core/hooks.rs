@@ -0,0 +1,116 @@
+use git2::Repository;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Serialize, Clone)]
+pub struct HookFailure {
+    pub repo: String,
+    pub hook: String,
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+/// Resolves the hooks directory, honouring `core.hooksPath` (used by husky and friends).
+pub fn hooks_dir(repo: &Repository) -> PathBuf {
+    let configured = repo
+        .config()
+        .and_then(|config| config.get_path("core.hooksPath"))
+        .ok();
+
+    match configured {
+        Some(path) if path.is_absolute() => path,
+        Some(path) => repo.workdir().unwrap_or_else(|| repo.path()).join(path),
+        None => repo.path().join("hooks"),
+    }
+}
+
+/// Runs the `pre-commit` hook if present. Returns the failure when it exits non-zero.
+pub fn run_pre_commit(repo: &Repository, repo_id: &str) -> Result<Option<HookFailure>, String> {
+    run_hook(repo, repo_id, "pre-commit", &[])
+}
+
+/// Runs the `commit-msg` hook against `message` and returns the (possibly
+/// rewritten) message, or the failure when the hook rejects it.
+pub fn run_commit_msg(
+    repo: &Repository,
+    repo_id: &str,
+    message: &str,
+) -> Result<Result<String, HookFailure>, String> {
+    let message_path = repo.path().join("COMMIT_EDITMSG");
+    fs::write(&message_path, message)
+        .map_err(|e| format!("Failed to write commit message file: {}", e))?;
+
+    let message_arg = message_path.to_string_lossy().to_string();
+    if let Some(failure) = run_hook(repo, repo_id, "commit-msg", &[&message_arg])? {
+        return Ok(Err(failure));
+    }
+
+    // Hooks may rewrite the message in place
+    let message = fs::read_to_string(&message_path)
+        .map_err(|e| format!("Failed to read commit message file: {}", e))?;
+    Ok(Ok(message.trim().to_string()))
+}
+
+fn run_hook(
+    repo: &Repository,
+    repo_id: &str,
+    hook: &str,
+    args: &[&str],
+) -> Result<Option<HookFailure>, String> {
+    let hook_path = hooks_dir(repo).join(hook);
+    if !is_executable(&hook_path) {
+        return Ok(None);
+    }
+
+    let workdir = repo.workdir().ok_or("Repository has no working directory")?;
+
+    let output = hook_command(&hook_path)
+        .args(args)
+        .current_dir(workdir)
+        .output()
+        .map_err(|e| format!("Failed to run {} hook: {}", hook, e))?;
+
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(HookFailure {
+        repo: repo_id.to_string(),
+        hook: hook.to_string(),
+        exit_code: output.status.code(),
+        output: format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    }))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(unix)]
+fn hook_command(hook_path: &Path) -> Command {
+    Command::new(hook_path)
+}
+
+// Hooks are shell scripts; on Windows run them through Git's bundled sh
+#[cfg(not(unix))]
+fn hook_command(hook_path: &Path) -> Command {
+    let mut command = Command::new("sh");
+    command.arg(hook_path);
+    command
+}
@@ -0,0 +1,187 @@
+//! Hunk-level staging: the unstaged changes split into hunks for the
+//! frontend, and a way to stage only some of them, so debug prints and the
+//! like can stay out of the commit.
+
+use git2::{ApplyLocation, ApplyOptions, Delta, Diff, DiffFormat, DiffOptions, Repository};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HunkLine {
+    // `+`, `-` or ` ` for context
+    pub origin: char,
+    pub content: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DiffHunk {
+    // Position of the hunk in its file, what `stage_hunks` takes
+    pub index: usize,
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FileHunks {
+    pub path: String,
+    pub untracked: bool,
+    // Binary files have no hunks, so they can't be staged from here
+    pub binary: bool,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Hunks of one file to stage, by their `DiffHunk::index`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HunkSelection {
+    pub path: String,
+    pub hunks: Vec<usize>,
+}
+
+// Both functions have to see the same hunks, so they share the options
+fn unstaged_diff(repo: &Repository) -> Result<Diff<'_>, String> {
+    let mut options = DiffOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    repo.diff_index_to_workdir(None, Some(&mut options))
+        .map_err(|e| format!("Failed to diff the working tree: {}", e))
+}
+
+/// The working tree's changes that aren't staged yet, file by file.
+pub fn diff_hunks(repo_id: &str) -> Result<Vec<FileHunks>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let diff = unstaged_diff(&repo)?;
+
+    let mut files: Vec<FileHunks> = Vec::new();
+    diff.print(DiffFormat::Patch, |delta, hunk, line| {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        if files.last().is_none_or(|file| file.path != path) {
+            files.push(FileHunks {
+                path,
+                untracked: delta.status() == Delta::Untracked,
+                binary: delta.flags().is_binary(),
+                hunks: Vec::new(),
+            });
+        }
+        let file = files.last_mut().expect("pushed above");
+
+        let hunk = match hunk {
+            Some(hunk) => hunk,
+            None => return true,
+        };
+        let header = String::from_utf8_lossy(hunk.header()).trim_end().to_string();
+        if file.hunks.last().is_none_or(|last| last.header != header) {
+            file.hunks.push(DiffHunk {
+                index: file.hunks.len(),
+                header,
+                old_start: hunk.old_start(),
+                old_lines: hunk.old_lines(),
+                new_start: hunk.new_start(),
+                new_lines: hunk.new_lines(),
+                lines: Vec::new(),
+            });
+        }
+
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            let current = file.hunks.last_mut().expect("pushed above");
+            current.lines.push(HunkLine {
+                origin: line.origin(),
+                content: String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string(),
+                old_lineno: line.old_lineno(),
+                new_lineno: line.new_lineno(),
+            });
+        }
+        true
+    })
+    .map_err(|e| format!("Failed to read the diff: {}", e))?;
+
+    Ok(files)
+}
+
+/// Applies only the selected hunks to the index; the working tree is left
+/// as it is. Indexes refer to what `diff_hunks` returned for the same
+/// working tree, so the frontend should refresh after the files change.
+/// Returns the number of hunks staged.
+pub fn stage_hunks(repo_id: &str, selection: &[HunkSelection]) -> Result<usize, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let diff = unstaged_diff(&repo)?;
+
+    let selected: HashMap<&str, HashSet<usize>> = selection
+        .iter()
+        .filter(|file| !file.hunks.is_empty())
+        .map(|file| (file.path.as_str(), file.hunks.iter().copied().collect()))
+        .collect();
+    if selected.is_empty() {
+        return Ok(0);
+    }
+
+    // An untracked file is one hunk, and `apply` only patches files the
+    // index already has, so those are added whole
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    let mut staged = 0;
+    for delta in diff.deltas().filter(|delta| delta.status() == Delta::Untracked) {
+        let path = match delta.new_file().path() {
+            Some(path) => path,
+            None => continue,
+        };
+        if selected.contains_key(path.to_string_lossy().replace('\\', "/").as_str()) {
+            index
+                .add_path(path)
+                .map_err(|e| format!("Failed to stage {}: {}", path.display(), e))?;
+            staged += 1;
+        }
+    }
+    index.write().map_err(|e| e.to_string())?;
+
+    // The callbacks don't say which file a hunk belongs to, so the delta
+    // callback keeps track of it
+    let current: RefCell<Option<(String, usize)>> = RefCell::new(None);
+    let staged = RefCell::new(staged);
+
+    let mut options = ApplyOptions::new();
+    options.delta_callback(|delta| {
+        let delta = delta.filter(|delta| delta.status() != Delta::Untracked);
+        let path = delta
+            .and_then(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+            .map(|path| path.to_string_lossy().replace('\\', "/"));
+        match path.filter(|path| selected.contains_key(path.as_str())) {
+            Some(path) => {
+                *current.borrow_mut() = Some((path, 0));
+                true
+            }
+            None => false,
+        }
+    });
+    options.hunk_callback(|_| {
+        let mut current = current.borrow_mut();
+        let (path, index) = match current.as_mut() {
+            Some(current) => current,
+            None => return false,
+        };
+        let keep = selected[path.as_str()].contains(index);
+        *index += 1;
+        if keep {
+            *staged.borrow_mut() += 1;
+        }
+        keep
+    });
+
+    repo.apply(&diff, ApplyLocation::Index, Some(&mut options))
+        .map_err(|e| format!("Failed to stage hunks: {}", e))?;
+    drop(options);
+
+    Ok(staged.into_inner())
+}
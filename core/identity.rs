@@ -0,0 +1,64 @@
+//! Commit identity: a repository's `author_name` / `author_email` override
+//! whatever git config would give, so a work repo gets the work email
+//! without touching the global config.
+
+use crate::config::RepoSettings;
+use git2::{Repository, Signature};
+use std::process::Command;
+
+/// The signature commits in `repo` are made with: the overrides from
+/// `settings` where set, git's `user.name` / `user.email` for the rest.
+/// Fails when no complete, valid identity can be put together, so a cycle
+/// stops before it stages anything.
+pub fn resolve(repo: &Repository, settings: &RepoSettings) -> Result<Signature<'static>, String> {
+    let git_config = repo.config().ok();
+    let configured = |key: &str| git_config.as_ref().and_then(|config| config.get_string(key).ok());
+    let name = Some(settings.author_name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| configured("user.name"));
+    let email = Some(settings.author_email.trim().to_string())
+        .filter(|email| !email.is_empty())
+        .or_else(|| configured("user.email"));
+
+    let (name, email) = match (name, email) {
+        (Some(name), Some(email)) => (name, email),
+        _ => {
+            return Err(
+                "No commit identity: set author_name and author_email in the repository's settings, \
+                 or user.name and user.email in git config"
+                    .into(),
+            )
+        }
+    };
+    validate_name(&name)?;
+    validate_email(&email)?;
+    Signature::now(&name, &email).map_err(|e| format!("Invalid commit identity {} <{}>: {}", name, email, e))
+}
+
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() || name.contains(['<', '>', '\n']) {
+        return Err(format!("Invalid author name: {:?}", name));
+    }
+    Ok(())
+}
+
+pub fn validate_email(email: &str) -> Result<(), String> {
+    let valid = email
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty());
+    if !valid || email.contains(char::is_whitespace) || email.contains(['<', '>']) {
+        return Err(format!("Invalid author email: {:?}", email));
+    }
+    Ok(())
+}
+
+/// Makes a `git` command commit as `signature`, as author and committer.
+pub fn apply_to_command(command: &mut Command, signature: &Signature) {
+    let name = signature.name().unwrap_or_default();
+    let email = signature.email().unwrap_or_default();
+    command
+        .env("GIT_AUTHOR_NAME", name)
+        .env("GIT_AUTHOR_EMAIL", email)
+        .env("GIT_COMMITTER_NAME", name)
+        .env("GIT_COMMITTER_EMAIL", email);
+}
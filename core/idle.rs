@@ -0,0 +1,50 @@
+use git2::{Repository, StatusOptions};
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time::sleep;
+
+/// Time since the most recent modification of any changed or untracked file
+/// in the working tree, or `None` when nothing has changed.
+pub fn quiet_for(repo_id: &str) -> Result<Option<Duration>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let workdir = repo.workdir().ok_or("Repository has no working directory")?;
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut options)).map_err(|e| e.to_string())?;
+
+    // Deleted files have no mtime and are skipped
+    let latest = statuses
+        .iter()
+        .filter_map(|entry| entry.path().map(|path| workdir.join(path)))
+        .filter_map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .max();
+
+    Ok(latest.map(|latest| {
+        SystemTime::now()
+            .duration_since(latest)
+            .unwrap_or_default()
+    }))
+}
+
+/// Waits until no file in the repository has been modified for `idle`, so
+/// a commit doesn't catch the user mid-edit. Gives up after `max_wait` and
+/// lets the cycle run anyway, so constant editing can't postpone commits
+/// forever. Returns how long it waited.
+pub async fn wait_for_idle(repo_id: &str, idle: Duration, max_wait: Duration) -> Duration {
+    let started = Instant::now();
+
+    loop {
+        let quiet = match quiet_for(repo_id) {
+            Ok(Some(quiet)) => quiet,
+            // Nothing changed, or the repo can't be read: the cycle will say so
+            _ => return started.elapsed(),
+        };
+        if quiet >= idle || started.elapsed() >= max_wait {
+            return started.elapsed();
+        }
+
+        let remaining = max_wait.saturating_sub(started.elapsed());
+        sleep((idle - quiet).min(remaining)).await;
+    }
+}
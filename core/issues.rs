@@ -0,0 +1,83 @@
+//! Issue-tracker references: a ticket ID such as `PROJ-123`, taken from the
+//! branch name or configured for the repository, is put into every commit
+//! message, and commits without it can be refused.
+
+use crate::config::RepoSettings;
+use crate::conventional;
+use crate::github;
+use crate::trailers;
+use git2::Repository;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IssuePlacement {
+    // Before the description, after any `type(scope): ` prefix
+    #[default]
+    Subject,
+    // As the last line, joining an existing trailer block
+    Footer,
+}
+
+/// The issue the repository is being worked on for: the first match of
+/// `issue_patterns` in the current branch name, or else `issue_ticket`. A
+/// pattern with a capture group yields the group, otherwise the whole
+/// match.
+pub fn detect(repo: &Repository, settings: &RepoSettings) -> Result<Option<String>, String> {
+    if let Ok(branch) = github::current_branch(repo) {
+        for pattern in settings.issue_patterns.iter().filter(|pattern| !pattern.trim().is_empty()) {
+            let regex = Regex::new(pattern).map_err(|e| format!("Invalid issue pattern `{}`: {}", pattern, e))?;
+            if let Some(captures) = regex.captures(&branch) {
+                if let Some(id) = captures.get(1).or_else(|| captures.get(0)) {
+                    return Ok(Some(id.as_str().to_string()));
+                }
+            }
+        }
+    }
+    let ticket = settings.issue_ticket.trim();
+    Ok((!ticket.is_empty()).then(|| ticket.to_string()))
+}
+
+/// Puts the reference to `id`, formatted with `issue_format`, where
+/// `issue_placement` says. A message that already mentions `id` is left
+/// as it is.
+pub fn apply(message: &str, id: &str, settings: &RepoSettings) -> String {
+    if message.contains(id) {
+        return message.to_string();
+    }
+    let reference = settings.issue_format.replace("{id}", id);
+    match settings.issue_placement {
+        IssuePlacement::Footer => trailers::append(message, &[reference]),
+        IssuePlacement::Subject => {
+            let (subject, rest) = match message.split_once('\n') {
+                Some((subject, rest)) => (subject, Some(rest)),
+                None => (message, None),
+            };
+            let subject = match conventional::parse(subject) {
+                Some(mut parsed) => {
+                    parsed.subject = format!("{} {}", reference, parsed.subject);
+                    parsed.header()
+                }
+                None => format!("{} {}", reference, subject.trim()),
+            };
+            match rest {
+                Some(rest) => format!("{}\n{}", subject, rest),
+                None => subject,
+            }
+        }
+    }
+}
+
+/// With `require_issue_reference`, fails unless an issue was found and the
+/// final message mentions it.
+pub fn check(message: &str, id: Option<&str>, settings: &RepoSettings) -> Result<(), String> {
+    if !settings.require_issue_reference {
+        return Ok(());
+    }
+    match id {
+        None => Err("No issue reference found: name the branch after the issue or set `issue_ticket`".into()),
+        Some(id) if !message.contains(id) => Err(format!("The commit message does not mention {}", id)),
+        Some(_) => Ok(()),
+    }
+}
@@ -0,0 +1,321 @@
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a commit cycle got to. Persisted in `cycle_journal.json` before
+/// each step, so a cycle that failed or died half-way can be undone.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleStep {
+    Staging,
+    Generating,
+    Committing,
+    Pushing,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ActiveCycle {
+    step: CycleStep,
+    // HEAD when the cycle started, None on an unborn branch
+    head_before: Option<String>,
+    // Set once a split-branch commit went to this branch
+    docs_branch: Option<String>,
+    // Process running the cycle; entries from other processes were left
+    // behind by a crash
+    pid: u32,
+    // Unix seconds
+    started_at: u64,
+}
+
+/// Commits that were created but never reached the remote.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingPush {
+    pub message: String,
+    // Pushed along with the current branch
+    pub docs_branch: Option<String>,
+    // Unix seconds
+    pub since: u64,
+}
+
+/// What was done with an unfinished cycle.
+#[derive(Serialize, Clone, Debug)]
+pub struct Recovery {
+    pub repo: String,
+    pub step: CycleStep,
+    // The cycle's staged changes were moved back to the working tree
+    pub unstaged: bool,
+    // Commits were left unpushed and are retried by the next cycle
+    pub pending_push: Option<PendingPush>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JournalStore {
+    active: HashMap<String, ActiveCycle>,
+    pending_pushes: HashMap<String, PendingPush>,
+}
+
+impl JournalStore {
+    fn load() -> Result<Self, String> {
+        let path = journal_path()?;
+        if !path.exists() {
+            return Ok(JournalStore::default());
+        }
+
+        let journal_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cycle journal: {}", e))?;
+        serde_json::from_str(&journal_str)
+            .map_err(|e| format!("Failed to parse cycle journal: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let journal_json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize cycle journal: {}", e))?;
+        crate::config::write_atomic(&journal_path()?, journal_json.as_bytes())
+            .map_err(|e| format!("Failed to save cycle journal: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn journal_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("cycle_journal.json");
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn head_sha(repo: &Repository) -> Option<String> {
+    repo.head().ok().and_then(|head| head.target()).map(|oid| oid.to_string())
+}
+
+/// Records that a cycle is about to stage changes in `repo_id`.
+pub fn begin(repo_id: &str, repo: &Repository) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    store.active.insert(repo_id.to_string(), ActiveCycle {
+        step: CycleStep::Staging,
+        head_before: head_sha(repo),
+        docs_branch: None,
+        pid: std::process::id(),
+        started_at: unix_now(),
+    });
+    store.save()
+}
+
+pub fn advance(repo_id: &str, step: CycleStep) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    if let Some(active) = store.active.get_mut(repo_id) {
+        active.step = step;
+    }
+    store.save()
+}
+
+/// Notes a split-branch commit, which moves `branch` but not HEAD.
+pub fn docs_committed(repo_id: &str, branch: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    if let Some(active) = store.active.get_mut(repo_id) {
+        active.docs_branch = Some(branch.to_string());
+    }
+    store.save()
+}
+
+/// Ends a cycle that completed.
+pub fn finish(repo_id: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    if store.active.remove(repo_id).is_some() {
+        store.save()?;
+    }
+    Ok(())
+}
+
+/// Ends a cycle that failed or was cancelled in this process. Changes it
+/// staged but didn't commit are unstaged, and commits it created but didn't
+/// push are recorded for the next cycle.
+pub fn roll_back(repo_id: &str) -> Result<Option<Recovery>, String> {
+    roll_back_where(repo_id, |_| true)
+}
+
+/// Rolls back a cycle left unfinished by another process, e.g. one that
+/// crashed mid-cycle. Cycles running in this process are left alone.
+pub fn recover(repo_id: &str) -> Result<Option<Recovery>, String> {
+    let pid = std::process::id();
+    roll_back_where(repo_id, |active| active.pid != pid)
+}
+
+fn roll_back_where(
+    repo_id: &str,
+    applies: impl Fn(&ActiveCycle) -> bool,
+) -> Result<Option<Recovery>, String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    let active = match store.active.get(repo_id) {
+        Some(active) if applies(active) => active.clone(),
+        _ => return Ok(None),
+    };
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+
+    // Past the commit the index holds nothing of the cycle's own
+    let unstaged = active.step != CycleStep::Pushing;
+    if unstaged {
+        unstage_all(&repo)?;
+    }
+
+    let head_moved = head_sha(&repo) != active.head_before;
+    if head_moved || active.docs_branch.is_some() {
+        let message = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .ok()
+            .and_then(|commit| commit.message().map(str::to_string))
+            .unwrap_or_default();
+        let previous = store.pending_pushes.get(repo_id).cloned();
+        store.pending_pushes.insert(repo_id.to_string(), PendingPush {
+            message: message.trim_end().to_string(),
+            docs_branch: active.docs_branch.clone().or(previous.as_ref().and_then(|p| p.docs_branch.clone())),
+            since: previous.map(|p| p.since).unwrap_or(active.started_at),
+        });
+    }
+
+    store.active.remove(repo_id);
+    store.save()?;
+    Ok(Some(Recovery {
+        repo: repo_id.to_string(),
+        step: active.step,
+        unstaged,
+        pending_push: store.pending_pushes.get(repo_id).cloned(),
+    }))
+}
+
+// Resets the index to HEAD, keeping the working tree
+fn unstage_all(repo: &Repository) -> Result<(), String> {
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    let reset = match repo.head().and_then(|head| head.peel_to_tree()) {
+        Ok(head_tree) => index.read_tree(&head_tree),
+        Err(_) => index.clear(),
+    };
+    reset
+        .and_then(|_| index.write())
+        .map_err(|e| format!("Failed to unstage changes: {}", e))
+}
+
+pub fn pending_push(repo_id: &str) -> Option<PendingPush> {
+    JournalStore::load()
+        .ok()
+        .and_then(|store| store.pending_pushes.get(repo_id).cloned())
+}
+
+/// Records commits held back on purpose, e.g. by the CI gate, so the next
+/// cycle pushes them.
+pub fn defer_push(repo_id: &str, message: &str, docs_branch: Option<&str>) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    let previous = store.pending_pushes.get(repo_id).cloned();
+    store.pending_pushes.insert(repo_id.to_string(), PendingPush {
+        message: message.trim_end().to_string(),
+        docs_branch: docs_branch
+            .map(str::to_string)
+            .or(previous.as_ref().and_then(|p| p.docs_branch.clone())),
+        since: previous.map(|p| p.since).unwrap_or_else(unix_now),
+    });
+    store.save()
+}
+
+/// Forgets the pending push once the commits reached the remote.
+pub fn clear_pending_push(repo_id: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = JournalStore::load()?;
+    if store.pending_pushes.remove(repo_id).is_some() {
+        store.save()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::path::Path;
+
+    fn repo_in(name: &str) -> (Repository, String) {
+        let dir = std::env::temp_dir().join(format!("gitgenius-journal-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let repo = Repository::init(&dir).unwrap();
+        (repo, dir.to_string_lossy().to_string())
+    }
+
+    fn stage_file(repo: &Repository, name: &str) {
+        fs::write(repo.workdir().unwrap().join(name), format!("{}\n", name)).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+    }
+
+    fn commit_staged(repo: &Repository, message: &str) {
+        let mut index = repo.index().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn staged_changes_are_unstaged() {
+        let (repo, repo_id) = repo_in("staged");
+        stage_file(&repo, "base.txt");
+        commit_staged(&repo, "chore: base");
+
+        begin(&repo_id, &repo).unwrap();
+        stage_file(&repo, "work.txt");
+        let recovery = roll_back(&repo_id).unwrap().unwrap();
+
+        assert_eq!(recovery.step, CycleStep::Staging);
+        assert!(recovery.unstaged);
+        assert!(recovery.pending_push.is_none());
+        assert!(repo.index().unwrap().get_path(Path::new("work.txt"), 0).is_none());
+        assert!(repo.workdir().unwrap().join("work.txt").exists());
+        assert!(roll_back(&repo_id).unwrap().is_none());
+        fs::remove_dir_all(repo.workdir().unwrap()).ok();
+    }
+
+    #[test]
+    fn unpushed_commits_are_kept_for_the_next_cycle() {
+        let (repo, repo_id) = repo_in("unpushed");
+        begin(&repo_id, &repo).unwrap();
+        stage_file(&repo, "work.txt");
+        commit_staged(&repo, "feat: add work\n");
+        advance(&repo_id, CycleStep::Pushing).unwrap();
+        let recovery = roll_back(&repo_id).unwrap().unwrap();
+
+        assert!(!recovery.unstaged);
+        let pending = recovery.pending_push.unwrap();
+        assert_eq!(pending.message, "feat: add work");
+        assert_eq!(pending_push(&repo_id).unwrap().message, "feat: add work");
+        clear_pending_push(&repo_id).unwrap();
+        assert!(pending_push(&repo_id).is_none());
+        fs::remove_dir_all(repo.workdir().unwrap()).ok();
+    }
+
+    #[test]
+    fn recovery_leaves_cycles_of_this_process_alone() {
+        let (repo, repo_id) = repo_in("recover");
+        begin(&repo_id, &repo).unwrap();
+        assert!(recover(&repo_id).unwrap().is_none());
+        finish(&repo_id).unwrap();
+        assert!(roll_back(&repo_id).unwrap().is_none());
+        fs::remove_dir_all(repo.workdir().unwrap()).ok();
+    }
+}
@@ -0,0 +1,105 @@
+//! Core of GitGenius shared by the Tauri app, the `gitgenius` CLI and any
+//! other host, such as an editor extension: configuration, Gemini message
+//! generation, the commit pipeline, scheduling and history. Nothing here
+//! depends on Tauri.
+//!
+//! The main entry points:
+//!
+//! - [`config::load_config`] reads the same `config.json` every host uses.
+//! - [`pipeline::run_commit`] and [`pipeline::run_scheduled_commit`] run one
+//!   stage, generate, commit and push cycle against a repository.
+//!   [`pipeline::generate_message`] only produces a message for a diff.
+//! - Hosts see what a cycle does by passing an [`events::EventSink`]; the
+//!   desktop app forwards events to its window, the CLI prints them.
+//! - [`schedule`] and [`health::cycle_delay`] decide when the next timed
+//!   cycle runs; [`remote::fetch_due_repos`] drives background fetches.
+//! - [`history`], [`summary`] and [`undo`] read and act on past cycles.
+
+pub mod amend;
+pub mod anonymize;
+pub mod approvals;
+pub mod artifacts;
+pub mod autostart;
+pub mod batch;
+pub mod bitbucket;
+pub mod branch;
+pub mod budget;
+pub mod busy;
+pub mod changelog;
+pub mod commitlint;
+pub mod ci;
+pub mod cleanup;
+pub mod condense;
+pub mod config;
+pub mod conflicts;
+pub mod control_api;
+pub mod conventional;
+pub mod cycle_lock;
+pub mod daily;
+pub mod diagnostics;
+pub mod divergence;
+pub mod diff_stream;
+pub mod editor;
+pub mod forge;
+pub mod events;
+pub mod gemini;
+pub mod gitattributes;
+pub mod gitignore;
+pub mod github;
+pub mod gitlab;
+pub mod guard;
+pub mod health;
+pub mod history;
+pub mod history_search;
+pub mod hooks;
+pub mod hunks;
+pub mod identity;
+pub mod idle;
+pub mod issues;
+pub mod journal;
+pub mod local_model;
+pub mod logging;
+pub mod manifest;
+pub mod markers;
+pub mod message_cache;
+pub mod mcp;
+pub mod monitor;
+pub mod monorepo;
+pub mod pipeline;
+pub mod portable;
+pub mod power;
+pub mod proxy;
+pub mod ranking;
+pub mod release;
+pub mod remote;
+pub mod repo_path;
+pub mod revert;
+pub mod rules;
+pub mod schedule;
+pub mod scheduler;
+pub mod scope;
+pub mod secrets;
+pub mod session;
+pub mod shutdown;
+pub mod significance;
+pub mod snapshot;
+pub mod split;
+pub mod squash;
+pub mod stats;
+pub mod status;
+pub mod stream;
+pub mod style;
+pub mod submodules;
+pub mod summary;
+pub mod throttle;
+pub mod timer_state;
+pub mod tracker;
+pub mod trailers;
+pub mod trial;
+pub mod undo;
+pub mod usage;
+pub mod validate;
+pub mod verify;
+pub mod webhook;
+pub mod work_summary;
+pub mod workspace;
@@ -0,0 +1,101 @@
+//! Client for a model served on this machine or the local network, used
+//! when Gemini can't be reached. Ollama and the llama.cpp server both offer
+//! the OpenAI-compatible chat completions endpoint, so that is what is spoken.
+
+use crate::config::AppConfig;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ChoiceMessage,
+}
+
+#[derive(Deserialize)]
+struct ChoiceMessage {
+    content: String,
+}
+
+/// Whether `local_model_url` is set.
+pub fn is_configured(config: &AppConfig) -> bool {
+    !config.local_model_url.trim().is_empty()
+}
+
+/// Sends the prompt to `local_model_url` and returns the model's answer.
+pub async fn generate(config: &AppConfig, system_context: &str, prompt: &str) -> Result<String, String> {
+    // A local server is never reached through the proxy
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let request = ChatRequest {
+        model: &config.local_model,
+        messages: vec![
+            ChatMessage { role: "system", content: system_context },
+            ChatMessage { role: "user", content: prompt },
+        ],
+        stream: false,
+        temperature: config.temperature,
+        max_tokens: config.max_output_tokens,
+    };
+
+    let response = client
+        .post(endpoint_url(&config.local_model_url))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| format!("Local model unreachable: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Local model error ({}): {}", status, error_text));
+    }
+
+    let chat: ChatResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse local model response: {}", e))?;
+
+    chat.choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .ok_or_else(|| "Local model returned no message".to_string())
+}
+
+// Accepts the server root (`http://localhost:11434`), the `/v1` root or the
+// full endpoint
+fn endpoint_url(base: &str) -> String {
+    let base = base.trim().trim_end_matches('/');
+    if base.ends_with("/chat/completions") {
+        base.to_string()
+    } else if base.ends_with("/v1") {
+        format!("{}/chat/completions", base)
+    } else {
+        format!("{}/v1/chat/completions", base)
+    }
+}
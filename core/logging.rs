@@ -0,0 +1,144 @@
+//! JSON logs in `logs/` under the app data dir, one file per day, so past
+//! cycles can be inspected after the fact.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::EnvFilter;
+
+const LOG_PREFIX: &str = "gitgenius";
+
+// Daily files older than this many days are deleted on rotation
+const MAX_LOG_FILES: usize = 14;
+
+const LEVELS: [&str; 5] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+// Unix seconds with milliseconds, so entries can be filtered without a date library
+struct UnixTime;
+
+impl FormatTime for UnixTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(w, "{}.{:03}", now.as_secs(), now.subsec_millis())
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LogEntry {
+    // Unix seconds
+    pub timestamp: f64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    // Structured fields other than the message, e.g. `repo`
+    pub fields: Value,
+}
+
+fn log_dir() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("logs");
+    fs::create_dir_all(&path).map_err(|e| format!("Failed to create log directory: {}", e))?;
+    Ok(path)
+}
+
+/// Installs the global subscriber. `GITGENIUS_LOG` overrides the default
+/// `info` filter. Keep the returned guard alive for the whole process, or
+/// buffered entries are lost on exit.
+pub fn init() -> Result<WorkerGuard, String> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir()?)
+        .map_err(|e| format!("Failed to open log file: {}", e))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let filter = EnvFilter::try_from_env("GITGENIUS_LOG").unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .json()
+        .with_timer(UnixTime)
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(false)
+        .try_init()
+        .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+
+    Ok(guard)
+}
+
+/// Log entries at or above `min_level` written since `since` (unix
+/// seconds), newest first.
+pub fn recent_logs(
+    min_level: Option<&str>,
+    since: Option<u64>,
+    limit: usize,
+) -> Result<Vec<LogEntry>, String> {
+    let min_rank = min_level.map(level_rank).unwrap_or(0);
+
+    let mut files: Vec<PathBuf> = fs::read_dir(log_dir()?)
+        .map_err(|e| format!("Failed to read log directory: {}", e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(LOG_PREFIX))
+        })
+        .collect();
+    // Dated file names sort chronologically
+    files.sort();
+
+    let mut entries = Vec::new();
+    for file in files.iter().rev() {
+        let contents = fs::read_to_string(file).unwrap_or_default();
+        for line in contents.lines().rev() {
+            let entry = match parse_line(line) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            if since.is_some_and(|since| entry.timestamp < since as f64) {
+                // Older files only hold older entries
+                return Ok(entries);
+            }
+            if level_rank(&entry.level) >= min_rank {
+                entries.push(entry);
+                if entries.len() >= limit {
+                    return Ok(entries);
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_line(line: &str) -> Option<LogEntry> {
+    let mut json: Value = serde_json::from_str(line).ok()?;
+    let mut fields = json.get_mut("fields").map(Value::take).unwrap_or(Value::Null);
+    let message = fields
+        .as_object_mut()
+        .and_then(|fields| fields.remove("message"))
+        .and_then(|message| message.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    Some(LogEntry {
+        timestamp: json.get("timestamp")?.as_str()?.parse().ok()?,
+        level: json.get("level")?.as_str()?.to_string(),
+        target: json.get("target").and_then(Value::as_str).unwrap_or_default().to_string(),
+        message,
+        fields,
+    })
+}
+
+fn level_rank(level: &str) -> usize {
+    LEVELS
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(level))
+        .unwrap_or(0)
+}
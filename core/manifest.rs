@@ -0,0 +1,121 @@
+//! A change manifest for the prompt: one line per file saying what happened
+//! to it (added, modified, renamed with its similarity, copied, deleted,
+//! mode changes, binary), read from git2 with rename detection. It goes
+//! ahead of the raw hunks, so renames and deletions survive when the diff
+//! itself gets cut to fit the model's budget.
+
+use git2::{Delta, Diff, DiffFindOptions, DiffOptions, FileMode, Patch, Repository, Tree};
+
+// Past this many files the rest is only counted
+const MAX_ENTRIES: usize = 200;
+
+pub const HEADING: &str = "Change manifest:";
+
+/// The manifest of the same comparison `git diff <against> -- <paths>`
+/// makes: `--cached` compares the index with a commit (HEAD by default),
+/// one revision compares it with the working tree, two revisions with each
+/// other. None when nothing changed or the comparison can't be read.
+pub fn build(path: &str, against: &[&str], paths: &[String]) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let mut options = DiffOptions::new();
+    for pathspec in paths {
+        options.pathspec(pathspec);
+    }
+
+    let cached = against.contains(&"--cached");
+    let revisions: Vec<&str> = against.iter().copied().filter(|arg| !arg.starts_with('-')).collect();
+    let tree = |spec: &str| -> Option<Tree> { repo.revparse_single(spec).ok()?.peel_to_tree().ok() };
+    let mut diff = match (cached, revisions.as_slice()) {
+        (true, []) => repo.diff_tree_to_index(tree("HEAD").as_ref(), None, Some(&mut options)),
+        (true, [rev]) => repo.diff_tree_to_index(tree(*rev).as_ref(), None, Some(&mut options)),
+        (false, [rev]) => repo.diff_tree_to_workdir_with_index(tree(*rev).as_ref(), Some(&mut options)),
+        (false, [from, to]) => repo.diff_tree_to_tree(tree(*from).as_ref(), tree(*to).as_ref(), Some(&mut options)),
+        _ => return None,
+    }
+    .ok()?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true))).ok()?;
+    describe(&repo, &diff)
+}
+
+fn describe(repo: &Repository, diff: &Diff) -> Option<String> {
+    let count = diff.deltas().len();
+    if count == 0 {
+        return None;
+    }
+
+    let mut lines = vec![HEADING.to_string()];
+    for (index, delta) in diff.deltas().enumerate().take(MAX_ENTRIES) {
+        let old_path = delta.old_file().path().map(|path| path.to_string_lossy().replace('\\', "/"));
+        let new_path = delta.new_file().path().map(|path| path.to_string_lossy().replace('\\', "/"));
+        let path = new_path.clone().or_else(|| old_path.clone()).unwrap_or_default();
+
+        // Loading the patch is what tells binary content apart
+        let binary = Patch::from_diff(diff, index)
+            .ok()
+            .flatten()
+            .is_some_and(|patch| patch.delta().flags().is_binary());
+
+        let mut line = match delta.status() {
+            Delta::Added => format!("- added: {}", path),
+            Delta::Deleted => format!("- deleted: {}", path),
+            Delta::Renamed | Delta::Copied => {
+                let verb = if delta.status() == Delta::Renamed { "renamed" } else { "copied" };
+                let similarity = similarity(repo, delta.old_file().id(), delta.new_file().id(), binary);
+                format!(
+                    "- {} ({}% similar): {} -> {}",
+                    verb,
+                    similarity,
+                    old_path.unwrap_or_default(),
+                    path
+                )
+            }
+            Delta::Typechange => format!("- type changed: {}", path),
+            _ => format!("- modified: {}", path),
+        };
+        let (old_mode, new_mode) = (delta.old_file().mode(), delta.new_file().mode());
+        if delta.status() != Delta::Added && delta.status() != Delta::Deleted && old_mode != new_mode {
+            line.push_str(&format!(", mode {} -> {}", mode_name(old_mode), mode_name(new_mode)));
+        }
+        if binary {
+            line.push_str(", binary");
+        }
+        lines.push(line);
+    }
+    if count > MAX_ENTRIES {
+        lines.push(format!("- ... and {} more files", count - MAX_ENTRIES));
+    }
+    Some(lines.join("\n"))
+}
+
+// Share of lines the two versions have in common; git's own score isn't
+// exposed by git2
+fn similarity(repo: &Repository, old: git2::Oid, new: git2::Oid, binary: bool) -> usize {
+    if old == new {
+        return 100;
+    }
+    let (old_blob, new_blob) = match (repo.find_blob(old), repo.find_blob(new)) {
+        (Ok(old_blob), Ok(new_blob)) => (old_blob, new_blob),
+        // Content only in the working tree
+        _ => return 0,
+    };
+    if binary {
+        return 0;
+    }
+    let lines = |content: &[u8]| content.iter().filter(|&&byte| byte == b'\n').count().max(1);
+    let total = lines(old_blob.content()).max(lines(new_blob.content()));
+    let changed = Patch::from_blobs(&old_blob, None, &new_blob, None, None)
+        .and_then(|patch| patch.line_stats())
+        .map_or(total, |(_, additions, deletions)| additions.max(deletions));
+    total.saturating_sub(changed) * 100 / total
+}
+
+fn mode_name(mode: FileMode) -> String {
+    match mode {
+        FileMode::Blob => "100644".into(),
+        FileMode::BlobExecutable => "100755".into(),
+        FileMode::Link => "symlink".into(),
+        FileMode::Commit => "submodule".into(),
+        FileMode::Tree => "tree".into(),
+        other => format!("{:o}", u32::from(other)),
+    }
+}
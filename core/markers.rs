@@ -0,0 +1,91 @@
+//! Guards against committing unresolved merge conflicts: staged files whose
+//! content still has `<<<<<<<` / `>>>>>>>` markers are taken out of the
+//! commit, and optionally stashed out of the working tree.
+
+use crate::split;
+use git2::Repository;
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConflictMarkers {
+    pub repo: String,
+    pub files: Vec<String>,
+    // The files were moved to a stash entry
+    pub stashed: bool,
+}
+
+/// True when `content` has both an opening and a closing conflict marker
+/// at the start of a line. Binary content never matches.
+pub fn has_markers(content: &[u8]) -> bool {
+    if content.contains(&0) {
+        return false;
+    }
+
+    let text = String::from_utf8_lossy(content);
+    let is_marker = |line: &str, marker: &str| {
+        line.strip_prefix(marker)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    };
+    let opening = text.lines().any(|line| is_marker(line, "<<<<<<<"));
+    let closing = text.lines().any(|line| is_marker(line, ">>>>>>>"));
+    opening && closing
+}
+
+/// Staged files whose staged content has conflict markers.
+pub fn staged_with_markers(repo: &Repository) -> Result<Vec<String>, String> {
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let mut found = Vec::new();
+    for path in split::staged_paths(repo)? {
+        // Deleted files have nothing to scan
+        let entry = match index.get_path(Path::new(&path), 0) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let blob = repo.find_blob(entry.id).map_err(|e| e.to_string())?;
+        if has_markers(blob.content()) {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+/// Takes `paths` out of the index, leaving the working tree as it is.
+pub fn unstage(repo: &Repository, paths: &[String]) -> Result<(), String> {
+    if repo.head().is_ok() {
+        return split::unstage(repo, paths);
+    }
+
+    // Nothing to reset to on an unborn branch
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    for path in paths {
+        index
+            .remove_path(Path::new(path))
+            .map_err(|e| format!("Failed to unstage {}: {}", path, e))?;
+    }
+    index.write().map_err(|e| e.to_string())
+}
+
+/// Moves `paths` to a new stash entry, untracked ones included, so the
+/// working tree no longer has the conflicted versions.
+pub fn stash(repo_id: &str, paths: &[String]) -> Result<(), String> {
+    let output = Command::new("git")
+        .arg("stash")
+        .arg("push")
+        .arg("--include-untracked")
+        .arg("-m")
+        .arg("GitGenius: files with conflict markers")
+        .arg("--")
+        .args(paths)
+        .current_dir(repo_id)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "git stash failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! Model Context Protocol server: the commit pipeline as tools for AI
+//! assistants, spoken as JSON-RPC 2.0 over stdin/stdout, one message per
+//! line. Run by `gitgenius mcp`; the config is read again for every call,
+//! so it follows changes made in the app, and commits land in the same
+//! history.
+//!
+//! Tools (`repo` defaults to the configured repository):
+//! - `generate_commit_message {repo?}` -> a message for the pending changes
+//! - `commit_and_push {repo?, confirm?}` -> the result of a full cycle
+//! - `get_repo_status {repo?}` -> branch, ahead/behind and changed files
+
+use crate::config::{self, AppConfig};
+use crate::events::EventSink;
+use crate::pipeline;
+use crate::status;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_util::sync::CancellationToken;
+
+// The revision of the protocol this server implements
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Answers requests from stdin until it closes. Pipeline events go to
+/// `events`, which must not write to stdout.
+pub async fn serve_stdio(events: &dyn EventSink) -> Result<(), String> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Failed to read stdin: {}", e))?
+    {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => handle_request(request, events).await,
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        // Notifications get no answer
+        if let Some(response) = response {
+            stdout
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .map_err(|e| format!("Failed to write stdout: {}", e))?;
+            stdout.flush().await.map_err(|e| format!("Failed to write stdout: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_request(request: Value, events: &dyn EventSink) -> Option<Value> {
+    let id = request.get("id").cloned()?;
+    let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+    let result = match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "gitgenius", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => match params.get("name").and_then(Value::as_str) {
+            Some(name) => {
+                let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+                call_tool(name, &arguments, events).await
+            }
+            None => Err((INVALID_PARAMS, "Missing tool name".to_string())),
+        },
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    };
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, &message),
+    })
+}
+
+fn tools() -> Value {
+    let repo = json!({
+        "type": "string",
+        "description": "Path of the repository; defaults to the one configured in GitGenius",
+    });
+    json!([
+        {
+            "name": "generate_commit_message",
+            "description": "Write a commit message for the repository's uncommitted changes, without committing",
+            "inputSchema": { "type": "object", "properties": { "repo": repo } },
+        },
+        {
+            "name": "commit_and_push",
+            "description": "Stage all changes, commit them with a generated message and push, using the repository's GitGenius settings",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "repo": repo,
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Commit even when the changes exceed the size guard",
+                    },
+                },
+            },
+        },
+        {
+            "name": "get_repo_status",
+            "description": "Branch, commits ahead of and behind the upstream, and staged, unstaged and untracked files",
+            "inputSchema": { "type": "object", "properties": { "repo": repo } },
+        },
+    ])
+}
+
+// Tool failures are results the assistant should see, not protocol errors
+async fn call_tool(name: &str, arguments: &Value, events: &dyn EventSink) -> Result<Value, (i64, String)> {
+    let config = match config::load_config() {
+        Ok(config) => config,
+        Err(e) => return Ok(tool_result(Err(e))),
+    };
+    let repo = repo_argument(arguments, &config);
+    let cancel = CancellationToken::new();
+
+    let outcome = match name {
+        "generate_commit_message" => match pipeline::pending_diff(&repo) {
+            Ok(diff) if diff.trim().is_empty() => Err(pipeline::NO_CHANGES.to_string()),
+            Ok(diff) => pipeline::generate_message(&repo, &config, &diff)
+                .await
+                .map(|generated| generated.message),
+            Err(e) => Err(e),
+        },
+        "commit_and_push" => {
+            if arguments.get("confirm").and_then(Value::as_bool).unwrap_or(false) {
+                pipeline::run_confirmed_commit(&repo, &config, events, &cancel).await
+            } else {
+                pipeline::run_commit(&repo, &config, events, &cancel).await
+            }
+        }
+        "get_repo_status" => status::repo_status(&repo).and_then(|status| {
+            serde_json::to_string_pretty(&status).map_err(|e| format!("Failed to serialize status: {}", e))
+        }),
+        _ => return Err((INVALID_PARAMS, format!("Unknown tool: {}", name))),
+    };
+    Ok(tool_result(outcome))
+}
+
+fn repo_argument(arguments: &Value, config: &AppConfig) -> String {
+    match arguments.get("repo").and_then(Value::as_str) {
+        Some(repo) if !repo.trim().is_empty() => repo.to_string(),
+        _ => config.repo_path.clone(),
+    }
+}
+
+fn tool_result(outcome: Result<String, String>) -> Value {
+    let (text, is_error) = match outcome {
+        Ok(text) => (text, false),
+        Err(error) => (error, true),
+    };
+    json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
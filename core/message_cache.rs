@@ -0,0 +1,112 @@
+//! Generated messages kept per repository, keyed by a hash of the diff they
+//! describe, so a cycle that retries unchanged content (e.g. after a failed
+//! push was rolled back) reuses the message instead of paying for it again.
+//! Entries expire after `message_cache_ttl_minutes`.
+
+use crate::pipeline::GenerationTier;
+use git2::{ObjectType, Oid};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Older entries go first once a repository has this many
+const MAX_ENTRIES_PER_REPO: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CachedMessage {
+    pub diff_hash: String,
+    pub message: String,
+    pub tier: GenerationTier,
+    // Unix seconds
+    pub cached_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheStore {
+    repos: HashMap<String, Vec<CachedMessage>>,
+}
+
+impl CacheStore {
+    fn load() -> Result<Self, String> {
+        let path = cache_path()?;
+        if !path.exists() {
+            return Ok(CacheStore::default());
+        }
+
+        let cache_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read message cache: {}", e))?;
+        serde_json::from_str(&cache_str)
+            .map_err(|e| format!("Failed to parse message cache: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let cache_json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize message cache: {}", e))?;
+        crate::config::write_atomic(&cache_path()?, cache_json.as_bytes())
+            .map_err(|e| format!("Failed to save message cache: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn cache_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("message_cache.json");
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The hash a diff is cached under: git's blob id of its text.
+pub fn diff_hash(diff_text: &str) -> String {
+    Oid::hash_object(ObjectType::Blob, diff_text.as_bytes())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default()
+}
+
+/// The message cached for `diff_text` in `repo_id`, unless it is older than
+/// `ttl_minutes`.
+pub fn lookup(repo_id: &str, diff_text: &str, ttl_minutes: u64) -> Option<CachedMessage> {
+    let hash = diff_hash(diff_text);
+    let oldest = unix_now().saturating_sub(ttl_minutes * 60);
+    CacheStore::load()
+        .ok()?
+        .repos
+        .remove(repo_id)?
+        .into_iter()
+        .find(|entry| entry.diff_hash == hash && entry.cached_at >= oldest)
+}
+
+/// Caches `message` for `diff_text`, dropping the repository's expired
+/// entries on the way.
+pub fn store(
+    repo_id: &str,
+    diff_text: &str,
+    message: &str,
+    tier: GenerationTier,
+    ttl_minutes: u64,
+) -> Result<(), String> {
+    let hash = diff_hash(diff_text);
+    let now = unix_now();
+    let oldest = now.saturating_sub(ttl_minutes * 60);
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+
+    let mut store = CacheStore::load()?;
+    let entries = store.repos.entry(repo_id.to_string()).or_default();
+    entries.retain(|entry| entry.diff_hash != hash && entry.cached_at >= oldest);
+    entries.push(CachedMessage { diff_hash: hash, message: message.to_string(), tier, cached_at: now });
+    if entries.len() > MAX_ENTRIES_PER_REPO {
+        let excess = entries.len() - MAX_ENTRIES_PER_REPO;
+        entries.drain(..excess);
+    }
+    store.save()
+}
@@ -0,0 +1,254 @@
+//! Machine-readable status for external monitoring. `StatusServer::status`
+//! gathers it; the host can rewrite it to `heartbeat.json` in the data dir
+//! every `heartbeat_interval_secs`, and with `status_server_port` set a
+//! localhost-only HTTP server answers `GET /status`, plus `GET /health` for
+//! plain liveness checks.
+
+use crate::config::AppConfig;
+use crate::health;
+use crate::journal;
+use crate::summary::{self, CycleDecision};
+use crate::usage;
+use serde::Serialize;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, RwLock};
+
+// Requests are a few headers and a small JSON body; anything longer is refused
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RepoReport {
+    pub repo: String,
+    // Unix seconds of the latest cycle that committed
+    pub last_success_at: Option<u64>,
+    // Unix seconds of the latest failed cycle
+    pub last_failure_at: Option<u64>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub safe_mode: bool,
+    // Unix seconds since commits have been waiting to be pushed
+    pub push_pending_since: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QuotaState {
+    pub month_tokens: u64,
+    // 0 when no budget is set
+    pub monthly_budget: u64,
+    pub exhausted: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AppStatus {
+    pub app_version: &'static str,
+    pub pid: u32,
+    // Unix seconds
+    pub generated_at: u64,
+    // The timer runs for `repo_path`
+    pub timer_repo: String,
+    pub timer_running: bool,
+    pub next_run_at: Option<u64>,
+    pub queued_pushes: usize,
+    pub quota: QuotaState,
+    pub repos: Vec<RepoReport>,
+}
+
+/// What the status needs from its host application.
+#[derive(Clone)]
+pub struct StatusServer {
+    // Shared with the host, which may replace it while the server is running
+    pub config: Arc<RwLock<AppConfig>>,
+    pub timer_running: Arc<Mutex<bool>>,
+    // Unix seconds
+    pub next_run: Arc<Mutex<Option<u64>>>,
+}
+
+impl StatusServer {
+    pub async fn status(&self) -> AppStatus {
+        let config = self.config.read().await.clone();
+        let timer_running = *self.timer_running.lock().await;
+        let next_run_at = if timer_running { *self.next_run.lock().await } else { None };
+        app_status(&config, timer_running, next_run_at)
+    }
+
+    /// Rewrites `heartbeat.json` every `interval` until writing fails.
+    pub async fn heartbeat(self, interval: Duration) -> Result<(), String> {
+        let mut path = crate::config::get_data_dir()?;
+        path.push("heartbeat.json");
+        loop {
+            let status = self.status().await;
+            let json = serde_json::to_string_pretty(&status)
+                .map_err(|e| format!("Failed to serialize status: {}", e))?;
+            fs::write(&path, json).map_err(|e| format!("Failed to write heartbeat: {}", e))?;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Serves the status on `127.0.0.1:port` until the listener fails.
+    pub async fn serve(self, port: u16) -> Result<(), String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|e| format!("Failed to bind status server on port {}: {}", port, e))?;
+        loop {
+            let (socket, _) = listener
+                .accept()
+                .await
+                .map_err(|e| format!("Status server error: {}", e))?;
+            tokio::spawn(self.clone().handle_connection(socket));
+        }
+    }
+
+    async fn handle_connection(self, mut socket: TcpStream) {
+        let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut socket)).await {
+            Ok(Some(request)) => request,
+            _ => return,
+        };
+        let (code, body) = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/status") => (200, serde_json::to_string(&self.status().await).unwrap_or_default()),
+            ("GET", "/health") => (200, r#"{"ok":true}"#.to_string()),
+            ("GET", _) => (404, r#"{"error":"not found"}"#.to_string()),
+            _ => (405, r#"{"error":"method not allowed"}"#.to_string()),
+        };
+        respond(&mut socket, code, &body).await;
+    }
+}
+
+/// The status of the app and every repository it knows about.
+pub fn app_status(config: &AppConfig, timer_running: bool, next_run_at: Option<u64>) -> AppStatus {
+    let summaries = summary::recent_summaries(None, usize::MAX).unwrap_or_default();
+    let repos: Vec<RepoReport> = config
+        .repo_paths()
+        .into_iter()
+        .map(|repo| {
+            // Newest first, so the first match is the latest
+            let latest = |wanted: &[CycleDecision]| {
+                summaries
+                    .iter()
+                    .find(|summary| summary.repo == repo && wanted.contains(&summary.decision))
+                    .map(|summary| summary.started_at)
+            };
+            let health = health::repo_health(&repo);
+            RepoReport {
+                last_success_at: latest(&[CycleDecision::Committed, CycleDecision::CommittedLocally]),
+                last_failure_at: latest(&[CycleDecision::Failed]),
+                last_error: health.last_error,
+                consecutive_failures: health.consecutive_failures,
+                safe_mode: health.safe_mode,
+                push_pending_since: journal::pending_push(&repo).map(|pending| pending.since),
+                repo,
+            }
+        })
+        .collect();
+
+    let month_tokens = usage::month_tokens_used();
+    AppStatus {
+        app_version: env!("CARGO_PKG_VERSION"),
+        pid: std::process::id(),
+        generated_at: unix_now(),
+        timer_repo: config.repo_path.clone(),
+        timer_running,
+        next_run_at,
+        queued_pushes: repos.iter().filter(|repo| repo.push_pending_since.is_some()).count(),
+        quota: QuotaState {
+            month_tokens,
+            monthly_budget: config.monthly_token_budget,
+            exhausted: config.monthly_token_budget > 0 && month_tokens >= config.monthly_token_budget,
+        },
+        repos,
+    }
+}
+
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads one HTTP/1.1 request. None for anything malformed or too big.
+pub async fn read_request(socket: &mut TcpStream) -> Option<HttpRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let head_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        if buffer.len() > MAX_REQUEST_BYTES {
+            return None;
+        }
+        let read = socket.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    // The query string isn't used
+    let path = request_line.next()?.split('?').next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let mut request = HttpRequest { method, path, headers, body: buffer[head_end + 4..].to_vec() };
+    let length: usize = request.header("Content-Length").and_then(|value| value.parse().ok()).unwrap_or(0);
+    if length > MAX_REQUEST_BYTES {
+        return None;
+    }
+    while request.body.len() < length {
+        let read = socket.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        request.body.extend_from_slice(&chunk[..read]);
+    }
+    request.body.truncate(length);
+    Some(request)
+}
+
+/// Writes a JSON response and closes the connection.
+pub async fn respond(socket: &mut TcpStream, code: u16, body: &str) {
+    let reason = match code {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await.ok();
+    socket.shutdown().await.ok();
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
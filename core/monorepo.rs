@@ -0,0 +1,65 @@
+//! Monorepo mode: a cycle's staged changes are committed one package at a
+//! time, each commit with its own message, packages before the ones that
+//! depend on them.
+
+use crate::config::AppConfig;
+use crate::workspace::{self, WorkspacePackage};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PlannedCommit {
+    // None for the files outside every package, committed last
+    pub package: Option<String>,
+    pub files: Vec<String>,
+}
+
+/// Groups `paths` by the package they are in and orders the groups so each
+/// package comes after the changed packages it depends on. Dependency
+/// cycles are broken in name order. Returns a single group when the repo
+/// has no packages or everything is in one.
+pub fn plan(repo_path: &str, config: &AppConfig, paths: Vec<String>) -> Vec<PlannedCommit> {
+    let packages = workspace::discover(Path::new(repo_path), &config.scope_patterns, config.workspace_scopes);
+
+    let mut groups: Vec<(&WorkspacePackage, Vec<String>)> = Vec::new();
+    let mut outside = Vec::new();
+    for path in paths {
+        match package_of(&packages, &path) {
+            Some(package) => match groups.iter_mut().find(|(known, _)| known.dir == package.dir) {
+                Some((_, files)) => files.push(path),
+                None => groups.push((package, vec![path])),
+            },
+            None => outside.push(path),
+        }
+    }
+    groups.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+
+    // Repeatedly take the first package none of the remaining ones is a
+    // dependency of
+    let mut ordered = Vec::new();
+    while !groups.is_empty() {
+        let ready = groups
+            .iter()
+            .position(|(package, _)| {
+                !package
+                    .dependencies
+                    .iter()
+                    .any(|dependency| groups.iter().any(|(other, _)| other.dir != package.dir && other.name == *dependency))
+            })
+            .unwrap_or(0);
+        let (package, files) = groups.remove(ready);
+        ordered.push(PlannedCommit { package: Some(package.name.clone()), files });
+    }
+    if !outside.is_empty() {
+        ordered.push(PlannedCommit { package: None, files: outside });
+    }
+    ordered
+}
+
+// The innermost package containing `path`
+fn package_of<'a>(packages: &'a [WorkspacePackage], path: &str) -> Option<&'a WorkspacePackage> {
+    packages
+        .iter()
+        .filter(|package| path.starts_with(&format!("{}/", package.dir)))
+        .max_by_key(|package| package.dir.len())
+}
@@ -0,0 +1,1915 @@
+use crate::amend;
+use crate::anonymize::Anonymizer;
+use crate::approvals::{self, ApprovalState};
+use crate::artifacts::{self, ArtifactsExcluded};
+use crate::batch;
+use crate::budget;
+use crate::busy::{self, BusyMarkers};
+use crate::ci::{self, CiState, CiStatus};
+use crate::commitlint::AllowList;
+use crate::condense;
+use crate::conflicts;
+use crate::cycle_lock;
+use crate::daily;
+use crate::divergence::{self, PushPlan, PushTarget};
+use crate::config::{AppConfig, RepoSettings};
+use crate::events::{self, CycleEvent, EventSink};
+use crate::forge;
+use crate::gemini::{self, ModelOptions, Role, TokenUsage};
+use crate::gitattributes::{self, Suggestion};
+use crate::gitignore;
+use crate::github;
+use crate::guard;
+use crate::health;
+use crate::history;
+use crate::remote::{self, RemoteFailure};
+use crate::hooks;
+use crate::identity;
+use crate::idle;
+use crate::issues;
+use crate::journal::{self, CycleStep, PendingPush};
+use crate::local_model;
+use crate::manifest;
+use crate::markers;
+use crate::message_cache;
+use crate::monorepo::{self, PlannedCommit};
+use crate::power::{self, PowerAction, PowerHold};
+use crate::proxy::{self, ProxySettings};
+use crate::ranking::{self, RankedCandidate};
+use crate::repo_path;
+use crate::rules;
+use crate::schedule;
+use crate::scope::{self, ScopeRules};
+use crate::shutdown;
+use crate::significance;
+use crate::snapshot;
+use crate::split;
+use crate::status::{self, RepoOperation};
+use crate::style::{self, MessageStyle, TemplateContext};
+use crate::submodules::{self, SubmodulePolicy};
+use crate::tracker;
+use crate::trailers;
+use crate::summary::{self, CycleDecision, CycleSummary};
+use crate::throttle;
+use crate::usage;
+use crate::validate;
+use crate::verify;
+use crate::webhook::{self, WebhookPayload};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+pub const NO_CHANGES: &str = "No changes to commit";
+
+pub const REPO_BUSY: &str = "Repository has a merge, rebase or cherry-pick in progress";
+
+pub const CANCELLED: &str = "Cycle cancelled";
+
+pub const ONLY_CONFLICTED: &str = "Only files with conflict markers changed";
+
+pub const TOKEN_BUDGET_EXCEEDED: &str = "Monthly token budget used up";
+
+pub const THRESHOLD_EXCEEDED: &str = "Changes exceed the size guard; waiting for confirmation";
+
+pub const CYCLE_RUNNING: &str = "Another commit cycle is running in this repository";
+
+pub const CI_FAILING: &str = "CI is failing on the target branch; push deferred";
+
+pub const TRIVIAL_CHANGES: &str = "Only whitespace or trivial changes";
+
+pub const POWER_DEFERRED: &str = "Deferred by battery or network conditions";
+
+pub const EDITOR_BUSY: &str = "Files have unsaved changes in an editor";
+
+pub const AWAITING_APPROVAL: &str = "Commit is waiting for approval";
+
+pub const APPROVAL_REJECTED: &str = "Commit was rejected; waiting for the changes to change";
+
+// `text_only_model` value that sends text-only cycles to the rule-based generator
+pub const RULE_BASED_GENERATOR: &str = "rule-based";
+
+const MISSING_API_KEY: &str = "Gemini API Key not configured. Please add your API key in settings.";
+
+const PROMPT_PREFIX: &str = "Analyze these git changes and generate a commit message:\n\n";
+
+// Diffs are read into memory whole up to this size
+const MAX_DIFF_BYTES: usize = 1_000_000;
+
+/// Which generator produced a commit message.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationTier {
+    Primary,
+    // `text_only_model`, for cycles that only touch Markdown or text files
+    TextOnly,
+    Fallback,
+    // The model at `local_model_url`
+    Local,
+    // `fallback_template`, no model involved
+    Template,
+    RuleBased,
+}
+
+/// A generator tried after the primary model, in `fallback_chain` order.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackStep {
+    // `fallback_model`; skipped when it is empty
+    FallbackModel,
+    // Ollama or llama.cpp at `local_model_url`; skipped when it is unset
+    LocalModel,
+    Template,
+    RuleBased,
+}
+
+impl FallbackStep {
+    fn tier(self) -> GenerationTier {
+        match self {
+            FallbackStep::FallbackModel => GenerationTier::Fallback,
+            FallbackStep::LocalModel => GenerationTier::Local,
+            FallbackStep::Template => GenerationTier::Template,
+            FallbackStep::RuleBased => GenerationTier::RuleBased,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct GeneratedMessage {
+    pub message: String,
+    pub tier: GenerationTier,
+    pub elapsed_ms: u128,
+    pub tokens: Option<TokenUsage>,
+    // Every candidate, best first, when more than one came back
+    pub candidates: Vec<RankedCandidate>,
+}
+
+#[derive(Serialize)]
+struct CycleSkipped<'a> {
+    repo: &'a str,
+    reason: &'a str,
+}
+
+#[derive(Serialize)]
+struct RepoBusy<'a> {
+    repo: &'a str,
+    operation: RepoOperation,
+    // Files left conflicted, for the conflict assistant
+    conflicts: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TokenBudgetExceeded<'a> {
+    repo: &'a str,
+    used: u64,
+    budget: u64,
+}
+
+#[derive(Serialize)]
+struct CommitCreated<'a> {
+    repo: &'a str,
+    sha: String,
+    message: &'a str,
+    // Set for split-branch commits that didn't go to the current branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<&'a str>,
+    // The previous app commit was amended instead of adding a new one
+    amended: bool,
+}
+
+// Sent before the commits of a monorepo cycle are made
+#[derive(Serialize)]
+struct CommitPlan<'a> {
+    repo: &'a str,
+    commits: &'a [PlannedCommit],
+}
+
+#[derive(Serialize)]
+struct MessageGenerated<'a> {
+    repo: &'a str,
+    tier: GenerationTier,
+    elapsed_ms: u128,
+}
+
+#[derive(Serialize)]
+struct AttributeAdvice<'a> {
+    repo: &'a str,
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Serialize)]
+struct PushResult<'a> {
+    repo: &'a str,
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<RemoteFailure>,
+}
+
+#[derive(Serialize)]
+struct SafeModeEntered<'a> {
+    repo: &'a str,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    // Lets the frontend escalate this above ordinary errors
+    severity: &'a str,
+}
+
+/// Stages every change in `path`, generates a commit message with Gemini,
+/// commits and pushes. Returns the commit message, or `NO_CHANGES` /
+/// `REPO_BUSY` when the cycle was skipped. Only one cycle runs per
+/// repository at a time, across processes; another one returns
+/// `CYCLE_RUNNING` straight away.
+///
+/// Cancelling `cancel` abandons the cycle with `CANCELLED` at the next
+/// await point (e.g. the Gemini request) or before the next git step. Past
+/// the commit, cancelling still drops the cycle, push included; the journal
+/// then records the commit as a pending push, which the next cycle pushes.
+///
+/// Each step is journaled on disk first. A cycle that fails or is
+/// cancelled before committing unstages what it staged, and commits it
+/// couldn't push are pushed by the next cycle, even one with nothing new to
+/// commit. A cycle left unfinished by a crash is rolled back the same way
+/// when the next one starts.
+///
+/// Every outcome is recorded in the repo's health; too many consecutive
+/// failures put the repo in safe mode, where commits stay local. Each cycle
+/// ends with a `cycle-summary` event, which is also persisted.
+pub async fn run_commit(
+    path: &str,
+    config: &AppConfig,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> Result<String, String> {
+    run_cycle(path, config, events, cancel, None, false).await
+}
+
+/// Like `run_commit`, after the user confirmed changes the size guard
+/// stopped; the guard is skipped for this one cycle.
+pub async fn run_confirmed_commit(
+    path: &str,
+    config: &AppConfig,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> Result<String, String> {
+    run_cycle(path, config, events, cancel, None, true).await
+}
+
+/// Like `run_commit`, for timers that run every `interval_minutes`; the
+/// summary then also carries the next scheduled run. With `idle_seconds`
+/// set, the cycle first waits (at most one interval) for editing to stop.
+pub async fn run_scheduled_commit(
+    path: &str,
+    config: &AppConfig,
+    interval_minutes: u64,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+) -> Result<String, String> {
+    if config.idle_seconds > 0 {
+        let max_wait = Duration::from_secs(interval_minutes.max(1) * 60);
+        let waited = tokio::select! {
+            waited = idle::wait_for_idle(path, Duration::from_secs(config.idle_seconds), max_wait) => waited,
+            _ = cancel.cancelled() => return Ok(CANCELLED.into()),
+        };
+        if waited.as_secs() > 0 {
+            info!(repo = path, waited_secs = waited.as_secs(), "waited for the working tree to go idle");
+        }
+    }
+    run_cycle(path, config, events, cancel, Some(interval_minutes), false).await
+}
+
+async fn run_cycle(
+    path: &str,
+    config: &AppConfig,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+    interval_minutes: Option<u64>,
+    confirmed: bool,
+) -> Result<String, String> {
+    // The app is exiting and waits for the cycles it knows about
+    let _in_flight = match shutdown::enter() {
+        Some(in_flight) => in_flight,
+        None => return Ok(CANCELLED.into()),
+    };
+
+    // A folder inside the working tree runs on its root, or `git add .`
+    // would only stage that folder. Not finding one is reported when the
+    // cycle opens the repository.
+    let root = repo_path::resolve(path).map(|found| found.root);
+    let path = root.as_deref().unwrap_or(path);
+
+    // Overlapping cycles would interleave their git commands on one index.
+    // Taken before recovery, which would otherwise roll back a running cycle
+    // of the CLI.
+    let _lock = match cycle_lock::try_acquire(path) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            let owner = cycle_lock::owner(path).map(|owner| owner.pid);
+            info!(repo = path, owner_pid = ?owner, "another cycle is running, skipping");
+            events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: CYCLE_RUNNING });
+            return Ok(CYCLE_RUNNING.into());
+        }
+        // The cycle reports the same problem when it opens the repository
+        Err(error) => {
+            warn!(repo = path, error = %error, "failed to take the cycle lock");
+            None
+        }
+    };
+
+    match journal::recover(path) {
+        Ok(Some(recovery)) => {
+            warn!(repo = path, step = ?recovery.step, "rolled back a cycle interrupted by a crash");
+            events::emit(events, "cycle-recovered", &recovery);
+        }
+        Ok(None) => {}
+        Err(error) => warn!(repo = path, error = %error, "failed to recover the cycle journal"),
+    }
+
+    events::cycle(events, path, CycleEvent::CycleStarted { scheduled: interval_minutes.is_some() });
+    let mut summary = CycleSummary::new(path);
+    let result = tokio::select! {
+        result = commit_cycle(path, config, events, cancel, interval_minutes.is_some(), confirmed, &mut summary) => result,
+        _ = cancel.cancelled() => Ok(CANCELLED.into()),
+    };
+
+    let cancelled = result.as_deref() == Ok(CANCELLED);
+    let settled = match &result {
+        Ok(_) if !cancelled => journal::finish(path).map(|_| None),
+        _ => journal::roll_back(path),
+    };
+    match settled {
+        Ok(Some(recovery)) => {
+            info!(repo = path, step = ?recovery.step, unstaged = recovery.unstaged, "cycle rolled back");
+            events::emit(events, "cycle-rolled-back", &recovery);
+        }
+        Ok(None) => {}
+        Err(error) => warn!(repo = path, error = %error, "failed to roll back the cycle"),
+    }
+    if cancelled {
+        info!(repo = path, "cycle cancelled");
+        summary.skip(CANCELLED);
+        events::emit(events, "cycle-cancelled", &CycleSkipped { repo: path, reason: CANCELLED });
+    }
+
+    // A cancelled cycle says nothing about the repo's health
+    if !cancelled && health::record_outcome(path, &result, config) == Ok(true) {
+        let health = health::repo_health(path);
+        warn!(repo = path, consecutive_failures = health.consecutive_failures, "entered safe mode");
+        events::emit(events, "safe-mode-entered", &SafeModeEntered {
+            repo: path,
+            consecutive_failures: health.consecutive_failures,
+            last_error: health.last_error,
+            severity: "critical",
+        });
+    }
+
+    summary.finish(&result);
+    if let Some(interval_minutes) = interval_minutes {
+        let delay = health::cycle_delay(path, interval_minutes, config);
+        summary.schedule_next(schedule::next_run_at(&config.schedule, delay));
+    }
+    match &result {
+        Ok(_) => info!(
+            repo = path,
+            decision = ?summary.decision,
+            reason = summary.reason.as_deref().unwrap_or_default(),
+            duration_ms = summary.duration_ms,
+            "cycle finished"
+        ),
+        Err(error) => warn!(repo = path, duration_ms = summary.duration_ms, error = %error, "cycle failed"),
+    }
+    events::emit(events, "cycle-summary", &summary);
+    let outcome = match &result {
+        Ok(reason) if is_skip(reason) => CycleEvent::CycleSkipped { reason: reason.clone() },
+        Ok(_) => CycleEvent::CycleFinished,
+        Err(error) => CycleEvent::CycleFailed { error: error.clone() },
+    };
+    events::cycle(events, path, outcome);
+    // The summary is informational; failing to store it must not fail the cycle
+    summary::record(&summary).ok();
+
+    result
+}
+
+// What the steps of one cycle work with. `commit_cycle` runs them in the
+// journal's order: `prepare` decides whether the cycle runs at all and
+// touches nothing, `stage` stages the changes (journaled from there on),
+// `commit` makes the commits and `publish` pushes them. A step that ends
+// the cycle returns `ControlFlow::Break` with the cycle's result.
+struct Cycle<'a> {
+    path: &'a str,
+    config: &'a AppConfig,
+    events: &'a dyn EventSink,
+    cancel: &'a CancellationToken,
+    scheduled: bool,
+    repo: Repository,
+    settings: RepoSettings,
+    proxy: ProxySettings,
+}
+
+// Found by `prepare` for the later steps
+struct Prepared {
+    // Commit, but leave the push to a cycle under better conditions
+    hold_push: Option<PowerHold>,
+    submodule_policy: SubmodulePolicy,
+    submodule_paths: Vec<String>,
+}
+
+// Moved submodule pointers `stage` left out, with their fixed message
+type GitlinkCommit = Option<(Vec<String>, String)>;
+
+// What `commit` made, for `publish`
+struct Commits {
+    message: String,
+    files: usize,
+    // Set when `docs_branch` got a commit too
+    docs_message: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn commit_cycle(
+    path: &str,
+    config: &AppConfig,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+    scheduled: bool,
+    confirmed: bool,
+    summary: &mut CycleSummary,
+) -> Result<String, String> {
+    let cycle = Cycle {
+        path,
+        config,
+        events,
+        cancel,
+        scheduled,
+        repo: Repository::open(path).map_err(|e| e.to_string())?,
+        settings: config.repo_settings(path),
+        proxy: ProxySettings::from_config(config),
+    };
+
+    let prepared = match cycle.prepare(confirmed, summary).await? {
+        ControlFlow::Continue(prepared) => prepared,
+        ControlFlow::Break(outcome) => return Ok(outcome),
+    };
+    let gitlink_commit = match cycle.stage(&prepared, summary).await? {
+        ControlFlow::Continue(gitlink_commit) => gitlink_commit,
+        ControlFlow::Break(outcome) => return Ok(outcome),
+    };
+    let commits = match cycle.commit(&prepared, gitlink_commit, summary).await? {
+        ControlFlow::Continue(commits) => commits,
+        ControlFlow::Break(outcome) => return Ok(outcome),
+    };
+    cycle.publish(&prepared, commits, summary).await
+}
+
+impl Cycle<'_> {
+    // Everything that can skip the cycle before it changes the index
+    async fn prepare(&self, confirmed: bool, summary: &mut CycleSummary) -> Result<ControlFlow<String, Prepared>, String> {
+        let (path, config, events, repo, settings) = (self.path, self.config, self.events, &self.repo, &self.settings);
+
+        // Staging now would commit conflict markers or split the user's operation
+        if let Some(operation) = status::operation_in_progress(repo) {
+            info!(repo = path, operation = ?operation, "git operation in progress, skipping");
+            let conflicts = conflicts::conflicted_paths(repo);
+            events::emit(events, "repo-busy", &RepoBusy { repo: path, operation, conflicts });
+            summary.skip(REPO_BUSY);
+            return Ok(ControlFlow::Break(REPO_BUSY.into()));
+        }
+
+        // Swap and lock files mean an editor still has unsaved changes
+        if self.scheduled {
+            let files = busy::find(repo, &config.busy_markers)?;
+            if !files.is_empty() {
+                info!(repo = path, files = ?files, "editor busy markers present, deferring");
+                events::emit(events, "busy-markers-found", &BusyMarkers { repo: path.to_string(), files });
+                summary.skip(EDITOR_BUSY);
+                return Ok(ControlFlow::Break(EDITOR_BUSY.into()));
+            }
+        }
+
+        // Low battery, a metered network or none at all hold timed cycles back
+        let power_hold = if self.scheduled { power::check(path, config) } else { None };
+        if let Some(hold) = power_hold.as_ref().filter(|hold| hold.action == PowerAction::Defer) {
+            info!(repo = path, reason = %hold.reason, "cycle deferred by power or network conditions");
+            events::emit(events, "cycle-deferred", hold);
+            summary.skip(POWER_DEFERRED);
+            return Ok(ControlFlow::Break(POWER_DEFERRED.into()));
+        }
+        let hold_push = power_hold.filter(|hold| hold.action == PowerAction::Local);
+
+        // Submodules with changes of their own go first, so the pointers
+        // committed here already point at pushed commits
+        let submodule_policy = settings.submodule_policy;
+        if submodule_policy == SubmodulePolicy::Recurse {
+            let (ready, detached) = submodules::with_changes(repo);
+            for submodule in &detached {
+                warn!(repo = path, submodule = %submodule, "submodule on a detached HEAD, not committing in it");
+                let skipped = CycleSkipped { repo: submodule, reason: "Submodule is on a detached HEAD" };
+                events::emit(events, "cycle-skipped", &skipped);
+            }
+            for submodule in &ready {
+                run_submodule_cycle(submodule, config, events, self.cancel)
+                    .await
+                    .map_err(|error| format!("Submodule {} failed: {}", submodule, error))?;
+            }
+        }
+
+        // Dirty submodule content isn't something this repo can commit
+        let submodule_paths = submodules::paths(repo);
+        let statuses = repo.statuses(None).map_err(|e| e.to_string())?;
+        let changed = statuses
+            .iter()
+            .any(|entry| entry.path().is_none_or(|entry_path| !submodule_paths.iter().any(|sub| sub == entry_path)))
+            || (submodule_policy != SubmodulePolicy::Ignore && !submodules::moved(repo).is_empty());
+
+        if !changed {
+            // Commits an earlier cycle couldn't push still need to go out
+            if let Some(pending) = journal::pending_push(path) {
+                if !health::is_in_safe_mode(path) && hold_push.is_none() {
+                    if settings.ci_gate {
+                        if let Some(status) = failing_ci(path, config, settings).await {
+                            events::emit(events, "ci-red", &status);
+                            summary.skip(CI_FAILING);
+                            return Ok(ControlFlow::Break(CI_FAILING.into()));
+                        }
+                    }
+                    let _push_slot = throttle::PUSHES.acquire(config.max_concurrent_pushes).await;
+                    return push_pending(path, &pending, settings, &self.proxy, events, summary).map(ControlFlow::Break);
+                }
+            }
+            events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: NO_CHANGES });
+            summary.skip(NO_CHANGES);
+            return Ok(ControlFlow::Break(NO_CHANGES.into()));
+        }
+
+        // Reformatting noise waits until it comes with a real change; manual
+        // runs commit it anyway
+        if self.scheduled {
+            if let Some(trivial) = significance::check(repo, path, settings)? {
+                info!(
+                    repo = path,
+                    changed_lines = trivial.changed_lines,
+                    significant_lines = trivial.significant_lines,
+                    "only trivial changes, skipping"
+                );
+                events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: TRIVIAL_CHANGES });
+                summary.skip(TRIVIAL_CHANGES);
+                return Ok(ControlFlow::Break(TRIVIAL_CHANGES.into()));
+            }
+        }
+        // Timed cycles in snapshot mode leave the branch and the index alone,
+        // and a rule-based message is enough for them
+        if self.scheduled && settings.snapshot_mode {
+            let started = Instant::now();
+            let message = rules::generate_message(&pending_diff(path)?);
+            let snapshot = snapshot::take(repo, &message, settings)?;
+            summary.stage("snapshot", started);
+            return match snapshot {
+                Some(snapshot) => {
+                    info!(repo = path, sha = %snapshot.sha, "snapshot taken");
+                    events::emit(events, "snapshot-created", &snapshot);
+                    summary.message = Some(snapshot.message.clone());
+                    Ok(ControlFlow::Break(snapshot.message))
+                }
+                None => {
+                    events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: NO_CHANGES });
+                    summary.skip(NO_CHANGES);
+                    Ok(ControlFlow::Break(NO_CHANGES.into()))
+                }
+            };
+        }
+
+        if config.api_key().is_empty() {
+            return Err(MISSING_API_KEY.into());
+        }
+        // Without an identity the commit would fail after the model was paid for
+        identity::resolve(repo, settings)?;
+
+        // Timed cycles stop spending tokens once the month's budget is used up;
+        // manual runs still go through
+        if self.scheduled {
+            if let Some(used) = usage::budget_exceeded(config) {
+                info!(repo = path, used, budget = config.monthly_token_budget, "token budget used up, skipping");
+                events::emit(
+                    events,
+                    "token-budget-exceeded",
+                    &TokenBudgetExceeded { repo: path, used, budget: config.monthly_token_budget },
+                );
+                summary.skip(TOKEN_BUDGET_EXCEEDED);
+                return Ok(ControlFlow::Break(TOKEN_BUDGET_EXCEEDED.into()));
+            }
+        }
+
+        // An unusually large change set waits for the user instead of being
+        // committed and pushed
+        if !confirmed {
+            if let Some(exceeded) = guard::check(repo, path, config)? {
+                warn!(repo = path, files = exceeded.files, exceeded = ?exceeded.exceeded, "changes exceed the size guard");
+                events::emit(events, "threshold-exceeded", &exceeded);
+                summary.skip(THRESHOLD_EXCEEDED);
+                return Ok(ControlFlow::Break(THRESHOLD_EXCEEDED.into()));
+            }
+        }
+
+        // Only commit code that passes the repo's verification command
+        if !settings.pre_commit_command.trim().is_empty() {
+            let command = settings.pre_commit_command.trim();
+            let started = Instant::now();
+            let verification =
+                verify::run_verification(path, command, settings.pre_commit_timeout_secs).await?;
+            summary.stage("verify", started);
+            if let Some(failure) = verification {
+                warn!(repo = path, command, "verification command failed");
+                events::emit(events, "verification-failed", &failure);
+                return Err(format!("Verification command `{}` failed, skipping commit", command));
+            }
+        }
+
+        Ok(ControlFlow::Continue(Prepared { hold_push, submodule_policy, submodule_paths }))
+    }
+
+    // Stages the changes and takes out what must not be committed. Returns
+    // the moved submodule pointers to commit on their own.
+    async fn stage(&self, prepared: &Prepared, summary: &mut CycleSummary) -> Result<ControlFlow<String, GitlinkCommit>, String> {
+        let (path, config, events, repo, settings) = (self.path, self.config, self.events, &self.repo, &self.settings);
+
+        // Git runs as blocking commands that dropping this future can't
+        // interrupt, so cancellation is also checked before each git step
+        if self.cancel.is_cancelled() {
+            return Ok(ControlFlow::Break(CANCELLED.into()));
+        }
+
+        // Daily-branch repos commit on today's branch, started here at the
+        // day's first commit
+        if settings.daily_branches {
+            if let Some(started) = daily::switch_to_today(repo, path)? {
+                info!(repo = path, branch = %started.branch, base = %started.base, "started the day branch");
+                events::emit(events, "day-branch-started", &started);
+            }
+        }
+
+        // Stage all changes
+        journal::begin(path, repo)?;
+        let started = Instant::now();
+        Command::new("git")
+            .arg("add")
+            .arg(".")
+            .current_dir(path)
+            .status()
+            .map_err(|e| e.to_string())?;
+        summary.stage("stage", started);
+
+        // Submodule pointers never go into a generated commit: they stay out, or
+        // get a commit of their own with a fixed message
+        let staged_gitlinks: Vec<String> = split::staged_paths(repo)?
+            .into_iter()
+            .filter(|staged| prepared.submodule_paths.contains(staged))
+            .collect();
+        if !staged_gitlinks.is_empty() {
+            split::unstage(repo, &staged_gitlinks)?;
+        }
+        let moved = submodules::moved(repo);
+        let gitlink_commit = match prepared.submodule_policy {
+            SubmodulePolicy::Ignore => None,
+            _ if moved.is_empty() => None,
+            _ => Some((
+                moved.iter().map(|change| change.path.clone()).collect::<Vec<_>>(),
+                submodules::message(&moved),
+            )),
+        };
+
+        // Merge leftovers and editor files stay out even where .gitignore
+        // doesn't cover them
+        let blocked = artifacts::staged_blocked(repo, &settings.staging_blocklist)?;
+        if !blocked.is_empty() {
+            markers::unstage(repo, &blocked)?;
+            info!(repo = path, files = ?blocked, "blocklisted files left out of the commit");
+            events::emit(events, "artifacts-excluded", &ArtifactsExcluded { repo: path.to_string(), files: blocked.clone() });
+        }
+        // Advisory only, like the attribute suggestions below
+        let mut cycle_files = split::staged_paths(repo).unwrap_or_default();
+        cycle_files.extend(blocked);
+        let after_cycles = config.gitignore_suggest_after_cycles;
+        if let Ok(Some(mut suggestion)) = gitignore::record_cycle(path, &cycle_files, &settings.staging_blocklist, after_cycles) {
+            if config.gitignore_ai_suggestions {
+                suggestion = gitignore::refine(config, path, suggestion).await;
+            }
+            info!(repo = path, rules = ?suggestion.rules, "files keep showing up that could be ignored");
+            events::emit(events, "gitignore-suggested", &suggestion);
+        }
+
+        // Unresolved conflicts must never be committed verbatim
+        let conflicted = markers::staged_with_markers(repo)?;
+        if !conflicted.is_empty() {
+            markers::unstage(repo, &conflicted)?;
+            let stashed = config.stash_conflicted_files
+                && match markers::stash(path, &conflicted) {
+                    Ok(()) => true,
+                    Err(error) => {
+                        warn!(repo = path, error = %error, "failed to stash files with conflict markers");
+                        false
+                    }
+                };
+            warn!(repo = path, files = ?conflicted, stashed, "files with conflict markers left out of the commit");
+            events::emit(events, "conflict-markers-found", &markers::ConflictMarkers {
+                repo: path.to_string(),
+                files: conflicted,
+                stashed,
+            });
+
+            if gitlink_commit.is_none() && split::nothing_staged(repo)? {
+                events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: ONLY_CONFLICTED });
+                summary.skip(ONLY_CONFLICTED);
+                return Ok(ControlFlow::Break(ONLY_CONFLICTED.into()));
+            }
+        }
+        if gitlink_commit.is_none() && split::nothing_staged(repo)? {
+            events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: NO_CHANGES });
+            summary.skip(NO_CHANGES);
+            return Ok(ControlFlow::Break(NO_CHANGES.into()));
+        }
+
+        // Run pre-commit before generating so a failing hook doesn't spend API quota
+        if config.run_git_hooks {
+            let started = Instant::now();
+            let failure = hooks::run_pre_commit(repo, path)?;
+            summary.stage("pre_commit_hook", started);
+            if let Some(failure) = failure {
+                events::emit(events, "hook-failed", &failure);
+                return Err(format!("{} hook failed", failure.hook));
+            }
+        }
+
+        // Advisory only: noise analysis must never block the commit
+        if let Ok(suggestions) = gitattributes::record_staged_noise(repo, path) {
+            if !suggestions.is_empty() {
+                let advice = AttributeAdvice { repo: path, suggestions };
+                events::emit(events, "gitattributes-suggested", &advice);
+            }
+        }
+
+        Ok(ControlFlow::Continue(gitlink_commit))
+    }
+
+    // Commits what is staged: the docs changes to `docs_branch`, then the
+    // rest in one commit or several batches
+    async fn commit(
+        &self,
+        prepared: &Prepared,
+        gitlink_commit: GitlinkCommit,
+        summary: &mut CycleSummary,
+    ) -> Result<ControlFlow<String, Commits>, String> {
+        let (path, config, events, repo, settings) = (self.path, self.config, self.events, &self.repo, &self.settings);
+
+        // Split-branch repos send their docs changes to `docs_branch` first
+        let mut docs_message = None;
+        let on_docs_branch = github::current_branch(repo)
+            .map(|branch| branch == settings.docs_branch)
+            .unwrap_or(false);
+        if !settings.docs_branch.is_empty() && !on_docs_branch {
+            docs_message = self.commit_docs(summary).await?;
+            if gitlink_commit.is_none() && split::nothing_staged(repo)? {
+                return match docs_message {
+                    Some(message) => self.publish_docs(prepared, message, summary).await.map(ControlFlow::Break),
+                    None => {
+                        events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason: NO_CHANGES });
+                        summary.skip(NO_CHANGES);
+                        Ok(ControlFlow::Break(NO_CHANGES.into()))
+                    }
+                };
+            }
+        }
+
+        // Past `max_files_per_commit`, the staged changes are committed in
+        // batches of nearby files, each with its own message. In monorepo mode
+        // every package gets its own commits, dependencies first.
+        let staged = split::staged_paths(repo)?;
+        let files = staged.len() + gitlink_commit.as_ref().map_or(0, |(gitlinks, _)| gitlinks.len());
+        let mut batches = if staged.is_empty() { Vec::new() } else { vec![Vec::new()] };
+        if repo.head().is_ok() && !staged.is_empty() {
+            if config.monorepo_commits {
+                let plan = monorepo::plan(path, config, staged);
+                if plan.len() > 1 {
+                    info!(repo = path, packages = plan.len(), "committing package by package");
+                    events::emit(events, "commit-plan", &CommitPlan { repo: path, commits: &plan });
+                }
+                batches = plan
+                    .into_iter()
+                    .flat_map(|commit| batch::plan(commit.files, config.max_files_per_commit))
+                    .collect();
+            } else if config.max_files_per_commit > 0 {
+                batches = batch::plan(staged, config.max_files_per_commit);
+            }
+            if batches.len() > 1 {
+                info!(repo = path, batches = batches.len(), "splitting the cycle into batched commits");
+                split::unstage(repo, &batches[1..].concat())?;
+            }
+        }
+
+        // Moved submodule pointers come last, once the code using them is in
+        let mut presets = vec![None; batches.len()];
+        if let Some((gitlinks, message)) = gitlink_commit {
+            batches.push(gitlinks);
+            presets.push(Some(message));
+        }
+
+        // A single commit may fold into the app's previous, unpushed one
+        let amend = match batches.len() {
+            1 if presets[0].is_none() => amend::amend_candidate(repo, path, config.amend_window_minutes),
+            _ => None,
+        };
+
+        let mut messages = Vec::new();
+        for (number, (paths, preset)) in batches.iter().zip(&presets).enumerate() {
+            if number > 0 || preset.is_some() {
+                stage_paths(path, paths)?;
+            }
+            let preset = preset.as_deref();
+            let staged = commit_staged(path, repo, config, settings, amend, preset, self.scheduled, events, self.cancel, summary).await?;
+            match staged {
+                Staged::Committed(message) => messages.push(message),
+                Staged::Cancelled => return Ok(ControlFlow::Break(CANCELLED.into())),
+                // Batches committed before it are still pushed
+                Staged::Waiting(_) if !messages.is_empty() => break,
+                Staged::Waiting(reason) => {
+                    events::emit(events, "cycle-skipped", &CycleSkipped { repo: path, reason });
+                    summary.skip(reason);
+                    return Ok(ControlFlow::Break(reason.into()));
+                }
+            }
+        }
+        let message = messages.join("\n\n");
+        summary.message = Some(message.clone());
+        Ok(ControlFlow::Continue(Commits { message, files, docs_message }))
+    }
+
+    // Commits the staged changes under `docs_paths` straight to
+    // `docs_branch` and unstages them. Returns the message, if there were any.
+    async fn commit_docs(&self, summary: &mut CycleSummary) -> Result<Option<String>, String> {
+        let (path, config, events, repo, settings) = (self.path, self.config, self.events, &self.repo, &self.settings);
+        let docs_paths = split::staged_paths_under(repo, &settings.docs_paths)?;
+        if docs_paths.is_empty() {
+            return Ok(None);
+        }
+
+        let started = Instant::now();
+        let docs_diff = collect_diff(path, &["--cached"], &docs_paths)?;
+        let generated = generate_for_cycle(path, config, &docs_diff, events).await?;
+        let issue = issues::detect(repo, settings)?;
+        let docs_message = match &issue {
+            Some(issue) => issues::apply(&generated.message, issue, settings),
+            None => generated.message,
+        };
+        let docs_message = trailers::apply(repo, &docs_message, settings)?;
+        issues::check(&docs_message, issue.as_deref(), settings)?;
+        AllowList::for_repo(path, settings).check(&docs_message)?;
+        let sha = split::commit_to_branch(
+            repo,
+            &settings.docs_branch,
+            &docs_paths,
+            &docs_message,
+            &identity::resolve(repo, settings)?,
+        )?;
+        split::unstage(repo, &docs_paths)?;
+        summary.stage("split_commit", started);
+
+        let sha = match sha {
+            Some(sha) => sha,
+            None => return Ok(None),
+        };
+        journal::docs_committed(path, &settings.docs_branch)?;
+        events::emit(events, "commit-created", &CommitCreated {
+            repo: path,
+            sha: sha.to_string(),
+            message: &docs_message,
+            branch: Some(&settings.docs_branch),
+            amended: false,
+        });
+        events::cycle(events, path, CycleEvent::CommitCreated { sha: sha.to_string(), message: docs_message.clone() });
+        Ok(Some(docs_message))
+    }
+
+    // Pushes a cycle whose only commit went to `docs_branch`
+    async fn publish_docs(&self, prepared: &Prepared, message: String, summary: &mut CycleSummary) -> Result<String, String> {
+        let (path, config, events, settings) = (self.path, self.config, self.events, &self.settings);
+        summary.message = Some(message.clone());
+
+        if health::is_in_safe_mode(path) {
+            summary.decision = CycleDecision::CommittedLocally;
+            return Ok(message);
+        }
+        if let Some(hold) = &prepared.hold_push {
+            journal::defer_push(path, &message, Some(&settings.docs_branch))?;
+            events::emit(events, "push-held", hold);
+            summary.decision = CycleDecision::CommittedLocally;
+            summary.reason = Some(hold.reason.clone());
+            return Ok(message);
+        }
+        journal::advance(path, CycleStep::Pushing)?;
+        let _push_slot = throttle::PUSHES.acquire(config.max_concurrent_pushes).await;
+        let started = Instant::now();
+        let pushed = push(path, Some(&settings.docs_branch), settings, &self.proxy, events);
+        summary.stage("push", started);
+        pushed
+            .map(|_| message.clone())
+            .map_err(|error| format!("Committed \"{}\" but push failed: {}", message, error))
+    }
+
+    // Pushes the cycle's commits, unless safe mode, power conditions or a
+    // failing CI hold them back, then opens or updates the pull request
+    async fn publish(&self, prepared: &Prepared, commits: Commits, summary: &mut CycleSummary) -> Result<String, String> {
+        let (path, config, events, repo, settings, proxy) =
+            (self.path, self.config, self.events, &self.repo, &self.settings, &self.proxy);
+        let Commits { message: clean_message, files, docs_message } = commits;
+        journal::advance(path, CycleStep::Pushing)?;
+
+        if health::is_in_safe_mode(path) {
+            let skipped = CycleSkipped {
+                repo: path,
+                reason: "Safe mode: push disabled until acknowledged",
+            };
+            events::emit(events, "push-skipped", &skipped);
+            summary.decision = CycleDecision::CommittedLocally;
+            summary.reason = Some(skipped.reason.to_string());
+            let branch = github::current_branch(repo).ok();
+            webhook::deliver(&config.webhook_urls, proxy, WebhookPayload::commit(path, branch, &clean_message, files, false));
+            return Ok(clean_message);
+        }
+
+        // The commits wait for a cycle under better conditions
+        if let Some(hold) = &prepared.hold_push {
+            let docs_branch = docs_message.as_ref().map(|_| settings.docs_branch.as_str());
+            journal::defer_push(path, &clean_message, docs_branch)?;
+            info!(repo = path, reason = %hold.reason, "push held by power or network conditions");
+            events::emit(events, "push-held", hold);
+            summary.decision = CycleDecision::CommittedLocally;
+            summary.reason = Some(hold.reason.clone());
+            let branch = github::current_branch(repo).ok();
+            webhook::deliver(&config.webhook_urls, proxy, WebhookPayload::commit(path, branch, &clean_message, files, false));
+            return Ok(clean_message);
+        }
+
+        // After failed cycles, make sure the remote is usable before pushing so
+        // the error says why it isn't
+        if health::repo_health(path).consecutive_failures > 0 {
+            let started = Instant::now();
+            let probe = remote::probe(path, &settings.push_remote, proxy)?;
+            summary.stage("remote_probe", started);
+            if let Some(failure) = probe.failure {
+                events::emit(events, "remote-probe-failed", &probe);
+                return Err(format!(
+                    "Committed \"{}\" but {}: {}",
+                    clean_message,
+                    failure.describe(),
+                    probe.detail
+                ));
+            }
+        }
+
+        // Pushing on top of a broken build only adds to it; the commits wait
+        // for a later cycle instead
+        if settings.ci_gate {
+            let started = Instant::now();
+            let failing = failing_ci(path, config, settings).await;
+            summary.stage("ci_gate", started);
+            if let Some(status) = failing {
+                let docs_branch = docs_message.as_ref().map(|_| settings.docs_branch.as_str());
+                journal::defer_push(path, &clean_message, docs_branch)?;
+                info!(repo = path, branch = %status.branch, failing = ?status.build.failing, "CI is failing, push deferred");
+                events::emit(events, "ci-red", &status);
+                summary.decision = CycleDecision::CommittedLocally;
+                summary.reason = Some(CI_FAILING.to_string());
+                let branch = github::current_branch(repo).ok();
+                webhook::deliver(&config.webhook_urls, proxy, WebhookPayload::commit(path, branch, &clean_message, files, false));
+                return Ok(clean_message);
+            }
+        }
+
+        // Push changes, including a docs branch an earlier cycle failed to push
+        let docs_branch = match docs_message {
+            Some(_) => Some(settings.docs_branch.clone()),
+            None => journal::pending_push(path).and_then(|pending| pending.docs_branch),
+        };
+        let _push_slot = throttle::PUSHES.acquire(config.max_concurrent_pushes).await;
+        let started = Instant::now();
+        let mut pushed = push(path, None, settings, proxy, events);
+        if let Some(docs_branch) = docs_branch.as_deref().filter(|_| pushed.is_ok()) {
+            pushed = push(path, Some(docs_branch), settings, proxy, events);
+        }
+        summary.stage("push", started);
+
+        if let Err(error) = pushed {
+            return Err(format!("Committed \"{}\" but push failed: {}", clean_message, error));
+        }
+        journal::clear_pending_push(path)?;
+        let branch = github::current_branch(repo).ok();
+        webhook::deliver(&config.webhook_urls, proxy, WebhookPayload::commit(path, branch, &clean_message, files, true));
+
+        let on_base = github::current_branch(repo)
+            .map(|branch| branch == settings.pull_request_base)
+            .unwrap_or(true);
+        if settings.auto_pull_request && !on_base {
+            let started = Instant::now();
+            let pull = forge::open_or_update_pull_request(
+                path,
+                config,
+                &settings.pull_request_base,
+                None,
+                None,
+                settings.generate_pull_request_description,
+            )
+            .await;
+            summary.stage("pull_request", started);
+            match pull {
+                Ok(pull) => events::emit(events, "pull-request-updated", &pull),
+                // The commit and push succeeded, so a PR failure doesn't fail the cycle
+                Err(error) => {
+                    let failure = PushResult { repo: path, error: Some(error), failure: None };
+                    events::emit(events, "pull-request-error", &failure);
+                }
+            }
+        }
+
+        Ok(clean_message)
+    }
+}
+
+// Pushes commits recorded by a cycle whose push failed or never ran
+fn push_pending(
+    path: &str,
+    pending: &PendingPush,
+    settings: &RepoSettings,
+    proxy: &ProxySettings,
+    events: &dyn EventSink,
+    summary: &mut CycleSummary,
+) -> Result<String, String> {
+    let started = Instant::now();
+    let mut pushed = push(path, None, settings, proxy, events);
+    if let Some(docs_branch) = pending.docs_branch.as_deref().filter(|_| pushed.is_ok()) {
+        pushed = push(path, Some(docs_branch), settings, proxy, events);
+    }
+    summary.stage("push", started);
+    pushed.map_err(|error| format!("Committed \"{}\" but push failed: {}", pending.message, error))?;
+
+    journal::clear_pending_push(path)?;
+    info!(repo = path, since = pending.since, "pushed commits left by an earlier cycle");
+    summary.message = Some(pending.message.clone());
+    Ok(pending.message.clone())
+}
+
+// The target branch's CI status when it is failing. A status that can't be
+// read doesn't hold the push back.
+async fn failing_ci(path: &str, config: &AppConfig, settings: &RepoSettings) -> Option<CiStatus> {
+    match ci::branch_status(path, config, settings).await {
+        Ok(status) if status.build.state == CiState::Failing => Some(status),
+        Ok(_) => None,
+        Err(error) => {
+            warn!(repo = path, error = %error, "failed to read the CI status, pushing anyway");
+            None
+        }
+    }
+}
+
+fn stage_paths(path: &str, paths: &[String]) -> Result<(), String> {
+    let output = Command::new("git")
+        .arg("add")
+        .arg("-A")
+        .arg("--")
+        .args(paths)
+        .current_dir(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!("git add failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+// What `commit_staged` did with the staged changes
+enum Staged {
+    Committed(String),
+    Cancelled,
+    // Held in the approval queue, with the reason to skip
+    Waiting(&'static str),
+}
+
+// Generates a message for what is staged and commits it, or with `amend`
+// folds it into that commit with a message for the combined changes. With
+// `commit_approval`, timed cycles queue the message instead, and an
+// approved one is committed as it is.
+#[allow(clippy::too_many_arguments)]
+async fn commit_staged(
+    path: &str,
+    repo: &Repository,
+    config: &AppConfig,
+    settings: &RepoSettings,
+    amend: Option<Oid>,
+    preset: Option<&str>,
+    scheduled: bool,
+    events: &dyn EventSink,
+    cancel: &CancellationToken,
+    summary: &mut CycleSummary,
+) -> Result<Staged, String> {
+    journal::advance(path, CycleStep::Generating)?;
+    // Fixed messages, such as for submodule pointers, skip the model
+    let (message, tier) = match preset {
+        Some(preset) => (preset.to_string(), None),
+        None => {
+            let started = Instant::now();
+            let diff_text = match amend {
+                Some(_) => collect_diff(path, &["--cached", "HEAD~1"], &[])?,
+                None => staged_diff(path)?,
+            };
+            // The queue only holds the changes it was asked about; manual
+            // runs commit them whatever was decided
+            let decision = config
+                .commit_approval
+                .then(|| approvals::decision(path, &message_cache::diff_hash(&diff_text)))
+                .flatten();
+            match decision {
+                Some(approved) if approved.state == ApprovalState::Approved => {
+                    info!(repo = path, id = %approved.id, "committing the approved message");
+                    (approved.message, None)
+                }
+                Some(waiting) if scheduled && waiting.state == ApprovalState::Pending => {
+                    return Ok(Staged::Waiting(AWAITING_APPROVAL));
+                }
+                Some(_) if scheduled => return Ok(Staged::Waiting(APPROVAL_REJECTED)),
+                _ => {
+                    events::cycle(events, path, CycleEvent::DiffCollected {
+                        files: diff_text.lines().filter(|line| line.starts_with("diff --git ")).count(),
+                        bytes: diff_text.len(),
+                    });
+                    let generated = generate_for_cycle(path, config, &diff_text, events).await?;
+                    summary.stage("generate", started);
+                    summary.tokens = generated.tokens;
+                    summary.generation_tier = Some(generated.tier);
+                    info!(repo = path, tier = ?generated.tier, elapsed_ms = generated.elapsed_ms as u64, "message generated");
+                    events::emit(events, "message-generated", &MessageGenerated {
+                        repo: path,
+                        tier: generated.tier,
+                        elapsed_ms: generated.elapsed_ms,
+                    });
+                    events::cycle(events, path, CycleEvent::MessageGenerated { message: generated.message.clone() });
+
+                    if config.commit_approval && scheduled {
+                        let files = split::staged_paths(repo).unwrap_or_default();
+                        let requested = approvals::request(path, &generated.message, &diff_text, files)?;
+                        info!(repo = path, id = %requested.id, "commit queued for approval");
+                        events::emit(events, "approval-requested", &requested);
+                        return Ok(Staged::Waiting(AWAITING_APPROVAL));
+                    }
+                    (generated.message, Some(generated.tier))
+                }
+            }
+        }
+    };
+    // The issue reference and trailers go on before the commit-msg hook so it
+    // validates the final message
+    let issue = issues::detect(repo, settings)?;
+    let message = match &issue {
+        Some(issue) => issues::apply(&message, issue, settings),
+        None => message,
+    };
+    let mut clean_message = trailers::apply(repo, &message, settings)?;
+
+    if config.run_git_hooks {
+        let started = Instant::now();
+        let outcome = hooks::run_commit_msg(repo, path, &clean_message)?;
+        summary.stage("commit_msg_hook", started);
+        match outcome {
+            Ok(message) => clean_message = message,
+            Err(failure) => {
+                events::emit(events, "hook-failed", &failure);
+                return Err(format!("{} hook failed", failure.hook));
+            }
+        }
+    }
+    issues::check(&clean_message, issue.as_deref(), settings)?;
+    // Fixed messages are the app's own and exempt
+    if preset.is_none() {
+        AllowList::for_repo(path, settings).check(&clean_message)?;
+    }
+
+    if cancel.is_cancelled() {
+        return Ok(Staged::Cancelled);
+    }
+
+    // Commit with generated message. Hooks already ran above (or are disabled),
+    // so git must not run them a second time.
+    journal::advance(path, CycleStep::Committing)?;
+    let started = Instant::now();
+    let mut command = Command::new("git");
+    command.arg("commit").arg("--no-verify");
+    identity::apply_to_command(&mut command, &identity::resolve(repo, settings)?);
+    if amend.is_some() {
+        command.arg("--amend");
+    }
+    let output = command
+        .arg("-m")
+        .arg(&clean_message)
+        .current_dir(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+    summary.stage("commit", started);
+    if !output.status.success() {
+        return Err(format!("git commit failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    // Record the commit before pushing; a history failure must not block the push
+    if let Some(amended) = amend {
+        info!(repo = path, amended = %amended, "amended the previous commit");
+        history::forget_commit(path, &amended.to_string()).ok();
+    }
+    history::record_head_commit(path, tier).ok();
+    if config.commit_approval {
+        approvals::finish(path).ok();
+    }
+
+    let sha = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+    events::emit(events, "commit-created", &CommitCreated {
+        repo: path,
+        sha: sha.clone(),
+        message: &clean_message,
+        branch: None,
+        amended: amend.is_some(),
+    });
+    events::cycle(events, path, CycleEvent::CommitCreated { sha, message: clean_message.clone() });
+
+    Ok(Staged::Committed(clean_message))
+}
+
+// A cycle of its own for a submodule of the repo being committed; boxed
+// because it runs inside the superproject's cycle
+fn run_submodule_cycle<'a>(
+    path: &'a str,
+    config: &'a AppConfig,
+    events: &'a dyn EventSink,
+    cancel: &'a CancellationToken,
+) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+    Box::pin(run_cycle(path, config, events, cancel, None, false))
+}
+
+// Reports generation failures separately so they can be told apart from git
+// errors. A diff a recent cycle already paid for gets the same message again.
+async fn generate_for_cycle(
+    path: &str,
+    config: &AppConfig,
+    diff_text: &str,
+    events: &dyn EventSink,
+) -> Result<GeneratedMessage, String> {
+    let ttl = config.message_cache_ttl_minutes;
+    if ttl > 0 {
+        if let Some(cached) = message_cache::lookup(path, diff_text, ttl) {
+            info!(repo = path, diff_hash = %cached.diff_hash, "reusing the cached message for an unchanged diff");
+            return Ok(GeneratedMessage {
+                message: cached.message,
+                tier: cached.tier,
+                elapsed_ms: 0,
+                tokens: None,
+                candidates: Vec::new(),
+            });
+        }
+    }
+
+    let generated = generate_message(path, config, diff_text).await;
+    match &generated {
+        Ok(generated) if ttl > 0 && is_model_tier(generated.tier) => {
+            // The cache only saves money; failing to write it costs nothing else
+            if let Err(error) = message_cache::store(path, diff_text, &generated.message, generated.tier, ttl) {
+                warn!(repo = path, error = %error, "could not cache the generated message");
+            }
+        }
+        Ok(_) => {}
+        Err(error) => {
+            warn!(repo = path, error = %error, "message generation failed");
+            let failure = PushResult { repo: path, error: Some(error.clone()), failure: None };
+            events::emit(events, "generation-failed", &failure);
+        }
+    }
+    generated
+}
+
+// Template and rule-based messages cost nothing, and caching one would keep
+// the model's message away once it is reachable again
+fn is_model_tier(tier: GenerationTier) -> bool {
+    !matches!(tier, GenerationTier::Template | GenerationTier::RuleBased)
+}
+
+/// Asks Gemini for a commit message describing `diff_text`, without touching
+/// the repo. The primary model is tried first, then each step of the repo's
+/// `fallback_chain` in order, so a degraded or unreachable model never stalls
+/// the cycle. Each model gets `generation_timeout_secs`; Gemini also moves on
+/// when it can't be reached or is out of quota, while a rejected request
+/// surfaces. Diffs that only touch Markdown or text files go to
+/// `text_only_model` instead of the primary model when one is configured.
+/// With `message_candidates` above 1 Gemini answers with that many
+/// candidates, and the best scoring one is returned.
+pub async fn generate_message(
+    repo_id: &str,
+    config: &AppConfig,
+    diff_text: &str,
+) -> Result<GeneratedMessage, String> {
+    if config.api_key().is_empty() {
+        return Err(MISSING_API_KEY.into());
+    }
+
+    let started = Instant::now();
+    let text_only = !config.text_only_model.is_empty() && rules::is_text_only(diff_text);
+    if text_only && config.text_only_model == RULE_BASED_GENERATOR {
+        return Ok(deterministic(repo_id, config, GenerationTier::RuleBased, diff_text, started));
+    }
+
+    // Shared by every repository, so parallel cycles don't hit the rate limit
+    let _api_slot = throttle::API_CALLS.acquire(config.max_concurrent_api_calls).await;
+    let (anonymizer, model_diff) = diff_for_model(config, diff_text);
+
+    let budget = Duration::from_secs(config.generation_timeout_secs.max(1));
+    let system_instruction = system_instruction(repo_id, config, diff_text).await;
+    let system_context = system_instruction.as_str();
+    let primary = ModelOptions::from_config(config)
+        .for_repo(repo_id)
+        .with_candidates(config.message_candidates);
+
+    let mut tiers = vec![if text_only { GenerationTier::TextOnly } else { GenerationTier::Primary }];
+    tiers.extend(config.fallback_chain_for(repo_id).into_iter().map(FallbackStep::tier));
+
+    let mut last_miss = None;
+    for tier in tiers {
+        let answer = match tier {
+            GenerationTier::Primary | GenerationTier::TextOnly | GenerationTier::Fallback => {
+                let model = match tier {
+                    GenerationTier::TextOnly => &config.text_only_model,
+                    GenerationTier::Fallback => &config.fallback_model,
+                    _ => &config.model,
+                };
+                if model.is_empty() {
+                    continue;
+                }
+                let options = primary.with_model(model);
+                ask_gemini(config, &options, system_context, &model_diff, anonymizer.as_ref(), budget).await?
+            }
+            GenerationTier::Local => {
+                if !local_model::is_configured(config) {
+                    continue;
+                }
+                ask_local_model(config, system_context, &model_diff, anonymizer.as_ref(), budget).await
+            }
+            GenerationTier::Template | GenerationTier::RuleBased => {
+                return Ok(deterministic(repo_id, config, tier, diff_text, started));
+            }
+        };
+
+        match answer {
+            Answer::Message(texts, tokens) => {
+                let finished = texts
+                    .iter()
+                    .map(|text| finish_message(repo_id, config, text, diff_text))
+                    .collect();
+                let mut candidates = ranking::rank(finished, diff_text, config);
+                // The best candidate the repository's commit rules allow goes first
+                let allow = AllowList::for_repo(repo_id, &config.repo_settings(repo_id));
+                if let Some(allowed) = candidates.iter().position(|candidate| allow.problems(&candidate.message).is_empty()) {
+                    candidates[..=allowed].rotate_right(1);
+                }
+                let mut message = match candidates.first() {
+                    Some(best) => best.message.clone(),
+                    None => finish_message(repo_id, config, texts.first().map_or("", String::as_str), diff_text),
+                };
+                let problems = allow.problems(&message);
+                if !problems.is_empty() && config.reask_invalid_messages {
+                    info!(repo = repo_id, problems = ?problems, "asking again for a message the commit rules allow");
+                    match regenerate_message(repo_id, config, diff_text, &message, &problems.join("; ")).await {
+                        Ok(retried) if allow.problems(&retried).len() < problems.len() => message = retried,
+                        Ok(_) => {}
+                        Err(error) => warn!(repo = repo_id, error = %error, "could not ask again"),
+                    }
+                }
+                if candidates.len() > 1 {
+                    info!(repo = repo_id, candidates = candidates.len(), score = candidates[0].score, "picked the best candidate");
+                } else {
+                    candidates.clear();
+                }
+                return Ok(GeneratedMessage {
+                    message,
+                    tier,
+                    elapsed_ms: started.elapsed().as_millis(),
+                    tokens,
+                    candidates,
+                });
+            }
+            Answer::Missed(reason) => {
+                warn!(repo = repo_id, tier = ?tier, reason = %reason, "no message from this tier, trying the next one");
+                last_miss = Some(reason);
+            }
+        }
+    }
+
+    Err(last_miss.unwrap_or_else(|| "No generator in the fallback chain produced a message".into()))
+}
+
+// What one model made of the prompt
+enum Answer {
+    // One text per candidate, in the model's order
+    Message(Vec<String>, Option<TokenUsage>),
+    // Timed out or unreachable; the next tier gets a go
+    Missed(String),
+}
+
+// Asks a Gemini model, with one more try for a non-conforming answer
+async fn ask_gemini(
+    config: &AppConfig,
+    options: &ModelOptions,
+    system_context: &str,
+    model_diff: &str,
+    anonymizer: Option<&Anonymizer>,
+    budget: Duration,
+) -> Result<Answer, String> {
+    // Models can have different limits, so the diff is fitted per tier
+    let fitted = budget::fit_diff(config, options, system_context, PROMPT_PREFIX, model_diff).await;
+    let user_prompt = format!("{}{}", PROMPT_PREFIX, fitted);
+    let api_key = config.api_key();
+    let request = gemini::generate_with_usage(&api_key, options, system_context, &user_prompt);
+
+    let generation = match timeout(budget, request).await {
+        Ok(Ok(generation)) => generation,
+        Ok(Err(error)) if gemini::is_unavailable(&error) => return Ok(Answer::Missed(error)),
+        Ok(Err(error)) => return Err(error),
+        Err(_) => return Ok(Answer::Missed(format!("{} timed out", options.model))),
+    };
+    let mut texts: Vec<String> = std::iter::once(&generation.text)
+        .chain(&generation.alternatives)
+        .map(|text| restore(anonymizer, text))
+        .collect();
+    let mut tokens = generation.usage;
+
+    // One more try, with the problems spelled out, for answers that can't be
+    // fixed by rewriting them; one good candidate is enough
+    let problems = texts
+        .iter()
+        .map(|text| validate::check(&validate::repair(&clean_message(text)), config))
+        .min_by_key(Vec::len)
+        .unwrap_or_default();
+    if !problems.is_empty() && config.reask_invalid_messages {
+        info!(model = %options.model, problems = ?problems, "asking again for a non-conforming message");
+        let retry_prompt = format!(
+            "{}\n\nYour previous answer was:\n{}\n\nIt was rejected because:\n- {}\n\nAnswer again, following the rules.",
+            user_prompt,
+            generation.text,
+            problems.join("\n- ")
+        );
+        let single = options.with_candidates(1);
+        let retry = gemini::generate_with_usage(&api_key, &single, system_context, &retry_prompt);
+        if let Ok(Ok(retried)) = timeout(budget, retry).await {
+            let retried_text = restore(anonymizer, &retried.text);
+            let remaining = validate::check(&validate::repair(&clean_message(&retried_text)), config);
+            if remaining.len() < problems.len() {
+                texts = vec![retried_text];
+            }
+            tokens = add_usage(tokens, retried.usage);
+        }
+    }
+
+    Ok(Answer::Message(texts, tokens))
+}
+
+// Asks the model at `local_model_url`; any failure moves on, as it is only
+// a fallback
+async fn ask_local_model(
+    config: &AppConfig,
+    system_context: &str,
+    model_diff: &str,
+    anonymizer: Option<&Anonymizer>,
+    budget: Duration,
+) -> Answer {
+    let limit = budget::limit_for(config, &config.local_model);
+    let fitted = budget::fit(model_diff, limit.saturating_sub(budget::estimate_tokens(system_context)), 1.25);
+    let user_prompt = format!("{}{}", PROMPT_PREFIX, fitted);
+    match timeout(budget, local_model::generate(config, system_context, &user_prompt)).await {
+        Ok(Ok(text)) => Answer::Message(vec![restore(anonymizer, &text)], None),
+        Ok(Err(error)) => Answer::Missed(error),
+        Err(_) => Answer::Missed("local model timed out".into()),
+    }
+}
+
+// Messages that don't need a model: `fallback_template` or the rule-based one
+fn deterministic(
+    repo_id: &str,
+    config: &AppConfig,
+    tier: GenerationTier,
+    diff_text: &str,
+    started: Instant,
+) -> GeneratedMessage {
+    let message = if tier == GenerationTier::Template {
+        let files = rules::changed_paths(diff_text).len();
+        config.fallback_template.replace("{files}", &files.to_string())
+    } else {
+        finish_message(repo_id, config, &rules::generate_message(diff_text), diff_text)
+    };
+    GeneratedMessage {
+        message,
+        tier,
+        elapsed_ms: started.elapsed().as_millis(),
+        tokens: None,
+        candidates: Vec::new(),
+    }
+}
+
+fn add_usage(first: Option<TokenUsage>, second: Option<TokenUsage>) -> Option<TokenUsage> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(TokenUsage {
+            prompt_tokens: first.prompt_tokens + second.prompt_tokens,
+            output_tokens: first.output_tokens + second.output_tokens,
+            total_tokens: first.total_tokens + second.total_tokens,
+        }),
+        (first, second) => first.or(second),
+    }
+}
+
+/// Asks for a new message after the user rejected `previous`, passing the
+/// earlier exchange and the user's `hint` as conversation context.
+pub async fn regenerate_message(
+    repo_id: &str,
+    config: &AppConfig,
+    diff_text: &str,
+    previous: &str,
+    hint: &str,
+) -> Result<String, String> {
+    if config.api_key().is_empty() {
+        return Err(MISSING_API_KEY.into());
+    }
+
+    let (mut anonymizer, model_diff) = diff_for_model(config, diff_text);
+    let options = ModelOptions::from_config(config).for_repo(repo_id);
+    let system_instruction = system_instruction(repo_id, config, diff_text).await;
+    let fitted = budget::fit_diff(config, &options, &system_instruction, PROMPT_PREFIX, &model_diff).await;
+    let user_prompt = format!("{}{}", PROMPT_PREFIX, fitted);
+    let previous = match anonymizer.as_mut() {
+        Some(anonymizer) => anonymizer.anonymize_text(previous),
+        None => previous.to_string(),
+    };
+    let feedback = format!(
+        "I rejected that message. Write a new commit message following the same rules, taking this feedback into account: {}",
+        hint
+    );
+
+    let commit_message = gemini::generate_chat(
+        &config.api_key(),
+        &options,
+        &system_instruction,
+        &[
+            (Role::User, &user_prompt),
+            (Role::Model, &previous),
+            (Role::User, &feedback),
+        ],
+    )
+    .await?;
+    let commit_message = restore(anonymizer.as_ref(), &commit_message);
+    Ok(finish_message(repo_id, config, &commit_message, diff_text))
+}
+
+// The style's instruction, plus the types and scopes the repository allows,
+// the scopes a monorepo allows and the issue the work is for
+async fn system_instruction(repo_id: &str, config: &AppConfig, diff_text: &str) -> String {
+    let mut instruction = style::system_instruction(config.message_style, &config.commit_language);
+    if let Some(hint) = AllowList::for_repo(repo_id, &config.repo_settings(repo_id)).prompt_hint() {
+        instruction = format!("{}\n\n{}", instruction, hint);
+    }
+    if config.infer_scope {
+        let rules = ScopeRules::for_repo(repo_id, config);
+        if let Some(hint) = scope::prompt_hint(&rules::changed_paths(diff_text), &rules) {
+            instruction = format!("{}\n\n{}", instruction, hint);
+        }
+    }
+    if let Some(issue) = tracker::issue_for_repo(repo_id, config).await {
+        instruction = format!("{}\n\n{}", instruction, tracker::prompt_hint(&issue));
+    }
+    instruction
+}
+
+// The diff as the model may see it: anonymized when `anonymize_diff` is set,
+// together with the mapping needed to restore the answer
+fn diff_for_model(config: &AppConfig, diff_text: &str) -> (Option<Anonymizer>, String) {
+    if !config.anonymize_diff {
+        return (None, diff_text.to_string());
+    }
+
+    let mut anonymizer = Anonymizer::new();
+    let anonymized = anonymizer.anonymize_diff(diff_text);
+    (Some(anonymizer), anonymized)
+}
+
+fn restore(anonymizer: Option<&Anonymizer>, text: &str) -> String {
+    match anonymizer {
+        Some(anonymizer) => anonymizer.restore(text),
+        None => text.to_string(),
+    }
+}
+
+// Pushes the current branch, or `branch`, to the remote `push_target`
+// resolves and reports the outcome. Without `push_refspec` the current
+// branch goes to its upstream, or the branch of the same name.
+fn push(
+    path: &str,
+    branch: Option<&str>,
+    settings: &RepoSettings,
+    proxy: &ProxySettings,
+    events: &dyn EventSink,
+) -> Result<(), String> {
+    let mut command = Command::new("git");
+    command.arg("push").current_dir(path);
+    proxy::configure_git(&mut command, proxy);
+
+    // Resolved once, so the fetch, the refspec and the reports all use the
+    // same remote. Fetch first, so a remote that moved is noticed before
+    // anything is forced.
+    let target = push_target(path, branch, settings)?;
+    events::cycle(events, path, CycleEvent::PushStarted { remote: target.remote.clone() });
+    let mut lease = None;
+    match divergence::plan(path, &target, proxy)? {
+        Ok(PushPlan::UpToDate) => {
+            events::emit(events, "push-completed", &PushResult { repo: path, error: None, failure: None });
+            events::cycle(events, path, CycleEvent::PushCompleted);
+            return Ok(());
+        }
+        Ok(PushPlan::FastForward) => {}
+        Ok(PushPlan::ForceWithLease(expected)) => {
+            info!(repo = path, remote_ref = %target.remote_ref, expected = %expected, "replacing history rewritten by the app");
+            command.arg(format!("--force-with-lease={}:{}", target.remote_ref, expected));
+            lease = Some(expected);
+        }
+        Err(diverged) => {
+            warn!(repo = path, remote_ref = %diverged.remote_ref, ahead = diverged.ahead, behind = diverged.behind, "remote has diverged");
+            events::emit(events, "diverged", &diverged);
+            let reason = format!(
+                "{} on {} has {} commit(s) that are not local; pull or rebase before the next push",
+                diverged.remote_ref, diverged.remote, diverged.behind
+            );
+            events::cycle(events, path, CycleEvent::PushFailed { reason: reason.clone() });
+            return Err(reason);
+        }
+    }
+
+    let refspec = settings.push_refspec.trim();
+    match branch {
+        Some(branch) => {
+            command.arg(&target.remote).arg(branch);
+        }
+        None => {
+            if !refspec.is_empty() {
+                command.arg(&target.remote).arg(refspec);
+            } else {
+                command.arg(&target.remote).arg(format!("HEAD:{}", target.remote_ref));
+            }
+        }
+    }
+
+    let output = command.output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let failure = remote::classify_failure(&error);
+        warn!(repo = path, remote = %target.remote, failure = ?failure, error = %error, "push failed");
+        events::emit(events, "push-failed", &PushResult {
+            repo: path,
+            error: Some(error.clone()),
+            failure: Some(failure),
+        });
+        let reason = format!("{}: {}", failure.describe(), error);
+        events::cycle(events, path, CycleEvent::PushFailed { reason: reason.clone() });
+        return Err(reason);
+    }
+
+    if lease.is_some() {
+        if let Some(local) = Repository::open(path).ok().and_then(|repo| divergence::local_branch(&repo, &target)) {
+            divergence::forget_rewrites(path, &local).ok();
+        }
+    }
+    events::emit(events, "push-completed", &PushResult { repo: path, error: None, failure: None });
+    events::cycle(events, path, CycleEvent::PushCompleted);
+    Ok(())
+}
+
+// Where `push` sends `branch` (default: HEAD): `push_remote`, else the
+// remote of the branch's upstream, else origin; the refspec's destination,
+// else the branch's upstream, else the branch of the same name
+fn push_target(path: &str, branch: Option<&str>, settings: &RepoSettings) -> Result<PushTarget, String> {
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let git_config = repo.config().ok();
+    let configured = |key: String| git_config.as_ref().and_then(|config| config.get_string(&key).ok());
+
+    let (local, remote_ref) = match branch {
+        Some(branch) => (format!("refs/heads/{}", branch), format!("refs/heads/{}", branch)),
+        None => {
+            let current = github::current_branch(&repo)?;
+            let refspec = settings.push_refspec.trim();
+            let remote_ref = match refspec.split_once(':') {
+                Some((_, destination)) => destination.trim_start_matches('+').to_string(),
+                None if !refspec.is_empty() => refspec.trim_start_matches('+').to_string(),
+                None => configured(format!("branch.{}.merge", current))
+                    .unwrap_or_else(|| format!("refs/heads/{}", current)),
+            };
+            let local = match refspec.split_once(':') {
+                Some((source, _)) if !source.trim_start_matches('+').is_empty() => {
+                    source.trim_start_matches('+').to_string()
+                }
+                _ => "HEAD".to_string(),
+            };
+            (local, remote_ref)
+        }
+    };
+    let remote_ref = if remote_ref.starts_with("refs/") { remote_ref } else { format!("refs/heads/{}", remote_ref) };
+    let remote = match settings.push_remote.trim() {
+        "" => branch
+            .map(str::to_string)
+            .or_else(|| github::current_branch(&repo).ok())
+            .and_then(|branch| configured(format!("branch.{}.remote", branch)))
+            .unwrap_or_else(|| "origin".to_string()),
+        configured => configured.to_string(),
+    };
+    Ok(PushTarget { remote, local, remote_ref })
+}
+
+/// True for the results of cycles that didn't commit anything.
+pub fn is_skip(result: &str) -> bool {
+    result == NO_CHANGES
+        || result == REPO_BUSY
+        || result == CANCELLED
+        || result == ONLY_CONFLICTED
+        || result == TOKEN_BUDGET_EXCEEDED
+        || result == THRESHOLD_EXCEEDED
+        || result == CI_FAILING
+        || result == CYCLE_RUNNING
+        || result == TRIVIAL_CHANGES
+        || result == POWER_DEFERRED
+        || result == EDITOR_BUSY
+        || result == AWAITING_APPROVAL
+        || result == APPROVAL_REJECTED
+}
+
+// Cleans the raw model output, fixes its scope if configured, forces it
+// into the configured style and keeps the subject within the length limit
+fn finish_message(repo_id: &str, config: &AppConfig, raw: &str, diff_text: &str) -> String {
+    let mut message = validate::repair(&clean_message(raw));
+    if config.infer_scope {
+        let rules = ScopeRules::for_repo(repo_id, config);
+        message = scope::apply(&message, &rules::changed_paths(diff_text), &rules);
+    }
+
+    let mut template = config.custom_message_template.as_str();
+    let mut context = TemplateContext::default();
+    if config.message_style == MessageStyle::CustomTemplate {
+        // Checked when the config is saved; a hand-edited file may still be wrong
+        if let Err(error) = style::validate_template(template) {
+            warn!(error = %error, "invalid message template, using the default");
+            template = style::DEFAULT_TEMPLATE;
+        }
+        context = TemplateContext::for_repo(repo_id, &config.repo_settings(repo_id));
+    }
+    let message = style::conform(&message, config.message_style, template, &context);
+    validate::shorten_subject(&message)
+}
+
+/// Returns the change manifest and the staged diff stat, followed by the
+/// (truncated) staged diff.
+pub fn staged_diff(path: &str) -> Result<String, String> {
+    collect_diff(path, &["--cached"], &[])
+}
+
+/// Like `staged_diff`, but for everything tracked that differs from HEAD,
+/// staged or not. Used to preview changes without staging them.
+pub fn pending_diff(path: &str) -> Result<String, String> {
+    collect_diff(path, &["HEAD"], &[])
+}
+
+/// The changes between two commits, condensed like `staged_diff`.
+pub fn range_diff(path: &str, from: &str, to: &str) -> Result<String, String> {
+    collect_diff(path, &[from, to], &[])
+}
+
+// An empty `paths` covers the whole tree
+fn collect_diff(path: &str, against: &[&str], paths: &[String]) -> Result<String, String> {
+    // Get diff with context
+    let diff = Command::new("git")
+        .arg("diff")
+        .args(against)
+        .arg("--stat")
+        .arg("--")
+        .args(paths)
+        .current_dir(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    let diff_detailed = Command::new("git")
+        .arg("diff")
+        .args(against)
+        .arg("--")
+        .args(paths)
+        .current_dir(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    let diff_stat = String::from_utf8_lossy(&diff.stdout);
+    // Lockfiles, generated and binary files would crowd out the real changes
+    let mut diff_content = condense::condense(&String::from_utf8_lossy(&diff_detailed.stdout));
+
+    // Only a guard against runaway diffs; the prompt's token budget
+    // decides what the model actually sees
+    if diff_content.len() > MAX_DIFF_BYTES {
+        let mut end = MAX_DIFF_BYTES;
+        while !diff_content.is_char_boundary(end) {
+            end -= 1;
+        }
+        diff_content.truncate(end);
+    }
+    // What happened to each file goes first, so it survives the budget's cuts
+    match manifest::build(path, against, paths) {
+        Some(manifest) => Ok(format!("{}\n\n{}\n\n{}", manifest, diff_stat, diff_content)),
+        None => Ok(format!("{}\n\n{}", diff_stat, diff_content)),
+    }
+}
+
+// Clean the message (remove quotes if present)
+pub fn clean_message(message: &str) -> String {
+    message
+        .trim_matches('"')
+        .trim_matches('\'')
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NoopSink;
+    use crate::trial::Sandbox;
+    use git2::Signature;
+    use std::fs;
+    use std::path::Path;
+
+    // A clone of a bare remote with one pushed commit, tracking its branch
+    fn sandbox() -> (Sandbox, Repository) {
+        let sandbox = Sandbox::create(None).unwrap();
+        let repo = Repository::open(&sandbox.workdir).unwrap();
+        let mut git_config = repo.config().unwrap();
+        git_config.set_str("user.name", "Test").unwrap();
+        git_config.set_str("user.email", "test@example.com").unwrap();
+
+        fs::write(sandbox.workdir.join("README.md"), "# Test\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[]).unwrap();
+        let pushed = Command::new("git")
+            .args(["push", "--quiet", "-u", "origin", "HEAD"])
+            .current_dir(&sandbox.workdir)
+            .status()
+            .unwrap();
+        assert!(pushed.success());
+        (sandbox, repo)
+    }
+
+    #[tokio::test]
+    async fn commit_cycle_commits_and_pushes() {
+        let (sandbox, repo) = sandbox();
+        let path = sandbox.workdir_str();
+        fs::write(sandbox.workdir.join("NOTES.md"), "# Notes\n\nWhat to do next.\n").unwrap();
+
+        // Markdown alone goes to the rule-based generator, so no model is asked
+        let config = AppConfig {
+            gemini_api_key: "unused".into(),
+            text_only_model: RULE_BASED_GENERATOR.into(),
+            ..AppConfig::default()
+        };
+        let mut summary = CycleSummary::new(&path);
+        let message = commit_cycle(&path, &config, &NoopSink, &CancellationToken::new(), false, true, &mut summary)
+            .await
+            .unwrap();
+
+        let head = repo.head().unwrap();
+        let commit = head.peel_to_commit().unwrap();
+        assert_eq!(commit.message().map(str::trim), Some(message.as_str()));
+        assert_eq!(commit.parent_count(), 1);
+        assert!(commit.tree().unwrap().get_name("NOTES.md").is_some());
+        assert_eq!(sandbox.remote_head(head.shorthand().unwrap()), Some(commit.id().to_string()));
+
+        // Nothing left to commit or push
+        let mut summary = CycleSummary::new(&path);
+        let again = commit_cycle(&path, &config, &NoopSink, &CancellationToken::new(), false, true, &mut summary)
+            .await
+            .unwrap();
+        assert_eq!(again, NO_CHANGES);
+        sandbox.remove().unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+//! Portable configuration: the whole `AppConfig` (repositories, schedules,
+//! prompt and style settings) as one JSON file to carry to another machine.
+//! Secrets only travel when asked for, and the file can be encrypted with a
+//! passphrase (Argon2id key, AES-256-GCM).
+
+use crate::config::AppConfig;
+use crate::secrets;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const FORMAT: &str = "gitgenius-config";
+const ENCRYPTED_FORMAT: &str = "gitgenius-config-encrypted";
+const FORMAT_VERSION: u32 = 1;
+
+// Keychain entries exported alongside the config's own API key
+const KEYCHAIN_SECRETS: [&str; 6] = [
+    secrets::GITHUB_TOKEN,
+    secrets::BITBUCKET_TOKEN,
+    secrets::GITLAB_TOKEN,
+    secrets::PROXY_PASSWORD,
+    secrets::JIRA_TOKEN,
+    secrets::LINEAR_TOKEN,
+];
+
+#[derive(Serialize, Deserialize)]
+struct ExportedConfig {
+    format: String,
+    version: u32,
+    exported_at: String,
+    config: AppConfig,
+    // Keychain entries by name, only when secrets were included
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    secrets: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedExport {
+    format: String,
+    version: u32,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Just enough to tell the two kinds of file apart
+#[derive(Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+/// Result of reading an export.
+#[derive(Serialize, Clone)]
+pub struct ImportedConfig {
+    pub config: AppConfig,
+    // Keychain entries that were written on this machine
+    pub secrets_restored: Vec<String>,
+    // The file carried no Gemini API key, so this machine's was kept
+    pub kept_api_key: bool,
+}
+
+/// Serializes `config` for export. Without `include_secrets` the Gemini API
+/// key is blanked and keychain entries are left out; with a `passphrase`
+/// the result is encrypted.
+pub fn export_config(config: &AppConfig, include_secrets: bool, passphrase: Option<&str>) -> Result<String, String> {
+    let mut config = config.clone();
+    let mut exported_secrets = BTreeMap::new();
+    if include_secrets {
+        for name in KEYCHAIN_SECRETS {
+            if let Some(secret) = secrets::get_secret(name)? {
+                exported_secrets.insert(name.to_string(), secret);
+            }
+        }
+    } else {
+        config.gemini_api_key.clear();
+    }
+
+    let export = ExportedConfig {
+        format: FORMAT.into(),
+        version: FORMAT_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        config,
+        secrets: exported_secrets,
+    };
+    let plain = serde_json::to_string_pretty(&export).map_err(|e| format!("Failed to serialize config: {}", e))?;
+
+    let passphrase = match passphrase.filter(|passphrase| !passphrase.is_empty()) {
+        Some(passphrase) => passphrase,
+        None => return Ok(plain),
+    };
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derive_key(passphrase, &salt)?));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain.as_bytes())
+        .map_err(|_| "Failed to encrypt the export".to_string())?;
+
+    let encrypted = EncryptedExport {
+        format: ENCRYPTED_FORMAT.into(),
+        version: FORMAT_VERSION,
+        kdf: "argon2id".into(),
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&encrypted).map_err(|e| format!("Failed to serialize config: {}", e))
+}
+
+/// Reads an export made by `export_config`, decrypting it with
+/// `passphrase` when needed, and stores any secrets it carries in the
+/// keychain. An export without an API key keeps `current`'s.
+pub fn import_config(text: &str, passphrase: Option<&str>, current: &AppConfig) -> Result<ImportedConfig, String> {
+    let header: Header = serde_json::from_str(text).map_err(|_| "Not a GitGenius config export".to_string())?;
+    if header.version > FORMAT_VERSION {
+        return Err(format!(
+            "The export was made by a newer version (format {}); update the app first",
+            header.version
+        ));
+    }
+
+    let plain = match header.format.as_str() {
+        FORMAT => text.to_string(),
+        ENCRYPTED_FORMAT => {
+            let passphrase = passphrase
+                .filter(|passphrase| !passphrase.is_empty())
+                .ok_or("The export is encrypted; enter its passphrase")?;
+            decrypt(text, passphrase)?
+        }
+        _ => return Err("Not a GitGenius config export".into()),
+    };
+    let export: ExportedConfig =
+        serde_json::from_str(&plain).map_err(|e| format!("Failed to parse config export: {}", e))?;
+
+    let mut config = export.config;
+    let kept_api_key = config.gemini_api_key.is_empty();
+    if kept_api_key {
+        config.gemini_api_key = current.gemini_api_key.clone();
+    }
+
+    let mut secrets_restored = Vec::new();
+    for (name, secret) in &export.secrets {
+        // Only names this version knows; anything else would be a stray entry
+        if KEYCHAIN_SECRETS.contains(&name.as_str()) {
+            secrets::set_secret(name, secret)?;
+            secrets_restored.push(name.clone());
+        }
+    }
+
+    Ok(ImportedConfig { config, secrets_restored, kept_api_key })
+}
+
+fn decrypt(text: &str, passphrase: &str) -> Result<String, String> {
+    let encrypted: EncryptedExport =
+        serde_json::from_str(text).map_err(|e| format!("Failed to parse config export: {}", e))?;
+    if encrypted.kdf != "argon2id" {
+        return Err(format!("Unsupported key derivation: {}", encrypted.kdf));
+    }
+    let decode = |field: &str| {
+        BASE64
+            .decode(field)
+            .map_err(|_| "The encrypted export is damaged".to_string())
+    };
+    let salt = decode(&encrypted.salt)?;
+    let nonce = decode(&encrypted.nonce)?;
+    if nonce.len() != 12 {
+        return Err("The encrypted export is damaged".into());
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derive_key(passphrase, &salt)?));
+    let plain = cipher
+        .decrypt(Nonce::from_slice(&nonce), decode(&encrypted.ciphertext)?.as_ref())
+        .map_err(|_| "Wrong passphrase, or the export is damaged".to_string())?;
+    String::from_utf8(plain).map_err(|_| "The encrypted export is damaged".into())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive the encryption key: {}", e))?;
+    Ok(key)
+}
@@ -0,0 +1,197 @@
+//! Battery and network conditions checked before timed cycles, so a laptop
+//! on a hotspot or a nearly flat battery doesn't push in the background.
+//! Each condition has an action: `defer` skips the cycle until a later one,
+//! `local` commits but leaves the push for the next cycle that may push.
+//! Conditions that can't be detected on this platform never trigger.
+
+use crate::config::AppConfig;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    // Run the cycle as usual
+    #[default]
+    Ignore,
+    // Commit, but don't push
+    Local,
+    // Skip the cycle
+    Defer,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PowerState {
+    // None where it can't be detected
+    pub on_battery: Option<bool>,
+    pub battery_percent: Option<u8>,
+    pub metered: Option<bool>,
+    pub online: Option<bool>,
+}
+
+/// What the current conditions allow a timed cycle to do.
+#[derive(Serialize, Clone, Debug)]
+pub struct PowerHold {
+    pub repo: String,
+    pub action: PowerAction,
+    pub reason: String,
+    pub state: PowerState,
+}
+
+/// The strictest action the configured checks call for right now, with the
+/// conditions behind it; None when the cycle can run as usual. Nothing is
+/// detected when every check is off.
+pub fn check(repo_id: &str, config: &AppConfig) -> Option<PowerHold> {
+    let checks = [
+        config.low_battery_action,
+        config.metered_network_action,
+        config.offline_action,
+    ];
+    if checks.iter().all(|action| *action == PowerAction::Ignore) {
+        return None;
+    }
+
+    let state = detect();
+    let mut reasons = Vec::new();
+    if let (Some(true), Some(percent)) = (state.on_battery, state.battery_percent) {
+        if percent < config.min_battery_percent {
+            reasons.push((config.low_battery_action, format!("battery at {}%", percent)));
+        }
+    }
+    if state.metered == Some(true) {
+        reasons.push((config.metered_network_action, "metered network".to_string()));
+    }
+    if state.online == Some(false) {
+        reasons.push((config.offline_action, "no network".to_string()));
+    }
+
+    let action = reasons.iter().map(|(action, _)| *action).max()?;
+    if action == PowerAction::Ignore {
+        return None;
+    }
+    let reason = reasons
+        .into_iter()
+        .filter(|(reason_action, _)| *reason_action == action)
+        .map(|(_, reason)| reason)
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(PowerHold { repo: repo_id.to_string(), action, reason, state })
+}
+
+/// The battery and network as the OS reports them.
+pub fn detect() -> PowerState {
+    let mut state = PowerState::default();
+    detect_battery(&mut state);
+    detect_network(&mut state);
+    state
+}
+
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn detect_battery(state: &mut PowerState) {
+    let supplies = match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(supplies) => supplies,
+        Err(_) => return,
+    };
+    for supply in supplies.flatten() {
+        let read = |name: &str| std::fs::read_to_string(supply.path().join(name)).map(|value| value.trim().to_string());
+        if read("type").as_deref() != Ok("Battery") {
+            continue;
+        }
+        state.battery_percent = read("capacity").ok().and_then(|capacity| capacity.parse().ok());
+        state.on_battery = read("status").ok().map(|status| status == "Discharging");
+        return;
+    }
+}
+
+// NetworkManager's Metered (1 yes, 3 guessed yes) and Connectivity (1 none)
+#[cfg(target_os = "linux")]
+fn detect_network(state: &mut PowerState) {
+    let property = |name: &str| {
+        output(
+            "busctl",
+            &[
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                name,
+            ],
+        )
+        .and_then(|value| value.split_whitespace().nth(1)?.parse::<u32>().ok())
+    };
+    state.metered = property("Metered").map(|metered| metered == 1 || metered == 3);
+    state.online = property("Connectivity").filter(|&connectivity| connectivity != 0).map(|connectivity| connectivity != 1);
+}
+
+// `Now drawing from 'Battery Power'` ... `-InternalBattery-0 (id=…)	54%; discharging`
+#[cfg(target_os = "macos")]
+fn detect_battery(state: &mut PowerState) {
+    let report = match output("pmset", &["-g", "batt"]) {
+        Some(report) => report,
+        None => return,
+    };
+    if !report.contains("InternalBattery") {
+        return;
+    }
+    state.on_battery = Some(report.contains("'Battery Power'"));
+    state.battery_percent = report
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .find_map(|word| word.strip_suffix('%')?.parse().ok());
+}
+
+// macOS has no command for metered networks; only connectivity is checked
+#[cfg(target_os = "macos")]
+fn detect_network(state: &mut PowerState) {
+    state.online = output("route", &["-n", "get", "default"]).map(|route| route.contains("gateway"));
+}
+
+#[cfg(target_os = "windows")]
+fn detect_battery(state: &mut PowerState) {
+    // BatteryStatus 1 means discharging
+    let report = output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "Get-CimInstance Win32_Battery | ForEach-Object { \"$($_.BatteryStatus) $($_.EstimatedChargeRemaining)\" }",
+        ],
+    );
+    if let Some(report) = report {
+        let mut fields = report.split_whitespace();
+        state.on_battery = fields.next().map(|status| status == "1");
+        state.battery_percent = fields.next().and_then(|percent| percent.parse().ok());
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn detect_network(state: &mut PowerState) {
+    // NetworkCostType is Unrestricted, Fixed or Variable; no profile means offline
+    let cost = output(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "[void][Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,ContentType=WindowsRuntime]; \
+             $profile = [Windows.Networking.Connectivity.NetworkInformation]::GetInternetConnectionProfile(); \
+             if ($profile) { $profile.GetConnectionCost().NetworkCostType } else { 'None' }",
+        ],
+    );
+    if let Some(cost) = cost.map(|cost| cost.trim().to_string()) {
+        state.online = Some(cost != "None");
+        state.metered = (cost != "None").then(|| cost != "Unrestricted");
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn detect_battery(_state: &mut PowerState) {}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn detect_network(_state: &mut PowerState) {}
@@ -0,0 +1,137 @@
+//! Scores candidate commit messages so the best of several can be picked
+//! without asking the user: a message that breaks the configured rules
+//! loses to any that follows them, then a subject of a useful length that
+//! names what changed beats a vague one.
+
+use crate::config::AppConfig;
+use crate::conventional;
+use crate::rules;
+use crate::validate;
+use serde::Serialize;
+use std::path::Path;
+
+// Each problem `validate::check` reports outweighs every other heuristic
+const PROBLEM_PENALTY: i32 = 100;
+
+// Subjects that say nothing about the change
+const VAGUE_WORDS: [&str; 12] = [
+    "update", "updates", "updated", "change", "changes", "changed", "misc", "minor", "stuff", "wip", "various",
+    "tweaks",
+];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RankedCandidate {
+    pub message: String,
+    pub score: i32,
+    // What `validate::check` found wrong with it
+    pub problems: Vec<String>,
+}
+
+/// `messages` scored against `config` and the change in `diff_text`, best
+/// first; ties keep the model's order. Duplicates are dropped.
+pub fn rank(messages: Vec<String>, diff_text: &str, config: &AppConfig) -> Vec<RankedCandidate> {
+    let names = changed_names(diff_text);
+    let mut ranked: Vec<RankedCandidate> = Vec::new();
+    for message in messages {
+        if message.trim().is_empty() || ranked.iter().any(|candidate| candidate.message == message) {
+            continue;
+        }
+        let problems = validate::check(&message, config);
+        let score = score(&message, &names, diff_text) - PROBLEM_PENALTY * problems.len() as i32;
+        ranked.push(RankedCandidate { message, score, problems });
+    }
+    ranked.sort_by(|a, b| b.score.cmp(&a.score));
+    ranked
+}
+
+fn score(message: &str, names: &[String], diff_text: &str) -> i32 {
+    let subject = message.lines().next().unwrap_or_default().trim();
+    // The description, without a Conventional Commits header
+    let description = conventional::parse(subject).map_or(subject.to_string(), |parsed| parsed.subject);
+    let mut score = 0;
+
+    score += match description.chars().count() {
+        0..=11 => -20,
+        12..=19 => 0,
+        20..=60 => 20,
+        _ => 5,
+    };
+
+    let lowercase = description.to_lowercase();
+    let words: Vec<&str> = lowercase
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    score -= 10 * words.iter().filter(|word| VAGUE_WORDS.contains(word)).count() as i32;
+
+    // Naming a changed file or module is the surest sign of a specific message
+    let whole = message.to_lowercase();
+    score += 10 * names.iter().filter(|name| whole.contains(name.as_str())).count().min(3) as i32;
+
+    // Changes to several files deserve a body saying what ties them together
+    if rules::changed_paths(diff_text).len() > 1 && !conventional::body(message).trim().is_empty() {
+        score += 5;
+    }
+    score
+}
+
+// Lowercase file stems and directory names of the changed files, long
+// enough to be telling
+fn changed_names(diff_text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for path in rules::changed_paths(diff_text) {
+        let path = Path::new(&path);
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_lowercase());
+        let parent = path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .map(|dir| dir.to_string_lossy().to_lowercase());
+        for name in stem.into_iter().chain(parent) {
+            if name.chars().count() >= 3 && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = " src/auth/login.rs | 12 +++---\n src/auth/token.rs | 4 ++--\n";
+
+    #[test]
+    fn rule_breaking_messages_lose() {
+        let ranked = rank(
+            vec!["Updated login".into(), "feat(auth): refresh expired login tokens".into()],
+            DIFF,
+            &AppConfig::default(),
+        );
+        assert_eq!(ranked[0].message, "feat(auth): refresh expired login tokens");
+        assert_eq!(ranked[1].problems.len(), 1);
+        assert!(ranked[0].score > ranked[1].score);
+    }
+
+    #[test]
+    fn specific_messages_beat_vague_ones() {
+        let ranked = rank(
+            vec!["fix: misc updates and tweaks".into(), "fix(auth): keep the token on login retries".into()],
+            DIFF,
+            &AppConfig::default(),
+        );
+        assert_eq!(ranked[0].message, "fix(auth): keep the token on login retries");
+    }
+
+    #[test]
+    fn drops_duplicates_and_empty_messages() {
+        let message = "fix(auth): keep the token on login retries".to_string();
+        let ranked = rank(vec![message.clone(), " ".into(), message], DIFF, &AppConfig::default());
+        assert_eq!(ranked.len(), 1);
+    }
+
+    #[test]
+    fn changed_names_are_stems_and_directories() {
+        assert_eq!(changed_names(DIFF), vec!["login", "auth", "token"]);
+    }
+}
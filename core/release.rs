@@ -0,0 +1,140 @@
+use crate::changelog;
+use crate::config::AppConfig;
+use git2::{Repository, Signature};
+use serde::Serialize;
+use std::process::Command;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+    // Only commits that don't change the public behaviour
+    None,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VersionSuggestion {
+    // Latest tag, None before the first release
+    pub current: Option<String>,
+    pub next: String,
+    pub bump: Bump,
+    pub commits: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ReleaseTag {
+    pub name: String,
+    pub sha: String,
+    pub notes: String,
+    pub signed: bool,
+}
+
+// `v1.2.3` -> ("v", 1, 2, 3); pre-release and build suffixes are dropped
+fn parse_version(tag: &str) -> Option<(String, u64, u64, u64)> {
+    let digits_start = tag.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, version) = tag.split_at(digits_start);
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((prefix.to_string(), major, minor, patch))
+}
+
+/// Next version from the commits since the latest tag: a breaking change
+/// bumps the major version, a `feat` the minor one and a `fix` or `perf`
+/// the patch. Without a tag the first version is `v0.1.0`.
+pub fn suggest_next_version(repo_id: &str) -> Result<VersionSuggestion, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let latest = changelog::latest_tag(&repo);
+    let commits = changelog::commits_between(&repo, latest.as_ref().map(|(name, _)| name.as_str()), "HEAD")?;
+
+    let bump = if commits.iter().any(|commit| commit.breaking) {
+        Bump::Major
+    } else if commits.iter().any(|commit| commit.commit_type == "feat") {
+        Bump::Minor
+    } else if commits.iter().any(|commit| matches!(commit.commit_type.as_str(), "fix" | "perf")) {
+        Bump::Patch
+    } else {
+        Bump::None
+    };
+
+    let current = latest.map(|(name, _)| name);
+    let next = match current.as_deref().and_then(parse_version) {
+        Some((prefix, major, minor, patch)) => {
+            let (major, minor, patch) = match bump {
+                Bump::Major => (major + 1, 0, 0),
+                Bump::Minor => (major, minor + 1, 0),
+                // Anything worth a release gets at least a patch bump
+                Bump::Patch | Bump::None => (major, minor, patch + 1),
+            };
+            format!("{}{}.{}.{}", prefix, major, minor, patch)
+        }
+        None => "v0.1.0".into(),
+    };
+
+    Ok(VersionSuggestion { current, next, bump, commits: commits.len() })
+}
+
+/// Creates an annotated tag at HEAD named `version` (the suggested next
+/// version by default), with release notes generated from the commits
+/// since the latest tag as its message. `sign` uses `git tag -s`, so the
+/// user's configured GPG or SSH key signs it.
+pub async fn create_release_tag(
+    repo_id: &str,
+    config: &AppConfig,
+    version: Option<String>,
+    sign: bool,
+) -> Result<ReleaseTag, String> {
+    let name = match version.filter(|version| !version.trim().is_empty()) {
+        Some(version) => version.trim().to_string(),
+        None => suggest_next_version(repo_id)?.next,
+    };
+    if !git2::Reference::is_valid_name(&format!("refs/tags/{}", name)) {
+        return Err(format!("Invalid tag name: {}", name));
+    }
+
+    let notes = changelog::generate_changelog(repo_id, config, None, None, Some(name.clone()), false)
+        .await
+        .map(|changelog| changelog.markdown)
+        .unwrap_or_else(|_| format!("Release {}", name));
+
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    if repo.find_reference(&format!("refs/tags/{}", name)).is_ok() {
+        return Err(format!("Tag {} already exists", name));
+    }
+
+    let sha = if sign {
+        let output = Command::new("git")
+            .arg("tag")
+            .arg("-s")
+            .arg(&name)
+            .arg("-m")
+            .arg(&notes)
+            .current_dir(repo_id)
+            .output()
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to create signed tag: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        repo.revparse_single(&name).map_err(|e| e.to_string())?.id()
+    } else {
+        let head = repo
+            .head()
+            .and_then(|head| head.peel(git2::ObjectType::Commit))
+            .map_err(|e| e.to_string())?;
+        let signature = repo
+            .signature()
+            .or_else(|_| Signature::now("GitGenius", "gitgenius@localhost"))
+            .map_err(|e| e.to_string())?;
+        repo.tag(&name, &head, &signature, &notes, false)
+            .map_err(|e| format!("Failed to create tag: {}", e))?
+    };
+
+    Ok(ReleaseTag { name, sha: sha.to_string(), notes, signed: sign })
+}
@@ -0,0 +1,337 @@
+use crate::config::AppConfig;
+use crate::events::{self, EventSink};
+use crate::proxy::{self, ProxySettings};
+use git2::{BranchType, Repository};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// Longest a background fetch may take before it is killed, so a hung
+// connection can't hold up the fetches of the other repositories
+const FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Why talking to a remote failed, read from git's error output.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteFailure {
+    Dns,
+    Connection,
+    Tls,
+    Auth,
+    NotFound,
+    // A server-side hook (pre-receive, update) declined the push
+    HookRejected,
+    NonFastForward,
+    Other,
+}
+
+impl RemoteFailure {
+    pub fn describe(self) -> &'static str {
+        match self {
+            RemoteFailure::Dns => "host name could not be resolved",
+            RemoteFailure::Connection => "could not connect to the remote",
+            RemoteFailure::Tls => "TLS handshake or certificate check failed",
+            RemoteFailure::Auth => "authentication was refused",
+            RemoteFailure::NotFound => "remote repository not found",
+            RemoteFailure::HookRejected => "a server-side hook rejected the push",
+            RemoteFailure::NonFastForward => "remote has commits that are not local",
+            RemoteFailure::Other => "remote operation failed",
+        }
+    }
+}
+
+// Checked in order; the first matching needle wins
+const FAILURE_PATTERNS: [(RemoteFailure, &[&str]); 7] = [
+    (
+        RemoteFailure::Dns,
+        &["could not resolve host", "could not resolve hostname", "name or service not known",
+          "nodename nor servname", "temporary failure in name resolution"],
+    ),
+    (
+        RemoteFailure::Tls,
+        &["ssl certificate problem", "server certificate verification failed", "ssl_",
+          "gnutls_handshake", "schannel", "tls handshake"],
+    ),
+    (
+        RemoteFailure::HookRejected,
+        &["hook declined", "pre-receive hook", "remote rejected"],
+    ),
+    (
+        RemoteFailure::Auth,
+        &["authentication failed", "permission denied", "could not read username",
+          "terminal prompts disabled", "returned error: 401", "returned error: 403",
+          "access denied", "invalid credentials"],
+    ),
+    (
+        RemoteFailure::NonFastForward,
+        &["non-fast-forward", "fetch first", "[rejected]"],
+    ),
+    (
+        RemoteFailure::NotFound,
+        &["repository not found", "does not appear to be a git repository", "returned error: 404"],
+    ),
+    (
+        RemoteFailure::Connection,
+        &["connection refused", "connection timed out", "failed to connect", "could not connect",
+          "no route to host", "network is unreachable", "connection reset", "operation timed out"],
+    ),
+];
+
+/// Classifies the stderr of a failed `git push`, `fetch` or `ls-remote`.
+pub fn classify_failure(stderr: &str) -> RemoteFailure {
+    let stderr = stderr.to_lowercase();
+    FAILURE_PATTERNS
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|needle| stderr.contains(needle)))
+        .map(|(failure, _)| *failure)
+        .unwrap_or(RemoteFailure::Other)
+}
+
+/// Outcome of checking that the default remote is reachable and accepts our
+/// credentials.
+#[derive(Serialize, Clone, Debug)]
+pub struct RemoteProbe {
+    pub repo: String,
+    pub remote: String,
+    pub url: Option<String>,
+    pub reachable: bool,
+    pub failure: Option<RemoteFailure>,
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+/// Lists the remote's heads without transferring objects, which exercises
+/// DNS, the connection, TLS and authentication in one round trip. An empty
+/// `push_remote` probes the default remote.
+pub fn probe(path: &str, push_remote: &str, proxy: &ProxySettings) -> Result<RemoteProbe, String> {
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let remote = match push_remote.trim() {
+        "" => default_remote(&repo),
+        configured => configured.to_string(),
+    };
+    let url = repo
+        .find_remote(&remote)
+        .ok()
+        .and_then(|found| found.url().map(str::to_string));
+
+    let started = Instant::now();
+    let mut command = Command::new("git");
+    command
+        .arg("ls-remote")
+        .arg("--heads")
+        .arg(&remote)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(path);
+    proxy::configure_git(&mut command, proxy);
+    let output = command.output().map_err(|e| e.to_string())?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let reachable = output.status.success();
+    Ok(RemoteProbe {
+        repo: path.to_string(),
+        remote,
+        url,
+        reachable,
+        failure: (!reachable).then(|| classify_failure(&stderr)),
+        detail: stderr,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+// The current branch's upstream remote, or `origin`
+fn default_remote(repo: &Repository) -> String {
+    repo.head()
+        .ok()
+        .and_then(|head| head.name().map(str::to_string))
+        .and_then(|name| repo.branch_upstream_remote(&name).ok())
+        .and_then(|remote| remote.as_str().map(str::to_string))
+        .unwrap_or_else(|| "origin".into())
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RemoteInfo {
+    pub name: String,
+    pub url: Option<String>,
+    // Only when it differs from `url`
+    pub push_url: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BranchInfo {
+    // Without the remote's prefix for remote branches
+    pub name: String,
+    pub is_head: bool,
+    // `origin/main` for local branches that track one
+    pub upstream: Option<String>,
+}
+
+/// The repository's remotes, for choosing `push_remote`.
+pub fn list_remotes(path: &str) -> Result<Vec<RemoteInfo>, String> {
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let names = repo.remotes().map_err(|e| format!("Failed to list remotes: {}", e))?;
+    Ok(names
+        .iter()
+        .flatten()
+        .filter_map(|name| repo.find_remote(name).ok())
+        .map(|remote| {
+            let url = remote.url().map(str::to_string);
+            let push_url = remote.pushurl().map(str::to_string).filter(|push| Some(push) != url.as_ref());
+            RemoteInfo { name: remote.name().unwrap_or_default().to_string(), url, push_url }
+        })
+        .collect())
+}
+
+/// Local branches, or with `remote` the branches last fetched from it, for
+/// choosing `push_refspec`.
+pub fn list_branches(path: &str, remote: Option<&str>) -> Result<Vec<BranchInfo>, String> {
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let kind = if remote.is_some() { BranchType::Remote } else { BranchType::Local };
+    let branches = repo.branches(Some(kind)).map_err(|e| format!("Failed to list branches: {}", e))?;
+
+    let mut listed = Vec::new();
+    for (branch, _) in branches.flatten() {
+        let full_name = match branch.name() {
+            Ok(Some(name)) => name.to_string(),
+            _ => continue,
+        };
+        let name = match remote {
+            Some(remote) => match full_name.strip_prefix(&format!("{}/", remote)) {
+                // `origin/HEAD` only points at another branch
+                Some(name) if name != "HEAD" => name.to_string(),
+                _ => continue,
+            },
+            None => full_name,
+        };
+        let upstream = branch
+            .upstream()
+            .ok()
+            .and_then(|upstream| upstream.name().ok().flatten().map(str::to_string));
+        listed.push(BranchInfo { name, is_head: branch.is_head(), upstream });
+    }
+    listed.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(listed)
+}
+
+#[derive(Serialize)]
+struct FetchCompleted {
+    repo: String,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct FetchFailed {
+    repo: String,
+    error: String,
+}
+
+/// Fetches (and prunes) the default remote of the repository at `path`,
+/// giving up after `FETCH_TIMEOUT`.
+pub async fn fetch(path: &str, proxy: &ProxySettings) -> Result<(), String> {
+    let mut command = Command::new("git");
+    command
+        .arg("fetch")
+        .arg("--prune")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(path);
+    proxy::configure_git(&mut command, proxy);
+    let mut command = tokio::process::Command::from(command);
+    command.kill_on_drop(true);
+    let output = match tokio::time::timeout(FETCH_TIMEOUT, command.output()).await {
+        Ok(output) => output.map_err(|e| e.to_string())?,
+        Err(_) => return Err(format!("git fetch timed out after {} seconds", FETCH_TIMEOUT.as_secs())),
+    };
+
+    if !output.status.success() {
+        return Err(format!(
+            "git fetch failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Commits the current branch is ahead of and behind its upstream, if it has one.
+pub fn ahead_behind(repo: &Repository) -> Option<(usize, usize)> {
+    let head = repo.head().ok()?;
+    let branch_name = head.shorthand()?;
+    let branch = repo.find_branch(branch_name, git2::BranchType::Local).ok()?;
+    let upstream = branch.upstream().ok()?;
+
+    let local = head.target()?;
+    let remote = upstream.get().target()?;
+    repo.graph_ahead_behind(local, remote).ok()
+}
+
+/// Fetches every repository whose fetch interval has elapsed since the last
+/// fetch recorded in `last_fetch`. Meant to be called from a periodic loop,
+/// independently of commit cycles.
+pub async fn fetch_due_repos(
+    config: &AppConfig,
+    last_fetch: &mut HashMap<String, Instant>,
+    events: &dyn EventSink,
+) {
+    let proxy = ProxySettings::from_config(config);
+    for path in config.repo_paths() {
+        let interval_minutes = config.repo_settings(&path).fetch_interval_minutes;
+        if interval_minutes == 0 {
+            continue;
+        }
+
+        let due = last_fetch
+            .get(&path)
+            .map(|last| last.elapsed() >= Duration::from_secs(interval_minutes * 60))
+            .unwrap_or(true);
+        if !due {
+            continue;
+        }
+        last_fetch.insert(path.clone(), Instant::now());
+
+        match fetch(&path, &proxy).await {
+            Ok(()) => {
+                let counts = Repository::open(&path).ok().and_then(|repo| ahead_behind(&repo));
+                events::emit(events, "fetch-completed", &FetchCompleted {
+                    repo: path.clone(),
+                    ahead: counts.map(|(ahead, _)| ahead),
+                    behind: counts.map(|(_, behind)| behind),
+                });
+            }
+            Err(error) => {
+                events::emit(events, "fetch-error", &FetchFailed {
+                    repo: path.clone(),
+                    error,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_errors_are_classified() {
+        let cases = [
+            ("fatal: unable to access 'https://x/': Could not resolve host: x", RemoteFailure::Dns),
+            ("fatal: unable to access: SSL certificate problem: self signed certificate", RemoteFailure::Tls),
+            ("git@github.com: Permission denied (publickey).", RemoteFailure::Auth),
+            ("fatal: could not read Username for 'https://github.com': terminal prompts disabled", RemoteFailure::Auth),
+            ("ERROR: Repository not found.", RemoteFailure::NotFound),
+            ("ssh: connect to host x port 22: Connection refused", RemoteFailure::Connection),
+            (" ! [rejected]        main -> main (fetch first)", RemoteFailure::NonFastForward),
+            ("fatal: the remote end hung up unexpectedly", RemoteFailure::Other),
+        ];
+        for (stderr, expected) in cases {
+            assert_eq!(classify_failure(stderr), expected, "{}", stderr);
+        }
+    }
+
+    #[test]
+    fn hook_rejections_win_over_the_rejected_push() {
+        let stderr = " ! [remote rejected] main -> main (pre-receive hook declined)";
+        assert_eq!(classify_failure(stderr), RemoteFailure::HookRejected);
+    }
+}
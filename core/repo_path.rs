@@ -0,0 +1,162 @@
+//! Checks a folder before it is used as the repository, so a wrong pick is
+//! reported up front instead of as a confusing error mid-cycle.
+
+use git2::Repository;
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PathProblem {
+    Missing,
+    NotADirectory,
+    InsideGitDir,
+    NotARepository,
+    BareRepository,
+    // A folder inside a working tree; the working tree's root is used
+    Subdirectory,
+    NoRemote,
+    ReadOnly,
+}
+
+impl PathProblem {
+    pub fn describe(self) -> &'static str {
+        match self {
+            PathProblem::Missing => "the folder does not exist",
+            PathProblem::NotADirectory => "the path is a file, not a folder",
+            PathProblem::InsideGitDir => "the folder is inside a .git directory",
+            PathProblem::NotARepository => "the folder is not in a Git repository",
+            PathProblem::BareRepository => "the repository is bare and has no working tree",
+            PathProblem::Subdirectory => "the folder is inside a repository; its root will be used",
+            PathProblem::NoRemote => "the repository has no remote, so commits can't be pushed",
+            PathProblem::ReadOnly => "the repository is not writable",
+        }
+    }
+
+    /// Whether the app can't work with the path at all. The others are
+    /// warnings.
+    pub fn is_blocking(self) -> bool {
+        !matches!(self, PathProblem::Subdirectory | PathProblem::NoRemote)
+    }
+}
+
+/// The working tree a path is in.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RepoRoot {
+    pub root: String,
+    // Name of the linked worktree (`git worktree add`), None for the main one
+    pub worktree: Option<String>,
+}
+
+/// The root of the working tree `path` is in, found like git does: any
+/// folder inside a repository or a linked worktree works.
+pub fn resolve(path: &str) -> Result<RepoRoot, String> {
+    let repo = Repository::discover(path.trim()).map_err(|e| format!("{} is not in a Git repository: {}", path, e))?;
+    let workdir = match repo.workdir() {
+        Some(workdir) if !repo.is_bare() => workdir,
+        _ => return Err(format!("{} is in a bare repository", path)),
+    };
+    // A linked worktree's git dir is `<common dir>/worktrees/<name>`
+    let worktree = repo
+        .is_worktree()
+        .then(|| repo.path().file_name().map(|name| name.to_string_lossy().to_string()))
+        .flatten();
+    let root = workdir.to_string_lossy();
+    Ok(RepoRoot { root: root.trim_end_matches(['/', '\\']).to_string(), worktree })
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RepoPathCheck {
+    pub path: String,
+    // Root of the working tree the path is in
+    pub repo_root: Option<String>,
+    // Name of the linked worktree the path is in
+    pub worktree: Option<String>,
+    pub remotes: Vec<String>,
+    pub problems: Vec<PathProblem>,
+    // `problems` in words, in the same order
+    pub messages: Vec<String>,
+    // No blocking problem, so `repo_root` can be used
+    pub usable: bool,
+}
+
+impl RepoPathCheck {
+    fn new(path: &str, problems: Vec<PathProblem>) -> Self {
+        RepoPathCheck {
+            path: path.to_string(),
+            repo_root: None,
+            worktree: None,
+            remotes: Vec::new(),
+            usable: !problems.iter().any(|problem| problem.is_blocking()),
+            messages: problems.iter().map(|problem| problem.describe().to_string()).collect(),
+            problems,
+        }
+    }
+}
+
+/// Checks that `path` is in a non-bare repository with a remote that the
+/// app can write to.
+pub fn validate_repo_path(path: &str) -> RepoPathCheck {
+    let folder = Path::new(path.trim());
+    if !folder.exists() {
+        return RepoPathCheck::new(path, vec![PathProblem::Missing]);
+    }
+    if !folder.is_dir() {
+        return RepoPathCheck::new(path, vec![PathProblem::NotADirectory]);
+    }
+
+    let canonical = fs::canonicalize(folder).unwrap_or_else(|_| folder.to_path_buf());
+    if canonical.components().any(|part| part == Component::Normal(".git".as_ref())) {
+        return RepoPathCheck::new(path, vec![PathProblem::InsideGitDir]);
+    }
+
+    let repo = match Repository::discover(folder) {
+        Ok(repo) => repo,
+        Err(_) => return RepoPathCheck::new(path, vec![PathProblem::NotARepository]),
+    };
+    let workdir = match repo.workdir() {
+        Some(workdir) if !repo.is_bare() => workdir.to_path_buf(),
+        _ => return RepoPathCheck::new(path, vec![PathProblem::BareRepository]),
+    };
+
+    // Canonical paths only for comparing; on Windows they carry a `\\?\`
+    // prefix that shouldn't end up in the config
+    let mut problems = Vec::new();
+    if fs::canonicalize(&workdir).unwrap_or_else(|_| workdir.clone()) != canonical {
+        problems.push(PathProblem::Subdirectory);
+    }
+
+    let remotes: Vec<String> = repo
+        .remotes()
+        .map(|remotes| remotes.iter().flatten().map(str::to_string).collect())
+        .unwrap_or_default();
+    if remotes.is_empty() {
+        problems.push(PathProblem::NoRemote);
+    }
+
+    // Commits write to the git directory, staging to the working tree too
+    if !is_writable(repo.path()) || !is_writable(&workdir) {
+        problems.push(PathProblem::ReadOnly);
+    }
+
+    let mut check = RepoPathCheck::new(path, problems);
+    let root = workdir.to_string_lossy();
+    check.repo_root = Some(root.trim_end_matches(['/', '\\']).to_string());
+    check.worktree = resolve(path).ok().and_then(|found| found.worktree);
+    check.remotes = remotes;
+    check
+}
+
+// Read-only bits don't tell the whole story (ACLs, read-only mounts), so
+// actually try to create a file
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".gitgenius-write-check-{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            fs::remove_file(&probe).ok();
+            true
+        }
+        Err(_) => false,
+    }
+}
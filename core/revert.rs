@@ -0,0 +1,154 @@
+//! Revert assistant: undoes a commit of the current branch with a new
+//! commit, and has the model explain in the message what is taken back and
+//! why, instead of git's bare `Revert "..."`.
+
+use crate::anonymize::Anonymizer;
+use crate::budget;
+use crate::config::AppConfig;
+use crate::gemini;
+use crate::history;
+use crate::identity;
+use crate::pipeline;
+use git2::build::CheckoutBuilder;
+use git2::{Oid, Repository};
+use serde::Serialize;
+
+const REVERT_SYSTEM_CONTEXT: &str = r#"You write the commit message for a git revert, given the message of the commit being reverted, the diff that commit introduced and, optionally, the reason for reverting it.
+
+RULES:
+1. Subject: revert: <what the reverted commit did, in the imperative>, max 72 characters
+2. Body: one short paragraph saying what behaviour or code goes away, then why it is reverted when a reason is given
+3. Don't restate the diff line by line and don't invent a reason
+
+EXAMPLE:
+revert: add JWT token validation
+
+Removes the JWT validation middleware and its configuration, so requests
+are authenticated by session cookie again. The validation rejected tokens
+issued by the old identity provider.
+
+Always respond with ONLY the commit message, no explanations or additional text."#;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RevertResult {
+    pub sha: String,
+    // The commit that was undone
+    pub reverted: String,
+    pub message: String,
+}
+
+/// Reverts `sha` on the current branch with a new commit on top of HEAD.
+/// The commit has to be on the current branch and have a single parent;
+/// the revert has to apply cleanly and leave the user's uncommitted
+/// changes alone, otherwise nothing is changed. `reason`, when given, ends
+/// up in the message.
+pub async fn revert_commit(
+    path: &str,
+    config: &AppConfig,
+    sha: &str,
+    reason: Option<&str>,
+) -> Result<RevertResult, String> {
+    let (head, target, original_message) = {
+        let repo = Repository::open(path).map_err(|e| e.to_string())?;
+        let target = repo
+            .revparse_single(sha)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| format!("{} is not a commit", sha))?;
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|e| format!("Failed to read HEAD: {}", e))?;
+        if head.id() != target.id() && !repo.graph_descendant_of(head.id(), target.id()).unwrap_or(false) {
+            return Err(format!("{} is not on the current branch", sha));
+        }
+        if target.parent_count() != 1 {
+            return Err(format!("{} is a merge or root commit; revert it with git", sha));
+        }
+        (head.id(), target.id(), target.message().unwrap_or_default().trim().to_string())
+    };
+
+    let parent = format!("{}~1", target);
+    let diff_text = pipeline::range_diff(path, &parent, &target.to_string())?;
+    let message = revert_message(path, config, target, &original_message, &diff_text, reason).await;
+
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    let head_commit = repo.find_commit(head).map_err(|e| e.to_string())?;
+    // Fails if HEAD moved while the message was being generated
+    if repo.head().ok().and_then(|current| current.target()) != Some(head) {
+        return Err("HEAD moved while reverting; nothing was changed".into());
+    }
+    let target_commit = repo.find_commit(target).map_err(|e| e.to_string())?;
+    let mut index = repo
+        .revert_commit(&target_commit, &head_commit, 0, None)
+        .map_err(|e| format!("Failed to revert {}: {}", sha, e))?;
+    if index.has_conflicts() {
+        return Err(format!("Reverting {} conflicts with later commits; revert it with git", sha));
+    }
+    let tree = index
+        .write_tree_to(&repo)
+        .and_then(|tree_id| repo.find_tree(tree_id))
+        .map_err(|e| e.to_string())?;
+
+    // Safe checkout refuses to overwrite local edits to the touched files
+    repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))
+        .map_err(|e| format!("Uncommitted changes to the files {} touched are in the way: {}", sha, e))?;
+    let signature = identity::resolve(&repo, &config.repo_settings(path))?;
+    let new_sha = repo
+        .commit(Some("HEAD"), &signature, &signature, &message, &tree, &[&head_commit])
+        .map_err(|e| format!("Failed to commit the revert: {}", e))?;
+    history::record_head_commit(path, None).ok();
+
+    Ok(RevertResult { sha: new_sha.to_string(), reverted: target.to_string(), message })
+}
+
+// The model's message, or one in git's style without a key or when the
+// model fails; both end with git's `This reverts commit` line
+async fn revert_message(
+    path: &str,
+    config: &AppConfig,
+    target: Oid,
+    original_message: &str,
+    diff_text: &str,
+    reason: Option<&str>,
+) -> String {
+    let subject = original_message.lines().next().unwrap_or_default();
+    let reason = reason.map(str::trim).filter(|reason| !reason.is_empty());
+    let trailer = format!("This reverts commit {}.", target);
+
+    let generated = if config.api_key().is_empty() {
+        None
+    } else {
+        let mut anonymizer = config.anonymize_diff.then(Anonymizer::new);
+        let (diff_for_model, original_for_model) = match anonymizer.as_mut() {
+            Some(anonymizer) => (anonymizer.anonymize_diff(diff_text), anonymizer.anonymize_text(original_message)),
+            None => (diff_text.to_string(), original_message.to_string()),
+        };
+        let options = gemini::ModelOptions::from_config(config).for_repo(path);
+        let prefix = format!(
+            "Reverted commit message:\n{}\n\nReason: {}\n\nDiff of the reverted commit:\n\n",
+            original_for_model,
+            reason.unwrap_or("not given")
+        );
+        let fitted = budget::fit_diff(config, &options, REVERT_SYSTEM_CONTEXT, &prefix, &diff_for_model).await;
+        let prompt = format!("{}{}", prefix, fitted);
+        match gemini::generate(&config.api_key(), &options, REVERT_SYSTEM_CONTEXT, &prompt).await {
+            Ok(text) => {
+                let text = match &anonymizer {
+                    Some(anonymizer) => anonymizer.restore(&text),
+                    None => text,
+                };
+                Some(pipeline::clean_message(&text)).filter(|message| message.to_lowercase().starts_with("revert"))
+            }
+            Err(error) => {
+                tracing::warn!(repo = path, error = %error, "could not generate a revert message");
+                None
+            }
+        }
+    };
+
+    let message = generated.unwrap_or_else(|| match reason {
+        Some(reason) => format!("revert: {}\n\n{}", subject, reason),
+        None => format!("revert: {}", subject),
+    });
+    format!("{}\n\n{}", message.trim_end(), trailer)
+}
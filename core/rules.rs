@@ -0,0 +1,181 @@
+//! Deterministic commit message generator used when no model answers in
+//! time. Works from the same diff text that is sent to the model.
+
+const TEXT_EXTENSIONS: [&str; 7] = [".md", ".markdown", ".mdx", ".txt", ".rst", ".adoc", ".org"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+}
+
+struct FileChange {
+    path: String,
+    kind: ChangeKind,
+}
+
+/// Builds a Conventional Commits message from the `git diff --stat` section
+/// and the file headers of `diff_text`.
+pub fn generate_message(diff_text: &str) -> String {
+    let changes = parse_changes(diff_text);
+
+    if changes.is_empty() {
+        return "chore: periodic auto-commit".into();
+    }
+
+    let commit_type = commit_type(&changes);
+    let scope = common_scope(&changes);
+
+    let subject = if changes.len() == 1 {
+        let change = &changes[0];
+        let verb = match change.kind {
+            ChangeKind::Added => "add",
+            ChangeKind::Deleted => "remove",
+            ChangeKind::Modified => "update",
+        };
+        format!("{} {}", verb, file_name(&change.path))
+    } else {
+        let all_same = changes.iter().all(|change| change.kind == changes[0].kind);
+        let verb = match (all_same, changes[0].kind) {
+            (true, ChangeKind::Added) => "add",
+            (true, ChangeKind::Deleted) => "remove",
+            _ => "update",
+        };
+        format!("{} {} files", verb, changes.len())
+    };
+
+    match scope {
+        Some(scope) => format!("{}({}): {}", commit_type, scope, subject),
+        None => format!("{}: {}", commit_type, subject),
+    }
+}
+
+/// Paths of every file touched by `diff_text`.
+pub fn changed_paths(diff_text: &str) -> Vec<String> {
+    parse_changes(diff_text).into_iter().map(|change| change.path).collect()
+}
+
+/// True when every file touched by `diff_text` is Markdown or plain text.
+pub fn is_text_only(diff_text: &str) -> bool {
+    let changes = parse_changes(diff_text);
+    !changes.is_empty() && changes.iter().all(|change| is_text(&change.path))
+}
+
+fn parse_changes(diff_text: &str) -> Vec<FileChange> {
+    let mut changes: Vec<FileChange> = Vec::new();
+
+    // Stat lines look like ` src/main.rs | 12 +++---`
+    for line in diff_text.lines() {
+        if let Some((path, counts)) = line.split_once(" | ") {
+            let path = path.trim();
+            let counts = counts.trim();
+            let is_stat = counts.starts_with(|c: char| c.is_ascii_digit()) || counts.starts_with("Bin");
+            if !path.is_empty() && !path.starts_with("...") && is_stat {
+                changes.push(FileChange {
+                    path: path.to_string(),
+                    kind: ChangeKind::Modified,
+                });
+            }
+        }
+    }
+
+    // File headers (when not truncated away) tell additions and deletions apart
+    let mut current: Option<String> = None;
+    for line in diff_text.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git a/") {
+            current = paths.split_once(" b/").map(|(_, path)| path.to_string());
+            if let Some(path) = &current {
+                if !changes.iter().any(|change| &change.path == path) {
+                    changes.push(FileChange {
+                        path: path.clone(),
+                        kind: ChangeKind::Modified,
+                    });
+                }
+            }
+        } else if let Some(path) = &current {
+            let kind = if line.starts_with("new file mode") {
+                Some(ChangeKind::Added)
+            } else if line.starts_with("deleted file mode") {
+                Some(ChangeKind::Deleted)
+            } else {
+                None
+            };
+
+            if let Some(kind) = kind {
+                if let Some(change) = changes.iter_mut().find(|change| &change.path == path) {
+                    change.kind = kind;
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+fn commit_type(changes: &[FileChange]) -> &'static str {
+    let all = |predicate: fn(&str) -> bool| changes.iter().all(|change| predicate(&change.path));
+
+    if all(is_docs) {
+        "docs"
+    } else if all(is_test) {
+        "test"
+    } else if all(is_config) {
+        "chore"
+    } else if changes.iter().all(|change| change.kind == ChangeKind::Added) {
+        "feat"
+    } else {
+        "chore"
+    }
+}
+
+fn is_docs(path: &str) -> bool {
+    is_text(path) || path.to_lowercase().starts_with("docs/")
+}
+
+fn is_text(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    TEXT_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
+}
+
+fn is_test(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.starts_with("test") || lower.contains("/test")
+        || ["_test.", ".test.", ".spec.", "_spec."].iter().any(|marker| lower.contains(marker))
+}
+
+fn is_config(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    [".json", ".toml", ".yaml", ".yml", ".lock", ".ini", ".cfg"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+        || file_name(&lower).starts_with('.')
+}
+
+// First directory shared by every changed path, skipping generic roots like `src`
+fn common_scope(changes: &[FileChange]) -> Option<String> {
+    let segments: Vec<Vec<&str>> = changes
+        .iter()
+        .map(|change| {
+            let parts: Vec<&str> = change.path.split('/').collect();
+            parts[..parts.len().saturating_sub(1)].to_vec()
+        })
+        .collect();
+
+    let first = segments.first()?;
+    let mut scope = None;
+    for (depth, segment) in first.iter().enumerate() {
+        if !segments.iter().all(|parts| parts.get(depth) == Some(segment)) {
+            break;
+        }
+        if !["src", "lib", "app", "packages"].contains(segment) {
+            scope = Some(segment.to_string());
+            break;
+        }
+    }
+    scope
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
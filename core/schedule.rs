@@ -0,0 +1,159 @@
+//! Active hours for the auto-commit timer. Outside every configured window
+//! scheduled cycles are held back until the next window opens; manual
+//! commits are not affected.
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// While the schedule is closed the timer wakes this often, so config
+// changes and stop requests are noticed before the next window opens
+pub const RECHECK: Duration = Duration::from_secs(5 * 60);
+
+/// Days and a time range, in local time, during which cycles may run, e.g.
+/// `["mon", "tue", "wed", "thu", "fri"]` from `09:00` to `19:00`. An `end`
+/// at or before `start` runs past midnight into the next day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduleWindow {
+    // Day names or three-letter abbreviations; empty means every day
+    #[serde(default)]
+    pub days: Vec<String>,
+    // `HH:MM`
+    pub start: String,
+    pub end: String,
+}
+
+impl ScheduleWindow {
+    fn parse(&self) -> Result<(Vec<Weekday>, NaiveTime, NaiveTime), String> {
+        let days = self
+            .days
+            .iter()
+            .map(|day| day.trim().parse::<Weekday>().map_err(|_| format!("Invalid day in schedule: {}", day)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .map_err(|_| format!("Invalid time in schedule, expected HH:MM: {}", value))
+        };
+        Ok((days, time(&self.start)?, time(&self.end)?))
+    }
+
+    fn runs_on(days: &[Weekday], day: Weekday) -> bool {
+        days.is_empty() || days.contains(&day)
+    }
+}
+
+/// Checks every window so a typo is reported when the config is saved
+/// rather than silently ignored by the timer.
+pub fn validate(windows: &[ScheduleWindow]) -> Result<(), String> {
+    windows.iter().try_for_each(|window| window.parse().map(|_| ()))
+}
+
+/// Whether a cycle may run at `at`. No windows means no restriction.
+pub fn is_open(windows: &[ScheduleWindow], at: DateTime<Local>) -> bool {
+    if windows.is_empty() {
+        return true;
+    }
+
+    let today = at.weekday();
+    let now = at.time();
+    windows.iter().filter_map(|window| window.parse().ok()).any(|(days, start, end)| {
+        if start < end {
+            ScheduleWindow::runs_on(&days, today) && now >= start && now < end
+        } else {
+            // Overnight: the evening part belongs to today's window, the
+            // early-morning part to yesterday's
+            (ScheduleWindow::runs_on(&days, today) && now >= start)
+                || (ScheduleWindow::runs_on(&days, today.pred()) && now < end)
+        }
+    })
+}
+
+/// The first moment at or after `at` when a cycle may run. Falls back to
+/// `at` when no window can ever open (e.g. every window is invalid).
+pub fn next_open(windows: &[ScheduleWindow], at: DateTime<Local>) -> DateTime<Local> {
+    if is_open(windows, at) {
+        return at;
+    }
+
+    let parsed: Vec<_> = windows.iter().filter_map(|window| window.parse().ok()).collect();
+    (0..=7)
+        .filter_map(|offset| at.date_naive().checked_add_days(Days::new(offset)))
+        .flat_map(|date| {
+            parsed
+                .iter()
+                .filter(move |(days, _, _)| ScheduleWindow::runs_on(days, date.weekday()))
+                .filter_map(move |(_, start, _)| Local.from_local_datetime(&date.and_time(*start)).earliest())
+        })
+        .filter(|opening| *opening > at)
+        .min()
+        .unwrap_or(at)
+}
+
+/// How long to wait before the schedule allows a cycle, or `None` if one
+/// may run now.
+pub fn delay_until_open(windows: &[ScheduleWindow]) -> Option<Duration> {
+    let now = Local::now();
+    let opening = next_open(windows, now);
+    (opening > now).then(|| (opening - now).to_std().unwrap_or_default())
+}
+
+/// Unix seconds of the next attempt for a cycle due in `delay`, pushed
+/// forward to the next window if the schedule is closed by then.
+pub fn next_run_at(windows: &[ScheduleWindow], delay: Duration) -> u64 {
+    let due = Local::now() + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+    next_open(windows, due).timestamp().max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(days: &[&str], start: &str, end: &str) -> ScheduleWindow {
+        ScheduleWindow {
+            days: days.iter().map(|day| day.to_string()).collect(),
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    // 2024-06-03 was a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn no_windows_is_always_open() {
+        assert!(is_open(&[], at(3, 3, 0)));
+        assert_eq!(next_open(&[], at(3, 3, 0)), at(3, 3, 0));
+    }
+
+    #[test]
+    fn open_inside_a_day_window_only() {
+        let windows = [window(&["mon", "tue", "wed", "thu", "fri"], "09:00", "19:00")];
+        assert!(is_open(&windows, at(3, 9, 0)));
+        assert!(!is_open(&windows, at(3, 19, 0)));
+        assert!(!is_open(&windows, at(8, 12, 0)));
+    }
+
+    #[test]
+    fn an_overnight_window_belongs_to_the_day_it_starts() {
+        let windows = [window(&["fri"], "22:00", "02:00")];
+        assert!(is_open(&windows, at(7, 23, 0)));
+        assert!(is_open(&windows, at(8, 1, 0)));
+        assert!(!is_open(&windows, at(7, 1, 0)));
+    }
+
+    #[test]
+    fn next_open_is_the_next_opening() {
+        let windows = [window(&["mon", "tue", "wed", "thu", "fri"], "09:00", "19:00")];
+        assert_eq!(next_open(&windows, at(3, 20, 0)), at(4, 9, 0));
+        assert_eq!(next_open(&windows, at(7, 20, 0)), at(10, 9, 0));
+    }
+
+    #[test]
+    fn validate_reports_typos() {
+        assert!(validate(&[window(&["monday"], "09:00", "17:30")]).is_ok());
+        assert!(validate(&[window(&["mnday"], "09:00", "17:30")]).is_err());
+        assert!(validate(&[window(&[], "9am", "17:30")]).is_err());
+    }
+}
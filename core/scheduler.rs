@@ -0,0 +1,312 @@
+//! The auto-commit timer for every scheduled repository: `repo_path` plus
+//! each `repos` entry with `auto_commit`. One queue orders their runs by
+//! due time, then `schedule_priority`. First runs are spread over
+//! `schedule_jitter_secs`, and every later run is pushed back by a random
+//! part of it, so repositories on the same interval drift apart instead of
+//! firing together. At most `max_concurrent_cycles` cycles run at once.
+
+use crate::config::AppConfig;
+use crate::events::{self, EventSink};
+use crate::health;
+use crate::pipeline;
+use crate::schedule;
+use crate::timer_state::{self, MissedRuns};
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, Mutex, RwLock, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+// Longest the queue sleeps before looking at the timer switch and the
+// config again
+const QUEUE_CHECK: Duration = Duration::from_secs(30);
+
+/// Everything the scheduler needs from its host application.
+#[derive(Clone)]
+pub struct Scheduler {
+    // Shared with the host, which may replace it while the timer runs
+    pub config: Arc<RwLock<AppConfig>>,
+    pub events: Arc<dyn EventSink>,
+    // Cancelled by the host to stop this run of the timer; a restarted
+    // timer gets a new one, so a loop still asleep can't carry on beside it
+    pub stop: CancellationToken,
+    // Unix seconds of the earliest queued run
+    pub next_run: Arc<Mutex<Option<u64>>>,
+    // Current cancellation token of the host, replaced after each cancel
+    pub cancel: Arc<Mutex<CancellationToken>>,
+}
+
+struct QueuedRun {
+    // Unix seconds
+    due: u64,
+    priority: i32,
+    repo: String,
+    // Already delayed for missed runs, so lateness isn't reported twice
+    caught_up: bool,
+}
+
+// BinaryHeap pops the greatest: the earliest due, then the highest priority
+impl Ord for QueuedRun {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .due
+            .cmp(&self.due)
+            .then(self.priority.cmp(&other.priority))
+            .then(other.repo.cmp(&self.repo))
+    }
+}
+
+impl PartialOrd for QueuedRun {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Equal when the queue can't tell them apart, as `Ord` requires
+impl PartialEq for QueuedRun {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedRun {}
+
+impl Scheduler {
+    /// Runs the timer until `stop` is cancelled. The first repository
+    /// is due after `initial_delay`, the others follow it spread over
+    /// `schedule_jitter_secs`. Repositories added to or removed from the
+    /// schedule while it runs are picked up.
+    pub async fn run(self, initial_delay: Duration) {
+        let config = self.config.read().await.clone();
+        let cycles = Arc::new(Semaphore::new(permits(config.max_concurrent_cycles)));
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel::<(String, Duration)>();
+        let mut queue = BinaryHeap::new();
+        let mut running: HashSet<String> = HashSet::new();
+
+        let repos = config.scheduled_repos();
+        let spacing = Duration::from_secs(config.schedule_jitter_secs) / repos.len().max(1) as u32;
+        for (index, repo) in repos.iter().enumerate() {
+            let delay = initial_delay + spacing * index as u32;
+            queue.push(queued(&config, repo, delay, false));
+        }
+
+        loop {
+            if self.stop.is_cancelled() {
+                tracing::info!("auto-commit timer stopped");
+                break;
+            }
+            let config = self.config.read().await.clone();
+
+            // Outside the configured hours, wait for the next window
+            if let Some(wait) = schedule::delay_until_open(&config.schedule) {
+                *self.next_run.lock().await = Some(schedule::next_run_at(&config.schedule, wait));
+                tracing::debug!(wait_secs = wait.as_secs(), "outside scheduled hours");
+                tokio::select! {
+                    _ = tokio::time::sleep(wait.min(schedule::RECHECK).min(QUEUE_CHECK)) => {}
+                    _ = self.stop.cancelled() => {}
+                }
+                continue;
+            }
+
+            // Finished cycles come back with their next delay
+            while let Ok((repo, delay)) = done_rx.try_recv() {
+                running.remove(&repo);
+                let jitter = jitter(&repo, config.schedule_jitter_secs);
+                queue.push(queued(&config, &repo, delay + jitter, false));
+            }
+            // Follow the schedule as the config changes
+            let scheduled = config.scheduled_repos();
+            queue.retain(|run| scheduled.contains(&run.repo));
+            for repo in &scheduled {
+                if !running.contains(repo) && !queue.iter().any(|run| &run.repo == repo) {
+                    let jitter = jitter(repo, config.schedule_jitter_secs);
+                    queue.push(queued(&config, repo, jitter, false));
+                }
+            }
+
+            let now = unix_now();
+            let (due, caught_up) = match queue.peek() {
+                Some(next) => (next.due, next.caught_up),
+                None => {
+                    // Everything is running; wait for a cycle to finish
+                    *self.next_run.lock().await = None;
+                    tokio::select! {
+                        done = wait_for_done(&mut done_rx) => {
+                            if let Some((repo, delay)) = done {
+                                running.remove(&repo);
+                                let jitter = jitter(&repo, config.schedule_jitter_secs);
+                                queue.push(queued(&config, &repo, delay + jitter, false));
+                            }
+                        }
+                        _ = self.stop.cancelled() => {}
+                    }
+                    continue;
+                }
+            };
+            *self.next_run.lock().await = Some(due.max(now));
+            if due > now {
+                let wait = Duration::from_secs(due - now).min(QUEUE_CHECK);
+                tokio::select! {
+                    _ = timer_state::sleep(wait) => {}
+                    done = done_rx.recv() => {
+                        if let Some((repo, delay)) = done {
+                            running.remove(&repo);
+                            let jitter = jitter(&repo, config.schedule_jitter_secs);
+                            queue.push(queued(&config, &repo, delay + jitter, false));
+                        }
+                    }
+                    _ = self.stop.cancelled() => {}
+                }
+                continue;
+            }
+
+            let run = queue.pop().expect("peeked above");
+            // Woken up after sleeping through whole intervals
+            let interval = Duration::from_secs(config.interval_for(&run.repo) * 60);
+            let late = Duration::from_secs(now - due);
+            if !caught_up && late >= interval {
+                let wait = timer_state::catch_up_wait(
+                    config.catch_up_policy,
+                    late,
+                    interval,
+                    Duration::from_secs(config.catch_up_delay_secs),
+                );
+                let missed = late.as_secs() / interval.as_secs().max(1);
+                tracing::info!(repo = %run.repo, missed, wait_secs = wait.as_secs(), "missed scheduled runs");
+                events::emit(self.events.as_ref(), "missed-runs", &MissedRuns {
+                    repo: run.repo.clone(),
+                    missed,
+                    policy: config.catch_up_policy,
+                    next_run_in_secs: wait.as_secs(),
+                });
+                if !wait.is_zero() {
+                    queue.push(queued(&config, &run.repo, wait, true));
+                    continue;
+                }
+            }
+
+            running.insert(run.repo.clone());
+            spawn_cycle(self.clone(), run.repo, cycles.clone(), done_tx.clone());
+        }
+    }
+}
+
+// One cycle, once a cycle slot is free; reports back the delay until the
+// repository is due again
+fn spawn_cycle(
+    scheduler: Scheduler,
+    repo: String,
+    cycles: Arc<Semaphore>,
+    done: mpsc::UnboundedSender<(String, Duration)>,
+) {
+    tokio::spawn(async move {
+        let _slot = cycles.acquire_owned().await.ok();
+        let config = scheduler.config.read().await.clone();
+        let interval_minutes = config.interval_for(&repo);
+
+        if let Err(e) = timer_state::record_attempt(&repo) {
+            tracing::warn!(repo = %repo, error = %e, "could not save timer state");
+        }
+        let cancel = scheduler.cancel.lock().await.clone();
+        let events = scheduler.events.as_ref();
+        match pipeline::run_scheduled_commit(&repo, &config, interval_minutes, events, &cancel).await {
+            Ok(message) => {
+                if let Err(e) = timer_state::record_run(&repo) {
+                    tracing::warn!(repo = %repo, error = %e, "could not save timer state");
+                }
+                if !pipeline::is_skip(&message) {
+                    events::emit(events, "commit-status", &message);
+                }
+            }
+            Err(error) => events::emit(events, "commit-error", &error),
+        }
+
+        // Repos in safe mode are retried less often
+        let delay = health::cycle_delay(&repo, interval_minutes, &config);
+        tracing::debug!(repo = %repo, delay_secs = delay.as_secs(), "next cycle scheduled");
+        done.send((repo, delay)).ok();
+    });
+}
+
+async fn wait_for_done(done: &mut mpsc::UnboundedReceiver<(String, Duration)>) -> Option<(String, Duration)> {
+    tokio::time::timeout(QUEUE_CHECK, done.recv()).await.ok().flatten()
+}
+
+fn queued(config: &AppConfig, repo: &str, delay: Duration, caught_up: bool) -> QueuedRun {
+    QueuedRun {
+        due: unix_now() + delay.as_secs(),
+        priority: config.repo_settings(repo).schedule_priority,
+        repo: repo.to_string(),
+        caught_up,
+    }
+}
+
+fn permits(limit: usize) -> usize {
+    if limit == 0 {
+        Semaphore::MAX_PERMITS
+    } else {
+        limit
+    }
+}
+
+// Up to `max_secs`, different on every call
+fn jitter(repo: &str, max_secs: u64) -> Duration {
+    if max_secs == 0 {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().hash_one((repo, SystemTime::now()));
+    Duration::from_secs(random % (max_secs + 1))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(due: u64, priority: i32, repo: &str) -> QueuedRun {
+        QueuedRun { due, priority, repo: repo.to_string(), caught_up: false }
+    }
+
+    #[test]
+    fn earliest_then_highest_priority_runs_first() {
+        let mut queue = BinaryHeap::from(vec![
+            run(200, 9, "late"),
+            run(100, 0, "low"),
+            run(100, 5, "high-b"),
+            run(100, 5, "high-a"),
+        ]);
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop().map(|run| run.repo)).collect();
+        assert_eq!(order, ["high-a", "high-b", "low", "late"]);
+    }
+
+    #[test]
+    fn equality_follows_the_ordering() {
+        let caught_up = QueuedRun { caught_up: true, ..run(100, 0, "app") };
+        assert_eq!(caught_up.cmp(&run(100, 0, "app")), Ordering::Equal);
+        assert!(caught_up == run(100, 0, "app"));
+        assert!(caught_up != run(100, 1, "app"));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_bound() {
+        assert_eq!(jitter("app", 0), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter("app", 5) <= Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn zero_cycles_means_no_limit() {
+        assert_eq!(permits(0), Semaphore::MAX_PERMITS);
+        assert_eq!(permits(2), 2);
+    }
+}
@@ -0,0 +1,243 @@
+//! Rule-based Conventional Commits scopes, so auto-commits use the same scope
+//! for the same part of the tree instead of whatever the model picks that day.
+//! In a monorepo the scope is the package the changes are in.
+
+use crate::config::AppConfig;
+use crate::conventional;
+use crate::workspace;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// What scopes are derived from in one repository.
+#[derive(Clone, Debug, Default)]
+pub struct ScopeRules {
+    pub root: String,
+    pub overrides: HashMap<String, String>,
+    // Package directory -> scope, from `scope_patterns` and, with
+    // `workspace_scopes`, the workspace manifests
+    pub packages: Vec<(String, String)>,
+}
+
+impl ScopeRules {
+    pub fn for_repo(repo_path: &str, config: &AppConfig) -> Self {
+        let packages = workspace::discover(Path::new(repo_path), &config.scope_patterns, config.workspace_scopes)
+            .into_iter()
+            .map(|package| (package.dir, package.name.to_lowercase()))
+            .collect();
+
+        ScopeRules {
+            root: config.scope_root.clone(),
+            overrides: config.scope_overrides.clone(),
+            packages,
+        }
+    }
+
+    /// The only scopes a message may use, lowercased; empty when the repo
+    /// has no packages and any grounded scope will do.
+    pub fn allowed(&self) -> Vec<String> {
+        if self.packages.is_empty() {
+            return Vec::new();
+        }
+        let mut allowed: Vec<String> = self
+            .packages
+            .iter()
+            .map(|(_, scope)| scope.clone())
+            .chain(self.overrides.values().map(|scope| scope.to_lowercase()))
+            .collect();
+        allowed.sort();
+        allowed.dedup();
+        allowed
+    }
+}
+
+/// The scope shared by every path in `paths`, or `None` when they span
+/// several scopes. Overrides match on the longest path prefix, then package
+/// directories; otherwise the first segment under `root` is used (the file
+/// stem for files directly in it), falling back to the top-level directory
+/// for paths outside `root`.
+pub fn infer(paths: &[String], rules: &ScopeRules) -> Option<String> {
+    let mut scopes = paths.iter().map(|path| scope_for(path, rules));
+
+    let first = scopes.next()??;
+    scopes.all(|scope| scope.as_ref() == Some(&first)).then_some(first)
+}
+
+/// The distinct scopes of `paths`, in order of first appearance.
+pub fn candidates(paths: &[String], rules: &ScopeRules) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in paths.iter().filter_map(|path| scope_for(path, rules)) {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    scopes
+}
+
+/// A line for the system instruction naming the scopes the model may use,
+/// when the repo has packages.
+pub fn prompt_hint(paths: &[String], rules: &ScopeRules) -> Option<String> {
+    let allowed = rules.allowed();
+    if allowed.is_empty() {
+        return None;
+    }
+    let candidates = candidates(paths, rules);
+    let mut hint = format!(
+        "This repository is a monorepo. The scope must be one of its packages: {}.",
+        allowed.join(", ")
+    );
+    match candidates.len() {
+        0 => {}
+        1 => hint.push_str(&format!(" These changes are in `{}`; use it as the scope.", candidates[0])),
+        _ => hint.push_str(&format!(
+            " These changes span {}; use the main one as the scope.",
+            candidates.join(", ")
+        )),
+    }
+    Some(hint)
+}
+
+fn scope_for(path: &str, rules: &ScopeRules) -> Option<String> {
+    let path = path.trim_start_matches("./");
+    let under = |prefix: &str| {
+        let prefix = prefix.trim_end_matches('/');
+        path == prefix || path.starts_with(&format!("{}/", prefix))
+    };
+
+    let best_override = rules
+        .overrides
+        .iter()
+        .filter(|(prefix, _)| under(prefix))
+        .max_by_key(|(prefix, _)| prefix.len());
+    if let Some((_, scope)) = best_override {
+        return Some(scope.clone());
+    }
+
+    // Nested packages: the innermost one wins
+    let package = rules
+        .packages
+        .iter()
+        .filter(|(dir, _)| under(dir))
+        .max_by_key(|(dir, _)| dir.len());
+    if let Some((_, scope)) = package {
+        return Some(scope.clone());
+    }
+
+    let root = rules.root.trim_matches('/');
+    let relative = if root.is_empty() {
+        Some(path)
+    } else {
+        path.strip_prefix(root).and_then(|rest| rest.strip_prefix('/'))
+    };
+
+    match relative {
+        Some(relative) => {
+            let segment = relative.split('/').next()?;
+            let stem = segment.split('.').next().unwrap_or(segment);
+            (!stem.is_empty()).then(|| stem.to_lowercase())
+        }
+        // Outside the root only directories make sensible scopes
+        None => path
+            .split_once('/')
+            .map(|(directory, _)| directory.to_lowercase()),
+    }
+}
+
+/// Replaces a missing or invented scope in a Conventional Commits `message`.
+/// A scope counts as invented when it is neither an override nor a segment
+/// of any changed path, or, in a repo with packages, not one of the allowed
+/// scopes. Messages that aren't Conventional are left alone.
+pub fn apply(message: &str, paths: &[String], rules: &ScopeRules) -> String {
+    let mut parsed = match conventional::parse(message) {
+        Some(parsed) => parsed,
+        None => return message.to_string(),
+    };
+
+    let inferred = infer(paths, rules);
+    let allowed = rules.allowed();
+    let grounded = parsed.scope.as_ref().is_some_and(|scope| {
+        let scope = scope.to_lowercase();
+        if !allowed.is_empty() {
+            return allowed.contains(&scope);
+        }
+        rules.overrides.values().any(|value| value.to_lowercase() == scope)
+            || paths.iter().any(|path| {
+                path.to_lowercase()
+                    .split(['/', '.'])
+                    .any(|segment| segment == scope)
+            })
+    });
+
+    match inferred {
+        Some(inferred) if !grounded => parsed.scope = Some(inferred),
+        None if !grounded => parsed.scope = None,
+        _ => return message.to_string(),
+    }
+
+    let body = conventional::body(message);
+    if body.trim().is_empty() {
+        parsed.header()
+    } else {
+        format!("{}\n\n{}", parsed.header(), body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> ScopeRules {
+        ScopeRules { root: "src".into(), ..ScopeRules::default() }
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn infers_the_segment_under_the_root() {
+        assert_eq!(infer(&paths(&["src/auth/login.rs", "src/auth/token.rs"]), &rules()), Some("auth".into()));
+        assert_eq!(infer(&paths(&["src/Config.rs"]), &rules()), Some("config".into()));
+        assert_eq!(infer(&paths(&["docs/guide.md"]), &rules()), Some("docs".into()));
+        assert_eq!(infer(&paths(&["README.md"]), &rules()), None);
+    }
+
+    #[test]
+    fn no_scope_when_paths_span_several() {
+        assert_eq!(infer(&paths(&["src/auth/login.rs", "src/api/routes.rs"]), &rules()), None);
+    }
+
+    #[test]
+    fn the_longest_override_wins() {
+        let mut rules = rules();
+        rules.overrides.insert("src".into(), "core".into());
+        rules.overrides.insert("src/auth".into(), "login".into());
+        assert_eq!(infer(&paths(&["src/auth/token.rs"]), &rules), Some("login".into()));
+        assert_eq!(infer(&paths(&["src/api/routes.rs"]), &rules), Some("core".into()));
+    }
+
+    #[test]
+    fn the_innermost_package_wins() {
+        let rules = ScopeRules {
+            packages: vec![("packages".into(), "all".into()), ("packages/web".into(), "web".into())],
+            ..rules()
+        };
+        assert_eq!(infer(&paths(&["packages/web/src/app.ts"]), &rules), Some("web".into()));
+        assert_eq!(rules.allowed(), vec!["all".to_string(), "web".to_string()]);
+    }
+
+    #[test]
+    fn apply_replaces_an_invented_scope() {
+        let changed = paths(&["src/auth/login.rs"]);
+        assert_eq!(apply("feat(ui): add login\n\nBody", &changed, &rules()), "feat(auth): add login\n\nBody");
+        assert_eq!(apply("feat(login): add login", &changed, &rules()), "feat(login): add login");
+        assert_eq!(apply("Add login", &changed, &rules()), "Add login");
+    }
+
+    #[test]
+    fn prompt_hint_only_for_monorepos() {
+        assert_eq!(prompt_hint(&paths(&["src/main.rs"]), &rules()), None);
+        let rules = ScopeRules { packages: vec![("packages/web".into(), "web".into())], ..rules() };
+        let hint = prompt_hint(&paths(&["packages/web/index.ts"]), &rules).unwrap();
+        assert!(hint.contains("use it as the scope"));
+    }
+}
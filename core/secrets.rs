@@ -0,0 +1,39 @@
+use keyring::Entry;
+
+// All secrets live under one keychain service so they are easy to find and remove
+const SERVICE: &str = "auto-commit-app";
+
+pub const BITBUCKET_TOKEN: &str = "bitbucket-token";
+pub const CONTROL_API_TOKEN: &str = "control-api-token";
+pub const GEMINI_API_KEY: &str = "gemini-api-key";
+pub const GITHUB_TOKEN: &str = "github-token";
+pub const GITLAB_TOKEN: &str = "gitlab-token";
+pub const PROXY_PASSWORD: &str = "proxy-password";
+pub const JIRA_TOKEN: &str = "jira-token";
+pub const LINEAR_TOKEN: &str = "linear-token";
+
+fn entry(name: &str) -> Result<Entry, String> {
+    Entry::new(SERVICE, name).map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+/// Reads a secret from the OS keychain. Returns `None` when it was never stored.
+pub fn get_secret(name: &str) -> Result<Option<String>, String> {
+    match entry(name)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read {} from keychain: {}", name, e)),
+    }
+}
+
+pub fn set_secret(name: &str, secret: &str) -> Result<(), String> {
+    entry(name)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to store {} in keychain: {}", name, e))
+}
+
+pub fn delete_secret(name: &str) -> Result<(), String> {
+    match entry(name)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to remove {} from keychain: {}", name, e)),
+    }
+}
@@ -0,0 +1,112 @@
+use crate::config::AppConfig;
+use crate::gemini::{self, ModelOptions};
+use crate::history::{EntryOrigin, HistoryStore};
+use crate::status;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SESSION_SYSTEM_CONTEXT: &str = r#"You write short end-of-session notes for a developer whose work was auto-committed.
+
+RULES:
+1. Two to four sentences, plain text, no Markdown headings
+2. Summarize what was committed during the session as themes, not a list of every commit
+3. Mention what is still uncommitted, if anything, so the developer knows where to pick up
+4. Do not invent work that is not in the commits or the uncommitted files
+
+Always respond with ONLY the note, no explanations or additional text."#;
+
+/// Checkpoint emitted as `session-summary` when the auto-commit timer stops.
+#[derive(Serialize, Clone, Debug)]
+pub struct SessionSummary {
+    pub repo: String,
+    // Unix seconds
+    pub started_at: i64,
+    pub ended_at: i64,
+    // Subject lines of the commits the app made during the session
+    pub commits: Vec<String>,
+    pub uncommitted: Vec<String>,
+    pub summary: String,
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Summarizes the session on `repo_id` that began at `started_at`. The note
+/// is written by Gemini; without a key, or if the request fails, a plain
+/// count is used instead so the checkpoint is never lost.
+pub async fn summarize(repo_id: &str, started_at: i64, config: &AppConfig) -> SessionSummary {
+    let commits: Vec<String> = HistoryStore::load()
+        .map(|store| store.entries)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| {
+            entry.repo_id == repo_id
+                && entry.origin == EntryOrigin::App
+                && entry.timestamp >= started_at
+        })
+        .map(|entry| entry.message.lines().next().unwrap_or_default().to_string())
+        .collect();
+
+    let uncommitted: Vec<String> = match status::repo_status(repo_id) {
+        Ok(status) => {
+            let mut paths: Vec<String> = status
+                .staged
+                .into_iter()
+                .chain(status.unstaged)
+                .map(|change| change.path)
+                .chain(status.untracked)
+                .collect();
+            paths.sort();
+            paths.dedup();
+            paths
+        }
+        Err(_) => Vec::new(),
+    };
+
+    let fallback = format!(
+        "{} commit(s) this session, {} file(s) left uncommitted.",
+        commits.len(),
+        uncommitted.len()
+    );
+    let summary = if config.api_key().is_empty() || (commits.is_empty() && uncommitted.is_empty()) {
+        fallback
+    } else {
+        let prompt = format!(
+            "Commits made this session:\n{}\n\nFiles still uncommitted:\n{}",
+            bullet_list(&commits),
+            bullet_list(&uncommitted)
+        );
+        gemini::generate(
+            &config.api_key(),
+            &ModelOptions::from_config(config).for_repo(repo_id),
+            SESSION_SYSTEM_CONTEXT,
+            &prompt,
+        )
+        .await
+        .unwrap_or(fallback)
+    };
+
+    SessionSummary {
+        repo: repo_id.to_string(),
+        started_at,
+        ended_at: unix_now(),
+        commits,
+        uncommitted,
+        summary,
+    }
+}
+
+fn bullet_list(items: &[String]) -> String {
+    if items.is_empty() {
+        return "(none)".into();
+    }
+    items
+        .iter()
+        .map(|item| format!("- {}", item))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
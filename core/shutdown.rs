@@ -0,0 +1,56 @@
+//! Graceful exit: cycles register while they run, so on exit the app can
+//! cancel them and wait, for a bounded time, until they have wound down:
+//! rolled back what they staged and recorded unpushed commits in the
+//! journal. Once shutdown has begun no new cycle starts.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held by a running cycle; dropping it deregisters the cycle.
+pub struct InFlight(());
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Registers a cycle, or returns None once shutdown has begun.
+pub fn enter() -> Option<InFlight> {
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    let guard = InFlight(());
+    // Checked after registering, so `wait_idle` can't miss a cycle that
+    // started just before shutdown
+    (!SHUTTING_DOWN.load(Ordering::SeqCst)).then_some(guard)
+}
+
+/// Stops new cycles from starting.
+pub fn begin() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Waits until no cycle is running, at most `timeout`. Returns false when
+/// some were still running; the journal rolls those back at the next
+/// launch.
+pub async fn wait_idle(timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, async {
+        while IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .is_ok()
+}
+
+pub fn in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::SeqCst)
+}
@@ -0,0 +1,78 @@
+//! Significance filter: timed cycles skip changes that are only whitespace
+//! (line endings, indentation, trailing spaces), or at most a few lines once
+//! whitespace is ignored, instead of committing "style: fix whitespace"
+//! every interval.
+
+use crate::config::RepoSettings;
+use git2::{Delta, Diff, DiffFormat, DiffOptions, Repository};
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TrivialChange {
+    pub repo: String,
+    pub files: usize,
+    // Changed lines as git sees them
+    pub changed_lines: usize,
+    // Changed lines left once whitespace is ignored
+    pub significant_lines: usize,
+    pub max_lines: usize,
+}
+
+/// Measures everything `git add .` would commit in `repo`. Returns the
+/// measurement when the change is trivial by `settings`: no file added,
+/// removed or binary, and no more than `trivial_change_max_lines` lines
+/// changed besides whitespace.
+pub fn check(repo: &Repository, repo_id: &str, settings: &RepoSettings) -> Result<Option<TrivialChange>, String> {
+    if !settings.skip_trivial_changes {
+        return Ok(None);
+    }
+
+    let raw = changes(repo, false)?;
+    // New, deleted, renamed and binary files matter whatever their content
+    let structural = raw.deltas().any(|delta| delta.status() != Delta::Modified || delta.flags().is_binary());
+    if structural {
+        return Ok(None);
+    }
+    let changed_lines = count_changed_lines(&raw)?;
+    if changed_lines == 0 {
+        return Ok(None);
+    }
+
+    let significant_lines = count_changed_lines(&changes(repo, true)?)?;
+    if significant_lines > settings.trivial_change_max_lines {
+        return Ok(None);
+    }
+    Ok(Some(TrivialChange {
+        repo: repo_id.to_string(),
+        files: raw.deltas().len(),
+        changed_lines,
+        significant_lines,
+        max_lines: settings.trivial_change_max_lines,
+    }))
+}
+
+fn changes(repo: &Repository, ignore_whitespace: bool) -> Result<Diff<'_>, String> {
+    let mut options = DiffOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .ignore_submodules(true)
+        .ignore_whitespace(ignore_whitespace)
+        .ignore_whitespace_eol(ignore_whitespace)
+        .ignore_blank_lines(ignore_whitespace);
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut options))
+        .map_err(|e| format!("Failed to read changes: {}", e))
+}
+
+fn count_changed_lines(diff: &Diff<'_>) -> Result<usize, String> {
+    let mut lines = 0;
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-') {
+            lines += 1;
+        }
+        true
+    })
+    .map_err(|e| format!("Failed to read the diff: {}", e))?;
+    Ok(lines)
+}
@@ -0,0 +1,172 @@
+//! Snapshot mode: the working tree is committed to a parallel ref,
+//! `refs/gitgenius/snapshots/<branch>`, instead of the current branch. The
+//! branch, HEAD and the index are never touched, so history stays clean
+//! while every cycle remains restorable.
+
+use crate::artifacts;
+use crate::config::RepoSettings;
+use crate::identity;
+use git2::build::CheckoutBuilder;
+use git2::{IndexAddOption, IndexMatchedPath, Oid, Repository, Signature};
+use serde::Serialize;
+use std::path::Path;
+
+const SNAPSHOT_REF_PREFIX: &str = "refs/gitgenius/snapshots/";
+
+// Trailer recording the commit HEAD pointed at
+const BASE_TRAILER: &str = "\n\nSnapshot-of: ";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Snapshot {
+    pub sha: String,
+    pub branch: String,
+    pub message: String,
+    // Unix seconds
+    pub timestamp: i64,
+    // HEAD when the snapshot was taken, None on an unborn branch
+    pub base: Option<String>,
+}
+
+// Detached HEADs and unborn branches get their own refs too
+fn current_branch(repo: &Repository) -> String {
+    match repo.head() {
+        Ok(head) if head.is_branch() => head.shorthand().unwrap_or("HEAD").to_string(),
+        Ok(_) => "HEAD".into(),
+        Err(_) => repo
+            .find_reference("HEAD")
+            .ok()
+            .and_then(|head| head.symbolic_target().map(str::to_string))
+            .and_then(|target| target.strip_prefix("refs/heads/").map(str::to_string))
+            .unwrap_or_else(|| "HEAD".into()),
+    }
+}
+
+fn from_commit(commit: &git2::Commit, branch: &str) -> Snapshot {
+    let full_message = commit.message().unwrap_or_default();
+    let (message, base) = match full_message.rsplit_once(BASE_TRAILER) {
+        Some((message, base)) => (message, Some(base.trim().to_string())),
+        None => (full_message, None),
+    };
+    Snapshot {
+        sha: commit.id().to_string(),
+        branch: branch.to_string(),
+        message: message.trim_end().to_string(),
+        timestamp: commit.time().seconds(),
+        base,
+    }
+}
+
+fn snapshot_ref(branch: &str) -> String {
+    format!("{}{}", SNAPSHOT_REF_PREFIX, branch)
+}
+
+// The repository's identity, or a placeholder: a snapshot never leaves the
+// machine, so it shouldn't fail for want of one
+fn signature(repo: &Repository, settings: &RepoSettings) -> Result<Signature<'static>, String> {
+    identity::resolve(repo, settings)
+        .or_else(|_| Signature::now("GitGenius", "gitgenius@localhost"))
+        .map_err(|e| e.to_string())
+}
+
+/// Commits the working tree, untracked files included (ignored ones
+/// excluded), on top of the current branch's snapshot ref. Returns `None`
+/// when nothing changed since the last snapshot.
+pub fn take(repo: &Repository, message: &str, settings: &RepoSettings) -> Result<Option<Snapshot>, String> {
+    let branch = current_branch(repo);
+    let refname = snapshot_ref(&branch);
+
+    // Build the tree in the in-memory index and reload it from disk
+    // afterwards, so the user's staging area is left as it was
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    // 1 skips the path, so blocklisted files stay out of snapshots too
+    let skip_blocked: &mut IndexMatchedPath = &mut |path: &Path, _: &[u8]| {
+        artifacts::is_blocked(&path.to_string_lossy().replace('\\', "/"), &settings.staging_blocklist) as i32
+    };
+    let tree_id = index
+        .add_all(["*"], IndexAddOption::DEFAULT, Some(&mut *skip_blocked))
+        .and_then(|_| index.update_all(["*"], Some(&mut *skip_blocked)))
+        .and_then(|_| index.write_tree());
+    index.read(true).map_err(|e| e.to_string())?;
+    let tree = repo
+        .find_tree(tree_id.map_err(|e| format!("Failed to build snapshot tree: {}", e))?)
+        .map_err(|e| e.to_string())?;
+
+    let parent = repo
+        .find_reference(&refname)
+        .ok()
+        .and_then(|reference| reference.peel_to_commit().ok());
+    if parent.as_ref().is_some_and(|parent| parent.tree_id() == tree.id()) {
+        return Ok(None);
+    }
+
+    let base = repo.head().ok().and_then(|head| head.target()).map(|oid| oid.to_string());
+    let full_message = match &base {
+        Some(base) => format!("{}{}{}", message.trim_end(), BASE_TRAILER, base),
+        None => message.trim_end().to_string(),
+    };
+
+    let signature = signature(repo, settings)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let sha = repo
+        .commit(Some(&refname), &signature, &signature, &full_message, &tree, &parents)
+        .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+
+    Ok(Some(Snapshot {
+        sha: sha.to_string(),
+        branch,
+        message: message.trim_end().to_string(),
+        timestamp: signature.when().seconds(),
+        base,
+    }))
+}
+
+/// Snapshots of `branch` (the current branch by default), newest first.
+pub fn list_snapshots(repo_id: &str, branch: Option<&str>, limit: usize) -> Result<Vec<Snapshot>, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let branch = branch.map(str::to_string).unwrap_or_else(|| current_branch(&repo));
+    let tip = match repo.find_reference(&snapshot_ref(&branch)) {
+        Ok(reference) => reference.target(),
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut revwalk = repo.revwalk().map_err(|e| e.to_string())?;
+    if let Some(tip) = tip {
+        revwalk.push(tip).map_err(|e| e.to_string())?;
+    }
+
+    let mut snapshots = Vec::new();
+    for oid in revwalk.take(limit) {
+        let commit = repo
+            .find_commit(oid.map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        snapshots.push(from_commit(&commit, &branch));
+    }
+    Ok(snapshots)
+}
+
+/// Overwrites the working tree with snapshot `sha`. The current state is
+/// snapshotted first so the restore can itself be undone. HEAD doesn't
+/// move and the index is reset to it, so the restored content shows up as
+/// unstaged changes.
+pub fn restore_snapshot(repo_id: &str, sha: &str) -> Result<Snapshot, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let oid = Oid::from_str(sha).map_err(|e| format!("Invalid snapshot id: {}", e))?;
+    let commit = repo.find_commit(oid).map_err(|_| format!("Snapshot {} not found", sha))?;
+    let tree = commit.tree().map_err(|e| e.to_string())?;
+
+    take(&repo, &format!("chore: state before restoring snapshot {:.7}", sha))?;
+
+    repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().force()))
+        .map_err(|e| format!("Failed to restore snapshot: {}", e))?;
+
+    let mut index = repo.index().map_err(|e| e.to_string())?;
+    let reset = match repo.head().and_then(|head| head.peel_to_tree()) {
+        Ok(head_tree) => index.read_tree(&head_tree),
+        Err(_) => index.clear(),
+    };
+    reset
+        .and_then(|_| index.write())
+        .map_err(|e| format!("Failed to reset the index: {}", e))?;
+
+    Ok(from_commit(&commit, &current_branch(&repo)))
+}
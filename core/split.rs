@@ -0,0 +1,121 @@
+//! Split-branch commits: staged changes under configured paths go to a
+//! separate branch (e.g. a GitHub Pages `docs` branch) by building the tree
+//! directly from the index, so the working tree is never checked out.
+
+use git2::build::TreeUpdateBuilder;
+use git2::{BranchType, FileMode, Oid, Repository, Signature};
+use std::path::Path;
+
+/// Paths staged for commit that fall under one of `prefixes`.
+pub fn staged_paths_under(repo: &Repository, prefixes: &[String]) -> Result<Vec<String>, String> {
+    Ok(staged_paths(repo)?
+        .into_iter()
+        .filter(|path| matches_prefix(path, prefixes))
+        .collect())
+}
+
+/// Every path staged for commit.
+pub fn staged_paths(repo: &Repository) -> Result<Vec<String>, String> {
+    let head_tree = repo.head().and_then(|head| head.peel_to_tree()).ok();
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, None)
+        .map_err(|e| e.to_string())?;
+
+    let mut paths = Vec::new();
+    for delta in diff.deltas() {
+        // Deletions only have an old path, renames touch both
+        for file in [delta.old_file(), delta.new_file()] {
+            if let Some(path) = file.path().map(|path| path.to_string_lossy().replace('\\', "/")) {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    Ok(paths)
+}
+
+fn matches_prefix(path: &str, prefixes: &[String]) -> bool {
+    prefixes.iter().any(|prefix| {
+        let prefix = prefix.trim_end_matches('/');
+        !prefix.is_empty() && (path == prefix || path.starts_with(&format!("{}/", prefix)))
+    })
+}
+
+/// Commits the staged state of `paths` on top of `branch`, creating the
+/// branch from HEAD if it doesn't exist. Returns `None` when the branch
+/// already has exactly that content.
+pub fn commit_to_branch(
+    repo: &Repository,
+    branch: &str,
+    paths: &[String],
+    message: &str,
+    signature: &Signature,
+) -> Result<Option<Oid>, String> {
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let parent = match repo.find_branch(branch, BranchType::Local) {
+        Ok(existing) => existing.get().peel_to_commit(),
+        Err(_) => repo.head().and_then(|head| head.peel_to_commit()),
+    }
+    .map_err(|e| format!("Failed to find the tip of {}: {}", branch, e))?;
+    let parent_tree = parent.tree().map_err(|e| e.to_string())?;
+
+    let mut builder = TreeUpdateBuilder::new();
+    for path in paths {
+        match index.get_path(Path::new(path), 0) {
+            Some(entry) => {
+                builder.upsert(path.as_str(), entry.id, file_mode(entry.mode));
+            }
+            None => {
+                builder.remove(path.as_str());
+            }
+        }
+    }
+
+    let tree_id = builder
+        .create_updated(repo, &parent_tree)
+        .map_err(|e| format!("Failed to build tree for {}: {}", branch, e))?;
+    if tree_id == parent_tree.id() {
+        return Ok(None);
+    }
+
+    let tree = repo.find_tree(tree_id).map_err(|e| e.to_string())?;
+    repo.commit(
+        Some(&format!("refs/heads/{}", branch)),
+        signature,
+        signature,
+        message,
+        &tree,
+        &[&parent],
+    )
+    .map(Some)
+    .map_err(|e| format!("Failed to commit to {}: {}", branch, e))
+}
+
+fn file_mode(mode: u32) -> FileMode {
+    match mode {
+        0o100755 => FileMode::BlobExecutable,
+        0o120000 => FileMode::Link,
+        0o160000 => FileMode::Commit,
+        _ => FileMode::Blob,
+    }
+}
+
+/// Resets `paths` in the index to HEAD, leaving the working tree alone.
+pub fn unstage(repo: &Repository, paths: &[String]) -> Result<(), String> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+    repo.reset_default(Some(head.as_object()), paths.iter().map(String::as_str))
+        .map_err(|e| format!("Failed to unstage split changes: {}", e))
+}
+
+/// True when the index matches HEAD.
+pub fn nothing_staged(repo: &Repository) -> Result<bool, String> {
+    let head_tree = repo.head().and_then(|head| head.peel_to_tree()).ok();
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, None)
+        .map_err(|e| e.to_string())?;
+    Ok(diff.deltas().len() == 0)
+}
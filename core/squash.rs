@@ -0,0 +1,111 @@
+//! Squash-and-summarize: a run of auto-commits becomes one commit with a
+//! message generated from their combined diff, the cleanup before opening
+//! a pull request.
+
+use crate::amend;
+use crate::config::AppConfig;
+use crate::divergence;
+use crate::history;
+use crate::identity;
+use crate::pipeline;
+use git2::{Oid, Repository, ResetType, Sort};
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SquashResult {
+    pub sha: String,
+    pub message: String,
+    // The commits that were replaced, newest first
+    pub squashed: Vec<String>,
+}
+
+/// Squashes the commits after `from` up to HEAD into one. `to` may name
+/// the end of the range but has to be HEAD, since nothing after it is
+/// replayed. HEAD is soft-reset to `from` and the new commit gets the tree
+/// of the old HEAD, so whatever is staged stays staged and out of it.
+/// Pushed commits are only squashed with `allow_pushed`, as that rewrites
+/// published history; the replaced tip is recorded so the next push may
+/// replace it on the remote.
+pub async fn squash_commits(
+    path: &str,
+    config: &AppConfig,
+    from: &str,
+    to: Option<&str>,
+    allow_pushed: bool,
+) -> Result<SquashResult, String> {
+    let (base, head, squashed, pushed) = {
+        let repo = Repository::open(path).map_err(|e| e.to_string())?;
+        let resolve = |spec: &str| {
+            repo.revparse_single(spec)
+                .and_then(|object| object.peel_to_commit())
+                .map(|commit| commit.id())
+                .map_err(|_| format!("{} is not a commit", spec))
+        };
+        let head = resolve("HEAD")?;
+        let base = resolve(from)?;
+        if let Some(to) = to {
+            if resolve(to)? != head {
+                return Err(format!("{} is not HEAD; only a range ending at HEAD can be squashed", to));
+            }
+        }
+        if !repo.graph_descendant_of(head, base).unwrap_or(false) {
+            return Err(format!("{} is not an ancestor of HEAD", from));
+        }
+
+        let squashed = range(&repo, base, head)?;
+        if squashed.len() < 2 {
+            return Err("Nothing to squash: the range holds fewer than two commits".into());
+        }
+        let pushed = squashed.iter().any(|oid| amend::is_pushed(&repo, *oid));
+        if pushed && !allow_pushed {
+            return Err("Some of these commits were already pushed; squashing them rewrites published history".into());
+        }
+        (base, head, squashed, pushed)
+    };
+
+    let diff_text = pipeline::range_diff(path, &base.to_string(), &head.to_string())?;
+    let generated = pipeline::generate_message(path, config, &diff_text).await?;
+
+    let repo = Repository::open(path).map_err(|e| e.to_string())?;
+    // Fails if HEAD moved while the message was being generated
+    if repo.head().ok().and_then(|current| current.target()) != Some(head) {
+        return Err("HEAD moved while squashing; nothing was changed".into());
+    }
+    let branch = repo.head().ok().and_then(|current| current.name().map(str::to_string));
+    let signature = identity::resolve(&repo, &config.repo_settings(path))?;
+    let tree = repo
+        .find_commit(head)
+        .and_then(|commit| commit.tree())
+        .map_err(|e| e.to_string())?;
+    let parent = repo.find_commit(base).map_err(|e| e.to_string())?;
+
+    repo.reset(parent.as_object(), ResetType::Soft, None)
+        .map_err(|e| format!("Failed to reset to {}: {}", from, e))?;
+    let sha = repo
+        .commit(Some("HEAD"), &signature, &signature, &generated.message, &tree, &[&parent])
+        .map_err(|e| format!("Failed to commit; HEAD was left at {} with the changes staged: {}", from, e))?;
+
+    for oid in &squashed {
+        history::forget_commit(path, &oid.to_string()).ok();
+    }
+    history::record_head_commit(path, Some(generated.tier)).ok();
+    // Lets the next push replace the squashed commits on the remote
+    if let Some(branch) = branch.filter(|_| pushed) {
+        divergence::record_rewrite(path, &branch, head)?;
+    }
+
+    Ok(SquashResult {
+        sha: sha.to_string(),
+        message: generated.message,
+        squashed: squashed.iter().map(Oid::to_string).collect(),
+    })
+}
+
+// Commits reachable from `head` but not `base`, newest first
+fn range(repo: &Repository, base: Oid, head: Oid) -> Result<Vec<Oid>, String> {
+    let mut walk = repo.revwalk().map_err(|e| e.to_string())?;
+    walk.set_sorting(Sort::TOPOLOGICAL).map_err(|e| e.to_string())?;
+    walk.push(head).map_err(|e| e.to_string())?;
+    walk.hide(base).map_err(|e| e.to_string())?;
+    walk.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
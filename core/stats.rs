@@ -0,0 +1,183 @@
+use crate::conventional;
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::summary::{self, CycleDecision};
+use chrono::{Days, Local, NaiveDate, TimeZone};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DayCount {
+    // Local date, `YYYY-MM-DD`
+    pub date: String,
+    pub commits: usize,
+}
+
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub average_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Chart data for one repository over the last `days` days. Commits are the
+/// ones the app made and those imported from before it; cycle counts come
+/// from the cycle summaries.
+#[derive(Serialize, Clone, Debug)]
+pub struct RepoStatistics {
+    pub repo: String,
+    pub commits: usize,
+    // One entry per day, days without commits included
+    pub commits_per_day: Vec<DayCount>,
+    // Conventional Commits type -> count, `other` for everything else
+    pub commit_types: BTreeMap<String, usize>,
+    pub average_files_changed: f64,
+    pub average_lines_changed: f64,
+    // Time spent generating messages
+    pub api_latency: LatencyStats,
+    pub cycles: usize,
+    pub skipped_cycles: usize,
+    pub failed_cycles: usize,
+}
+
+/// Statistics for `repo_id`, or for every repository with history or
+/// cycle summaries in the period.
+pub fn statistics(repo_id: Option<&str>, days: u32) -> Result<Vec<RepoStatistics>, String> {
+    let today = Local::now().date_naive();
+    let first_day = today
+        .checked_sub_days(Days::new(days.saturating_sub(1) as u64))
+        .unwrap_or(today);
+    let since = Local
+        .from_local_datetime(&first_day.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|start| start.timestamp())
+        .unwrap_or_default();
+
+    let history = HistoryStore::load()?;
+    let entries: Vec<_> = history
+        .entries
+        .iter()
+        .filter(|entry| entry.timestamp >= since)
+        .filter(|entry| repo_id.is_none_or(|repo_id| entry.repo_id == repo_id))
+        .collect();
+    let summaries: Vec<_> = summary::recent_summaries(repo_id, usize::MAX)?
+        .into_iter()
+        .filter(|summary| summary.started_at as i64 >= since)
+        .collect();
+
+    let mut repos: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry.repo_id.as_str())
+        .chain(summaries.iter().map(|summary| summary.repo.as_str()))
+        .collect();
+    repos.sort_unstable();
+    repos.dedup();
+
+    let mut statistics = Vec::new();
+    for repo in repos {
+        let commits: Vec<_> = entries.iter().filter(|entry| entry.repo_id == repo).collect();
+
+        let mut per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+        let mut day = first_day;
+        while day <= today {
+            per_day.insert(day, 0);
+            day = match day.succ_opt() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        let mut commit_types = BTreeMap::new();
+        for entry in &commits {
+            if let Some(time) = Local.timestamp_opt(entry.timestamp, 0).single() {
+                *per_day.entry(time.date_naive()).or_default() += 1;
+            }
+            let commit_type = conventional::parse(&entry.message)
+                .map(|parsed| parsed.commit_type)
+                .unwrap_or_else(|| "other".into());
+            *commit_types.entry(commit_type).or_default() += 1;
+        }
+
+        let average = |value: fn(&HistoryEntry) -> usize| {
+            if commits.is_empty() {
+                0.0
+            } else {
+                commits.iter().map(|entry| value(entry) as f64).sum::<f64>() / commits.len() as f64
+            }
+        };
+
+        let cycles: Vec<_> = summaries.iter().filter(|summary| summary.repo == repo).collect();
+        let mut latencies: Vec<u64> = cycles
+            .iter()
+            .flat_map(|summary| summary.stages.iter())
+            .filter(|stage| stage.stage == "generate")
+            .map(|stage| stage.duration_ms)
+            .collect();
+
+        statistics.push(RepoStatistics {
+            repo: repo.to_string(),
+            commits: commits.len(),
+            commits_per_day: per_day
+                .into_iter()
+                .map(|(date, commits)| DayCount { date: date.to_string(), commits })
+                .collect(),
+            commit_types,
+            average_files_changed: average(|entry| entry.files_changed),
+            average_lines_changed: average(|entry| entry.insertions + entry.deletions),
+            api_latency: latency_stats(&mut latencies),
+            cycles: cycles.len(),
+            skipped_cycles: cycles
+                .iter()
+                .filter(|summary| summary.decision == CycleDecision::Skipped)
+                .count(),
+            failed_cycles: cycles
+                .iter()
+                .filter(|summary| summary.decision == CycleDecision::Failed)
+                .count(),
+        });
+    }
+    Ok(statistics)
+}
+
+fn latency_stats(latencies: &mut [u64]) -> LatencyStats {
+    if latencies.is_empty() {
+        return LatencyStats::default();
+    }
+    latencies.sort_unstable();
+
+    let p95_index = (latencies.len() * 95).div_ceil(100).saturating_sub(1);
+    LatencyStats {
+        samples: latencies.len(),
+        average_ms: latencies.iter().sum::<u64>() / latencies.len() as u64,
+        p95_ms: latencies[p95_index],
+        max_ms: latencies[latencies.len() - 1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history;
+    use git2::{Repository, Signature};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn imported_commits_count() {
+        let dir = std::env::temp_dir().join(format!("gitgenius-stats-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("main.rs")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "feat: add main", &tree, &[]).unwrap();
+
+        let repo_id = dir.to_string_lossy().to_string();
+        assert_eq!(history::import_history(&repo_id).unwrap(), 1);
+        let statistics = statistics(Some(&repo_id), 7).unwrap();
+        assert_eq!(statistics.len(), 1);
+        assert_eq!(statistics[0].commits, 1);
+        assert_eq!(statistics[0].commit_types.get("feat"), Some(&1));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,175 @@
+use crate::github;
+use crate::remote;
+use crate::repo_path;
+use git2::{Delta, DiffDelta, Repository, RepositoryState, Status, StatusOptions};
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeType {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Typechange,
+    Conflicted,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FileChange {
+    pub path: String,
+    pub change: ChangeType,
+    // Previous path of a renamed file
+    pub old_path: Option<String>,
+}
+
+/// A multi-step git operation the user has started but not finished.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoOperation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+/// Snapshot of a repository's working tree for the frontend.
+#[derive(Serialize, Clone, Debug)]
+pub struct RepoStatus {
+    // Root of the working tree the requested path is in
+    pub root: String,
+    // Name of the linked worktree, None in the main working tree
+    pub worktree: Option<String>,
+    // None on a detached HEAD
+    pub branch: Option<String>,
+    // None when the branch has no upstream
+    pub ahead: Option<usize>,
+    pub behind: Option<usize>,
+    pub staged: Vec<FileChange>,
+    pub unstaged: Vec<FileChange>,
+    pub untracked: Vec<String>,
+    pub operation: Option<RepoOperation>,
+}
+
+/// The status of the working tree `repo_id` is in; any folder inside it
+/// works.
+pub fn repo_status(repo_id: &str) -> Result<RepoStatus, String> {
+    let found = repo_path::resolve(repo_id)?;
+    let repo = Repository::open(&found.root).map_err(|e| e.to_string())?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true);
+    let statuses = repo.statuses(Some(&mut options)).map_err(|e| e.to_string())?;
+
+    let mut staged = Vec::new();
+    let mut unstaged = Vec::new();
+    let mut untracked = Vec::new();
+
+    for entry in statuses.iter() {
+        let status = entry.status();
+        let path = entry.path().unwrap_or_default().to_string();
+
+        if status.is_conflicted() {
+            unstaged.push(FileChange { path, change: ChangeType::Conflicted, old_path: None });
+            continue;
+        }
+        if status.is_wt_new() {
+            untracked.push(path.clone());
+        }
+
+        if let Some(change) = index_change(status) {
+            staged.push(file_change(&path, change, entry.head_to_index()));
+        }
+        if let Some(change) = workdir_change(status) {
+            unstaged.push(file_change(&path, change, entry.index_to_workdir()));
+        }
+    }
+
+    let (ahead, behind) = match remote::ahead_behind(&repo) {
+        Some((ahead, behind)) => (Some(ahead), Some(behind)),
+        None => (None, None),
+    };
+
+    Ok(RepoStatus {
+        root: found.root,
+        worktree: found.worktree,
+        branch: github::current_branch(&repo).ok(),
+        ahead,
+        behind,
+        staged,
+        unstaged,
+        untracked,
+        operation: operation_in_progress(&repo),
+    })
+}
+
+/// The merge, rebase, cherry-pick, revert or bisect currently in progress.
+pub fn operation_in_progress(repo: &Repository) -> Option<RepoOperation> {
+    match repo.state() {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => Some(RepoOperation::Merge),
+        RepositoryState::Revert | RepositoryState::RevertSequence => Some(RepoOperation::Revert),
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+            Some(RepoOperation::CherryPick)
+        }
+        RepositoryState::Bisect => Some(RepoOperation::Bisect),
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge
+        | RepositoryState::ApplyMailbox
+        | RepositoryState::ApplyMailboxOrRebase => Some(RepoOperation::Rebase),
+    }
+}
+
+fn index_change(status: Status) -> Option<ChangeType> {
+    if status.is_index_new() {
+        Some(ChangeType::Added)
+    } else if status.is_index_modified() {
+        Some(ChangeType::Modified)
+    } else if status.is_index_deleted() {
+        Some(ChangeType::Deleted)
+    } else if status.is_index_renamed() {
+        Some(ChangeType::Renamed)
+    } else if status.is_index_typechange() {
+        Some(ChangeType::Typechange)
+    } else {
+        None
+    }
+}
+
+// Untracked files are listed separately, so `WT_NEW` is not a change here
+fn workdir_change(status: Status) -> Option<ChangeType> {
+    if status.is_wt_modified() {
+        Some(ChangeType::Modified)
+    } else if status.is_wt_deleted() {
+        Some(ChangeType::Deleted)
+    } else if status.is_wt_renamed() {
+        Some(ChangeType::Renamed)
+    } else if status.is_wt_typechange() {
+        Some(ChangeType::Typechange)
+    } else {
+        None
+    }
+}
+
+// `StatusEntry::path` is the old path for renames, so prefer the delta's new path
+fn file_change(path: &str, change: ChangeType, delta: Option<DiffDelta>) -> FileChange {
+    let new_path = delta
+        .as_ref()
+        .and_then(|delta| delta.new_file().path())
+        .map(|new| new.to_string_lossy().to_string());
+    let old_path = delta
+        .filter(|delta| delta.status() == Delta::Renamed)
+        .and_then(|delta| delta.old_file().path().map(|old| old.to_string_lossy().to_string()));
+
+    FileChange {
+        path: new_path.unwrap_or_else(|| path.to_string()),
+        change,
+        old_path,
+    }
+}
@@ -0,0 +1,108 @@
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\gitgenius-events";
+
+/// Newline-delimited JSON feed of pipeline events for external consumers
+/// (dashboards, overlays, status bars). Each line is
+/// `{"event": ..., "payload": ..., "timestamp": <unix seconds>}`.
+#[derive(Clone)]
+pub struct EventStream {
+    sender: broadcast::Sender<String>,
+}
+
+impl Default for EventStream {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(256);
+        EventStream { sender }
+    }
+}
+
+impl EventStream {
+    pub fn publish(&self, event: &str, payload: &Value) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let line = json!({
+            "event": event,
+            "payload": payload,
+            "timestamp": timestamp,
+        });
+
+        // No subscribers is not an error
+        self.sender.send(line.to_string()).ok();
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+
+    /// Accepts subscribers on a Unix socket in the app data dir until the listener fails.
+    #[cfg(unix)]
+    pub async fn serve(&self) -> Result<(), String> {
+        use tokio::net::UnixListener;
+
+        let mut socket_path = crate::config::get_data_dir()?;
+        socket_path.push("events.sock");
+
+        // A stale socket from a previous run would make bind fail
+        std::fs::remove_file(&socket_path).ok();
+
+        let listener = UnixListener::bind(&socket_path)
+            .map_err(|e| format!("Failed to bind event socket: {}", e))?;
+
+        loop {
+            let (socket, _) = listener
+                .accept()
+                .await
+                .map_err(|e| format!("Event socket error: {}", e))?;
+            tokio::spawn(forward(self.sender.subscribe(), socket));
+        }
+    }
+
+    /// Accepts subscribers on the `PIPE_NAME` named pipe until pipe creation fails.
+    #[cfg(windows)]
+    pub async fn serve(&self) -> Result<(), String> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(PIPE_NAME)
+            .map_err(|e| format!("Failed to create event pipe: {}", e))?;
+
+        loop {
+            server
+                .connect()
+                .await
+                .map_err(|e| format!("Event pipe error: {}", e))?;
+
+            // Hand the connected instance to the client and open the next one
+            let client = server;
+            server = ServerOptions::new()
+                .create(PIPE_NAME)
+                .map_err(|e| format!("Failed to create event pipe: {}", e))?;
+            tokio::spawn(forward(self.sender.subscribe(), client));
+        }
+    }
+}
+
+async fn forward<W: AsyncWrite + Unpin>(mut receiver: broadcast::Receiver<String>, mut writer: W) {
+    loop {
+        match receiver.recv().await {
+            Ok(line) => {
+                let line = format!("{}\n", line);
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            // A slow consumer only misses events, it isn't disconnected
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
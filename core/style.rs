@@ -0,0 +1,299 @@
+use crate::config::RepoSettings;
+use crate::conventional::{self, ConventionalSubject};
+use crate::gemini;
+use crate::github;
+use crate::issues;
+use chrono::Local;
+use git2::Repository;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// How generated commit messages are written.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum MessageStyle {
+    #[default]
+    Conventional,
+    Gitmoji,
+    Plain,
+    // Conventional output rendered through `custom_message_template`
+    CustomTemplate,
+}
+
+pub const DEFAULT_TEMPLATE: &str = "{type}({scope}): {subject}";
+
+// Everything `custom_message_template` may use
+pub const TEMPLATE_VARIABLES: [&str; 9] =
+    ["type", "scope", "breaking", "subject", "emoji", "branch", "ticket", "repo", "date"];
+
+/// Template variables that come from the repository rather than the model.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateContext {
+    pub branch: String,
+    // The issue found by `issue_patterns` or set as `issue_ticket`
+    pub ticket: String,
+    // Name of the repository's folder
+    pub repo: String,
+    // Today, `YYYY-MM-DD`
+    pub date: String,
+}
+
+impl TemplateContext {
+    /// The context of the repository at `repo_id`; what can't be read
+    /// stays empty.
+    pub fn for_repo(repo_id: &str, settings: &RepoSettings) -> Self {
+        let repo = Repository::open(repo_id).ok();
+        TemplateContext {
+            branch: repo
+                .as_ref()
+                .and_then(|repo| github::current_branch(repo).ok())
+                .unwrap_or_default(),
+            ticket: repo
+                .as_ref()
+                .and_then(|repo| issues::detect(repo, settings).ok().flatten())
+                .unwrap_or_default(),
+            repo: std::path::Path::new(repo_id)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            date: Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+fn placeholder_regex() -> Regex {
+    Regex::new(r"\{([^{}]*)\}").expect("valid placeholder regex")
+}
+
+/// Checks a `custom_message_template`: only known variables, `{subject}`
+/// among them, on a single line. Empty means the default template.
+pub fn validate_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Ok(());
+    }
+    if template.contains('\n') {
+        return Err("The message template must be a single line".into());
+    }
+    for captures in placeholder_regex().captures_iter(template) {
+        let name = &captures[1];
+        if !TEMPLATE_VARIABLES.contains(&name) {
+            return Err(format!(
+                "Unknown template variable {{{}}}; use one of {}",
+                name,
+                TEMPLATE_VARIABLES.map(|name| format!("{{{}}}", name)).join(", ")
+            ));
+        }
+    }
+    if !template.contains("{subject}") {
+        return Err("The message template must contain {subject}".into());
+    }
+    Ok(())
+}
+
+const GITMOJI: [(&str, &str); 8] = [
+    ("feat", "✨"),
+    ("fix", "🐛"),
+    ("docs", "📝"),
+    ("style", "🎨"),
+    ("refactor", "♻️"),
+    ("test", "✅"),
+    ("chore", "🔧"),
+    ("perf", "⚡️"),
+];
+
+const GITMOJI_CONTEXT: &str = r#"You are an expert Git commit message generator that writes gitmoji commit messages.
+
+COMMIT MESSAGE RULES:
+1. Format: <emoji> <subject>
+2. Emojis: ✨ new feature, 🐛 bug fix, 📝 documentation, 🎨 code style/structure, ♻️ refactor, ✅ tests, 🔧 configuration/chores, ⚡️ performance
+3. Subject: Imperative mood, starts with a capital letter, no period, max 50 characters
+4. Be specific and descriptive
+
+EXAMPLES:
+- ✨ Add JWT token validation
+- 🐛 Resolve null pointer in user endpoint
+- 📝 Update installation instructions
+
+Always respond with ONLY the commit message, no explanations or additional text."#;
+
+const PLAIN_CONTEXT: &str = r#"You are an expert Git commit message generator that writes plain, descriptive commit messages.
+
+COMMIT MESSAGE RULES:
+1. Format: a single sentence subject, optionally followed by a blank line and a short body
+2. No type prefixes, scopes, tags or emojis
+3. Subject: Imperative mood, starts with a capital letter, no period, max 50 characters
+4. Be specific and descriptive
+
+EXAMPLES:
+- Add JWT token validation
+- Resolve null pointer in user endpoint
+
+Always respond with ONLY the commit message, no explanations or additional text."#;
+
+/// System instruction for the given style. Custom templates are filled from a
+/// Conventional Commits answer, so they share its instruction.
+pub fn system_context(style: MessageStyle) -> &'static str {
+    match style {
+        MessageStyle::Conventional | MessageStyle::CustomTemplate => gemini::SYSTEM_CONTEXT,
+        MessageStyle::Gitmoji => GITMOJI_CONTEXT,
+        MessageStyle::Plain => PLAIN_CONTEXT,
+    }
+}
+
+/// `system_context` plus, when `language` is set and isn't English, the
+/// instruction to write in it. Type prefixes and emojis stay as they are.
+pub fn system_instruction(style: MessageStyle, language: &str) -> String {
+    let context = system_context(style);
+    let language = language.trim();
+    if language.is_empty() || language.eq_ignore_ascii_case("english") {
+        return context.to_string();
+    }
+
+    let keep = match style {
+        MessageStyle::Conventional | MessageStyle::CustomTemplate => {
+            "Keep the type and scope in English exactly as in the rules above, e.g. `feat(auth): ...`, and write only the description after the colon"
+        }
+        MessageStyle::Gitmoji => "Keep the emoji, and write the subject",
+        MessageStyle::Plain => "Write the subject",
+    };
+    format!(
+        "{}\n\nLANGUAGE:\n{} and the body in {}.",
+        context, keep, language
+    )
+}
+
+/// Rewrites a generated message so its subject line always matches `style`,
+/// whatever format the model actually answered in. The body is kept as is.
+/// `context` fills the repository variables of a custom template.
+pub fn conform(message: &str, style: MessageStyle, template: &str, context: &TemplateContext) -> String {
+    let first_line = message.lines().next().unwrap_or_default().trim();
+    let parsed = conventional::parse(first_line).or_else(|| from_gitmoji(first_line));
+
+    let subject_line = match style {
+        MessageStyle::Conventional => {
+            let mut parsed = parsed.unwrap_or_else(|| ConventionalSubject {
+                commit_type: "chore".into(),
+                scope: None,
+                breaking: false,
+                subject: first_line.to_string(),
+            });
+            parsed.subject = lowercase_first(trim_period(&parsed.subject));
+            parsed.header()
+        }
+        MessageStyle::Gitmoji => match parsed {
+            Some(parsed) => format!(
+                "{} {}",
+                emoji_for(&parsed.commit_type),
+                capitalize(trim_period(&parsed.subject))
+            ),
+            None => format!("{} {}", emoji_for("chore"), capitalize(trim_period(first_line))),
+        },
+        MessageStyle::Plain => {
+            let subject = parsed.map(|parsed| parsed.subject).unwrap_or_else(|| first_line.to_string());
+            capitalize(trim_period(&subject))
+        }
+        MessageStyle::CustomTemplate => {
+            let parsed = parsed.unwrap_or_else(|| ConventionalSubject {
+                commit_type: "chore".into(),
+                scope: None,
+                breaking: false,
+                subject: first_line.to_string(),
+            });
+            render_template(template, &parsed, context)
+        }
+    };
+
+    let body = conventional::body(message);
+    if body.trim().is_empty() {
+        subject_line
+    } else {
+        format!("{}\n\n{}", subject_line, body)
+    }
+}
+
+/// Fills the `TEMPLATE_VARIABLES` in `template`, in one pass so text the
+/// model wrote is never read as a variable. An empty value also drops the
+/// parentheses or brackets around it, e.g. `({scope})` or `[{ticket}]`.
+pub fn render_template(template: &str, parsed: &ConventionalSubject, context: &TemplateContext) -> String {
+    let template = if template.trim().is_empty() { DEFAULT_TEMPLATE } else { template };
+    let value = |name: &str| template_value(name, parsed, context);
+
+    let mut rendered = template.to_string();
+    for name in TEMPLATE_VARIABLES.iter().filter(|name| value(name) == Some("")) {
+        rendered = rendered
+            .replace(&format!("({{{}}})", name), "")
+            .replace(&format!("[{{{}}}]", name), "");
+    }
+    let rendered = placeholder_regex().replace_all(&rendered, |captures: &Captures| {
+        value(&captures[1]).map_or_else(|| captures[0].to_string(), str::to_string)
+    });
+    // Dropped variables leave doubled spaces behind
+    rendered.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn template_value<'a>(name: &str, parsed: &'a ConventionalSubject, context: &'a TemplateContext) -> Option<&'a str> {
+    Some(match name {
+        "type" => parsed.commit_type.as_str(),
+        "scope" => parsed.scope.as_deref().unwrap_or_default(),
+        "breaking" => {
+            if parsed.breaking {
+                "!"
+            } else {
+                ""
+            }
+        }
+        "subject" => trim_period(&parsed.subject),
+        "emoji" => emoji_for(&parsed.commit_type),
+        "branch" => context.branch.as_str(),
+        "ticket" => context.ticket.as_str(),
+        "repo" => context.repo.as_str(),
+        "date" => context.date.as_str(),
+        _ => return None,
+    })
+}
+
+fn emoji_for(commit_type: &str) -> &'static str {
+    GITMOJI
+        .iter()
+        .find(|(name, _)| *name == commit_type)
+        .map(|(_, emoji)| *emoji)
+        .unwrap_or("🔧")
+}
+
+// Reads a gitmoji subject back as its Conventional equivalent
+fn from_gitmoji(line: &str) -> Option<ConventionalSubject> {
+    GITMOJI.iter().find_map(|(commit_type, emoji)| {
+        let subject = line.strip_prefix(emoji)?.trim();
+        Some(ConventionalSubject {
+            commit_type: commit_type.to_string(),
+            scope: None,
+            breaking: false,
+            subject: subject.to_string(),
+        })
+    })
+}
+
+fn trim_period(subject: &str) -> &str {
+    subject.trim().trim_end_matches('.')
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Leaves acronyms such as "JWT" or "API" untouched
+fn lowercase_first(text: &str) -> String {
+    if text.chars().nth(1).is_some_and(char::is_uppercase) {
+        return text.to_string();
+    }
+
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,118 @@
+//! Submodule policy: whether a superproject's cycles leave submodules
+//! alone, commit their pointer moves on their own, or also commit inside
+//! them first.
+
+use git2::{Repository, StatusOptions};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmodulePolicy {
+    // Neither dirty submodules nor moved pointers are committed
+    #[default]
+    Ignore,
+    // Moved pointers get a commit of their own with a fixed message
+    CommitGitlinks,
+    // Submodules with changes get their own cycle first, then their
+    // pointers are committed as with `CommitGitlinks`
+    Recurse,
+}
+
+/// A submodule whose checked-out commit differs from the one recorded in
+/// the superproject's HEAD.
+#[derive(Serialize, Clone, Debug)]
+pub struct GitlinkChange {
+    pub name: String,
+    pub path: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Paths of all submodules, `/`-separated.
+pub fn paths(repo: &Repository) -> Vec<String> {
+    repo.submodules()
+        .map(|submodules| submodules.iter().map(|submodule| path_of(submodule.path())).collect())
+        .unwrap_or_default()
+}
+
+/// Submodules checked out at another commit than HEAD records.
+pub fn moved(repo: &Repository) -> Vec<GitlinkChange> {
+    let submodules = match repo.submodules() {
+        Ok(submodules) => submodules,
+        Err(_) => return Vec::new(),
+    };
+    submodules
+        .iter()
+        .filter_map(|submodule| {
+            let (from, to) = (submodule.head_id(), submodule.workdir_id());
+            // Not checked out at all is not a move
+            if from == to || to.is_none() {
+                return None;
+            }
+            Some(GitlinkChange {
+                name: submodule.name().unwrap_or_default().to_string(),
+                path: path_of(submodule.path()),
+                from: from.map(|oid| oid.to_string()),
+                to: to.map(|oid| oid.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Working trees of submodules with uncommitted changes, split into those
+/// on a branch and those on a detached HEAD, which a cycle can't push from.
+pub fn with_changes(repo: &Repository) -> (Vec<String>, Vec<String>) {
+    let (mut ready, mut detached) = (Vec::new(), Vec::new());
+    let (submodules, workdir) = match (repo.submodules(), repo.workdir()) {
+        (Ok(submodules), Some(workdir)) => (submodules, workdir),
+        _ => return (ready, detached),
+    };
+    for submodule in submodules {
+        let inner = match submodule.open() {
+            Ok(inner) => inner,
+            Err(_) => continue,
+        };
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).include_ignored(false);
+        let dirty = inner.statuses(Some(&mut options)).is_ok_and(|statuses| !statuses.is_empty());
+        if !dirty {
+            continue;
+        }
+        let full_path = workdir.join(submodule.path()).to_string_lossy().to_string();
+        match inner.head() {
+            Ok(head) if head.is_branch() => ready.push(full_path),
+            _ => detached.push(full_path),
+        }
+    }
+    (ready, detached)
+}
+
+/// Message for the commit that records moved submodule pointers.
+pub fn message(changes: &[GitlinkChange]) -> String {
+    let short = |oid: &Option<String>| oid.as_deref().map(|oid| oid[..7.min(oid.len())].to_string());
+    match changes {
+        [change] => format!(
+            "chore(submodules): update {} to {}",
+            change.path,
+            short(&change.to).unwrap_or_default()
+        ),
+        _ => {
+            let lines: Vec<String> = changes
+                .iter()
+                .map(|change| {
+                    format!(
+                        "- {}: {} -> {}",
+                        change.path,
+                        short(&change.from).unwrap_or_else(|| "new".into()),
+                        short(&change.to).unwrap_or_default()
+                    )
+                })
+                .collect();
+            format!("chore(submodules): update {} submodules\n\n{}", changes.len(), lines.join("\n"))
+        }
+    }
+}
+
+fn path_of(path: &std::path::Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
@@ -0,0 +1,157 @@
+use crate::gemini::TokenUsage;
+use crate::pipeline::GenerationTier;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Older summaries are dropped once the store holds this many
+const MAX_SUMMARIES: usize = 500;
+
+/// What a cycle ended up doing.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleDecision {
+    Committed,
+    // Committed, but not pushed because the repo is in safe mode
+    CommittedLocally,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StageTiming {
+    pub stage: String,
+    pub duration_ms: u64,
+}
+
+/// One record per cycle, emitted as `cycle-summary` and kept in
+/// `cycle_summaries.json`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CycleSummary {
+    pub repo: String,
+    // Unix seconds
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub decision: CycleDecision,
+    // Why the cycle was skipped or failed
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub stages: Vec<StageTiming>,
+    pub tokens: Option<TokenUsage>,
+    pub generation_tier: Option<GenerationTier>,
+    // Unix seconds; only set for scheduled cycles
+    pub next_run_at: Option<u64>,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl CycleSummary {
+    pub fn new(repo: &str) -> Self {
+        CycleSummary {
+            repo: repo.to_string(),
+            started_at: unix_now(),
+            duration_ms: 0,
+            decision: CycleDecision::Committed,
+            reason: None,
+            message: None,
+            stages: Vec::new(),
+            tokens: None,
+            generation_tier: None,
+            next_run_at: None,
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// Records how long `stage` took since `started`.
+    pub fn stage(&mut self, stage: &str, started: Instant) {
+        self.stages.push(StageTiming {
+            stage: stage.to_string(),
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    pub fn skip(&mut self, reason: &str) {
+        self.decision = CycleDecision::Skipped;
+        self.reason = Some(reason.to_string());
+    }
+
+    /// Fills in the outcome once the cycle is over.
+    pub fn finish(&mut self, result: &Result<String, String>) {
+        if let Err(error) = result {
+            self.decision = CycleDecision::Failed;
+            self.reason = Some(error.clone());
+        }
+        self.duration_ms = self
+            .started
+            .map(|started| started.elapsed().as_millis() as u64)
+            .unwrap_or_default();
+    }
+
+    pub fn schedule_next(&mut self, next_run_at: u64) {
+        self.next_run_at = Some(next_run_at);
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SummaryStore {
+    summaries: Vec<CycleSummary>,
+}
+
+impl SummaryStore {
+    fn load() -> Result<Self, String> {
+        let path = summaries_path()?;
+        if !path.exists() {
+            return Ok(SummaryStore::default());
+        }
+
+        let summaries_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cycle summaries: {}", e))?;
+        serde_json::from_str(&summaries_str)
+            .map_err(|e| format!("Failed to parse cycle summaries: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let summaries_json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize cycle summaries: {}", e))?;
+        crate::config::write_atomic(&summaries_path()?, summaries_json.as_bytes())
+            .map_err(|e| format!("Failed to save cycle summaries: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn summaries_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("cycle_summaries.json");
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn record(summary: &CycleSummary) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = SummaryStore::load()?;
+    store.summaries.push(summary.clone());
+
+    let excess = store.summaries.len().saturating_sub(MAX_SUMMARIES);
+    store.summaries.drain(..excess);
+    store.save()
+}
+
+/// The most recent summaries, newest first, optionally for one repository.
+pub fn recent_summaries(repo_id: Option<&str>, limit: usize) -> Result<Vec<CycleSummary>, String> {
+    Ok(SummaryStore::load()?
+        .summaries
+        .into_iter()
+        .rev()
+        .filter(|summary| repo_id.is_none_or(|repo_id| summary.repo == repo_id))
+        .take(limit)
+        .collect())
+}
@@ -0,0 +1,49 @@
+//! Limits on how many model calls and pushes run at once across every
+//! repository's cycle, so staggered cycles that still overlap don't all hit
+//! the API or the network together. The limit is passed on every acquire,
+//! so a config change applies to the next one.
+
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+pub static API_CALLS: Throttle = Throttle::new();
+pub static PUSHES: Throttle = Throttle::new();
+
+pub struct Throttle {
+    running: Mutex<usize>,
+    freed: Notify,
+}
+
+/// Held while the throttled work runs.
+pub struct Permit(&'static Throttle);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        self.0.freed.notify_waiters();
+    }
+}
+
+impl Throttle {
+    const fn new() -> Self {
+        Throttle { running: Mutex::new(0), freed: Notify::const_new() }
+    }
+
+    /// Waits until fewer than `limit` permits are out; 0 means no limit.
+    pub async fn acquire(&'static self, limit: usize) -> Permit {
+        loop {
+            // Registered before checking, so a release in between isn't missed
+            let freed = self.freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+            {
+                let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+                if limit == 0 || *running < limit {
+                    *running += 1;
+                    return Permit(self);
+                }
+            }
+            freed.await;
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How often a sleeping timer looks at the wall clock, and so how soon it
+// notices the machine woke up past its deadline
+const WAKE_CHECK: Duration = Duration::from_secs(30);
+
+/// What a timer does about intervals it missed while the app was closed or
+/// the machine was asleep. However many were missed, at most one cycle
+/// makes up for them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpPolicy {
+    // Wait for the next tick of the original schedule
+    Skip,
+    // Commit right away
+    #[default]
+    Immediate,
+    // Commit after `catch_up_delay_secs`, giving the network time to come
+    // back after waking up
+    Delayed,
+}
+
+/// Whether a repository's timer was running, kept across restarts so the
+/// app can pick up where it left off.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct TimerState {
+    pub running: bool,
+    // Unix seconds of the last scheduled cycle that didn't fail
+    pub last_run_at: Option<u64>,
+    // Unix seconds of the last scheduled cycle, whatever its outcome
+    #[serde(default)]
+    pub last_attempt_at: Option<u64>,
+}
+
+/// Reported when a timer finds it missed whole intervals.
+#[derive(Serialize, Clone, Debug)]
+pub struct MissedRuns {
+    pub repo: String,
+    pub missed: u64,
+    pub policy: CatchUpPolicy,
+    // Until the catch-up cycle, or the next regular one with `Skip`
+    pub next_run_in_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct TimerStore {
+    repos: HashMap<String, TimerState>,
+}
+
+impl TimerStore {
+    fn load() -> Result<Self, String> {
+        let path = timer_state_path()?;
+        if !path.exists() {
+            return Ok(TimerStore::default());
+        }
+
+        let state_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read timer state: {}", e))?;
+        serde_json::from_str(&state_str)
+            .map_err(|e| format!("Failed to parse timer state: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let state_json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize timer state: {}", e))?;
+        crate::config::write_atomic(&timer_state_path()?, state_json.as_bytes())
+            .map_err(|e| format!("Failed to save timer state: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn timer_state_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("timer_state.json");
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn timer_state(repo_id: &str) -> TimerState {
+    TimerStore::load()
+        .ok()
+        .and_then(|store| store.repos.get(repo_id).cloned())
+        .unwrap_or_default()
+}
+
+/// Records that the user started or stopped the timer for `repo_id`.
+pub fn set_running(repo_id: &str, running: bool) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = TimerStore::load()?;
+    store.repos.entry(repo_id.to_string()).or_default().running = running;
+    store.save()
+}
+
+/// Records that a scheduled cycle is starting for `repo_id`.
+pub fn record_attempt(repo_id: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = TimerStore::load()?;
+    store.repos.entry(repo_id.to_string()).or_default().last_attempt_at = Some(unix_now());
+    store.save()
+}
+
+pub fn record_run(repo_id: &str) -> Result<(), String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = TimerStore::load()?;
+    store.repos.entry(repo_id.to_string()).or_default().last_run_at = Some(unix_now());
+    store.save()
+}
+
+/// Whole intervals missed since the last scheduled cycle; the one due now
+/// doesn't count.
+pub fn missed_runs(state: &TimerState, interval: Duration) -> u64 {
+    let interval_secs = interval.as_secs().max(1);
+    state
+        .last_attempt_at
+        .or(state.last_run_at)
+        .map(|last| (unix_now().saturating_sub(last) / interval_secs).saturating_sub(1))
+        .unwrap_or(0)
+}
+
+/// How long a resumed timer waits before its first cycle. A run that is
+/// still due later waits for it; a missed one follows `policy`.
+pub fn resume_delay(state: &TimerState, interval: Duration, policy: CatchUpPolicy, catch_up_delay: Duration) -> Duration {
+    let interval_secs = interval.as_secs().max(1);
+    let elapsed = match state.last_attempt_at.or(state.last_run_at) {
+        Some(last) => unix_now().saturating_sub(last),
+        None if policy == CatchUpPolicy::Skip => return interval,
+        None => return catch_up_wait(policy, Duration::ZERO, interval, catch_up_delay),
+    };
+
+    if elapsed < interval_secs {
+        Duration::from_secs(interval_secs - elapsed)
+    } else {
+        catch_up_wait(policy, Duration::from_secs(elapsed - interval_secs), interval, catch_up_delay)
+    }
+}
+
+/// Wait before the cycle that makes up for missed runs, `overdue` after the
+/// one that was due. `Skip` waits for the next tick of the original
+/// schedule.
+pub fn catch_up_wait(policy: CatchUpPolicy, overdue: Duration, interval: Duration, catch_up_delay: Duration) -> Duration {
+    let interval_secs = interval.as_secs().max(1);
+    match policy {
+        CatchUpPolicy::Immediate => Duration::ZERO,
+        CatchUpPolicy::Delayed => catch_up_delay,
+        CatchUpPolicy::Skip => Duration::from_secs(interval_secs - overdue.as_secs() % interval_secs),
+    }
+}
+
+/// Sleeps for `delay` by the wall clock. Tokio's timers stop while the
+/// machine sleeps, so on their own they would wake up late by however long
+/// it slept. Returns how much later than planned this woke up.
+pub async fn sleep(delay: Duration) -> Duration {
+    let deadline = SystemTime::now() + delay;
+    loop {
+        match deadline.duration_since(SystemTime::now()) {
+            Ok(remaining) if !remaining.is_zero() => tokio::time::sleep(remaining.min(WAKE_CHECK)).await,
+            Ok(_) => return Duration::ZERO,
+            Err(late) => return late.duration(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    const DELAY: Duration = Duration::from_secs(60);
+
+    fn last_attempt(secs_ago: u64) -> TimerState {
+        TimerState { running: true, last_run_at: None, last_attempt_at: Some(unix_now() - secs_ago) }
+    }
+
+    #[test]
+    fn catch_up_follows_the_policy() {
+        let overdue = Duration::from_secs(600);
+        assert_eq!(catch_up_wait(CatchUpPolicy::Immediate, overdue, HOUR, DELAY), Duration::ZERO);
+        assert_eq!(catch_up_wait(CatchUpPolicy::Delayed, overdue, HOUR, DELAY), DELAY);
+        assert_eq!(catch_up_wait(CatchUpPolicy::Skip, overdue, HOUR, DELAY), Duration::from_secs(3000));
+        // Several intervals late, `Skip` still lands on the original schedule
+        let overdue = Duration::from_secs(2 * 3600 + 600);
+        assert_eq!(catch_up_wait(CatchUpPolicy::Skip, overdue, HOUR, DELAY), Duration::from_secs(3000));
+    }
+
+    #[test]
+    fn only_whole_intervals_count_as_missed() {
+        assert_eq!(missed_runs(&TimerState::default(), HOUR), 0);
+        assert_eq!(missed_runs(&last_attempt(1800), HOUR), 0);
+        assert_eq!(missed_runs(&last_attempt(3 * 3600 + 1800), HOUR), 2);
+    }
+
+    #[test]
+    fn resuming_waits_for_a_run_still_due() {
+        let delay = resume_delay(&last_attempt(600), HOUR, CatchUpPolicy::Immediate, DELAY);
+        assert!(delay <= Duration::from_secs(3000) && delay >= Duration::from_secs(2990));
+        let missed = last_attempt(2 * 3600);
+        assert_eq!(resume_delay(&missed, HOUR, CatchUpPolicy::Immediate, DELAY), Duration::ZERO);
+        assert_eq!(resume_delay(&missed, HOUR, CatchUpPolicy::Delayed, DELAY), DELAY);
+        assert_eq!(resume_delay(&TimerState::default(), HOUR, CatchUpPolicy::Skip, DELAY), HOUR);
+    }
+}
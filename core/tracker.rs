@@ -0,0 +1,163 @@
+//! Issue-tracker context: the title and description of the issue a commit
+//! is for, fetched from Jira or Linear so the message can say what the work
+//! is meant to achieve and not only what the diff changes.
+
+use crate::config::AppConfig;
+use crate::issues;
+use crate::proxy::{self, ProxySettings};
+use crate::secrets;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const LINEAR_API_URL: &str = "https://api.linear.app/graphql";
+
+// Enough to tell the model what the issue is about without crowding out the diff
+const MAX_DESCRIPTION_CHARS: usize = 1500;
+
+// A slow tracker must not hold up the commit for long
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueTracker {
+    #[default]
+    None,
+    Jira,
+    Linear,
+}
+
+impl IssueTracker {
+    /// Keychain entry holding the tracker's API token.
+    pub fn secret_name(self) -> Option<&'static str> {
+        match self {
+            IssueTracker::None => None,
+            IssueTracker::Jira => Some(secrets::JIRA_TOKEN),
+            IssueTracker::Linear => Some(secrets::LINEAR_TOKEN),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TrackerIssue {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+}
+
+/// The issue `repo_id` is being worked on, as detected by
+/// `issues::detect`, fetched from the configured tracker. None when no
+/// tracker is set up, no issue was found, or the tracker can't be reached;
+/// failures are logged, never returned, so they can't block a commit.
+pub async fn issue_for_repo(repo_id: &str, config: &AppConfig) -> Option<TrackerIssue> {
+    let secret = config.issue_tracker.secret_name()?;
+    let id = {
+        let repo = Repository::open(repo_id).ok()?;
+        match issues::detect(&repo, &config.repo_settings(repo_id)) {
+            Ok(id) => id?,
+            Err(error) => {
+                tracing::warn!(repo = repo_id, error = %error, "failed to detect the issue");
+                return None;
+            }
+        }
+    };
+
+    let fetched = match secrets::get_secret(secret) {
+        Ok(Some(token)) => fetch_issue(config, &token, &id).await,
+        Ok(None) => Err(format!("No API token stored for {:?}", config.issue_tracker)),
+        Err(error) => Err(error),
+    };
+    match fetched {
+        Ok(issue) => Some(issue),
+        Err(error) => {
+            tracing::warn!(repo = repo_id, issue = %id, error = %error, "failed to fetch the issue");
+            None
+        }
+    }
+}
+
+/// Fetches `id` from the configured tracker with `token`.
+pub async fn fetch_issue(config: &AppConfig, token: &str, id: &str) -> Result<TrackerIssue, String> {
+    let client = proxy::client(&ProxySettings::from_config(config))?;
+    let issue = match config.issue_tracker {
+        IssueTracker::None => return Err("No issue tracker is configured".into()),
+        IssueTracker::Jira => {
+            let base = config.jira_base_url.trim().trim_end_matches('/');
+            if base.is_empty() {
+                return Err("Set `jira_base_url` to the Jira site, e.g. https://example.atlassian.net".into());
+            }
+            let request = client
+                .get(format!("{}/rest/api/2/issue/{}", base, id))
+                .query(&[("fields", "summary,description")]);
+            // Jira Cloud takes the account email with an API token, Jira
+            // Server a personal access token on its own
+            let request = match config.jira_email.trim() {
+                "" => request.bearer_auth(token),
+                email => request.basic_auth(email, Some(token)),
+            };
+            let body = send(request.timeout(REQUEST_TIMEOUT), "Jira").await?;
+            TrackerIssue {
+                id: body["key"].as_str().unwrap_or(id).to_string(),
+                title: body["fields"]["summary"].as_str().unwrap_or_default().to_string(),
+                description: body["fields"]["description"].as_str().unwrap_or_default().to_string(),
+            }
+        }
+        IssueTracker::Linear => {
+            let query = json!({
+                "query": "query Issue($id: String!) { issue(id: $id) { identifier title description } }",
+                "variables": { "id": id },
+            });
+            let request = client
+                .post(LINEAR_API_URL)
+                .header("Authorization", token)
+                .json(&query)
+                .timeout(REQUEST_TIMEOUT);
+            let body = send(request, "Linear").await?;
+            if let Some(message) = body["errors"][0]["message"].as_str() {
+                return Err(format!("Linear API error: {}", message));
+            }
+            let issue = &body["data"]["issue"];
+            TrackerIssue {
+                id: issue["identifier"].as_str().unwrap_or(id).to_string(),
+                title: issue["title"].as_str().unwrap_or_default().to_string(),
+                description: issue["description"].as_str().unwrap_or_default().to_string(),
+            }
+        }
+    };
+    if issue.title.is_empty() {
+        return Err(format!("Issue {} was not found", id));
+    }
+    Ok(issue)
+}
+
+/// Text for the system instruction describing the issue.
+pub fn prompt_hint(issue: &TrackerIssue) -> String {
+    let mut hint = format!(
+        "The changes are work on issue {}: \"{}\". Use it to explain the intent of the change, but only describe what the diff actually changes.",
+        issue.id, issue.title
+    );
+    let description = issue.description.trim();
+    if !description.is_empty() {
+        let description: String = description.chars().take(MAX_DESCRIPTION_CHARS).collect();
+        hint.push_str(&format!("\n\nIssue description:\n{}", description));
+    }
+    hint
+}
+
+async fn send(request: reqwest::RequestBuilder, tracker: &str) -> Result<Value, String> {
+    let response = request
+        .header("User-Agent", "auto-commit-app")
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("{} API error ({}): {}", tracker, status, error_text));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
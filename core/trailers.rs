@@ -0,0 +1,66 @@
+use crate::config::RepoSettings;
+use crate::identity;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+
+/// A `Key: value` line appended to the end of commit messages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+impl Trailer {
+    fn line(&self) -> String {
+        format!("{}: {}", self.key.trim(), self.value.trim())
+    }
+}
+
+/// Appends the repo's configured trailers, and a `Signed-off-by` built from
+/// the commit identity when `sign_off` is set. Trailers the
+/// message already has are not repeated.
+pub fn apply(repo: &Repository, message: &str, settings: &RepoSettings) -> Result<String, String> {
+    let mut trailers: Vec<String> = settings
+        .trailers
+        .iter()
+        .filter(|trailer| !trailer.key.trim().is_empty())
+        .map(Trailer::line)
+        .collect();
+
+    if settings.sign_off {
+        let signature = identity::resolve(repo, settings)?;
+        trailers.push(format!(
+            "Signed-off-by: {} <{}>",
+            signature.name().unwrap_or_default(),
+            signature.email().unwrap_or_default()
+        ));
+    }
+
+    Ok(append(message, &trailers))
+}
+
+/// Adds `trailers` the message doesn't have yet to its trailer block.
+pub fn append(message: &str, trailers: &[String]) -> String {
+    let message = message.trim_end();
+    let missing: Vec<&String> = trailers
+        .iter()
+        .filter(|trailer| !message.lines().any(|line| line.trim() == trailer.as_str()))
+        .collect();
+    if missing.is_empty() {
+        return message.to_string();
+    }
+
+    // Extend an existing trailer block instead of starting a second one
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or_default();
+    let has_block = message.contains("\n\n") && last_paragraph.lines().all(is_trailer_line);
+    let separator = if has_block { "\n" } else { "\n\n" };
+
+    let block: Vec<&str> = missing.iter().map(|trailer| trailer.as_str()).collect();
+    format!("{}{}{}", message, separator, block.join("\n"))
+}
+
+fn is_trailer_line(line: &str) -> bool {
+    line.split_once(": ").is_some_and(|(key, _)| {
+        !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
@@ -0,0 +1,157 @@
+//! Trial runs: the full commit-and-push pipeline against a throwaway local
+//! bare repository (`file://` remote) instead of the real one, so a setup
+//! can be checked before it is pointed at anything that matters.
+
+use crate::config::AppConfig;
+use crate::events::EventSink;
+use crate::pipeline;
+use git2::Repository;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+const TRIAL_FILE: &str = "GITGENIUS_TRIAL.md";
+
+/// A bare repository and a clone of it whose `origin` points at it, in a
+/// temporary directory.
+pub struct Sandbox {
+    pub root: PathBuf,
+    pub remote: PathBuf,
+    pub workdir: PathBuf,
+}
+
+impl Sandbox {
+    /// Creates the sandbox. With `source`, the bare repository starts as a
+    /// copy of that repository's history; otherwise it is empty.
+    pub fn create(source: Option<&str>) -> Result<Self, String> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let root = std::env::temp_dir().join(format!("gitgenius-trial-{}", nanos));
+        fs::create_dir_all(&root).map_err(|e| format!("Failed to create trial directory: {}", e))?;
+
+        let sandbox = Sandbox {
+            remote: root.join("remote.git"),
+            workdir: root.join("work"),
+            root,
+        };
+        match source {
+            Some(source) => git(&sandbox.root, &["clone", "--bare", "--quiet", source, "remote.git"])?,
+            None => {
+                Repository::init_bare(&sandbox.remote)
+                    .map_err(|e| format!("Failed to create bare repository: {}", e))?;
+            }
+        }
+
+        let remote_url = format!("file://{}", sandbox.remote.to_string_lossy().replace('\\', "/"));
+        git(&sandbox.root, &["clone", "--quiet", &remote_url, "work"])?;
+        Ok(sandbox)
+    }
+
+    pub fn workdir_str(&self) -> String {
+        self.workdir.to_string_lossy().to_string()
+    }
+
+    /// The commit the bare repository's `branch` points at.
+    pub fn remote_head(&self, branch: &str) -> Option<String> {
+        let remote = Repository::open_bare(&self.remote).ok()?;
+        let reference = remote.find_reference(&format!("refs/heads/{}", branch)).ok()?;
+        reference.target().map(|oid| oid.to_string())
+    }
+
+    pub fn remove(self) -> Result<(), String> {
+        fs::remove_dir_all(&self.root).map_err(|e| format!("Failed to remove trial directory: {}", e))
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<(), String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TrialResult {
+    // Kept only when the trial was asked to keep it
+    pub workdir: Option<String>,
+    pub message: String,
+    pub branch: String,
+    pub local_head: Option<String>,
+    pub remote_head: Option<String>,
+    // The remote ended up at the commit the cycle created
+    pub pushed: bool,
+}
+
+/// Runs one cycle in a sandbox cloned from `source` (or an empty one), with
+/// a generated change to commit, and checks that the push reached the bare
+/// remote. `source`'s per-repo settings apply, except pull requests.
+pub async fn run(
+    source: Option<&str>,
+    config: &AppConfig,
+    events: &dyn EventSink,
+    keep: bool,
+) -> Result<TrialResult, String> {
+    let sandbox = Sandbox::create(source)?;
+    let result = run_in(&sandbox, source, config, events).await;
+
+    let workdir = sandbox.workdir_str();
+    if keep {
+        result.map(|result| TrialResult { workdir: Some(workdir), ..result })
+    } else {
+        sandbox.remove().ok();
+        result
+    }
+}
+
+async fn run_in(
+    sandbox: &Sandbox,
+    source: Option<&str>,
+    config: &AppConfig,
+    events: &dyn EventSink,
+) -> Result<TrialResult, String> {
+    let workdir = sandbox.workdir_str();
+    fs::write(
+        sandbox.workdir.join(TRIAL_FILE),
+        "# GitGenius trial\n\nThis file was added to try the commit pipeline against a local remote.\n",
+    )
+    .map_err(|e| format!("Failed to write trial change: {}", e))?;
+
+    let mut settings = config.repo_settings(source.unwrap_or_default());
+    settings.path = workdir.clone();
+    // A pull request would reach GitHub, which is exactly what a trial avoids
+    settings.auto_pull_request = false;
+    let mut config = config.clone();
+    config.repos.retain(|repo| repo.path != workdir);
+    config.repos.push(settings);
+
+    let message = pipeline::run_commit(&workdir, &config, events, &CancellationToken::new()).await?;
+
+    let repo = Repository::open(&sandbox.workdir).map_err(|e| e.to_string())?;
+    let head = repo.head().map_err(|e| e.to_string())?;
+    let branch = head.shorthand().unwrap_or_default().to_string();
+    let local_head = head.target().map(|oid| oid.to_string());
+    let remote_head = sandbox.remote_head(&branch);
+
+    Ok(TrialResult {
+        workdir: None,
+        pushed: local_head.is_some() && local_head == remote_head,
+        message,
+        branch,
+        local_head,
+        remote_head,
+    })
+}
@@ -0,0 +1,154 @@
+use crate::history::{self, EntryOrigin, HistoryStore};
+use git2::Repository;
+use serde::Serialize;
+use std::process::Command;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UndoMethod {
+    // HEAD~1 with the changes left staged
+    SoftReset,
+    // The commit was already pushed, so a revert commit was created instead
+    Revert,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UndoResult {
+    pub sha: String,
+    pub message: String,
+    pub method: UndoMethod,
+}
+
+/// Undoes the HEAD commit of `repo_id`, provided the history store shows the
+/// app created it. Unpushed commits are soft-reset so their changes stay
+/// staged; pushed ones are reverted to avoid rewriting published history.
+pub fn undo_last_commit(repo_id: &str) -> Result<UndoResult, String> {
+    let repo = Repository::open(repo_id).map_err(|e| e.to_string())?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| e.to_string())?;
+    let sha = head.id().to_string();
+    let message = head.message().unwrap_or_default().trim().to_string();
+
+    let created_by_app = HistoryStore::load()?
+        .entries
+        .iter()
+        .any(|entry| entry.repo_id == repo_id && entry.sha == sha && entry.origin == EntryOrigin::App);
+    if !created_by_app {
+        return Err("The latest commit was not created by GitGenius".into());
+    }
+
+    if head.parent_count() == 0 {
+        return Err("Cannot undo the initial commit".into());
+    }
+
+    let method = if is_pushed(&repo, head.id()) {
+        UndoMethod::Revert
+    } else {
+        UndoMethod::SoftReset
+    };
+
+    let mut git = Command::new("git");
+    match method {
+        UndoMethod::SoftReset => git.arg("reset").arg("--soft").arg("HEAD~1"),
+        UndoMethod::Revert => git.arg("revert").arg("--no-edit").arg("HEAD"),
+    };
+
+    let output = git
+        .current_dir(repo_id)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "Undo failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    match method {
+        // The commit no longer exists, so it must not count in statistics
+        UndoMethod::SoftReset => history::forget_commit(repo_id, &sha)?,
+        UndoMethod::Revert => {
+            history::record_head_commit(repo_id, None).ok();
+        }
+    }
+
+    Ok(UndoResult { sha, message, method })
+}
+
+// Whether the upstream branch already contains `commit`
+fn is_pushed(repo: &Repository, commit: git2::Oid) -> bool {
+    let upstream = repo
+        .head()
+        .ok()
+        .and_then(|head| head.shorthand().map(str::to_string))
+        .and_then(|name| repo.find_branch(&name, git2::BranchType::Local).ok())
+        .and_then(|branch| branch.upstream().ok())
+        .and_then(|upstream| upstream.get().target());
+
+    match upstream {
+        Some(upstream) => {
+            upstream == commit || repo.graph_descendant_of(upstream, commit).unwrap_or(false)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use std::fs;
+    use std::path::Path;
+
+    fn repo_in(name: &str) -> (Repository, String) {
+        let dir = std::env::temp_dir().join(format!("gitgenius-undo-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let repo = Repository::init(&dir).unwrap();
+        (repo, dir.to_string_lossy().to_string())
+    }
+
+    fn commit_file(repo: &Repository, name: &str, message: &str) {
+        fs::write(repo.workdir().unwrap().join(name), format!("{}\n", name)).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn unpushed_app_commits_are_reset_with_their_changes_staged() {
+        let (repo, repo_id) = repo_in("reset");
+        commit_file(&repo, "base.txt", "chore: base");
+        commit_file(&repo, "work.txt", "feat: add work");
+        history::record_head_commit(&repo_id, None).unwrap();
+
+        let undone = undo_last_commit(&repo_id).unwrap();
+        assert!(undone.method == UndoMethod::SoftReset);
+        assert_eq!(undone.message, "feat: add work");
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("chore: base"));
+        assert!(repo.index().unwrap().get_path(Path::new("work.txt"), 0).is_some());
+        let store = HistoryStore::load().unwrap();
+        assert!(!store.entries.iter().any(|entry| entry.sha == undone.sha));
+        fs::remove_dir_all(repo.workdir().unwrap()).ok();
+    }
+
+    #[test]
+    fn only_app_commits_past_the_first_are_undone() {
+        let (repo, repo_id) = repo_in("refused");
+        commit_file(&repo, "base.txt", "chore: base");
+        history::record_head_commit(&repo_id, None).unwrap();
+        assert_eq!(undo_last_commit(&repo_id).unwrap_err(), "Cannot undo the initial commit");
+
+        commit_file(&repo, "user.txt", "feat: made by hand");
+        assert!(undo_last_commit(&repo_id).is_err());
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().message(), Some("feat: made by hand"));
+        fs::remove_dir_all(repo.workdir().unwrap()).ok();
+    }
+}
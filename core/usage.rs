@@ -0,0 +1,269 @@
+//! Token usage reports built from the per-call records in `usage.json`,
+//! and the monthly budget that pauses timed cycles once spent.
+
+use crate::config::AppConfig;
+use crate::gemini::TokenUsage;
+use crate::history::{self, HistoryStore};
+use chrono::{Datelike, Days, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Group for calls that weren't made for a repository
+const NO_REPO: &str = "other";
+
+/// Longest report `usage_summary` gives. Records older than this are
+/// dropped; it also covers the calendar month the budget is checked against.
+pub const MAX_REPORT_DAYS: u32 = 90;
+
+/// Tokens billed for one Gemini call.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UsageRecord {
+    pub timestamp: i64,
+    // None for calls not made for a particular repository
+    pub repo_id: Option<String>,
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct UsageStore {
+    records: Vec<UsageRecord>,
+}
+
+impl UsageStore {
+    // Callers hold `STORE_LOCK`. Without a usage store yet, the records
+    // older versions kept in the history store are moved over.
+    fn load() -> Result<Self, String> {
+        let path = usage_path()?;
+        if !path.exists() {
+            let store = UsageStore { records: HistoryStore::load()?.usage };
+            if !store.records.is_empty() {
+                store.save()?;
+                history::clear_legacy_usage()?;
+            }
+            return Ok(store);
+        }
+
+        let usage_str = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read token usage: {}", e))?;
+        serde_json::from_str(&usage_str)
+            .map_err(|e| format!("Failed to parse token usage: {}", e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let usage_json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize token usage: {}", e))?;
+        crate::config::write_atomic(&usage_path()?, usage_json.as_bytes())
+            .map_err(|e| format!("Failed to save token usage: {}", e))
+    }
+}
+
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn usage_path() -> Result<PathBuf, String> {
+    let mut path = crate::config::get_data_dir()?;
+    path.push("usage.json");
+    Ok(path)
+}
+
+fn records() -> Result<Vec<UsageRecord>, String> {
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    Ok(UsageStore::load()?.records)
+}
+
+/// Records the tokens of one Gemini call, dropping records older than
+/// `MAX_REPORT_DAYS`.
+pub fn record_usage(repo_id: Option<&str>, model: &str, usage: TokenUsage) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp();
+    let oldest = now - MAX_REPORT_DAYS as i64 * 24 * 60 * 60;
+
+    let _lock = crate::config::lock_store(&STORE_LOCK);
+    let mut store = UsageStore::load()?;
+    store.records.retain(|record| record.timestamp >= oldest);
+    store.records.push(UsageRecord {
+        timestamp: now,
+        repo_id: repo_id.map(str::to_string),
+        model: model.to_string(),
+        prompt_tokens: usage.prompt_tokens,
+        output_tokens: usage.output_tokens,
+        total_tokens: usage.total_tokens,
+    });
+    store.save()
+}
+
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct UsageTotals {
+    pub calls: usize,
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.calls += 1;
+        self.prompt_tokens += record.prompt_tokens as u64;
+        self.output_tokens += record.output_tokens as u64;
+        self.total_tokens += record.total_tokens as u64;
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UsageGroup {
+    // Local date `YYYY-MM-DD`, repository path or model name
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UsageSummary {
+    pub days: u32,
+    pub total: UsageTotals,
+    // Oldest first, days without calls included
+    pub by_day: Vec<UsageGroup>,
+    // Most tokens first
+    pub by_repo: Vec<UsageGroup>,
+    pub by_model: Vec<UsageGroup>,
+    // Calendar month so far, what the budget is checked against
+    pub month_tokens: u64,
+    // 0 when no budget is set
+    pub monthly_budget: u64,
+    pub budget_exceeded: bool,
+}
+
+/// Usage over the last `days` days, at most `MAX_REPORT_DAYS`, grouped by
+/// day, repository and model.
+pub fn usage_summary(config: &AppConfig, days: u32) -> Result<UsageSummary, String> {
+    let days = days.min(MAX_REPORT_DAYS);
+    let today = Local::now().date_naive();
+    let first_day = today
+        .checked_sub_days(Days::new(days.saturating_sub(1) as u64))
+        .unwrap_or(today);
+    let records = records()?;
+
+    let mut total = UsageTotals::default();
+    let mut by_day: BTreeMap<String, UsageTotals> = BTreeMap::new();
+    let mut day = first_day;
+    while day <= today {
+        by_day.insert(day.format("%Y-%m-%d").to_string(), UsageTotals::default());
+        day = day.succ_opt().unwrap_or(today + Days::new(1));
+    }
+    let mut by_repo: BTreeMap<String, UsageTotals> = BTreeMap::new();
+    let mut by_model: BTreeMap<String, UsageTotals> = BTreeMap::new();
+
+    for record in &records {
+        let date = match Local.timestamp_opt(record.timestamp, 0).single() {
+            Some(time) => time.date_naive(),
+            None => continue,
+        };
+        if date < first_day {
+            continue;
+        }
+        total.add(record);
+        by_day.entry(date.format("%Y-%m-%d").to_string()).or_default().add(record);
+        by_repo
+            .entry(record.repo_id.clone().unwrap_or_else(|| NO_REPO.into()))
+            .or_default()
+            .add(record);
+        by_model.entry(record.model.clone()).or_default().add(record);
+    }
+
+    let month_tokens = month_tokens(&records);
+    let monthly_budget = config.monthly_token_budget;
+    Ok(UsageSummary {
+        days,
+        total,
+        by_day: groups(by_day),
+        by_repo: by_most_tokens(groups(by_repo)),
+        by_model: by_most_tokens(groups(by_model)),
+        month_tokens,
+        monthly_budget,
+        budget_exceeded: monthly_budget > 0 && month_tokens >= monthly_budget,
+    })
+}
+
+/// Tokens used this calendar month, when `monthly_token_budget` is set and
+/// they have reached it.
+pub fn budget_exceeded(config: &AppConfig) -> Option<u64> {
+    if config.monthly_token_budget == 0 {
+        return None;
+    }
+    let used = month_tokens(&records().ok()?);
+    (used >= config.monthly_token_budget).then_some(used)
+}
+
+/// Tokens used this calendar month.
+pub fn month_tokens_used() -> u64 {
+    records().map(|records| month_tokens(&records)).unwrap_or(0)
+}
+
+fn month_tokens(records: &[UsageRecord]) -> u64 {
+    let today = Local::now().date_naive();
+    let month_start = today.with_day(1).unwrap_or(today);
+    let since = Local
+        .from_local_datetime(&month_start.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|start| start.timestamp())
+        .unwrap_or_default();
+    records
+        .iter()
+        .filter(|record| record.timestamp >= since)
+        .map(|record| record.total_tokens as u64)
+        .sum()
+}
+
+fn groups(totals: BTreeMap<String, UsageTotals>) -> Vec<UsageGroup> {
+    totals
+        .into_iter()
+        .map(|(key, totals)| UsageGroup { key, totals })
+        .collect()
+}
+
+fn by_most_tokens(mut groups: Vec<UsageGroup>) -> Vec<UsageGroup> {
+    groups.sort_by(|a, b| b.totals.total_tokens.cmp(&a.totals.total_tokens));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: i64, total_tokens: u32) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            repo_id: None,
+            model: "gemini-test".into(),
+            prompt_tokens: total_tokens,
+            output_tokens: 0,
+            total_tokens,
+        }
+    }
+
+    #[test]
+    fn old_records_are_pruned() {
+        let day = 24 * 60 * 60;
+        let now = chrono::Utc::now().timestamp();
+        {
+            let _lock = crate::config::lock_store(&STORE_LOCK);
+            let store = UsageStore { records: vec![record(now - 100 * day, 7), record(now - day, 5)] };
+            store.save().unwrap();
+        }
+
+        let tokens = TokenUsage { prompt_tokens: 2, output_tokens: 1, total_tokens: 3 };
+        record_usage(Some("/work/app"), "gemini-test", tokens).unwrap();
+
+        let kept = records().unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(kept.iter().all(|record| record.timestamp >= now - MAX_REPORT_DAYS as i64 * day));
+
+        let summary = usage_summary(&AppConfig::default(), 365).unwrap();
+        assert_eq!(summary.days, MAX_REPORT_DAYS);
+        assert_eq!(summary.total.total_tokens, 8);
+    }
+}
@@ -0,0 +1,240 @@
+//! Checks generated commit messages against the rules the prompt asks for.
+//! What can be fixed without changing the meaning (code fences, a chatty
+//! introduction, an overlong subject) is fixed here; the rest is reported
+//! so the model can be asked again.
+
+use crate::config::AppConfig;
+use crate::conventional;
+use crate::style::MessageStyle;
+
+pub const MAX_SUBJECT_LENGTH: usize = 72;
+
+// First lines models sometimes put before the message despite the prompt
+const PREAMBLES: [&str; 6] = ["here is", "here's", "sure", "certainly", "the commit message", "commit message"];
+
+// Paragraphs explaining the message rather than the change
+const EXPLANATIONS: [&str; 3] = ["explanation:", "note:", "this commit message"];
+
+// Conventional Commits types, which stay in English whatever `commit_language` is
+const ENGLISH_TYPES: [&str; 11] = [
+    "feat", "fix", "docs", "style", "refactor", "test", "chore", "perf", "build", "ci", "revert",
+];
+
+// Verbs an English description starts with; seeing one means the model
+// ignored `commit_language`
+const ENGLISH_VERBS: [&str; 24] = [
+    "add", "adds", "fix", "fixes", "update", "updates", "remove", "removes", "implement", "improve",
+    "change", "create", "delete", "rename", "move", "bump", "use", "handle", "support", "allow",
+    "make", "refactor", "clean", "replace",
+];
+
+// Frequent English words that aren't also words in the usual European
+// languages, unlike `in` or `is`
+const ENGLISH_STOPWORDS: [&str; 10] = ["the", "and", "with", "for", "from", "when", "into", "of", "to", "on"];
+
+/// Problems `repair` can't fix, worded so they can be sent back to the
+/// model. Empty when the message is acceptable.
+pub fn check(message: &str, config: &AppConfig) -> Vec<String> {
+    let subject = message.lines().next().unwrap_or_default().trim();
+    if subject.is_empty() {
+        return vec!["The message is empty".into()];
+    }
+
+    let mut problems = Vec::new();
+    let conventional_style = matches!(
+        config.message_style,
+        MessageStyle::Conventional | MessageStyle::CustomTemplate
+    );
+    if conventional_style && conventional::parse(subject).is_none() {
+        problems.push("The subject line must follow `type(scope): subject`".into());
+    }
+
+    let length = subject.chars().count();
+    if length > MAX_SUBJECT_LENGTH {
+        problems.push(format!(
+            "The subject line is {} characters long, the limit is {}",
+            length, MAX_SUBJECT_LENGTH
+        ));
+    }
+
+    let language = config.commit_language.trim();
+    if !language.is_empty() && !language.eq_ignore_ascii_case("english") && conventional_style {
+        problems.extend(language_problems(subject, language));
+    }
+
+    let lowercase = message.to_lowercase();
+    for pattern in &config.forbidden_message_patterns {
+        if !pattern.trim().is_empty() && lowercase.contains(&pattern.to_lowercase()) {
+            problems.push(format!("The message must not contain \"{}\"", pattern));
+        }
+    }
+    problems
+}
+
+/// Removes Markdown fences, an introduction such as "Here is the commit
+/// message:" and trailing explanation paragraphs.
+pub fn repair(message: &str) -> String {
+    let mut lines: Vec<&str> = message
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect();
+
+    while let Some(&first) = lines.first() {
+        let trimmed = first.trim();
+        if trimmed.is_empty() || is_preamble(trimmed) {
+            lines.remove(0);
+        } else {
+            break;
+        }
+    }
+
+    // `Commit message: feat: add x` keeps what follows the label
+    let mut subject = lines.first().map(|line| line.trim()).unwrap_or_default().to_string();
+    if let Some(rest) = strip_prefix_ignore_case(&subject, "commit message:") {
+        subject = rest.trim().to_string();
+    }
+    let subject = subject.trim_matches('`').trim().to_string();
+
+    let mut paragraphs: Vec<String> = Vec::new();
+    for paragraph in lines.iter().skip(1).copied().collect::<Vec<_>>().join("\n").split("\n\n") {
+        let lowercase = paragraph.trim().to_lowercase();
+        if EXPLANATIONS.iter().any(|start| lowercase.starts_with(start)) {
+            break;
+        }
+        if !paragraph.trim().is_empty() {
+            paragraphs.push(paragraph.trim_matches('\n').to_string());
+        }
+    }
+
+    if paragraphs.is_empty() {
+        subject
+    } else {
+        format!("{}\n\n{}", subject, paragraphs.join("\n\n"))
+    }
+}
+
+/// Cuts the subject line down to `MAX_SUBJECT_LENGTH` at a word boundary.
+pub fn shorten_subject(message: &str) -> String {
+    let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+    if subject.chars().count() <= MAX_SUBJECT_LENGTH {
+        return message.to_string();
+    }
+
+    let limit = subject
+        .char_indices()
+        .nth(MAX_SUBJECT_LENGTH)
+        .map(|(index, _)| index)
+        .unwrap_or(subject.len());
+    let cut = subject[..limit]
+        .rfind(' ')
+        .filter(|space| *space > limit / 2)
+        .unwrap_or(limit);
+    let shortened = subject[..cut].trim_end_matches([' ', ',', ';', ':', '-', '.']);
+
+    if rest.is_empty() {
+        shortened.to_string()
+    } else {
+        format!("{}\n{}", shortened, rest)
+    }
+}
+
+// The type must stay English while the description must not be
+fn language_problems(subject: &str, language: &str) -> Vec<String> {
+    let parsed = match conventional::parse(subject) {
+        Some(parsed) => parsed,
+        None => return Vec::new(),
+    };
+
+    let mut problems = Vec::new();
+    if !ENGLISH_TYPES.contains(&parsed.commit_type.as_str()) {
+        problems.push(format!(
+            "The type `{}` must be one of the English Conventional Commits types ({})",
+            parsed.commit_type,
+            ENGLISH_TYPES.join(", ")
+        ));
+    }
+
+    let words: Vec<String> = parsed
+        .subject
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let starts_english = words
+        .first()
+        .is_some_and(|word| ENGLISH_VERBS.contains(&word.as_str()));
+    let stopwords = words
+        .iter()
+        .filter(|word| ENGLISH_STOPWORDS.contains(&word.as_str()))
+        .count();
+    if starts_english || (words.len() >= 4 && stopwords * 4 >= words.len()) {
+        problems.push(format!("The description after the colon must be written in {}", language));
+    }
+    problems
+}
+
+// A line that introduces the message instead of being part of it
+fn is_preamble(line: &str) -> bool {
+    let lowercase = line.to_lowercase();
+    PREAMBLES.iter().any(|start| lowercase.starts_with(start)) && lowercase.ends_with(':')
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &text[prefix.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_a_conventional_subject() {
+        assert!(check("feat(api): paginate the user list", &AppConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn reports_a_subject_that_is_not_conventional() {
+        let problems = check("Paginate the user list", &AppConfig::default());
+        assert_eq!(problems, vec!["The subject line must follow `type(scope): subject`".to_string()]);
+    }
+
+    #[test]
+    fn reports_forbidden_patterns_whatever_their_case() {
+        let problems = check("fix: as an ai I changed the parser", &AppConfig::default());
+        assert!(problems.iter().any(|problem| problem.contains("as an AI")));
+    }
+
+    #[test]
+    fn reports_an_english_description_in_another_language() {
+        let config = AppConfig { commit_language: "Spanish".into(), ..AppConfig::default() };
+        assert_eq!(check("feat: add the login page", &config).len(), 1);
+        assert!(check("feat: añade la página de acceso", &config).is_empty());
+    }
+
+    #[test]
+    fn repair_strips_fences_preamble_and_explanations() {
+        let raw = "Here is the commit message:\n```\nfeat: add login\n\nAdds the form.\n\nExplanation: the diff adds a form\n```";
+        assert_eq!(repair(raw), "feat: add login\n\nAdds the form.");
+    }
+
+    #[test]
+    fn repair_keeps_what_follows_a_label() {
+        assert_eq!(repair("Commit message: `fix: handle empty input`"), "fix: handle empty input");
+    }
+
+    #[test]
+    fn shorten_subject_cuts_at_a_word_and_keeps_the_body() {
+        let subject = format!("feat: {}", "word ".repeat(20).trim_end());
+        let shortened = shorten_subject(&format!("{}\n\nBody", subject));
+        let (new_subject, body) = shortened.split_once('\n').unwrap();
+        assert!(new_subject.chars().count() <= MAX_SUBJECT_LENGTH);
+        assert!(new_subject.ends_with("word"));
+        assert_eq!(body, "\nBody");
+    }
+
+    #[test]
+    fn shorten_subject_leaves_short_subjects_alone() {
+        assert_eq!(shorten_subject("fix: typo\n\nBody"), "fix: typo\n\nBody");
+    }
+}
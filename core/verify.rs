@@ -0,0 +1,71 @@
+use serde::Serialize;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+#[derive(Serialize, Clone)]
+pub struct VerificationFailure {
+    pub repo: String,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub output: String,
+}
+
+/// Runs the repo's verification command through the platform shell. Returns
+/// the failure (with captured output) when it exits non-zero or times out.
+pub async fn run_verification(
+    repo_id: &str,
+    command: &str,
+    timeout_secs: u64,
+) -> Result<Option<VerificationFailure>, String> {
+    let mut child = shell_command(command);
+    child.current_dir(repo_id).kill_on_drop(true);
+
+    let failure = |exit_code, timed_out, output| VerificationFailure {
+        repo: repo_id.to_string(),
+        command: command.to_string(),
+        exit_code,
+        timed_out,
+        output,
+    };
+
+    let output = match timeout(Duration::from_secs(timeout_secs), child.output()).await {
+        Ok(output) => output.map_err(|e| format!("Failed to run verification command: {}", e))?,
+        Err(_) => {
+            return Ok(Some(failure(
+                None,
+                true,
+                format!("Timed out after {} seconds", timeout_secs),
+            )));
+        }
+    };
+
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(failure(
+        output.status.code(),
+        false,
+        format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    )))
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
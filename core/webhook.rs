@@ -0,0 +1,130 @@
+//! Post-commit webhooks: a JSON POST to every configured URL after a cycle
+//! commits. The payload carries `text` and `content` summaries as well, so
+//! Slack and Discord incoming webhooks can take it unchanged.
+
+use crate::proxy::{self, ProxySettings};
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::warn;
+
+const MAX_ATTEMPTS: u32 = 3;
+
+// Doubled after each failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WebhookPayload {
+    // `commit` or `test`
+    pub event: String,
+    pub repo: String,
+    pub branch: Option<String>,
+    pub message: String,
+    pub files: usize,
+    // False when the commit stayed local (safe mode)
+    pub pushed: bool,
+    // Slack reads `text`, Discord reads `content`
+    pub text: String,
+    pub content: String,
+}
+
+impl WebhookPayload {
+    pub fn commit(repo: &str, branch: Option<String>, message: &str, files: usize, pushed: bool) -> Self {
+        let repo_name = std::path::Path::new(repo)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| repo.to_string());
+        let subject = message.lines().next().unwrap_or_default();
+        let text = format!(
+            "GitGenius committed {} file(s) to {}{}{}: {}",
+            files,
+            repo_name,
+            branch.as_deref().map(|branch| format!(" ({})", branch)).unwrap_or_default(),
+            if pushed { "" } else { ", not pushed" },
+            subject
+        );
+
+        WebhookPayload {
+            event: "commit".into(),
+            repo: repo.to_string(),
+            branch,
+            message: message.to_string(),
+            files,
+            pushed,
+            content: text.clone(),
+            text,
+        }
+    }
+}
+
+async fn post(client: &Client, url: &str, payload: &WebhookPayload) -> Result<(), String> {
+    let response = client
+        .post(url)
+        .header("User-Agent", "auto-commit-app")
+        .timeout(Duration::from_secs(10))
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Webhook returned {}: {}", status, error_text.trim()));
+    }
+    Ok(())
+}
+
+// Retries with a growing delay; returns the last error if every attempt failed
+async fn post_with_retry(client: &Client, url: &str, payload: &WebhookPayload) -> Result<(), String> {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match post(client, url, payload).await {
+            Ok(()) => return Ok(()),
+            Err(error) if attempt >= MAX_ATTEMPTS => return Err(error),
+            Err(_) => {
+                sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Delivers `payload` to every URL in the background, so a slow or broken
+/// endpoint never holds up the cycle. Failures are only logged.
+pub fn deliver(urls: &[String], proxy: &ProxySettings, payload: WebhookPayload) {
+    let urls: Vec<String> = urls.iter().filter(|url| !url.trim().is_empty()).cloned().collect();
+    if urls.is_empty() {
+        return;
+    }
+
+    let client = match proxy::client(proxy) {
+        Ok(client) => client,
+        Err(error) => {
+            warn!(repo = %payload.repo, error = %error, "webhook delivery failed");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        for url in urls {
+            if let Err(error) = post_with_retry(&client, &url, &payload).await {
+                warn!(url = %url, repo = %payload.repo, error = %error, "webhook delivery failed");
+            }
+        }
+    });
+}
+
+/// Sends a sample payload to `url` once, without retries, so a bad URL is
+/// reported right away.
+pub async fn test_webhook(url: &str, proxy: &ProxySettings) -> Result<String, String> {
+    let mut payload = WebhookPayload::commit("gitgenius-test", Some("main".into()), "test: webhook check", 1, true);
+    payload.event = "test".into();
+    payload.text = "GitGenius webhook test: this endpoint will be notified after each commit".into();
+    payload.content = payload.text.clone();
+
+    post(&proxy::client(proxy)?, url, &payload).await?;
+    Ok("Webhook delivered".into())
+}
@@ -0,0 +1,209 @@
+//! Standup and weekly reports: the user's commits across every repository
+//! in a date range, summarized by the model as Markdown bullets.
+
+use crate::config::AppConfig;
+use crate::gemini;
+use crate::history::{EntryOrigin, HistoryStore};
+use chrono::{Days, Local, NaiveDate, TimeZone};
+use git2::{Repository, Sort};
+use serde::Serialize;
+use std::collections::HashSet;
+
+const WORK_SUMMARY_SYSTEM_CONTEXT: &str = r#"You write a developer's standup or weekly status update from the commits they made, grouped by repository.
+
+RULES:
+1. Start with the given level-2 heading, unchanged
+2. Then one level-3 section per repository, named after it
+3. One bullet per piece of work, in plain language and past tense: "Added ...", "Fixed ..."
+4. Merge commits that belong to the same piece of work, and leave out formatting, merge and auto-save chores
+5. Do not invent work that is not in the commits
+
+Always respond with ONLY the Markdown, no explanations or additional text."#;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SummaryCommit {
+    pub repo: String,
+    pub sha: String,
+    pub subject: String,
+    pub timestamp: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WorkSummary {
+    // Local dates, `YYYY-MM-DD`, both included
+    pub from: String,
+    pub to: String,
+    pub commits: Vec<SummaryCommit>,
+    pub markdown: String,
+    // The model wrote `markdown`; otherwise it lists the commits
+    pub generated: bool,
+}
+
+/// Summarizes the commits made between `from` and `to` (today by default)
+/// in `repos`, or every repository the app knows about. Commits come from
+/// the app's history and from each repository's log, where only those by
+/// the repository's configured `user.email` count.
+pub async fn generate_work_summary(
+    config: &AppConfig,
+    from: &str,
+    to: Option<&str>,
+    repos: Option<Vec<String>>,
+) -> Result<WorkSummary, String> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("Invalid date {}, expected YYYY-MM-DD", date))
+    };
+    let first_day = parse(from)?;
+    let last_day = match to {
+        Some(to) => parse(to)?,
+        None => Local::now().date_naive(),
+    };
+    if last_day < first_day {
+        return Err("The end of the range is before its start".into());
+    }
+    let since = day_start(first_day);
+    let until = day_start(last_day.checked_add_days(Days::new(1)).unwrap_or(last_day));
+
+    let repos = repos.unwrap_or_else(|| config.repo_paths());
+    let commits = tokio::task::spawn_blocking(move || collect_commits(&repos, since, until))
+        .await
+        .map_err(|e| e.to_string())??;
+    if commits.is_empty() {
+        return Err("No commits in the selected range".into());
+    }
+
+    let heading = if first_day == last_day {
+        format!("## Work summary for {}", first_day)
+    } else {
+        format!("## Work summary for {} to {}", first_day, last_day)
+    };
+    let fallback = render(&heading, &commits);
+
+    let (markdown, generated) = if config.api_key().is_empty() {
+        (fallback, false)
+    } else {
+        let prompt = format!(
+            "Write the summary with the heading \"{}\" for these commits:\n\n{}",
+            heading, fallback
+        );
+        let options = gemini::ModelOptions::from_config(config);
+        match gemini::generate(&config.api_key(), &options, WORK_SUMMARY_SYSTEM_CONTEXT, &prompt).await {
+            Ok(text) if text.trim_start().starts_with("## ") => (text.trim().to_string(), true),
+            Ok(_) => (fallback, false),
+            Err(error) => {
+                tracing::warn!(error = %error, "work summary generation failed, listing commits");
+                (fallback, false)
+            }
+        }
+    };
+
+    Ok(WorkSummary {
+        from: first_day.to_string(),
+        to: last_day.to_string(),
+        commits,
+        markdown,
+        generated,
+    })
+}
+
+/// Deterministic summary: the commit subjects, one section per repository.
+pub fn render(heading: &str, commits: &[SummaryCommit]) -> String {
+    let mut markdown = heading.to_string();
+    let mut current = None;
+    for commit in commits {
+        if current != Some(&commit.repo) {
+            markdown.push_str(&format!("\n\n### {}\n", repo_name(&commit.repo)));
+            current = Some(&commit.repo);
+        }
+        markdown.push_str(&format!("\n- {} ({:.7})", commit.subject, commit.sha));
+    }
+    markdown
+}
+
+// Oldest first within each repository, repositories in the given order
+fn collect_commits(repos: &[String], since: i64, until: i64) -> Result<Vec<SummaryCommit>, String> {
+    let history = HistoryStore::load()?;
+    let mut commits = Vec::new();
+    for repo_id in repos {
+        let mut seen = HashSet::new();
+        let mut found: Vec<SummaryCommit> = history
+            .entries
+            .iter()
+            .filter(|entry| entry.repo_id == *repo_id && entry.origin == EntryOrigin::App)
+            .filter(|entry| entry.timestamp >= since && entry.timestamp < until)
+            .map(|entry| SummaryCommit {
+                repo: repo_id.clone(),
+                sha: entry.sha.clone(),
+                subject: subject(&entry.message),
+                timestamp: entry.timestamp,
+            })
+            .collect();
+        seen.extend(found.iter().map(|commit| commit.sha.clone()));
+
+        // A repository that can't be opened still has its history entries
+        match Repository::open(repo_id) {
+            Ok(repo) => {
+                for commit in log_commits(&repo, repo_id, since, until).map_err(|e| e.to_string())? {
+                    if seen.insert(commit.sha.clone()) {
+                        found.push(commit);
+                    }
+                }
+            }
+            Err(error) => tracing::warn!(repo = %repo_id, error = %error, "failed to read the log for the work summary"),
+        }
+
+        found.sort_by_key(|commit| commit.timestamp);
+        commits.extend(found);
+    }
+    Ok(commits)
+}
+
+fn log_commits(repo: &Repository, repo_id: &str, since: i64, until: i64) -> Result<Vec<SummaryCommit>, git2::Error> {
+    let email = repo.config()?.get_string("user.email").ok();
+    let mut revwalk = repo.revwalk()?;
+    if revwalk.push_head().is_err() {
+        return Ok(Vec::new());
+    }
+    revwalk.set_sorting(Sort::TIME)?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let time = commit.time().seconds();
+        if time < since {
+            break;
+        }
+        let mine = email
+            .as_deref()
+            .is_none_or(|email| commit.author().email().is_some_and(|author| author.eq_ignore_ascii_case(email)));
+        if time >= until || commit.parent_count() > 1 || !mine {
+            continue;
+        }
+        commits.push(SummaryCommit {
+            repo: repo_id.to_string(),
+            sha: commit.id().to_string(),
+            subject: subject(commit.message().unwrap_or_default()),
+            timestamp: time,
+        });
+    }
+    Ok(commits)
+}
+
+fn subject(message: &str) -> String {
+    message.lines().next().unwrap_or_default().trim().to_string()
+}
+
+fn repo_name(repo_id: &str) -> &str {
+    repo_id
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(repo_id)
+}
+
+fn day_start(day: NaiveDate) -> i64 {
+    Local
+        .from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|start| start.timestamp())
+        .unwrap_or_default()
+}
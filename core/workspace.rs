@@ -0,0 +1,200 @@
+//! Packages of a monorepo, from the workspace manifests at its root: Cargo
+//! `[workspace] members` and npm/Yarn `workspaces` in `package.json`, or
+//! from directory patterns such as `packages/*`.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct WorkspacePackage {
+    pub name: String,
+    // Relative to the repository root, `/`-separated
+    pub dir: String,
+    // Every dependency the manifest declares, workspace packages or not
+    pub dependencies: Vec<String>,
+}
+
+/// The packages of `root`: the workspace members when `from_manifests` is
+/// set, then the directories matching `patterns`, named after their folder.
+pub fn discover(root: &Path, patterns: &[String], from_manifests: bool) -> Vec<WorkspacePackage> {
+    let mut found = if from_manifests { packages(root) } else { Vec::new() };
+    for pattern in patterns {
+        for dir in expand(root, pattern) {
+            if found.iter().any(|known| known.dir == dir) {
+                continue;
+            }
+            let name = dir.rsplit('/').next().unwrap_or(&dir).to_string();
+            let dependencies = cargo_member(root, &dir)
+                .or_else(|| npm_member(root, &dir))
+                .map(|package| package.dependencies)
+                .unwrap_or_default();
+            found.push(WorkspacePackage { name, dir, dependencies });
+        }
+    }
+    found
+}
+
+/// Every workspace package declared at `root`. Manifests that are missing
+/// or can't be parsed contribute nothing.
+pub fn packages(root: &Path) -> Vec<WorkspacePackage> {
+    let mut packages = cargo_packages(root);
+    for package in npm_packages(root) {
+        if !packages.iter().any(|known| known.dir == package.dir) {
+            packages.push(package);
+        }
+    }
+    packages
+}
+
+/// Directories under `root` matching `pattern`, where a `*` segment matches
+/// any one folder, e.g. `packages/*`.
+pub fn expand(root: &Path, pattern: &str) -> Vec<String> {
+    let segments: Vec<&str> = pattern
+        .trim_start_matches("./")
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    let mut dirs = vec![String::new()];
+    for segment in segments {
+        let mut next = Vec::new();
+        for dir in &dirs {
+            if segment.contains('*') {
+                let entries = match fs::read_dir(root.join(dir)) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                let mut names: Vec<String> = entries
+                    .flatten()
+                    .filter(|entry| entry.path().is_dir())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .filter(|name| !name.starts_with('.') && matches_segment(segment, name))
+                    .collect();
+                names.sort();
+                next.extend(names.into_iter().map(|name| join(dir, &name)));
+            } else if root.join(dir).join(segment).is_dir() {
+                next.push(join(dir, segment));
+            }
+        }
+        dirs = next;
+    }
+    dirs.retain(|dir| !dir.is_empty());
+    dirs
+}
+
+fn cargo_packages(root: &Path) -> Vec<WorkspacePackage> {
+    let manifest = match read_toml(&root.join("Cargo.toml")) {
+        Some(manifest) => manifest,
+        None => return Vec::new(),
+    };
+    let workspace = match manifest.get("workspace") {
+        Some(workspace) => workspace,
+        None => return Vec::new(),
+    };
+    let patterns = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|value| value.as_array())
+            .map(|values| values.iter().filter_map(|value| value.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    };
+    let excluded: Vec<String> = patterns("exclude").iter().flat_map(|pattern| expand(root, pattern)).collect();
+
+    patterns("members")
+        .iter()
+        .flat_map(|pattern| expand(root, pattern))
+        .filter(|dir| !excluded.contains(dir))
+        .filter_map(|dir| cargo_member(root, &dir))
+        .collect()
+}
+
+fn cargo_member(root: &Path, dir: &str) -> Option<WorkspacePackage> {
+    let member = read_toml(&root.join(dir).join("Cargo.toml"))?;
+    let name = member.get("package")?.get("name")?.as_str()?.to_string();
+    let mut dependencies = Vec::new();
+    for table in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        if let Some(table) = member.get(table).and_then(|table| table.as_table()) {
+            for (key, spec) in table {
+                // `alias = { package = "real-name" }`
+                let real = spec.get("package").and_then(|name| name.as_str()).unwrap_or(key);
+                dependencies.push(real.to_string());
+            }
+        }
+    }
+    Some(WorkspacePackage { name, dir: dir.to_string(), dependencies })
+}
+
+fn npm_packages(root: &Path) -> Vec<WorkspacePackage> {
+    let manifest = match read_json(&root.join("package.json")) {
+        Some(manifest) => manifest,
+        None => return Vec::new(),
+    };
+    // Either a list of patterns or Yarn's `{ "packages": [...] }`
+    let workspaces = match manifest.get("workspaces") {
+        Some(serde_json::Value::Object(object)) => object.get("packages").cloned(),
+        other => other.cloned(),
+    };
+    let patterns: Vec<String> = workspaces
+        .and_then(|value| value.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect();
+
+    patterns
+        .iter()
+        .filter(|pattern| !pattern.starts_with('!'))
+        .flat_map(|pattern| expand(root, pattern))
+        .filter_map(|dir| npm_member(root, &dir))
+        .collect()
+}
+
+fn npm_member(root: &Path, dir: &str) -> Option<WorkspacePackage> {
+    let member = read_json(&root.join(dir).join("package.json"))?;
+    let name = short_npm_name(member.get("name")?.as_str()?);
+    let mut dependencies = Vec::new();
+    for field in ["dependencies", "devDependencies", "peerDependencies"] {
+        if let Some(object) = member.get(field).and_then(|field| field.as_object()) {
+            dependencies.extend(object.keys().map(|key| short_npm_name(key)));
+        }
+    }
+    Some(WorkspacePackage { name, dir: dir.to_string(), dependencies })
+}
+
+// `@acme/ui` is the `ui` package
+fn short_npm_name(name: &str) -> String {
+    name.rsplit('/').next().unwrap_or(name).to_string()
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn read_json(path: &Path) -> Option<serde_json::Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+// `*` anywhere in the segment, e.g. `*-service`
+fn matches_segment(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || !name[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
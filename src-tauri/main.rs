@@ -1,239 +1,631 @@
 use tauri::Manager;
-use git2::Repository;
-use std::process::Command;
-use reqwest::Client;
-use serde::{Serialize, Deserialize};
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tokio_util::sync::CancellationToken;
 use tokio::time::{interval, Duration};
-use tauri::State;
-use std::fs;
-use std::path::PathBuf;
+use tauri::{ClipboardManager, State};
+use tauri::api::notification::Notification;
 
-#[derive(Serialize, Deserialize, Clone)]
-struct AppConfig {
-    repo_path: String,
-    auto_commit_enabled: bool,
-    interval_minutes: u64,
-    auto_start: bool,
-    gemini_api_key: String,
-}
+use gitgenius_core::config::{self, AppConfig, RepoSettings};
+use gitgenius_core::control_api::{self, ControlApi};
+use gitgenius_core::editor::EditorApi;
+use gitgenius_core::monitor::{self, StatusServer};
+use gitgenius_core::events::{self, EventSink, RecordedEvent};
+use gitgenius_core::stream::EventStream;
+use gitgenius_core::forge::{self, PullRequest};
+use gitgenius_core::gitattributes::{self, Suggestion};
+use gitgenius_core::gitignore::{self, GitignoreSuggestion};
+use gitgenius_core::conflicts::{self, Conflicts, Resolution};
+use gitgenius_core::undo::{self, UndoResult};
+use gitgenius_core::health::{self, RepoHealth};
+use gitgenius_core::summary::{self, CycleSummary};
+use gitgenius_core::status::{self, RepoStatus};
+use gitgenius_core::logging::{self, LogEntry};
+use gitgenius_core::proxy::ProxySettings;
+use gitgenius_core::scheduler::Scheduler;
+use gitgenius_core::{approvals, autostart, branch, changelog, cleanup, daily, diagnostics, diff_stream, gemini, history, history_search, hunks, identity, pipeline, portable, release, remote, repo_path, revert, schedule, secrets, session, shutdown, snapshot, squash, stats, style, timer_state, tracker, trial, usage, webhook, work_summary};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct AppState {
-    config: Arc<Mutex<AppConfig>>,
+    config: Arc<RwLock<AppConfig>>,
     timer_running: Arc<Mutex<bool>>,
+    event_stream: EventStream,
+    // When the running auto-commit session began, unix seconds
+    session_started: Arc<Mutex<Option<i64>>>,
+    // When the timer will next attempt a cycle, unix seconds
+    next_run: Arc<Mutex<Option<u64>>>,
+    // Cancelled by `cancel_current_operation`, then replaced with a fresh one
+    cancel: Arc<Mutex<CancellationToken>>,
+    // Stops the running scheduler loop; replaced each time the timer starts
+    timer_stop: Arc<Mutex<CancellationToken>>,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        AppConfig {
-            repo_path: String::new(),
-            auto_commit_enabled: false,
-            interval_minutes: 30,
-            auto_start: false,
-            gemini_api_key: String::new(),
+// Forwards pipeline events to the frontend, the external event stream and,
+// for the categories enabled in the config, desktop notifications
+struct WindowEvents(tauri::AppHandle);
+
+impl EventSink for WindowEvents {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        let state = self.0.state::<AppState>();
+        state.event_stream.publish(event, &payload);
+        // Events are emitted from sync code; skip the notification rather
+        // than block if the config is being saved at this moment
+        if let Ok(config) = state.config.try_read() {
+            notify(&self.0, &config, event, &payload);
         }
+        self.0.emit_all(event, payload).ok();
     }
 }
 
-#[derive(Serialize)]
-struct GeminiRequest {
-    contents: Vec<Content>,
-    #[serde(rename = "systemInstruction")]
-    system_instruction: SystemInstruction,
+fn notify(app_handle: &tauri::AppHandle, config: &AppConfig, event: &str, payload: &serde_json::Value) {
+    let field = |name: &str| payload.get(name).and_then(|value| value.as_str()).unwrap_or_default();
+    let repo_name = std::path::Path::new(field("repo"))
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let (title, body) = match event {
+        "commit-created" if config.notify_commits => {
+            (format!("Committed to {}", repo_name), field("message").to_string())
+        }
+        "push-failed" if config.notify_push_failures => {
+            (format!("Push failed for {}", repo_name), field("error").to_string())
+        }
+        "generation-failed" if config.notify_api_errors => {
+            (format!("Gemini error for {}", repo_name), field("error").to_string())
+        }
+        // Tauri 1 notifications can't carry buttons; the window lists the
+        // waiting commits with their approve and reject buttons
+        "approval-requested" if config.notify_approvals => {
+            (format!("Commit waiting for approval in {}", repo_name), field("message").to_string())
+        }
+        "safe-mode-entered" if config.notify_safe_mode => {
+            let failures = payload.get("consecutive_failures").and_then(|value| value.as_u64()).unwrap_or_default();
+            (
+                format!("{} entered safe mode", repo_name),
+                format!("Pushing is paused after {} failed cycles: {}", failures, field("last_error")),
+            )
+        }
+        "safe-mode-left" if config.notify_safe_mode => {
+            (format!("{} left safe mode", repo_name), "Pushing and the regular schedule are back".to_string())
+        }
+        _ => return,
+    };
+
+    let identifier = app_handle.config().tauri.bundle.identifier.clone();
+    Notification::new(identifier).title(title).body(body).show().ok();
 }
 
-#[derive(Serialize)]
-struct SystemInstruction {
-    parts: Vec<Part>,
+// Timed commits queued with `commit_approval` on, oldest first
+#[tauri::command]
+async fn get_pending_approvals() -> Result<Vec<approvals::PendingCommit>, String> {
+    approvals::pending()
 }
 
-#[derive(Serialize)]
-struct Content {
-    parts: Vec<Part>,
+// Approves a queued commit, with `message` in place of the generated one
+// if given, and commits it right away
+#[tauri::command]
+async fn approve_commit(
+    id: String,
+    message: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let approved = approvals::approve(&id, message)?;
+    let config = state.config.read().await.clone();
+    let cancel = state.cancel.lock().await.clone();
+    pipeline::run_commit(&approved.repo, &config, &WindowEvents(app_handle), &cancel).await
 }
 
-#[derive(Serialize)]
-struct Part {
-    text: String,
+#[tauri::command]
+async fn reject_commit(id: String) -> Result<(), String> {
+    approvals::reject(&id).map(|_| ())
 }
 
-#[derive(Deserialize)]
-struct GeminiResponse {
-    candidates: Vec<Candidate>,
+#[tauri::command]
+async fn run_commit(
+    path: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let config = state.config.read().await.clone();
+    let cancel = state.cancel.lock().await.clone();
+    pipeline::run_commit(&path, &config, &WindowEvents(app_handle), &cancel).await
+}
+
+// A message for the pending changes without committing, with every
+// candidate ranked so the user can choose another one
+#[tauri::command]
+async fn preview_commit_message(path: String, state: State<'_, AppState>) -> Result<pipeline::GeneratedMessage, String> {
+    let config = state.config.read().await.clone();
+    let diff_text = pipeline::pending_diff(&path)?;
+    if diff_text.trim().is_empty() {
+        return Err(pipeline::NO_CHANGES.into());
+    }
+
+    let cancel = state.cancel.lock().await.clone();
+    tokio::select! {
+        generated = pipeline::generate_message(&path, &config, &diff_text) => generated,
+        _ = cancel.cancelled() => Err(pipeline::CANCELLED.into()),
+    }
+}
+
+#[tauri::command]
+async fn regenerate_message(
+    path: String,
+    previous_message: String,
+    hint: String,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let config = state.config.read().await.clone();
+
+    // Prefer what is already staged; nothing is staged when previewing from the editor
+    let mut diff_text = pipeline::staged_diff(&path)?;
+    if diff_text.trim().is_empty() {
+        diff_text = pipeline::pending_diff(&path)?;
+    }
+
+    let cancel = state.cancel.lock().await.clone();
+    tokio::select! {
+        message = pipeline::regenerate_message(&path, &config, &diff_text, &previous_message, &hint) => message,
+        _ = cancel.cancelled() => Err(pipeline::CANCELLED.into()),
+    }
+}
+
+// Names a branch after a task description, or after the pending changes when there is none
+#[tauri::command]
+async fn suggest_branch_name(
+    path: String,
+    description: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let config = state.config.read().await.clone();
+    let work = match description.filter(|description| !description.trim().is_empty()) {
+        Some(description) => description,
+        None => pipeline::pending_diff(&path)?,
+    };
+    branch::suggest_branch_name(&path, &config, &work).await
+}
+
+// Between `from` (default: latest tag) and `to` (default: HEAD); `write` adds it to CHANGELOG.md
+#[tauri::command]
+async fn generate_changelog(
+    path: String,
+    from: Option<String>,
+    to: Option<String>,
+    write: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<changelog::Changelog, String> {
+    let config = state.config.read().await.clone();
+    changelog::generate_changelog(&path, &config, from, to, None, write.unwrap_or(false)).await
+}
+
+// Standup summary of the commits from `from` to `to` (default: today), as
+// `YYYY-MM-DD`, across `repos` (default: all); `copy` puts it on the clipboard
+#[tauri::command]
+async fn generate_work_summary(
+    from: String,
+    to: Option<String>,
+    repos: Option<Vec<String>>,
+    copy: Option<bool>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<work_summary::WorkSummary, String> {
+    let config = state.config.read().await.clone();
+    let summary = work_summary::generate_work_summary(&config, &from, to.as_deref(), repos).await?;
+    if copy.unwrap_or(false) {
+        app_handle
+            .clipboard_manager()
+            .write_text(summary.markdown.clone())
+            .map_err(|e| format!("Failed to copy the summary: {}", e))?;
+    }
+    Ok(summary)
 }
 
-#[derive(Deserialize)]
-struct Candidate {
-    content: ContentResponse,
+#[tauri::command]
+async fn suggest_next_version(path: String) -> Result<release::VersionSuggestion, String> {
+    release::suggest_next_version(&path)
 }
 
-#[derive(Deserialize)]
-struct ContentResponse {
-    parts: Vec<PartResponse>,
+#[tauri::command]
+async fn create_release_tag(
+    path: String,
+    version: Option<String>,
+    sign: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<release::ReleaseTag, String> {
+    let config = state.config.read().await.clone();
+    release::create_release_tag(&path, &config, version, sign.unwrap_or(false)).await
 }
 
-#[derive(Deserialize)]
-struct PartResponse {
-    text: String,
+#[tauri::command]
+async fn create_and_checkout_branch(path: String, name: String) -> Result<String, String> {
+    branch::create_and_checkout_branch(&path, &name)
 }
 
-// RAG: System context for commit message generation
-const SYSTEM_CONTEXT: &str = r#"You are an expert Git commit message generator specialized in creating professional, concise, and meaningful commit messages following industry best practices.
+// Aborts the running cycle or regeneration; the timer keeps going
+#[tauri::command]
+async fn cancel_current_operation(state: State<'_, AppState>) -> Result<(), String> {
+    let mut cancel = state.cancel.lock().await;
+    cancel.cancel();
+    *cancel = CancellationToken::new();
+    tracing::info!("current operation cancelled");
+    Ok(())
+}
 
-CONTEXT AND PURPOSE:
-- You analyze git diffs to understand code changes
-- You generate commit messages following the Conventional Commits specification
-- Your primary function is to create clear, actionable commit messages that help developers understand changes at a glance
+#[tauri::command]
+async fn import_existing_history(repo_id: String) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || history::import_history(&repo_id))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-COMMIT MESSAGE RULES:
-1. Format: <type>(<scope>): <subject>
-2. Types: feat, fix, docs, style, refactor, test, chore, perf
-3. Subject: Imperative mood, lowercase, no period, max 50 characters
-4. Be specific and descriptive
-5. Focus on WHAT and WHY, not HOW
+// Full-text search over the recorded commits
+#[tauri::command]
+async fn search_history(query: history_search::HistoryQuery) -> Result<Vec<history_search::HistoryHit>, String> {
+    tokio::task::spawn_blocking(move || history_search::search_history(&query))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-EXAMPLES:
-- feat(auth): add JWT token validation
-- fix(api): resolve null pointer in user endpoint
-- refactor(database): optimize query performance
-- docs(readme): update installation instructions
-- style(components): format code with prettier
+// `git show` of a search result
+#[tauri::command]
+async fn show_history_commit(path: String, sha: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || history_search::show_commit(&path, &sha))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-ANALYSIS APPROACH:
-1. Identify modified files and their purpose
-2. Determine the type of change (feature, bug fix, etc.)
-3. Extract the main impact or goal
-4. Formulate a clear, concise message
+#[tauri::command]
+async fn create_pull_request(
+    path: String,
+    base: String,
+    title: Option<String>,
+    body: Option<String>,
+    generate: bool,
+    state: State<'_, AppState>,
+) -> Result<PullRequest, String> {
+    let config = state.config.read().await.clone();
+    forge::open_or_update_pull_request(&path, &config, &base, title, body, generate).await
+}
 
-Always respond with ONLY the commit message, no explanations or additional text."#;
+// Stores the Gemini API key in the keychain, where it wins over config.json;
+// an empty key removes it
+#[tauri::command]
+async fn set_gemini_api_key(api_key: String) -> Result<(), String> {
+    let result = if api_key.is_empty() {
+        secrets::delete_secret(secrets::GEMINI_API_KEY)
+    } else {
+        secrets::set_secret(secrets::GEMINI_API_KEY, api_key.trim())
+    };
+    config::forget_keychain_api_key();
+    result
+}
 
 #[tauri::command]
-async fn run_commit(path: String, state: State<'_, AppState>) -> Result<String, String> {
-    let repo = Repository::open(&path).map_err(|e| e.to_string())?;
-    let statuses = repo.statuses(None).map_err(|e| e.to_string())?;
-    
-    if statuses.is_empty() {
-        return Ok("No changes to commit".into());
+async fn set_github_token(token: String) -> Result<(), String> {
+    if token.is_empty() {
+        secrets::delete_secret(secrets::GITHUB_TOKEN)
+    } else {
+        secrets::set_secret(secrets::GITHUB_TOKEN, &token)
     }
+}
 
-    // Get API key from config
-    let config = state.config.lock().map_err(|e| e.to_string())?;
-    let api_key = config.gemini_api_key.clone();
-    drop(config);
+// Replaces the control API token; the new one is returned once for scripts to use
+#[tauri::command]
+async fn generate_control_api_token() -> Result<String, String> {
+    control_api::generate_token()
+}
 
-    if api_key.is_empty() {
-        return Err("Gemini API Key not configured. Please add your API key in settings.".into());
+// Token for GitLab's API, used by the CI gate
+#[tauri::command]
+async fn set_gitlab_token(token: String) -> Result<(), String> {
+    if token.is_empty() {
+        secrets::delete_secret(secrets::GITLAB_TOKEN)
+    } else {
+        secrets::set_secret(secrets::GITLAB_TOKEN, &token)
     }
+}
 
-    // Stage all changes
-    Command::new("git")
-        .arg("add")
-        .arg(".")
-        .current_dir(&path)
-        .status()
-        .map_err(|e| e.to_string())?;
-
-    // Get diff with context
-    let diff = Command::new("git")
-        .arg("diff")
-        .arg("--cached")
-        .arg("--stat")
-        .current_dir(&path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    let diff_detailed = Command::new("git")
-        .arg("diff")
-        .arg("--cached")
-        .current_dir(&path)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    let diff_stat = String::from_utf8_lossy(&diff.stdout);
-    let diff_content = String::from_utf8_lossy(&diff_detailed.stdout);
-
-    // Limit diff size to avoid token limits (max 10000 chars)
-    let diff_text = if diff_content.len() > 10000 {
-        format!("{}\n\n{}", diff_stat, &diff_content[..10000])
+// Bitbucket access token, or `username:app-password`
+#[tauri::command]
+async fn set_bitbucket_token(token: String) -> Result<(), String> {
+    if token.is_empty() {
+        secrets::delete_secret(secrets::BITBUCKET_TOKEN)
     } else {
-        format!("{}\n\n{}", diff_stat, diff_content)
-    };
+        secrets::set_secret(secrets::BITBUCKET_TOKEN, &token)
+    }
+}
 
-    // Create RAG-enhanced prompt
-    let user_prompt = format!(
-        "Analyze these git changes and generate a commit message:\n\n{}",
-        diff_text
-    );
+// Password for `proxy_username`, kept out of config.json
+#[tauri::command]
+async fn set_proxy_password(password: String) -> Result<(), String> {
+    if password.is_empty() {
+        secrets::delete_secret(secrets::PROXY_PASSWORD)
+    } else {
+        secrets::set_secret(secrets::PROXY_PASSWORD, &password)
+    }
+}
 
-    let client = Client::new();
-    
-    let request_body = GeminiRequest {
-        system_instruction: SystemInstruction {
-            parts: vec![Part {
-                text: SYSTEM_CONTEXT.to_string(),
-            }],
-        },
-        contents: vec![Content {
-            parts: vec![Part {
-                text: user_prompt,
-            }],
-        }],
-    };
+// API token for the configured `issue_tracker`, kept out of config.json
+#[tauri::command]
+async fn set_issue_tracker_token(token: String, state: State<'_, AppState>) -> Result<(), String> {
+    let tracker = state.config.read().await.issue_tracker;
+    let name = tracker.secret_name().ok_or("Choose an issue tracker first")?;
+    if token.is_empty() {
+        secrets::delete_secret(name)
+    } else {
+        secrets::set_secret(name, &token)
+    }
+}
+
+// Fetches `issue` from the configured tracker, to check the token and URL
+#[tauri::command]
+async fn test_issue_tracker(issue: String, state: State<'_, AppState>) -> Result<tracker::TrackerIssue, String> {
+    let config = state.config.read().await.clone();
+    let name = config.issue_tracker.secret_name().ok_or("Choose an issue tracker first")?;
+    let token = secrets::get_secret(name)?.ok_or("No API token is stored for the issue tracker")?;
+    tracker::fetch_issue(&config, &token, issue.trim()).await
+}
+
+#[tauri::command]
+async fn get_gitattributes_suggestions(path: String) -> Result<Vec<Suggestion>, String> {
+    gitattributes::pending_suggestions(&path)
+}
 
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash-exp:generateContent?key={}",
-        api_key
-    );
+#[tauri::command]
+async fn apply_gitattributes_suggestions(path: String) -> Result<Vec<String>, String> {
+    gitattributes::apply_suggestions(&path)
+}
 
-    let response = client
-        .post(&url)
-        .json(&request_body)
-        .send()
+// The pending .gitignore rules for files that keep showing up
+#[tauri::command]
+async fn get_gitignore_suggestion(path: String) -> Result<Option<GitignoreSuggestion>, String> {
+    gitignore::pending_suggestion(&path)
+}
+
+// Appends `rules`, or the pending suggestion, to the repository's .gitignore
+#[tauri::command]
+async fn append_gitignore(path: String, rules: Option<Vec<String>>) -> Result<Vec<String>, String> {
+    gitignore::append_suggestions(&path, rules)
+}
+
+// The conflicted files of a merge or rebase in progress, with base, ours and theirs
+#[tauri::command]
+async fn get_conflicts(path: String) -> Result<Conflicts, String> {
+    conflicts::get_conflicts(&path)
+}
+
+// Resolves `file` with one side or custom content; returns the files still conflicted
+#[tauri::command]
+async fn apply_resolution(path: String, file: String, resolution: Resolution) -> Result<Vec<String>, String> {
+    conflicts::apply_resolution(&path, &file, resolution)
+}
+
+// A merged version of `file` proposed by the model, to review before applying
+#[tauri::command]
+async fn suggest_conflict_resolution(path: String, file: String, state: State<'_, AppState>) -> Result<String, String> {
+    let config = state.config.read().await.clone();
+    conflicts::suggest_resolution(&config, &path, &file).await
+}
+
+#[tauri::command]
+async fn undo_last_commit(path: String) -> Result<UndoResult, String> {
+    undo::undo_last_commit(&path)
+}
+
+#[tauri::command]
+async fn get_repo_health(path: String) -> Result<RepoHealth, String> {
+    Ok(health::repo_health(&path))
+}
+
+#[derive(serde::Serialize)]
+struct SafeModeLeft {
+    repo: String,
+}
+
+#[tauri::command]
+async fn acknowledge_safe_mode(path: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    if health::acknowledge_safe_mode(&path)? {
+        events::emit(&WindowEvents(app_handle), "safe-mode-left", &SafeModeLeft { repo: path });
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_recent_logs(
+    min_level: Option<String>,
+    since: Option<u64>,
+    limit: Option<usize>,
+) -> Result<Vec<LogEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        logging::recent_logs(min_level.as_deref(), since, limit.unwrap_or(200))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn probe_remote(path: String, state: State<'_, AppState>) -> Result<remote::RemoteProbe, String> {
+    let config = state.config.read().await.clone();
+    let proxy = ProxySettings::from_config(&config);
+    let push_remote = config.repo_settings(&path).push_remote;
+    tokio::task::spawn_blocking(move || remote::probe(&path, &push_remote, &proxy))
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(|e| e.to_string())?
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Gemini API error: {}", error_text));
+// Choices for the `push_remote` and `push_refspec` dropdowns
+#[tauri::command]
+async fn list_remotes(path: String) -> Result<Vec<remote::RemoteInfo>, String> {
+    tokio::task::spawn_blocking(move || remote::list_remotes(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn list_branches(path: String, remote: Option<String>) -> Result<Vec<remote::BranchInfo>, String> {
+    tokio::task::spawn_blocking(move || remote::list_branches(&path, remote.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn list_snapshots(
+    path: String,
+    branch: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<snapshot::Snapshot>, String> {
+    snapshot::list_snapshots(&path, branch.as_deref(), limit.unwrap_or(100))
+}
+
+#[tauri::command]
+async fn restore_snapshot(path: String, sha: String) -> Result<snapshot::Snapshot, String> {
+    snapshot::restore_snapshot(&path, &sha)
+}
+
+// Backup branches the cleanup would delete, for the user to confirm
+#[tauri::command]
+async fn list_stale_branches(path: String, state: State<'_, AppState>) -> Result<Vec<cleanup::StaleBranch>, String> {
+    let config = state.config.read().await.clone();
+    tokio::task::spawn_blocking(move || cleanup::stale_branches(&path, &config))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn delete_stale_branches(path: String, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let config = state.config.read().await.clone();
+    let (deleted, errors) = tokio::task::spawn_blocking(move || cleanup::delete_stale_branches(&path, &config))
+        .await
+        .map_err(|e| e.to_string())??;
+    if !errors.is_empty() {
+        return Err(format!("Failed to delete: {}", errors.join("; ")));
     }
+    Ok(deleted)
+}
+
+// Tries the whole pipeline against a temporary local remote
+#[tauri::command]
+async fn run_trial(
+    path: Option<String>,
+    keep: Option<bool>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<trial::TrialResult, String> {
+    let config = state.config.read().await.clone();
+    let source = path.filter(|path| !path.is_empty());
+    trial::run(source.as_deref(), &config, &WindowEvents(app_handle), keep.unwrap_or(false)).await
+}
+
+#[tauri::command]
+async fn get_repo_status(path: String) -> Result<RepoStatus, String> {
+    status::repo_status(&path)
+}
+
+// Squashes `from`..HEAD into one commit with a generated message; `force` allows pushed commits
+#[tauri::command]
+async fn squash_commits(
+    path: String,
+    from: String,
+    to: Option<String>,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<squash::SquashResult, String> {
+    let config = state.config.read().await.clone();
+    squash::squash_commits(&path, &config, &from, to.as_deref(), force.unwrap_or(false)).await
+}
+
+// Undoes a commit from the history view with a generated `revert:` message
+#[tauri::command]
+async fn revert_commit(
+    path: String,
+    sha: String,
+    reason: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<revert::RevertResult, String> {
+    let config = state.config.read().await.clone();
+    revert::revert_commit(&path, &config, &sha, reason.as_deref()).await
+}
+
+// `date` is `YYYY-MM-DD`; defaults to the current day branch, or today's
+#[tauri::command]
+async fn squash_day_into_branch(
+    path: String,
+    date: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<daily::SquashedDay, String> {
+    let config = state.config.read().await.clone();
+    daily::squash_day(&path, &config, date.as_deref()).await
+}
+
+// Sent as `staged-diff-chunk` events tagged with `stream`; resolves with the totals once all are sent
+#[tauri::command]
+async fn get_staged_diff(
+    path: String,
+    stream: String,
+    app_handle: tauri::AppHandle,
+) -> Result<diff_stream::DiffSummary, String> {
+    let events = WindowEvents(app_handle);
+    tokio::task::spawn_blocking(move || diff_stream::stream_staged_diff(&path, &stream, &events))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Unstaged changes split into hunks, for picking what goes in the commit
+#[tauri::command]
+async fn get_diff_hunks(path: String) -> Result<Vec<hunks::FileHunks>, String> {
+    hunks::diff_hunks(&path)
+}
+
+#[tauri::command]
+async fn stage_hunks(path: String, selection: Vec<hunks::HunkSelection>) -> Result<usize, String> {
+    hunks::stage_hunks(&path, &selection)
+}
+
+#[tauri::command]
+async fn get_cycle_summaries(
+    path: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<CycleSummary>, String> {
+    summary::recent_summaries(path.as_deref(), limit.unwrap_or(50))
+}
 
-    let gemini_response: GeminiResponse = response
-        .json()
+#[tauri::command]
+async fn get_statistics(path: Option<String>, days: Option<u32>) -> Result<Vec<stats::RepoStatistics>, String> {
+    tokio::task::spawn_blocking(move || stats::statistics(path.as_deref(), days.unwrap_or(30)))
         .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-
-    let commit_message = gemini_response
-        .candidates
-        .get(0)
-        .and_then(|c| c.content.parts.get(0))
-        .map(|p| p.text.trim().to_string())
-        .ok_or("No commit message generated")?;
-
-    // Clean the message (remove quotes if present)
-    let clean_message = commit_message
-        .trim_matches('"')
-        .trim_matches('\'')
-        .trim()
-        .to_string();
-
-    // Commit with generated message
-    Command::new("git")
-        .arg("commit")
-        .arg("-m")
-        .arg(&clean_message)
-        .current_dir(&path)
-        .status()
-        .map_err(|e| e.to_string())?;
-
-    // Push changes
-    Command::new("git")
-        .arg("push")
-        .current_dir(&path)
-        .status()
-        .map_err(|e| e.to_string())?;
-
-    Ok(clean_message)
+        .map_err(|e| e.to_string())?
+}
+
+// Token usage of the last `days` days and how much of the monthly budget is left
+#[tauri::command]
+async fn get_usage_summary(days: Option<u32>, state: State<'_, AppState>) -> Result<usage::UsageSummary, String> {
+    let config = state.config.read().await.clone();
+    tokio::task::spawn_blocking(move || usage::usage_summary(&config, days.unwrap_or(30)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// The last `limit` events (all kept ones by default), for a window opened late
+#[tauri::command]
+async fn get_recent_events(limit: Option<usize>) -> Result<Vec<RecordedEvent>, String> {
+    Ok(events::recent_events(limit))
+}
+
+// Same JSON as the status server's `GET /status`
+#[tauri::command]
+async fn get_app_status(state: State<'_, AppState>) -> Result<monitor::AppStatus, String> {
+    Ok(status_server(&state).status().await)
+}
+
+fn status_server(state: &AppState) -> StatusServer {
+    StatusServer {
+        config: state.config.clone(),
+        timer_running: state.timer_running.clone(),
+        next_run: state.next_run.clone(),
+    }
 }
 
 #[tauri::command]
@@ -241,54 +633,125 @@ async fn save_config(
     config: AppConfig,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let mut app_config = state.config.lock().map_err(|e| e.to_string())?;
-    *app_config = config.clone();
+    apply_config(config, &state).await
+}
+
+// Checks `config`, makes it current and persists it
+async fn apply_config(config: AppConfig, state: &AppState) -> Result<(), String> {
+    // The same repository added under two paths would get two timers
+    let mut config = config;
+    for path in config.dedupe_repos() {
+        tracing::warn!(repo = %path, "dropped duplicate repository entry");
+    }
+    schedule::validate(&config.schedule)?;
+    style::validate_template(&config.custom_message_template)?;
+    let default_template = config.default_repo_template.trim();
+    if !default_template.is_empty() && !config.repo_templates.contains_key(default_template) {
+        return Err(format!("No repository template named {:?}", default_template));
+    }
+    for repo in &config.repos {
+        let (name, email) = (repo.author_name.trim(), repo.author_email.trim());
+        if !name.is_empty() {
+            identity::validate_name(name).map_err(|e| format!("{}: {}", repo.path, e))?;
+        }
+        if !email.is_empty() {
+            identity::validate_email(email).map_err(|e| format!("{}: {}", repo.path, e))?;
+        }
+    }
+
+    if config.auto_start != state.config.read().await.auto_start {
+        let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+        autostart::sync(config.auto_start, &exe)?;
+    }
+
+    *state.config.write().await = config.clone();
     
     // Persist config to file
-    let config_path = get_config_path()?;
-    let config_json = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("Failed to serialize config: {}", e))?;
-    
-    fs::write(config_path, config_json)
-        .map_err(|e| format!("Failed to save config: {}", e))?;
-    
-    Ok(())
+    config::save_config(&config)
+}
+
+// Writes the whole configuration to `path`, for setting up another machine
+#[tauri::command]
+async fn export_config(
+    path: String,
+    include_secrets: bool,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let config = state.config.read().await.clone();
+    let exported = tokio::task::spawn_blocking(move || {
+        portable::export_config(&config, include_secrets, passphrase.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    std::fs::write(&path, exported).map_err(|e| format!("Failed to write export: {}", e))
+}
+
+// Replaces the configuration with the one exported to `path`
+#[tauri::command]
+async fn import_config(
+    path: String,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<portable::ImportedConfig, String> {
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read export: {}", e))?;
+    let current = state.config.read().await.clone();
+    let imported = tokio::task::spawn_blocking(move || {
+        portable::import_config(&text, passphrase.as_deref(), &current)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    apply_config(imported.config.clone(), &state).await?;
+    Ok(imported)
+}
+
+// Commits changes the size guard stopped, once the user has looked at them
+#[tauri::command]
+async fn confirm_large_commit(
+    path: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let config = state.config.read().await.clone();
+    let cancel = state.cancel.lock().await.clone();
+    pipeline::run_confirmed_commit(&path, &config, &WindowEvents(app_handle), &cancel).await
+}
+
+#[tauri::command]
+async fn get_launch_at_login() -> Result<bool, String> {
+    Ok(autostart::is_enabled())
+}
+
+// Registers or removes the OS login item and keeps `auto_start` in step
+#[tauri::command]
+async fn set_launch_at_login(enabled: bool, state: State<'_, AppState>) -> Result<bool, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    autostart::sync(enabled, &exe)?;
+
+    let mut config = state.config.write().await;
+    config.auto_start = enabled;
+    config::save_config(&config)?;
+    Ok(autostart::is_enabled())
 }
 
 #[tauri::command]
 async fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
-    let config = state.config.lock().map_err(|e| e.to_string())?;
-    Ok(config.clone())
+    Ok(state.config.read().await.clone())
 }
 
 #[tauri::command]
 async fn load_config_from_file(state: State<'_, AppState>) -> Result<AppConfig, String> {
-    let config_path = get_config_path()?;
+    let config = config::load_config()?;
     
-    if config_path.exists() {
-        let config_str = fs::read_to_string(config_path)
-            .map_err(|e| format!("Failed to read config: {}", e))?;
-        
-        let config: AppConfig = serde_json::from_str(&config_str)
-            .map_err(|e| format!("Failed to parse config: {}", e))?;
-        
-        let mut app_config = state.config.lock().map_err(|e| e.to_string())?;
-        *app_config = config.clone();
-        
-        Ok(config)
-    } else {
-        Ok(AppConfig::default())
+    // The same repository added under two paths would get two timers
+    let mut config = config;
+    for path in config.dedupe_repos() {
+        tracing::warn!(repo = %path, "dropped duplicate repository entry");
     }
-}
 
-fn get_config_path() -> Result<PathBuf, String> {
-    let mut path = dirs::config_dir()
-        .ok_or("Failed to get config directory")?;
-    path.push("auto-commit-app");
-    fs::create_dir_all(&path)
-        .map_err(|e| format!("Failed to create config directory: {}", e))?;
-    path.push("config.json");
-    Ok(path)
+    *state.config.write().await = config.clone();
+    
+    Ok(config)
 }
 
 #[tauri::command]
@@ -296,55 +759,114 @@ async fn start_auto_commit(
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    let config = state.config.lock().map_err(|e| e.to_string())?;
+    start_timer(state.inner(), app_handle, Duration::ZERO).await
+}
+
+// `initial_delay` holds back the first cycle, for a timer resumed at launch
+async fn start_timer(
+    state: &AppState,
+    app_handle: tauri::AppHandle,
+    initial_delay: Duration,
+) -> Result<(), String> {
+    let config = state.config.read().await;
     let interval_minutes = config.interval_minutes;
-    let repo_path = config.repo_path.clone();
+    let repos = config.scheduled_repos();
     drop(config);
 
-    let mut timer_running = state.timer_running.lock().map_err(|e| e.to_string())?;
+    let mut timer_running = state.timer_running.lock().await;
     if *timer_running {
         return Err("Timer is already running".into());
     }
     *timer_running = true;
     drop(timer_running);
-
-    let state_clone = state.inner().clone();
-    
-    tauri::async_runtime::spawn(async move {
-        let mut interval_timer = interval(Duration::from_secs(interval_minutes * 60));
-        
-        loop {
-            interval_timer.tick().await;
-            
-            let timer_running = state_clone.timer_running.lock().unwrap();
-            if !*timer_running {
-                break;
-            }
-            drop(timer_running);
-
-            match run_commit(repo_path.clone(), State::from(&state_clone)).await {
-                Ok(msg) => {
-                    if msg != "No changes to commit" {
-                        app_handle.emit_all("commit-status", msg).ok();
-                    }
-                }
-                Err(e) => {
-                    app_handle.emit_all("commit-error", e).ok();
-                }
-            }
+    *state.session_started.lock().await = Some(session::unix_now());
+    for repo in &repos {
+        if let Err(e) = timer_state::set_running(repo, true) {
+            tracing::warn!(repo = %repo, error = %e, "could not save timer state");
         }
-    });
+    }
+
+    // A loop from before a quick stop and start may still be asleep
+    let stop = CancellationToken::new();
+    std::mem::replace(&mut *state.timer_stop.lock().await, stop.clone()).cancel();
+    let scheduler = Scheduler {
+        config: state.config.clone(),
+        events: Arc::new(WindowEvents(app_handle)),
+        stop,
+        next_run: state.next_run.clone(),
+        cancel: state.cancel.clone(),
+    };
+    tracing::info!(repos = repos.len(), interval_minutes, "auto-commit timer started");
+    tauri::async_runtime::spawn(scheduler.run(initial_delay));
 
     Ok(())
 }
 
 #[tauri::command]
-async fn stop_auto_commit(state: State<'_, AppState>) -> Result<(), String> {
-    let mut timer_running = state.timer_running.lock().map_err(|e| e.to_string())?;
-    *timer_running = false;
+async fn stop_auto_commit(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    stop_timer(state.inner()).await;
+
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        end_session(&state, &WindowEvents(app_handle)).await;
+    });
     Ok(())
 }
 
+// Cancels running cycles and waits for them to roll back and record
+// unpushed commits; the timer's saved state is left alone so it resumes
+async fn shut_down(state: &AppState) {
+    shutdown::begin();
+    state.cancel.lock().await.cancel();
+    let timeout = Duration::from_secs(state.config.read().await.shutdown_timeout_secs);
+    if shutdown::in_flight() > 0 {
+        tracing::info!(cycles = shutdown::in_flight(), "waiting for running cycles before exiting");
+    }
+    if !shutdown::wait_idle(timeout).await {
+        tracing::warn!(cycles = shutdown::in_flight(), "exiting with cycles still running; they roll back at the next launch");
+    }
+}
+
+// Not called on exit, so a timer left running resumes at the next launch
+async fn stop_timer(state: &AppState) {
+    *state.timer_running.lock().await = false;
+    state.timer_stop.lock().await.cancel();
+    *state.next_run.lock().await = None;
+
+    for repo in state.config.read().await.scheduled_repos() {
+        if let Err(e) = timer_state::set_running(&repo, false) {
+            tracing::warn!(repo = %repo, error = %e, "could not save timer state");
+        }
+    }
+}
+
+// Unix seconds of the timer's next attempt, None while it is stopped
+#[tauri::command]
+async fn next_scheduled_run(state: State<'_, AppState>) -> Result<Option<u64>, String> {
+    if !*state.timer_running.lock().await {
+        return Ok(None);
+    }
+    Ok(*state.next_run.lock().await)
+}
+
+// Emits a session summary for each scheduled repository, if a session
+// was running
+async fn end_session(state: &AppState, sink: &WindowEvents) {
+    let started_at = match state.session_started.lock().await.take() {
+        Some(started_at) => started_at,
+        None => return,
+    };
+    let config = state.config.read().await.clone();
+
+    for repo in config.scheduled_repos() {
+        let summary = session::summarize(&repo, started_at, &config).await;
+        events::emit(sink, "session-summary", &summary);
+    }
+}
+
 #[tauri::command]
 async fn select_directory() -> Result<String, String> {
     use tauri::api::dialog::blocking::FileDialogBuilder;
@@ -356,56 +878,396 @@ async fn select_directory() -> Result<String, String> {
     Ok(path.to_string_lossy().to_string())
 }
 
+// Checks for the setup wizard; the UI passes the notification permission,
+// which only the webview can read
 #[tauri::command]
-async fn test_api_key(api_key: String) -> Result<String, String> {
-    let client = Client::new();
-    
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash-exp:generateContent?key={}",
-        api_key
-    );
-
-    let test_request = GeminiRequest {
-        system_instruction: SystemInstruction {
-            parts: vec![Part {
-                text: "You are a helpful assistant.".to_string(),
-            }],
-        },
-        contents: vec![Content {
-            parts: vec![Part {
-                text: "Say 'API Key is valid' if you can read this.".to_string(),
-            }],
-        }],
-    };
+async fn run_diagnostics(
+    path: Option<String>,
+    notifications_granted: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<diagnostics::DiagnosticsReport, String> {
+    let config = state.config.read().await.clone();
+    Ok(diagnostics::run_diagnostics(&config, path.as_deref(), notifications_granted).await)
+}
 
-    let response = client
-        .post(&url)
-        .json(&test_request)
-        .send()
+// Run on a picked folder before it is saved as the repository
+#[tauri::command]
+async fn validate_repo_path(path: String) -> Result<repo_path::RepoPathCheck, String> {
+    tokio::task::spawn_blocking(move || repo_path::validate_repo_path(&path))
         .await
-        .map_err(|e| format!("Connection error: {}", e))?;
+        .map_err(|e| e.to_string())
+}
 
-    if response.status().is_success() {
-        Ok("API Key is valid!".to_string())
-    } else {
-        let error_text = response.text().await.unwrap_or_default();
-        Err(format!("Invalid API Key: {}", error_text))
+// Adds the repository at `path` with the settings of `template`, or of the default template
+#[tauri::command]
+async fn add_repo_from_template(
+    path: String,
+    template: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<RepoSettings, String> {
+    let root = repo_path::resolve(&path)?.root;
+    let mut config = state.config.read().await.clone();
+    if config.repos.iter().any(|repo| repo.path == root) {
+        return Err(format!("{} has already been added", root));
+    }
+    let settings = config.settings_from_template(&root, template.as_deref())?;
+    config.repos.push(settings.clone());
+    apply_config(config, &state).await?;
+    Ok(settings)
+}
+
+// Saves the settings of the repository at `path` as the template `name`, replacing one of that name
+#[tauri::command]
+async fn save_repo_template(name: String, path: String, state: State<'_, AppState>) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Template name is empty".into());
+    }
+    let mut config = state.config.read().await.clone();
+    let settings = RepoSettings { path: String::new(), ..config.repo_settings(&path) };
+    config.repo_templates.insert(name, settings);
+    apply_config(config, &state).await
+}
+
+// Marks `name` as the template for new repositories; empty clears it
+#[tauri::command]
+async fn set_default_repo_template(name: String, state: State<'_, AppState>) -> Result<(), String> {
+    let mut config = state.config.read().await.clone();
+    config.default_repo_template = name.trim().to_string();
+    apply_config(config, &state).await
+}
+
+#[tauri::command]
+async fn test_api_key(api_key: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let config = state.config.read().await.clone();
+    // Without a key from the form, the one cycles would use
+    let api_key = api_key
+        .filter(|key| !key.is_empty())
+        .unwrap_or_else(|| config.api_key());
+    gemini::test_api_key(&api_key, &gemini::ModelOptions::from_config(&config)).await
+}
+
+#[tauri::command]
+async fn test_webhook(url: String, state: State<'_, AppState>) -> Result<String, String> {
+    let proxy = ProxySettings::from_config(&*state.config.read().await);
+    webhook::test_webhook(&url, &proxy).await
+}
+
+#[tauri::command]
+async fn list_available_models(
+    api_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<gemini::ModelInfo>, String> {
+    let config = state.config.read().await.clone();
+    // The settings form may hold a key that hasn't been saved yet
+    let api_key = api_key
+        .filter(|key| !key.is_empty())
+        .unwrap_or_else(|| config.api_key());
+    gemini::list_models(&api_key, &gemini::ModelOptions::from_config(&config)).await
+}
+
+#[derive(serde::Serialize)]
+struct TimerToggled {
+    running: bool,
+    reason: &'static str,
+}
+
+// Starts the timer when a schedule window opens and, after one last commit,
+// stops it when the window closes. Only transitions act, so a timer the
+// user stopped by hand stays stopped until the next working day
+async fn working_hours_loop(state: AppState, app_handle: tauri::AppHandle) {
+    let events = WindowEvents(app_handle.clone());
+    let mut interval_timer = interval(Duration::from_secs(60));
+    let mut was_open = None;
+
+    loop {
+        interval_timer.tick().await;
+
+        let config = state.config.read().await.clone();
+        if !config.working_hours_auto_toggle || config.schedule.is_empty() {
+            was_open = None;
+            continue;
+        }
+
+        let open = schedule::delay_until_open(&config.schedule).is_none();
+        let running = *state.timer_running.lock().await;
+        match (was_open, open) {
+            // Opening, or the app was launched during working hours
+            (None | Some(false), true) if !running => match start_timer(&state, app_handle.clone(), Duration::ZERO).await {
+                Ok(()) => {
+                    tracing::info!("working hours started, timer started");
+                    events::emit(&events, "timer-toggled", &TimerToggled { running: true, reason: "working_hours" });
+                }
+                Err(e) => tracing::warn!(error = %e, "could not start timer"),
+            },
+            (Some(true), false) if running => {
+                // Don't leave the day's last changes uncommitted overnight
+                let cancel = state.cancel.lock().await.clone();
+                for repo in config.scheduled_repos() {
+                    if let Err(e) = pipeline::run_commit(&repo, &config, &events, &cancel).await {
+                        events::emit(&events, "commit-error", &e);
+                    }
+                }
+                stop_timer(&state).await;
+                end_session(&state, &events).await;
+                tracing::info!("working hours ended, timer stopped");
+                events::emit(&events, "timer-toggled", &TimerToggled { running: false, reason: "working_hours" });
+            }
+            _ => {}
+        }
+        was_open = Some(open);
+    }
+}
+
+// Background fetches run for the whole app lifetime, independent of the commit timer
+async fn fetch_loop(state: AppState, app_handle: tauri::AppHandle) {
+    let events = WindowEvents(app_handle);
+    let mut last_fetch = HashMap::new();
+    let mut interval_timer = interval(Duration::from_secs(60));
+
+    loop {
+        interval_timer.tick().await;
+
+        let config = state.config.read().await.clone();
+        remote::fetch_due_repos(&config, &mut last_fetch, &events).await;
     }
 }
 
 fn main() {
+    // Held until exit so buffered log lines are flushed
+    let _log_guard = match logging::init() {
+        Ok(guard) => Some(guard),
+        // Nothing to log to yet
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    };
+
+    let config = config::load_config().unwrap_or_default();
+    // Launched at login: stay in the background and start committing right away
+    let minimized = std::env::args().any(|arg| arg == autostart::MINIMIZED_FLAG);
+    let app_state = AppState {
+        config: Arc::new(RwLock::new(config.clone())),
+        ..AppState::default()
+    };
+
     tauri::Builder::default()
-        .manage(AppState::default())
+        .manage(app_state)
+        .setup(move |app| {
+            let state = app.state::<AppState>().inner().clone();
+
+            if config.event_stream_enabled {
+                let event_stream = state.event_stream.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = event_stream.serve().await {
+                        tracing::error!(error = %e, "event stream stopped");
+                    }
+                });
+            }
+
+            if config.editor_api_enabled {
+                let editor_api = EditorApi {
+                    config: state.config.clone(),
+                    events: Arc::new(WindowEvents(app.handle())),
+                    stream: state.event_stream.clone(),
+                    cancel: state.cancel.clone(),
+                };
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = editor_api.serve().await {
+                        tracing::error!(error = %e, "editor API stopped");
+                    }
+                });
+            }
+
+            if config.status_server_port != 0 {
+                let server = status_server(&state);
+                let port = config.status_server_port;
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = server.serve(port).await {
+                        tracing::error!(port, error = %e, "status server stopped");
+                    }
+                });
+            }
+
+            if config.control_api_port != 0 {
+                let control_api = ControlApi {
+                    config: state.config.clone(),
+                    events: Arc::new(WindowEvents(app.handle())),
+                    timer_running: state.timer_running.clone(),
+                    timer_stop: state.timer_stop.clone(),
+                    next_run: state.next_run.clone(),
+                    cancel: state.cancel.clone(),
+                };
+                let port = config.control_api_port;
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = control_api.serve(port).await {
+                        tracing::error!(port, error = %e, "control API stopped");
+                    }
+                });
+            }
+
+            if config.heartbeat_interval_secs != 0 {
+                let server = status_server(&state);
+                let interval = Duration::from_secs(config.heartbeat_interval_secs);
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = server.heartbeat(interval).await {
+                        tracing::error!(interval_secs = interval.as_secs(), error = %e, "heartbeat stopped");
+                    }
+                });
+            }
+
+            if minimized {
+                if let Some(window) = app.get_window("main") {
+                    window.hide().ok();
+                }
+            }
+
+            // Started at login, or running when the app last exited. The
+            // timer starts once for all repositories, as soon as the first
+            // one that was running is due
+            let mut resumed = Vec::new();
+            if config.resume_timer_on_startup {
+                for repo in config.scheduled_repos() {
+                    let saved = timer_state::timer_state(&repo);
+                    if !saved.running {
+                        continue;
+                    }
+                    let interval = Duration::from_secs(config.interval_for(&repo) * 60);
+                    let delay = timer_state::resume_delay(
+                        &saved,
+                        interval,
+                        config.catch_up_policy,
+                        Duration::from_secs(config.catch_up_delay_secs),
+                    );
+                    resumed.push((repo, delay, timer_state::missed_runs(&saved, interval)));
+                }
+            }
+            let resume = !resumed.is_empty();
+            if !config.scheduled_repos().is_empty() && (minimized || resume) {
+                let initial_delay = resumed
+                    .iter()
+                    .map(|(_, delay, _)| *delay)
+                    .min()
+                    .unwrap_or(Duration::ZERO);
+                let catch_up_policy = config.catch_up_policy;
+                let state = state.clone();
+                let app_handle = app.handle();
+                tauri::async_runtime::spawn(async move {
+                    let events = WindowEvents(app_handle.clone());
+                    match start_timer(&state, app_handle, initial_delay).await {
+                        Ok(()) if resume => {
+                            tracing::info!(delay_secs = initial_delay.as_secs(), "timer resumed");
+                            events::emit(&events, "timer-toggled", &TimerToggled { running: true, reason: "resumed" });
+                            for (repo, _, missed) in resumed.into_iter().filter(|(_, _, missed)| *missed > 0) {
+                                events::emit(&events, "missed-runs", &timer_state::MissedRuns {
+                                    repo,
+                                    missed,
+                                    policy: catch_up_policy,
+                                    next_run_in_secs: initial_delay.as_secs(),
+                                });
+                            }
+                        }
+                        Ok(()) => {}
+                        Err(e) => tracing::warn!(error = %e, "could not start timer at launch"),
+                    }
+                });
+            }
+
+            tauri::async_runtime::spawn(fetch_loop(state.clone(), app.handle()));
+            tauri::async_runtime::spawn(working_hours_loop(state, app.handle()));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             run_commit,
+            get_pending_approvals,
+            approve_commit,
+            reject_commit,
+            confirm_large_commit,
             save_config,
+            export_config,
+            import_config,
             get_config,
+            get_launch_at_login,
+            set_launch_at_login,
             load_config_from_file,
             start_auto_commit,
             stop_auto_commit,
+            next_scheduled_run,
+            cancel_current_operation,
             select_directory,
+            validate_repo_path,
+            add_repo_from_template,
+            save_repo_template,
+            set_default_repo_template,
+            run_diagnostics,
             test_api_key,
+            list_available_models,
+            test_webhook,
+            import_existing_history,
+            search_history,
+            show_history_commit,
+            preview_commit_message,
+            regenerate_message,
+            suggest_branch_name,
+            create_and_checkout_branch,
+            generate_changelog,
+            generate_work_summary,
+            suggest_next_version,
+            create_release_tag,
+            create_pull_request,
+            set_gemini_api_key,
+            set_github_token,
+            generate_control_api_token,
+            set_gitlab_token,
+            set_bitbucket_token,
+            set_proxy_password,
+            set_issue_tracker_token,
+            test_issue_tracker,
+            get_gitattributes_suggestions,
+            apply_gitattributes_suggestions,
+            get_gitignore_suggestion,
+            append_gitignore,
+            get_conflicts,
+            apply_resolution,
+            suggest_conflict_resolution,
+            undo_last_commit,
+            get_repo_health,
+            acknowledge_safe_mode,
+            get_cycle_summaries,
+            get_statistics,
+            get_usage_summary,
+            get_app_status,
+            get_recent_events,
+            get_repo_status,
+            get_diff_hunks,
+            get_staged_diff,
+            revert_commit,
+            squash_day_into_branch,
+            squash_commits,
+            stage_hunks,
+            probe_remote,
+            list_remotes,
+            list_branches,
+            run_trial,
+            list_snapshots,
+            restore_snapshot,
+            list_stale_branches,
+            delete_stale_branches,
+            get_recent_logs,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Let a running cycle wind down and close the session with a
+            // summary before the app goes away
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                let state = app_handle.state::<AppState>().inner().clone();
+                let events = WindowEvents(app_handle.clone());
+                tauri::async_runtime::block_on(async {
+                    shut_down(&state).await;
+                    end_session(&state, &events).await;
+                });
+            }
+        });
 }